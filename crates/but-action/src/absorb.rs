@@ -1,4 +1,5 @@
 use anyhow::Context;
+use but_tools::sanitize::MessageSanitizer;
 use but_tools::workspace::{FileChange, SimpleCommit, amend_toolset};
 use but_workspace::StackId;
 use gitbutler_command_context::CommandContext;
@@ -252,12 +253,17 @@ fn absorb_file_changes_into_commit(
     let outcome = but_tools::workspace::amend_commit_inner(
         ctx,
         Some(app_handle),
+        None,
+        &MessageSanitizer::default(),
         but_tools::workspace::AmendParameters {
             commit_id: commit_id.to_owned().to_git2().to_string(),
             stack_id: stack_id.to_owned().to_string(),
             message_title: commit.message_title.clone(),
             message_body: commit.message_body.clone(),
             files: files.iter().map(|f| f.to_owned().path).collect(),
+            predict_only: false,
+            keep_author_date: true,
+            author_date: None,
         },
     )?;
 
@@ -177,6 +177,7 @@ fn handle_changes_simple_inner(
             diff_specs,
             commit_message.clone(),
             stack_branch_name.clone(),
+            None,
             perm,
         )?;
 
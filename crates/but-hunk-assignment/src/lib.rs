@@ -52,6 +52,14 @@ pub struct HunkAssignment {
     pub line_nums_added: Option<Vec<usize>>,
     /// The line numbers that were removed in this hunk.
     pub line_nums_removed: Option<Vec<usize>>,
+    /// How certain the assignment is, from 0.0 to 1.0.
+    ///   - 1.0 if the hunk is assigned and locked to exactly one commit.
+    ///   - 0.5 if the hunk is assigned, but could go to more than one commit.
+    ///   - 0.0 if the hunk is unassigned.
+    ///
+    /// This field is ignored when HunkAssignment is passed by the UI to create a new assignment.
+    #[serde(default)]
+    pub confidence: f32,
 }
 
 impl TryFrom<but_db::HunkAssignment> for HunkAssignment {
@@ -66,7 +74,7 @@ impl TryFrom<but_db::HunkAssignment> for HunkAssignment {
             .as_ref()
             .and_then(|id| uuid::Uuid::parse_str(id).ok())
             .map(StackId::from);
-        Ok(HunkAssignment {
+        let mut assignment = HunkAssignment {
             id: value.id.map(|id| Uuid::parse_str(&id)).transpose()?,
             hunk_header: header,
             path: value.path,
@@ -75,7 +83,10 @@ impl TryFrom<but_db::HunkAssignment> for HunkAssignment {
             hunk_locks: None,
             line_nums_added: None,   // derived data (not persisted)
             line_nums_removed: None, // derived data (not persisted)
-        })
+            confidence: 0.0,         // derived data (not persisted)
+        };
+        assignment.confidence = assignment.derive_confidence();
+        Ok(assignment)
     }
 }
 
@@ -206,6 +217,21 @@ impl HunkAssignment {
         }
         false
     }
+
+    /// Derive the [confidence](Self::confidence) of the assignment from its stack and its locks.
+    pub(crate) fn derive_confidence(&self) -> f32 {
+        if self.stack_id.is_none() {
+            return 0.0;
+        }
+        let locked_commits = self
+            .hunk_locks
+            .iter()
+            .flatten()
+            .map(|lock| lock.commit_id)
+            .unique()
+            .count();
+        if locked_commits == 1 { 1.0 } else { 0.5 }
+    }
 }
 
 /// Sets the assignment for a hunk. It must be already present in the current assignments, errors out if it isn't.
@@ -303,6 +329,21 @@ pub fn assign(
     Ok(rejections)
 }
 
+/// Returns the hunk assignments as they were last persisted, without reconciling them with the worktree.
+pub fn persisted_assignments(ctx: &mut CommandContext) -> Result<Vec<HunkAssignment>> {
+    state::assignments(ctx)
+}
+
+/// Replaces all persisted hunk assignments with `assignments`.
+///
+/// This is for callers that reconciled only some of the worktree changes, and merged the outcome with assignments of the other changes they already knew.
+pub fn set_persisted_assignments(
+    ctx: &mut CommandContext,
+    assignments: Vec<HunkAssignment>,
+) -> Result<()> {
+    state::set_assignments(ctx, assignments)
+}
+
 /// Same as the `reconcile_with_worktree_and_locks` function, but if the operation produces an error, it will create a fallback set of assignments from the worktree changes alone.
 /// An optional error is returned alongside the assignments, which will be `None` if the operation was successful and it will be set if the operation failed and a fallback was used.
 ///
@@ -421,7 +462,7 @@ fn hunk_dependency_assignments(deps: &HunkDependencies) -> Result<Vec<HunkAssign
         } else {
             None
         };
-        let assignment = HunkAssignment {
+        let mut assignment = HunkAssignment {
             id: None,
             hunk_header: Some(hunk.into()),
             path: path.clone(),
@@ -430,7 +471,9 @@ fn hunk_dependency_assignments(deps: &HunkDependencies) -> Result<Vec<HunkAssign
             hunk_locks: Some(locks.clone()),
             line_nums_added: None,   // derived data (not persisted)
             line_nums_removed: None, // derived data (not persisted)
+            confidence: 0.0,         // derived data (not persisted)
         };
+        assignment.confidence = assignment.derive_confidence();
         assignments.push(assignment);
     }
     Ok(assignments)
@@ -450,6 +493,7 @@ fn diff_to_assignments(diff: Option<UnifiedDiff>, path: BString) -> Vec<HunkAssi
                 hunk_locks: None,
                 line_nums_added: None,
                 line_nums_removed: None,
+                confidence: 0.0,
             }],
             but_core::UnifiedDiff::TooLarge { .. } => vec![HunkAssignment {
                 id: Some(Uuid::new_v4()),
@@ -460,6 +504,7 @@ fn diff_to_assignments(diff: Option<UnifiedDiff>, path: BString) -> Vec<HunkAssi
                 hunk_locks: None,
                 line_nums_added: None,
                 line_nums_removed: None,
+                confidence: 0.0,
             }],
             but_core::UnifiedDiff::Patch {
                 hunks,
@@ -477,6 +522,7 @@ fn diff_to_assignments(diff: Option<UnifiedDiff>, path: BString) -> Vec<HunkAssi
                         hunk_locks: None,
                         line_nums_added: None,
                         line_nums_removed: None,
+                        confidence: 0.0,
                     }]
                 } else {
                     hunks
@@ -493,6 +539,7 @@ fn diff_to_assignments(diff: Option<UnifiedDiff>, path: BString) -> Vec<HunkAssi
                                 hunk_locks: None,
                                 line_nums_added: Some(line_nums_added_new),
                                 line_nums_removed: Some(line_nums_removed_old),
+                                confidence: 0.0,
                             }
                         })
                         .collect()
@@ -509,6 +556,7 @@ fn diff_to_assignments(diff: Option<UnifiedDiff>, path: BString) -> Vec<HunkAssi
             hunk_locks: None,
             line_nums_added: None,
             line_nums_removed: None,
+            confidence: 0.0,
         }]
     }
 }
@@ -559,7 +607,7 @@ fn line_nums_from_hunk(diff: &BString, old_start: u32, new_start: u32) -> (Vec<u
 fn requests_to_assignments(request: Vec<HunkAssignmentRequest>) -> Vec<HunkAssignment> {
     let mut assignments = vec![];
     for req in request {
-        let assignment = HunkAssignment {
+        let mut assignment = HunkAssignment {
             id: None,
             hunk_header: req.hunk_header,
             path: req.path_bytes.to_str_lossy().into(),
@@ -568,7 +616,9 @@ fn requests_to_assignments(request: Vec<HunkAssignmentRequest>) -> Vec<HunkAssig
             hunk_locks: None,
             line_nums_added: None,
             line_nums_removed: None,
+            confidence: 0.0,
         };
+        assignment.confidence = assignment.derive_confidence();
         assignments.push(assignment);
     }
     assignments
@@ -604,6 +654,7 @@ mod tests {
                 hunk_locks: None,
                 line_nums_added: None,
                 line_nums_removed: None,
+                confidence: 0.0,
             }
         }
     }
@@ -810,4 +861,45 @@ mod tests {
         let hunk2 = HunkAssignment::new("bar.rs", 10, 15, Some(2), None);
         assert_ne!(hunk1, hunk2);
     }
+
+    #[test]
+    fn test_reconcile_sets_confidence() {
+        let lock = |commit: &str| HunkLock {
+            stack_id: stack_id_seq(1),
+            commit_id: gix::ObjectId::from_hex(commit.repeat(40).as_bytes()).unwrap(),
+        };
+        let with_locks = |locks: Vec<HunkLock>| HunkAssignment {
+            hunk_locks: Some(locks),
+            ..HunkAssignment::new("foo.rs", 10, 5, Some(1), None)
+        };
+        let worktree_assignments = vec![
+            HunkAssignment::new("foo.rs", 10, 5, None, None),
+            HunkAssignment::new("bar.rs", 10, 5, None, None),
+            HunkAssignment::new("baz.rs", 10, 5, None, None),
+        ];
+        let previous_assignments = vec![
+            with_locks(vec![lock("1")]),
+            HunkAssignment {
+                path: "bar.rs".into(),
+                path_bytes: "bar.rs".into(),
+                ..with_locks(vec![lock("1"), lock("2")])
+            },
+        ];
+        let applied_stacks = vec![stack_id_seq(1)];
+        let result = reconcile::assignments(
+            &worktree_assignments,
+            &previous_assignments,
+            &applied_stacks,
+            MultipleOverlapping::SetMostLines,
+            true,
+        )
+        .unwrap();
+
+        let confidences = result.iter().map(|a| a.confidence).collect::<Vec<_>>();
+        assert_eq!(
+            confidences,
+            [1.0, 0.5, 0.0],
+            "locked to one commit, to several commits, and unassigned"
+        );
+    }
 }
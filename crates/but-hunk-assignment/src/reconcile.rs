@@ -91,6 +91,7 @@ pub(crate) fn assignments(
                 }
             }
         }
+        new_assignment.confidence = new_assignment.derive_confidence();
         reconciled.push(new_assignment);
     }
     Ok(reconciled)
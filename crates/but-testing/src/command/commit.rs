@@ -125,6 +125,7 @@ fn commit_with_project(
         but_workspace::commit_engine::Destination::AmendCommit {
             commit_id: parent_id,
             new_message: message.map(ToOwned::to_owned),
+            author_date: None,
         }
     } else {
         let (stack_segment, parent_commit_id) =
@@ -175,6 +176,7 @@ fn commit_without_project(
         but_workspace::commit_engine::Destination::AmendCommit {
             commit_id: parent_id,
             new_message: message.map(ToOwned::to_owned),
+            author_date: None,
         }
     } else {
         but_workspace::commit_engine::Destination::NewCommit {
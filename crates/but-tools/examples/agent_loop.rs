@@ -0,0 +1,221 @@
+//! An end-to-end agent loop on top of the public API of `but-tools`.
+//!
+//! It builds the workspace toolset for a fixture project, hands the tool definitions to the model, calls each tool
+//! the model asks for, and feeds the results back until the model is done. The model is scripted, so the example
+//! runs without network access or an API key.
+//!
+//! Run it with `cargo run -p but-tools --example agent_loop`, or as a test with `cargo test -p but-tools --examples`.
+
+use anyhow::{Context as _, bail};
+use but_settings::AppSettings;
+use but_tools::tool::{TOOL_API_VERSION, Toolset};
+use but_tools::workspace::workspace_toolset;
+use gitbutler_command_context::CommandContext;
+use gitbutler_testsupport::{TestProject, paths};
+use serde_json::{Value, json};
+
+fn main() -> anyhow::Result<()> {
+    let data_dir = paths::data_dir();
+    let fixture = TestProject::default();
+    let project = gitbutler_project::Controller::from_path(data_dir.path()).add(
+        fixture.path(),
+        None,
+        None,
+    )?;
+    let mut ctx = CommandContext::open(&project, AppSettings::default())?;
+    gitbutler_branch_actions::set_base_branch(
+        &ctx,
+        &"refs/remotes/origin/master".parse()?,
+        false,
+        ctx.project().exclusive_worktree_access().write_permission(),
+    )?;
+
+    // The user made some changes the agent is asked to commit.
+    std::fs::write(fixture.path().join("greeting.txt"), "Hello, world!\n")?;
+    std::fs::write(fixture.path().join("notes.txt"), "Say hello to everyone.\n")?;
+
+    let summary = run_agent(&mut ctx, &mut ScriptedModel::default())?;
+    println!("{summary}");
+    Ok(())
+}
+
+/// Let `model` call tools on the workspace of `ctx` until it's done, and return its final answer.
+fn run_agent(ctx: &mut CommandContext, model: &mut ScriptedModel) -> anyhow::Result<String> {
+    let mut toolset = workspace_toolset(ctx, None, "example-message".into())?;
+    let definitions = toolset.definitions();
+    if definitions["apiVersion"] != TOOL_API_VERSION {
+        bail!("The toolset provides an unexpected version of the tool API");
+    }
+
+    let mut transcript = vec![];
+    loop {
+        match model.respond(&definitions, &transcript)? {
+            Reply::ToolCall { name, arguments } => {
+                let response = toolset.call_tool(&name, &arguments.to_string());
+                println!("{name} -> {}", summarize(&response));
+                transcript.push(ToolExchange { name, response });
+            }
+            Reply::Done(answer) => {
+                print_session(&toolset);
+                return Ok(answer);
+            }
+        }
+    }
+}
+
+/// Print what the session did, as a host would show it to the user.
+fn print_session(toolset: &Toolset) {
+    let session = toolset.session_summary(None);
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&session).unwrap_or_default()
+    );
+}
+
+/// A tool call of the model, along with the response of the toolset to it.
+struct ToolExchange {
+    name: String,
+    response: Value,
+}
+
+/// What the model wants to do next.
+enum Reply {
+    /// Call the tool `name` with the JSON `arguments`.
+    ToolCall { name: String, arguments: Value },
+    /// Stop calling tools and answer the user.
+    Done(String),
+}
+
+/// Split the `response` of a tool call into its result, or the error it failed with.
+///
+/// Successful calls have a `result`, while failed calls have an `error` message, along with a `code` for errors
+/// the model can react to, like an ambiguous commit id.
+fn result_of(response: &Value) -> anyhow::Result<&Value> {
+    match response.get("error").and_then(Value::as_str) {
+        Some(error) => {
+            let code = response["code"].as_str().unwrap_or("unknown");
+            bail!("the tool failed with code '{code}': {error}")
+        }
+        None => response
+            .get("result")
+            .context("the response has neither a result nor an error"),
+    }
+}
+
+/// Describe `response` in one line.
+fn summarize(response: &Value) -> String {
+    match result_of(response) {
+        Ok(result) => {
+            let text = result.to_string();
+            let cut = text
+                .char_indices()
+                .nth(120)
+                .map_or(text.len(), |(index, _)| index);
+            format!("ok: {}", &text[..cut])
+        }
+        Err(err) => format!("error: {err}"),
+    }
+}
+
+/// Stands in for the LLM: it commits the changed files in a few steps, reading what it needs from previous results.
+#[derive(Default)]
+struct ScriptedModel {
+    step: usize,
+}
+
+impl ScriptedModel {
+    fn respond(
+        &mut self,
+        definitions: &Value,
+        transcript: &[ToolExchange],
+    ) -> anyhow::Result<Reply> {
+        // A real model would see the tool results as messages. This one fails loudly instead of reasoning about errors.
+        let last_result = |name: &str| -> anyhow::Result<&Value> {
+            let exchange = transcript
+                .iter()
+                .rev()
+                .find(|exchange| exchange.name == name)
+                .with_context(|| format!("'{name}' wasn't called yet"))?;
+            result_of(&exchange.response).with_context(|| format!("calling '{name}' failed"))
+        };
+
+        self.step += 1;
+        let (name, arguments) = match self.step {
+            1 => ("get_project_status", json!({})),
+            2 => {
+                let status = last_result("get_project_status")?;
+                let changed = status["fileChanges"]
+                    .as_array()
+                    .map(Vec::len)
+                    .unwrap_or_default();
+                if changed != 2 {
+                    bail!("Expected two changed files, got {changed}");
+                }
+                (
+                    "create_branch",
+                    json!({
+                        "branchName": "greeting",
+                        "branchDescription": "Greets the world.",
+                    }),
+                )
+            }
+            3 => (
+                "commit",
+                json!({
+                    "messageTitle": "Add a greeting",
+                    "messageBody": "",
+                    "branchName": "greeting",
+                    "branchDescription": "Greets the world.",
+                    "files": ["greeting.txt"],
+                }),
+            ),
+            4 => {
+                let stack_id = &last_result("create_branch")?["id"];
+                let commit_id = &last_result("commit")?["newCommit"];
+                (
+                    "amend",
+                    json!({
+                        "commitId": commit_id,
+                        "messageTitle": "Add a greeting with notes",
+                        "messageBody": "The notes explain who to greet.",
+                        "stackId": stack_id,
+                        "files": ["notes.txt"],
+                    }),
+                )
+            }
+            5 => ("get_project_status", json!({})),
+            _ => {
+                let status = last_result("get_project_status")?;
+                let left = status["fileChanges"]
+                    .as_array()
+                    .map(Vec::len)
+                    .unwrap_or_default();
+                if left != 0 {
+                    bail!("{left} files were left uncommitted");
+                }
+                return Ok(Reply::Done(
+                    "Committed the greeting and its notes to the new branch 'greeting'.".into(),
+                ));
+            }
+        };
+
+        let known = definitions["tools"]
+            .as_array()
+            .is_some_and(|tools| tools.iter().any(|tool| tool["name"] == name));
+        if !known {
+            bail!("The toolset doesn't provide '{name}'");
+        }
+        Ok(Reply::ToolCall {
+            name: name.to_owned(),
+            arguments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn agent_commits_all_changes() -> anyhow::Result<()> {
+        super::main()
+    }
+}
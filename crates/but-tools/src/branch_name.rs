@@ -0,0 +1,89 @@
+//! Resolve the branch names passed to tools, which are ambiguous if the heads of several stacks share a name.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use but_workspace::StackId;
+
+/// The number of characters of the stack id that qualify a branch name.
+const STACK_ID_PREFIX_LEN: usize = 8;
+
+/// Return the name that selects the branch `name` of the stack `stack_id` even if other stacks have a branch of the same name,
+/// which is `<stack-id-prefix>/<name>`.
+pub fn qualified_branch_name(stack_id: StackId, name: &str) -> String {
+    let stack_id = stack_id.to_string();
+    format!("{}/{}", &stack_id[..STACK_ID_PREFIX_LEN], name)
+}
+
+/// Resolve `name` to the stack that has a head of this name, and the plain name of the head.
+///
+/// `name` is either the name of a branch, or its [qualified name](qualified_branch_name).
+/// `heads` are the ids of the stacks along with the names of their heads.
+///
+/// Return `None` if no stack has a head of this name, and fail with [`AmbiguousBranchNameError`]
+/// if the heads of several stacks have it.
+pub fn resolve_branch_name(
+    heads: impl IntoIterator<Item = (StackId, String)>,
+    name: &str,
+) -> anyhow::Result<Option<(StackId, String)>> {
+    let heads = heads.into_iter().collect::<Vec<_>>();
+    let matches = heads
+        .iter()
+        .filter(|(_, head)| head == name)
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        [(stack_id, head)] => Ok(Some((*stack_id, head.clone()))),
+        [] => Ok(heads
+            .into_iter()
+            .find(|(stack_id, head)| qualified_branch_name(*stack_id, head) == name)),
+        _ => Err(AmbiguousBranchNameError {
+            name: name.to_owned(),
+            candidates: matches
+                .into_iter()
+                .map(|(stack_id, head)| qualified_branch_name(*stack_id, head))
+                .collect(),
+        }
+        .into()),
+    }
+}
+
+/// Return the names that are shared by the heads of more than one stack in `heads`.
+pub fn ambiguous_branch_names(heads: impl IntoIterator<Item = (StackId, String)>) -> Vec<String> {
+    let mut stacks_by_name = BTreeMap::<String, usize>::new();
+    for (_, name) in heads {
+        *stacks_by_name.entry(name).or_default() += 1;
+    }
+    stacks_by_name
+        .into_iter()
+        .filter(|(_, stacks)| *stacks > 1)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// The error returned if a branch name is shared by the heads of more than one stack.
+#[derive(Debug, Clone)]
+pub struct AmbiguousBranchNameError {
+    /// The ambiguous branch name.
+    pub name: String,
+    /// The qualified names of all branches of this name.
+    pub candidates: Vec<String>,
+}
+
+impl fmt::Display for AmbiguousBranchNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The branch name '{}' is ambiguous as multiple stacks have a branch of this name, use one of the qualified names instead: {}",
+            self.name,
+            self.candidates.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for AmbiguousBranchNameError {}
+
+/// Return `name` without the qualification of the stack `stack_id`, if it is the [qualified name](qualified_branch_name) of one of its branches.
+pub fn unqualified_branch_name(stack_id: StackId, name: &str) -> &str {
+    name.strip_prefix(&qualified_branch_name(stack_id, ""))
+        .unwrap_or(name)
+}
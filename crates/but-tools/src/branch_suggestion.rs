@@ -0,0 +1,135 @@
+//! Suggest names for branches from the paths of the files they change, without looking at the repository.
+
+/// Directories that only organize a project, and say nothing about what is changed in them.
+const GENERIC_DIRECTORIES: &[&str] = &["src", "lib", "source", "crates", "packages", "pkg"];
+
+/// File names that only mark the entry point of a module, so the directory they are in names it instead.
+const GENERIC_FILE_NAMES: &[&str] = &["mod", "index", "main", "lib", "init"];
+
+/// The maximum number of words in a suggested name.
+const MAX_WORDS: usize = 5;
+
+/// Return kebab-case branch names for changes of the files at `paths`, from the most specific to the least specific one.
+///
+/// The names are built from the directories the files have in common, like `auth-login` for `src/auth/login.rs`.
+/// Generic directories like `src` and file names like `mod.rs` are skipped, and names have at most five words,
+/// keeping the most specific ones. The result is empty if none of the paths has a meaningful component.
+pub fn suggest_branch_names(paths: &[impl AsRef<str>]) -> Vec<String> {
+    let components = paths
+        .iter()
+        .map(|path| meaningful_components(path.as_ref()))
+        .filter(|components| !components.is_empty())
+        .collect::<Vec<_>>();
+    let Some(first) = components.first() else {
+        return vec![];
+    };
+
+    let common_len = components.iter().skip(1).fold(first.len(), |len, other| {
+        first
+            .iter()
+            .zip(other)
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    let common = &first[..common_len];
+
+    // What follows the common prefix in each path, in the order the paths were passed.
+    let mut next = vec![];
+    for component in components.iter().filter_map(|c| c.get(common_len)) {
+        if !next.contains(component) {
+            next.push(component.clone());
+        }
+    }
+
+    let mut candidates = vec![];
+    if !next.is_empty() && next.len() <= 2 {
+        candidates.push(common.iter().chain(&next).cloned().collect::<Vec<_>>());
+    }
+    for len in (1..=common.len()).rev() {
+        candidates.push(common[..len].to_vec());
+    }
+    if common.is_empty() && next.len() > 2 {
+        // Without a common prefix, the top-level components that change most often are the best guess.
+        let mut counts = next
+            .iter()
+            .map(|component| {
+                let count = components
+                    .iter()
+                    .filter(|c| c.first() == Some(component))
+                    .count();
+                (count, component)
+            })
+            .collect::<Vec<_>>();
+        counts.sort_by(|(a, _), (b, _)| b.cmp(a));
+        let most_changed = counts
+            .into_iter()
+            .map(|(_, component)| component.clone())
+            .collect::<Vec<_>>();
+        candidates.push(most_changed[..2].to_vec());
+        candidates.push(most_changed[..1].to_vec());
+    }
+
+    let mut names = vec![];
+    for candidate in candidates {
+        let name = join_words(&candidate);
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Return the kebab-case components of `path` that describe what it is, without generic directories,
+/// generic file names and file extensions.
+fn meaningful_components(path: &str) -> Vec<String> {
+    let parts = path
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .collect::<Vec<_>>();
+    let mut components = vec![];
+    for (index, part) in parts.iter().enumerate() {
+        let is_file = index + 1 == parts.len();
+        let (part, generic) = if is_file {
+            // Hidden files like `.gitignore` are named by what follows the dot.
+            let stem = part.split('.').find(|s| !s.is_empty()).unwrap_or_default();
+            (stem, GENERIC_FILE_NAMES)
+        } else {
+            (*part, GENERIC_DIRECTORIES)
+        };
+        let component = kebab_case(part);
+        if !component.is_empty() && !generic.contains(&component.as_str()) {
+            components.push(component);
+        }
+    }
+    components
+}
+
+/// Turn `name`, which may be in camelCase, snake_case or contain other separators, into kebab-case.
+fn kebab_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            let starts_word = c.is_ascii_uppercase()
+                && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit());
+            if starts_word && !out.is_empty() && !out.ends_with('-') {
+                out.push('-');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else if !out.is_empty() && !out.ends_with('-') {
+            out.push('-');
+        }
+        previous = Some(c);
+    }
+    out.trim_end_matches('-').to_owned()
+}
+
+/// Join the kebab-case `components` into one name of at most [`MAX_WORDS`] words, keeping the last ones.
+fn join_words(components: &[String]) -> String {
+    let words = components
+        .iter()
+        .flat_map(|component| component.split('-'))
+        .collect::<Vec<_>>();
+    words[words.len().saturating_sub(MAX_WORDS)..].join("-")
+}
@@ -0,0 +1,88 @@
+//! Resolve the commit ids passed to tools, which are often abbreviated.
+use std::fmt;
+use std::str::FromStr;
+
+/// Resolve `id`, which is either a full commit id or an abbreviation of one, to the full id of a commit in `repo`.
+///
+/// Fails with [`InvalidCommitIdError`] if `id` isn't a hexadecimal object id of at least 4 characters,
+/// and with [`AmbiguousCommitIdError`] if the abbreviation matches more than one commit.
+pub fn resolve_commit_id(repo: &gix::Repository, id: &str) -> anyhow::Result<gix::ObjectId> {
+    let id = id.trim();
+    if let Ok(full_id) = gix::ObjectId::from_str(id) {
+        return Ok(full_id);
+    }
+    let prefix =
+        gix::hash::Prefix::from_hex(id).map_err(|_| InvalidCommitIdError { id: id.to_owned() })?;
+
+    let mut candidates = Default::default();
+    repo.objects.lookup_prefix(prefix, Some(&mut candidates))?;
+    // Objects that aren't commits can share the prefix, but can't be meant.
+    let mut commits = candidates
+        .into_iter()
+        .filter(|candidate: &gix::ObjectId| {
+            repo.find_object(*candidate)
+                .is_ok_and(|object| object.kind == gix::object::Kind::Commit)
+        })
+        .collect::<Vec<_>>();
+    commits.sort();
+
+    match commits.as_slice() {
+        [] => Err(anyhow::anyhow!("No commit found with id '{}'", id)),
+        [commit] => Ok(*commit),
+        _ => Err(AmbiguousCommitIdError {
+            prefix: id.to_owned(),
+            candidates: commits,
+        }
+        .into()),
+    }
+}
+
+/// Return true if `id` is an abbreviated object id, instead of a full one.
+pub fn is_abbreviated(id: &str) -> bool {
+    gix::ObjectId::from_str(id.trim()).is_err() && gix::hash::Prefix::from_hex(id.trim()).is_ok()
+}
+
+/// The error returned if a commit id isn't a hexadecimal object id.
+#[derive(Debug, Clone)]
+pub struct InvalidCommitIdError {
+    /// The invalid commit id.
+    pub id: String,
+}
+
+impl fmt::Display for InvalidCommitIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' isn't a valid commit id, which has to be a full or abbreviated hexadecimal id of at least 4 characters",
+            self.id
+        )
+    }
+}
+
+impl std::error::Error for InvalidCommitIdError {}
+
+/// The error returned if an abbreviated commit id matches more than one commit.
+#[derive(Debug, Clone)]
+pub struct AmbiguousCommitIdError {
+    /// The abbreviated commit id.
+    pub prefix: String,
+    /// The full ids of all commits the abbreviation matches.
+    pub candidates: Vec<gix::ObjectId>,
+}
+
+impl fmt::Display for AmbiguousCommitIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The commit id '{}' is ambiguous, use one of the full ids instead: {}",
+            self.prefix,
+            self.candidates
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl std::error::Error for AmbiguousCommitIdError {}
@@ -1,7 +1,38 @@
+use std::cell::RefCell;
+
 use but_workspace::StackId;
 use gitbutler_project::ProjectId;
 use tauri::Emitter;
 
+thread_local! {
+    /// The names of the events emitted on this thread since [`record_events()`] started recording, if it is.
+    static RECORDED_EVENTS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Call `f`, and return its result along with the names of the events it emitted, in the order they were emitted.
+///
+/// Calls can be nested, in which case the events of the inner call are recorded by the outer one, too.
+pub(crate) fn record_events<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let previous = RECORDED_EVENTS.replace(Some(vec![]));
+    let out = f();
+    let events = RECORDED_EVENTS.replace(previous).unwrap_or_default();
+    RECORDED_EVENTS.with_borrow_mut(|recorded| {
+        if let Some(recorded) = recorded {
+            recorded.extend(events.iter().cloned());
+        }
+    });
+    (out, events)
+}
+
+/// Remember that the event `name` was emitted, if events are [recorded](record_events()).
+fn record_event(name: &str) {
+    RECORDED_EVENTS.with_borrow_mut(|recorded| {
+        if let Some(recorded) = recorded {
+            recorded.push(name.to_owned());
+        }
+    });
+}
+
 pub trait EmitStackUpdate {
     /// Emits a stack update event with the given stack ID.
     ///
@@ -11,13 +42,20 @@ pub trait EmitStackUpdate {
     ///
     /// * `project_id` - The ID of the project to which the stack belongs.
     /// * `stack_id` - The ID of the stack to update.
-    fn emit_stack_update(&self, project_id: ProjectId, stack_id: StackId);
+    /// * `message_id` - The ID of the message whose tool calls caused the update, if any.
+    fn emit_stack_update(&self, project_id: ProjectId, stack_id: StackId, message_id: Option<&str>);
 }
 
 impl EmitStackUpdate for tauri::AppHandle {
-    fn emit_stack_update(&self, project_id: ProjectId, stack_id: StackId) {
+    fn emit_stack_update(
+        &self,
+        project_id: ProjectId,
+        stack_id: StackId,
+        message_id: Option<&str>,
+    ) {
         let name = format!("project://{}/stack_details_update", project_id);
-        let payload = serde_json::json!({ "stackId": stack_id });
+        let payload = serde_json::json!({ "stackId": stack_id, "messageId": message_id });
+        record_event(&name);
         self.emit(&name, payload)
             .expect("Failed to emit stack details update");
     }
@@ -51,7 +89,37 @@ impl EmitToolCall for tauri::AppHandle {
             "parameters": tool_call.parameters,
             "result": tool_call.result,
         });
+        record_event(&name);
         self.emit(&name, payload)
             .expect("Failed to emit tool call event");
     }
 }
+
+pub trait EmitWorktreeChanges {
+    /// Emits the worktree changes along with their hunk assignments, just like the file watcher does.
+    ///
+    /// This method should be implemented to emit an event that updates the uncommitted changes and their assignments in the UI.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id` - The ID of the project the worktree belongs to.
+    /// * `changes` - The worktree changes with their assignments.
+    fn emit_worktree_changes(
+        &self,
+        project_id: ProjectId,
+        changes: &but_hunk_assignment::WorktreeChanges,
+    );
+}
+
+impl EmitWorktreeChanges for tauri::AppHandle {
+    fn emit_worktree_changes(
+        &self,
+        project_id: ProjectId,
+        changes: &but_hunk_assignment::WorktreeChanges,
+    ) {
+        let name = format!("project://{}/worktree_changes", project_id);
+        record_event(&name);
+        self.emit(&name, serde_json::json!(changes))
+            .expect("Failed to emit worktree changes");
+    }
+}
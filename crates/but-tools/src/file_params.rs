@@ -0,0 +1,116 @@
+//! Normalize the lists of file paths passed to tools, which often name the same file more than once.
+
+/// The file paths passed to a tool after normalization, and how the passed paths were changed to get there.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizedInput {
+    /// The normalized paths, without duplicates and with directories expanded, in the order they were first named.
+    pub files: Vec<String>,
+    /// The passed paths that weren't used as they were.
+    pub collapsed: Vec<CollapsedPath>,
+}
+
+/// A passed path that was normalized, expanded or dropped.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollapsedPath {
+    /// The path as it was passed.
+    pub input: String,
+    /// Why the path wasn't used as it was.
+    pub reason: CollapseReason,
+    /// The normalized paths the passed path stands for.
+    pub paths: Vec<String>,
+}
+
+/// Why a passed path wasn't used as it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollapseReason {
+    /// The path was spelled differently, like `./src//lib.rs` instead of `src/lib.rs`.
+    Normalized,
+    /// The path names a directory, and was replaced by the changed files in it.
+    Directory,
+    /// All files the path stands for were already named before, so it was dropped.
+    Duplicate,
+}
+
+/// Normalize `files`, the paths passed to a tool, against `changed_paths`, the paths the tool can act on.
+///
+/// Every path is [normalized](normalize_path), paths of directories that contain any of `changed_paths` are
+/// replaced by those, and all paths that were named before are dropped, keeping the order in which they were
+/// first named.
+/// Paths that aren't in `changed_paths` are kept, so the tool can report them.
+pub fn normalize_file_params(files: &[String], changed_paths: &[String]) -> NormalizedInput {
+    let mut normalized = NormalizedInput::default();
+    for input in files {
+        let path = normalize_path(input);
+        let (reason, paths) = if changed_paths.contains(&path) {
+            (None, vec![path.clone()])
+        } else {
+            let mut contained = changed_paths
+                .iter()
+                .filter(|changed_path| is_in_directory(changed_path, &path))
+                .cloned()
+                .collect::<Vec<_>>();
+            contained.sort();
+            contained.dedup();
+            if contained.is_empty() {
+                (None, vec![path.clone()])
+            } else {
+                (Some(CollapseReason::Directory), contained)
+            }
+        };
+
+        let mut added_any = false;
+        for path in &paths {
+            if !normalized.files.contains(path) {
+                normalized.files.push(path.clone());
+                added_any = true;
+            }
+        }
+
+        let reason = match reason {
+            Some(reason) => Some(reason),
+            None if !added_any => Some(CollapseReason::Duplicate),
+            None if *input != path => Some(CollapseReason::Normalized),
+            None => None,
+        };
+        if let Some(reason) = reason {
+            normalized.collapsed.push(CollapsedPath {
+                input: input.clone(),
+                reason,
+                paths,
+            });
+        }
+    }
+    normalized
+}
+
+/// Normalize `path` to the way paths relative to the workspace root are spelled in the repository.
+///
+/// Surrounding whitespace, `.` components, empty components and trailing slashes are removed, backslashes
+/// are turned into slashes and `..` components are resolved where possible.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.trim().replace('\\', "/");
+    let mut components: Vec<&str> = vec![];
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|last| *last != "..") => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// Return true if `path` is contained in `directory`.
+///
+/// The workspace root isn't treated as a directory, so an empty path never stands for all changes.
+fn is_in_directory(path: &str, directory: &str) -> bool {
+    !directory.is_empty()
+        && path
+            .strip_prefix(directory)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
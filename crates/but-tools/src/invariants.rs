@@ -0,0 +1,101 @@
+//! Verify that the workspace is consistent, to catch corruption at the operation that caused it.
+use std::collections::BTreeMap;
+
+use but_workspace::StackId;
+use gitbutler_command_context::CommandContext;
+use gitbutler_stack::VirtualBranchesHandle;
+
+/// A way in which the workspace is inconsistent, as found by [`verify_workspace_invariants()`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum InvariantViolation {
+    /// The head of a branch of an applied stack isn't part of the workspace commit.
+    #[serde(rename_all = "camelCase")]
+    HeadNotInWorkspace {
+        stack_id: StackId,
+        branch_name: String,
+        #[serde(with = "gitbutler_serde::object_id")]
+        head: gix::ObjectId,
+    },
+    /// The head of a branch recorded in the metadata doesn't exist in the repository.
+    #[serde(rename_all = "camelCase")]
+    MissingHead {
+        stack_id: StackId,
+        branch_name: String,
+        #[serde(with = "gitbutler_serde::object_id")]
+        head: gix::ObjectId,
+    },
+    /// More than one applied stack has a branch of the same name, and with it the same reference.
+    #[serde(rename_all = "camelCase")]
+    SharedHead {
+        branch_name: String,
+        stack_ids: Vec<StackId>,
+    },
+}
+
+/// Check that the applied stacks are consistent with the workspace commit and the repository, and return all violations.
+///
+/// These invariants are checked:
+///   - the head of every branch of an applied stack is an ancestor of the workspace commit,
+///   - the head of every branch recorded in the metadata exists,
+///   - no two applied stacks have a branch of the same name.
+pub fn verify_workspace_invariants(
+    ctx: &CommandContext,
+) -> anyhow::Result<Vec<InvariantViolation>> {
+    let repo = ctx.gix_repo()?;
+    let workspace_commit = repo.head_id()?.detach();
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+
+    let mut violations = vec![];
+    let mut stacks_by_branch_name = BTreeMap::<String, Vec<StackId>>::new();
+    for stack in vb_state.list_stacks_in_workspace()? {
+        for branch in &stack.heads {
+            stacks_by_branch_name
+                .entry(branch.name().to_owned())
+                .or_default()
+                .push(stack.id);
+
+            // Null heads are written for metadata that doesn't know the head, which is then read from the reference.
+            let persisted_head = branch.persisted_head_oid().filter(|head| !head.is_null());
+            if let Some(head) = persisted_head {
+                if !repo.has_object(head) {
+                    violations.push(InvariantViolation::MissingHead {
+                        stack_id: stack.id,
+                        branch_name: branch.name().to_owned(),
+                        head,
+                    });
+                }
+            }
+
+            // `head_oid()` isn't used as it creates the reference if it's missing.
+            let head = match repo.try_find_reference(branch.name().as_str())? {
+                Some(mut reference) => reference.peel_to_id_in_place()?.detach(),
+                None => match persisted_head {
+                    Some(head) if repo.has_object(head) => head,
+                    _ => continue,
+                },
+            };
+            let is_ancestor = repo
+                .merge_base(head, workspace_commit)
+                .is_ok_and(|base| base == head);
+            if !is_ancestor {
+                violations.push(InvariantViolation::HeadNotInWorkspace {
+                    stack_id: stack.id,
+                    branch_name: branch.name().to_owned(),
+                    head,
+                });
+            }
+        }
+    }
+
+    violations.extend(
+        stacks_by_branch_name
+            .into_iter()
+            .filter(|(_, stack_ids)| stack_ids.len() > 1)
+            .map(|(branch_name, stack_ids)| InvariantViolation::SharedHead {
+                branch_name,
+                stack_ids,
+            }),
+    );
+    Ok(violations)
+}
@@ -1,4 +1,14 @@
+pub mod branch_name;
+pub mod branch_suggestion;
+pub mod commit_id;
 pub mod emit;
+pub mod file_params;
+pub mod invariants;
+pub mod linked_issues;
+pub mod localization;
 pub mod openai;
+pub mod sanitize;
+pub mod session;
 pub mod tool;
+pub mod trailers;
 pub mod workspace;
@@ -0,0 +1,133 @@
+//! Find references to issues in commit messages, like `Fixes #123` or `PROJ-123`, with patterns projects can configure.
+use std::path::Path;
+
+use anyhow::Context as _;
+
+/// The path of the file that configures the issue patterns, relative to the root of the worktree.
+///
+/// It contains a `[[patterns]]` table for each pattern, with its `regex` and optionally its `url`.
+/// Without this file, the [default patterns](default_issue_patterns()) are used.
+pub const ISSUE_PATTERNS_PATH: &str = ".gitbutler/issue-patterns.toml";
+
+/// A pattern of references to issues, along with the URL of the issues it matches.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct IssuePattern {
+    /// The regular expression that matches a reference.
+    ///
+    /// If it has a group named `ref`, only this group is the reference, and the rest of the match is context.
+    pub regex: String,
+    /// The URL of the issues, in which `{ref}` is replaced by the reference and `{number}` by its last number.
+    pub url: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct IssuePatternsFile {
+    patterns: Vec<IssuePattern>,
+}
+
+/// Return the patterns of GitHub issues like `#123`, and of Jira or Linear issues like `PROJ-123`, without URLs.
+pub fn default_issue_patterns() -> Vec<IssuePattern> {
+    vec![
+        IssuePattern {
+            regex: r"(?:^|[^\w/&])(?P<ref>#\d+)\b".to_owned(),
+            url: None,
+        },
+        IssuePattern {
+            regex: r"\b(?P<ref>[A-Z][A-Z0-9]+-\d+)\b".to_owned(),
+            url: None,
+        },
+    ]
+}
+
+/// Read the issue patterns configured in the worktree at `worktree_dir`, or return the default patterns
+/// if none are configured.
+pub fn read_issue_patterns(worktree_dir: &Path) -> anyhow::Result<Vec<IssuePattern>> {
+    let path = worktree_dir.join(ISSUE_PATTERNS_PATH);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(default_issue_patterns());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let file: IssuePatternsFile = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {ISSUE_PATTERNS_PATH}"))?;
+    Ok(file.patterns)
+}
+
+/// A reference to an issue in a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssueReference {
+    /// The reference as it appears in the message, like `#123`.
+    pub ref_id: String,
+    /// The URL of the issue, if its pattern has one.
+    pub url: Option<String>,
+}
+
+/// Finds the references of a set of [issue patterns](IssuePattern) in messages.
+pub struct IssueMatcher {
+    patterns: Vec<(regex::Regex, Option<String>)>,
+}
+
+impl IssueMatcher {
+    /// Compile `patterns`, and fail if one of them isn't a valid regular expression.
+    pub fn new(patterns: &[IssuePattern]) -> anyhow::Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                let regex = regex::Regex::new(&pattern.regex)
+                    .with_context(|| format!("'{}' isn't a valid issue pattern", pattern.regex))?;
+                Ok((regex, pattern.url.clone()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(IssueMatcher { patterns })
+    }
+
+    /// Return the references to issues in `message`, in the order they appear in, each only once.
+    ///
+    /// If the references of several patterns overlap, the one of the first pattern is used.
+    pub fn references(&self, message: &str) -> Vec<IssueReference> {
+        let mut matches = vec![];
+        for (index, (regex, url)) in self.patterns.iter().enumerate() {
+            for captures in regex.captures_iter(message) {
+                let Some(found) = captures.name("ref").or_else(|| captures.get(0)) else {
+                    continue;
+                };
+                if found.is_empty() {
+                    continue;
+                }
+                matches.push((found.range(), index, found.as_str(), url));
+            }
+        }
+        matches.sort_by_key(|(range, index, ..)| (range.start, *index));
+
+        let mut references: Vec<IssueReference> = vec![];
+        let mut end = 0;
+        for (range, _, ref_id, url) in matches {
+            if range.start < end {
+                continue;
+            }
+            end = range.end;
+            if references
+                .iter()
+                .any(|reference| reference.ref_id == ref_id)
+            {
+                continue;
+            }
+            references.push(IssueReference {
+                ref_id: ref_id.to_owned(),
+                url: url.as_ref().map(|url| issue_url(url, ref_id)),
+            });
+        }
+        references
+    }
+}
+
+/// Fill in the placeholders of the URL template `url` for the reference `ref_id`.
+fn issue_url(url: &str, ref_id: &str) -> String {
+    let number = ref_id
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .find(|part| !part.is_empty())
+        .unwrap_or_default();
+    url.replace("{ref}", ref_id).replace("{number}", number)
+}
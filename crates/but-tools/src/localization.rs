@@ -0,0 +1,160 @@
+//! Descriptions of tools and their parameters in other languages, for hosts that show them to their users.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use gitbutler_command_context::CommandContext;
+
+use crate::tool::{Tool, ToolOptions};
+
+/// The descriptions of a tool and of its parameters, in one language.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolDescription {
+    /// The description of the tool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The descriptions of the parameters of the tool, keyed by their name in the parameter schema, like `messageTitle`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, String>,
+}
+
+/// The descriptions of tools and of their parameters in one language, keyed by the name of the tool.
+///
+/// As JSON, they read `{ "<tool>": { "description": "…", "parameters": { "<parameter>": "…" } } }`.
+/// Every key is optional, and the built-in English description is used wherever one is missing or empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct ToolDescriptions {
+    tools: BTreeMap<String, ToolDescription>,
+}
+
+impl ToolDescriptions {
+    /// Parse the descriptions from a JSON bundle.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("Failed to parse the tool descriptions: {}", e))
+    }
+
+    /// Return the built-in English descriptions of `tools`, to be used as a template for translations.
+    ///
+    /// Only the parameters at the top level of the parameter schemas are included, as only these can be replaced.
+    pub fn of_tools<'a>(tools: impl IntoIterator<Item = &'a Arc<dyn Tool>>) -> Self {
+        let tools = tools
+            .into_iter()
+            .map(|tool| {
+                let schema = tool.parameters();
+                let parameters = schema["properties"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(name, property)| {
+                        Some((name.clone(), property["description"].as_str()?.to_owned()))
+                    })
+                    .collect();
+                let description = ToolDescription {
+                    description: Some(tool.description()),
+                    parameters,
+                };
+                (tool.name(), description)
+            })
+            .collect();
+        ToolDescriptions { tools }
+    }
+
+    /// Return the descriptions of the tool `name`, if there are any.
+    pub fn get(&self, name: &str) -> Option<&ToolDescription> {
+        self.tools.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Return `tool` with its descriptions replaced by the ones for it, or `tool` itself if there are none.
+    pub fn localize(&self, tool: Arc<dyn Tool>) -> Arc<dyn Tool> {
+        match self.tools.get(&tool.name()) {
+            Some(description) => Arc::new(LocalizedTool {
+                tool,
+                description: description.clone(),
+            }),
+            None => tool,
+        }
+    }
+}
+
+/// A tool whose descriptions are replaced by the ones in another language, where these aren't empty.
+struct LocalizedTool {
+    tool: Arc<dyn Tool>,
+    description: ToolDescription,
+}
+
+/// Return `text`, unless it's blank.
+fn non_blank(text: &str) -> Option<&str> {
+    (!text.trim().is_empty()).then_some(text)
+}
+
+impl Tool for LocalizedTool {
+    fn name(&self) -> String {
+        self.tool.name()
+    }
+
+    fn description(&self) -> String {
+        self.description
+            .description
+            .as_deref()
+            .and_then(non_blank)
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| self.tool.description())
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let mut schema = self.tool.parameters();
+        if let Some(properties) = schema
+            .get_mut("properties")
+            .and_then(serde_json::Value::as_object_mut)
+        {
+            for (name, description) in &self.description.parameters {
+                let Some(description) = non_blank(description) else {
+                    continue;
+                };
+                if let Some(property) = properties
+                    .get_mut(name)
+                    .and_then(serde_json::Value::as_object_mut)
+                {
+                    property.insert("description".into(), description.into());
+                }
+            }
+        }
+        schema
+    }
+
+    fn version(&self) -> u32 {
+        self.tool.version()
+    }
+
+    fn host_only(&self) -> bool {
+        self.tool.host_only()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        self.tool.mutates_workspace()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        self.tool.estimated_duration_ms()
+    }
+
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        self.tool.capabilities()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        self.tool.clone().call(parameters, ctx, app_handle, options)
+    }
+}
@@ -0,0 +1,141 @@
+//! Sanitization of the text that tools persist, like commit messages and branch descriptions.
+//!
+//! Agents sometimes echo the scaffolding of our own tool descriptions, or large parts of the diffs they were shown,
+//! and none of that should end up in commits or in the virtual branches state.
+
+use std::fmt;
+
+/// The markers of the scaffolding used in tool and parameter descriptions.
+pub const DEFAULT_STRIPPED_PATTERNS: &[&str] = &[
+    "<description>",
+    "</description>",
+    "<important_notes>",
+    "</important_notes>",
+];
+
+/// The maximum number of characters of a branch description, by default.
+pub const DEFAULT_MAX_DESCRIPTION_LEN: usize = 2_000;
+
+/// The maximum number of characters of a commit message title, by default.
+pub const DEFAULT_MAX_MESSAGE_TITLE_LEN: usize = 200;
+
+/// The maximum number of characters of a commit message body, by default.
+pub const DEFAULT_MAX_MESSAGE_BODY_LEN: usize = 10_000;
+
+/// Cleans up commit messages and branch descriptions before they are persisted.
+#[derive(Debug, Clone)]
+pub struct MessageSanitizer {
+    /// Patterns that are removed verbatim wherever they appear.
+    pub stripped_patterns: Vec<String>,
+    /// The number of characters after which branch descriptions are truncated.
+    pub max_description_len: usize,
+    /// The number of characters after which commit message titles are truncated.
+    pub max_message_title_len: usize,
+    /// The number of characters after which commit message bodies are truncated.
+    pub max_message_body_len: usize,
+}
+
+impl Default for MessageSanitizer {
+    fn default() -> Self {
+        MessageSanitizer {
+            stripped_patterns: DEFAULT_STRIPPED_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            max_description_len: DEFAULT_MAX_DESCRIPTION_LEN,
+            max_message_title_len: DEFAULT_MAX_MESSAGE_TITLE_LEN,
+            max_message_body_len: DEFAULT_MAX_MESSAGE_BODY_LEN,
+        }
+    }
+}
+
+impl MessageSanitizer {
+    /// Return the commit message made of `title` and `body`, with the stripped patterns removed and both truncated.
+    /// Without a body, the message is just the title, without the blank line that separates it from the body.
+    ///
+    /// Fails with [`EmptyMessageError`] if nothing but whitespace is left,
+    /// and with [`EmptyTitleError`] if only the body is left.
+    pub fn commit_message(&self, title: &str, body: &str) -> anyhow::Result<String> {
+        let title = self.strip(title);
+        let body = self.strip(body);
+        let (title, body) = (title.trim(), body.trim());
+        if title.is_empty() && body.is_empty() {
+            return Err(EmptyMessageError.into());
+        }
+        if title.is_empty() {
+            return Err(EmptyTitleError.into());
+        }
+        let title = truncate_title(title, self.max_message_title_len);
+        if body.is_empty() {
+            return Ok(title);
+        }
+        Ok(format!(
+            "{}\n\n{}",
+            title,
+            truncate(body, self.max_message_body_len)
+        ))
+    }
+
+    /// Return `description` with the stripped patterns removed and truncated.
+    pub fn description(&self, description: &str) -> String {
+        truncate(self.strip(description).trim(), self.max_description_len)
+    }
+
+    /// Return `text` with all stripped patterns removed.
+    pub fn strip(&self, text: &str) -> String {
+        self.stripped_patterns
+            .iter()
+            .filter(|pattern| !pattern.is_empty())
+            .fold(text.to_owned(), |text, pattern| {
+                text.replace(pattern.as_str(), "")
+            })
+    }
+}
+
+/// Return `text`, cut off after `max_len` characters with a marker saying how much was left out.
+fn truncate(text: &str, max_len: usize) -> String {
+    let len = text.chars().count();
+    if len <= max_len {
+        return text.to_owned();
+    }
+    let kept = text.chars().take(max_len).collect::<String>();
+    format!("{}\n[{} characters truncated]", kept, len - max_len)
+}
+
+/// Like [`truncate()`], but keep the marker on the same line so the title stays the first line of the message.
+fn truncate_title(title: &str, max_len: usize) -> String {
+    let len = title.chars().count();
+    if len <= max_len {
+        return title.to_owned();
+    }
+    let kept = title.chars().take(max_len).collect::<String>();
+    format!(
+        "{} [{} characters truncated]",
+        kept.trim_end(),
+        len - max_len
+    )
+}
+
+/// The error returned if a commit message is empty after sanitization.
+#[derive(Debug, Clone, Copy)]
+pub struct EmptyMessageError;
+
+impl fmt::Display for EmptyMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("The commit message is empty")
+    }
+}
+
+impl std::error::Error for EmptyMessageError {}
+
+/// The error returned if the title of a commit message is empty after sanitization, while its body isn't.
+#[derive(Debug, Clone, Copy)]
+pub struct EmptyTitleError;
+
+impl fmt::Display for EmptyTitleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("The title of the commit message is empty")
+    }
+}
+
+impl std::error::Error for EmptyTitleError {}
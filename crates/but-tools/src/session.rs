@@ -0,0 +1,295 @@
+//! Summarize what the tools of a [`Toolset`](crate::tool::Toolset) did, for hosts to render at the end of an agent run.
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+/// A call of a tool, as it's recorded by the toolset.
+#[derive(Debug, Clone)]
+pub struct ToolCallRecord {
+    /// The name of the called tool.
+    pub name: String,
+    /// The id of the message the tool was called for, if there is one.
+    pub message_id: Option<String>,
+    /// The parameters the tool was called with, or `null` if they aren't valid JSON.
+    pub parameters: Value,
+    /// The result of the tool, as it was returned to the caller.
+    pub result: Value,
+    /// The snapshots that were recorded while the tool ran, oldest first.
+    pub snapshots: Vec<RecordedSnapshot>,
+}
+
+/// A snapshot of the oplog.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedSnapshot {
+    /// The id of the snapshot.
+    pub id: String,
+    /// The title of the snapshot, which names the operation it was taken for.
+    pub title: String,
+}
+
+/// What the tools of a toolset did, either for a single message or for the whole lifetime of the toolset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSummary {
+    /// The message the summary is scoped to, or `None` if it covers all calls.
+    pub message_id: Option<String>,
+    /// All tool calls in the order they were made, including the failed ones.
+    pub tool_calls: Vec<ToolCallSummary>,
+    /// The names of the branches that were created.
+    pub branches_created: Vec<String>,
+    /// The commits that were created or rewritten, in the order it happened.
+    pub commits: Vec<CommitActivity>,
+    /// The paths of the files that were committed or moved between commits, sorted.
+    pub files_touched: Vec<String>,
+    /// The snapshots that were recorded, oldest first.
+    pub snapshots: Vec<RecordedSnapshot>,
+    /// The warnings that tools reported along the way.
+    pub warnings: Vec<String>,
+}
+
+/// A single tool call, and whether it succeeded.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallSummary {
+    /// The name of the called tool.
+    pub name: String,
+    /// Whether the tool succeeded.
+    pub succeeded: bool,
+    /// The error of the tool if it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A commit that was created or rewritten by a tool.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitActivity {
+    /// What happened to the commit.
+    pub action: CommitAction,
+    /// The id of the commit after the tool ran.
+    pub id: String,
+    /// The title of the commit message, if it's known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The branch of the commit, if it's known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+}
+
+/// What happened to a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommitAction {
+    Created,
+    Amended,
+    Reworded,
+    Moved,
+    Copied,
+    Recovered,
+}
+
+impl SessionSummary {
+    /// Summarize `calls`, only considering the ones made for `message_id` if it's set.
+    pub fn from_calls<'a>(
+        calls: impl IntoIterator<Item = &'a ToolCallRecord>,
+        message_id: Option<&str>,
+    ) -> Self {
+        let mut summary = SessionSummary {
+            message_id: message_id.map(ToOwned::to_owned),
+            ..Default::default()
+        };
+        let mut files_touched = BTreeSet::new();
+        for call in calls {
+            if message_id.is_some_and(|message_id| call.message_id.as_deref() != Some(message_id)) {
+                continue;
+            }
+            summary.snapshots.extend(call.snapshots.iter().cloned());
+
+            let error = call.result.get("error").map(text);
+            summary.tool_calls.push(ToolCallSummary {
+                name: call.name.clone(),
+                succeeded: error.is_none(),
+                error: error.clone(),
+            });
+            if error.is_some() {
+                continue;
+            }
+
+            let result = &call.result["result"];
+            summary.record_activity(call, result);
+            files_touched.extend(touched_files(call, result));
+            if let Some(warnings) = result.get("warnings").and_then(Value::as_array) {
+                summary.warnings.extend(warnings.iter().map(|warning| {
+                    format!(
+                        "{}: '{}' was skipped: {}",
+                        call.name,
+                        text(&warning["path"]),
+                        text(&warning["error"])
+                    )
+                }));
+            }
+            if let Some(rejections) = result
+                .get("pathsToRejectedChanges")
+                .and_then(Value::as_array)
+            {
+                summary.warnings.extend(rejections.iter().map(|rejection| {
+                    format!(
+                        "{}: changes to '{}' were rejected: {}",
+                        call.name,
+                        text(&rejection[1]),
+                        text(&rejection[0])
+                    )
+                }));
+            }
+        }
+        summary.files_touched = files_touched.into_iter().collect();
+        summary
+    }
+
+    /// Record the branches and commits created or rewritten by the successful `call` with `result`.
+    fn record_activity(&mut self, call: &ToolCallRecord, result: &Value) {
+        let parameters = &call.parameters;
+        let title = || string(&parameters["messageTitle"]).filter(|title| !title.is_empty());
+        match call.name.as_str() {
+            "create_branch" => {
+                self.branch_created(string(&parameters["branchName"]));
+            }
+            "commit" => {
+                let action = if parameters["amendHead"].as_bool() == Some(true) {
+                    CommitAction::Amended
+                } else {
+                    CommitAction::Created
+                };
+                self.commit(
+                    action,
+                    &result["newCommit"],
+                    title(),
+                    string(&parameters["branchName"]),
+                );
+            }
+            "amend" => self.commit(CommitAction::Amended, &result["newCommit"], title(), None),
+            "reassign_and_commit" => {
+                self.commit(CommitAction::Created, &result["newCommit"], title(), None)
+            }
+            "reword_commits" => {
+                let rewritten = result.as_array().into_iter().flatten();
+                let updates = parameters["commits"].as_array();
+                for commit in rewritten {
+                    let old_id = string(&commit["oldId"]).unwrap_or_default();
+                    // Commits that were only rebased because a commit below them was reworded aren't listed.
+                    let Some(update) = updates.into_iter().flatten().find(|update| {
+                        string(&update["commitId"])
+                            .is_some_and(|id| !id.is_empty() && old_id.starts_with(&id))
+                    }) else {
+                        continue;
+                    };
+                    self.commit(
+                        CommitAction::Reworded,
+                        &commit["newId"],
+                        string(&update["messageTitle"]),
+                        None,
+                    );
+                }
+            }
+            "add_trailer_to_commit" => {
+                let commit_id = string(&parameters["commitId"]).unwrap_or_default();
+                // Only the commit the trailer was added to is listed, not the ones above it.
+                if let Some(commit) = result.as_array().into_iter().flatten().find(|commit| {
+                    !commit_id.is_empty()
+                        && string(&commit["oldId"]).is_some_and(|id| id.starts_with(&commit_id))
+                }) {
+                    self.commit(CommitAction::Reworded, &commit["newId"], None, None);
+                }
+            }
+            "move_commit_to_new_branch" => {
+                let branch = string(&result["newBranch"]["name"]);
+                self.branch_created(branch.clone());
+                if let Some(commit) = result["newBranch"]["commits"]
+                    .as_array()
+                    .and_then(|commits| commits.first())
+                {
+                    self.commit(
+                        CommitAction::Moved,
+                        &commit["id"],
+                        string(&commit["messageTitle"]),
+                        branch,
+                    );
+                }
+            }
+            "copy_commit_to_stack" => self.commit(
+                CommitAction::Copied,
+                &result["newOid"],
+                None,
+                string(&result["branchName"]),
+            ),
+            "recover_commit" => self.commit(
+                CommitAction::Recovered,
+                &result["newOid"],
+                None,
+                string(&result["branchName"]),
+            ),
+            _ => {}
+        }
+    }
+
+    fn branch_created(&mut self, name: Option<String>) {
+        if let Some(name) = name.filter(|name| !self.branches_created.contains(name)) {
+            self.branches_created.push(name);
+        }
+    }
+
+    fn commit(
+        &mut self,
+        action: CommitAction,
+        id: &Value,
+        title: Option<String>,
+        branch: Option<String>,
+    ) {
+        // Tools that didn't end up writing a commit, like a rejected commit, don't return its id.
+        if let Some(id) = string(id) {
+            self.commits.push(CommitActivity {
+                action,
+                id,
+                title,
+                branch,
+            });
+        }
+    }
+}
+
+/// Return the paths of the files the successful `call` with `result` committed or moved.
+fn touched_files(call: &ToolCallRecord, result: &Value) -> Vec<String> {
+    let committed_files = result["committedFiles"].as_array().into_iter().flatten();
+    let mut files = committed_files
+        .filter_map(|file| string(&file["path"]))
+        .collect::<Vec<_>>();
+    if call.name == "restore_file_from_commit" {
+        files.extend(
+            string(&call.parameters["path"]).map(|path| crate::file_params::normalize_path(&path)),
+        );
+    }
+    if call.name == "move_file_changes" && call.parameters["predictOnly"].as_bool() != Some(true) {
+        files.extend(
+            call.parameters["files"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(string)
+                .map(|file| crate::file_params::normalize_path(&file)),
+        );
+    }
+    files
+}
+
+fn string(value: &Value) -> Option<String> {
+    value.as_str().map(ToOwned::to_owned)
+}
+
+/// Return `value` as text, without quotes if it's a string.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
@@ -1,42 +1,789 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
 
+use bstr::ByteSlice;
 use but_workspace::ui::StackEntry;
 use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::OplogExt;
+use gitbutler_oplog::entry::{OperationKind, SnapshotDetails};
+use gitbutler_oxidize::OidExt;
+use gitbutler_project::{Project, ProjectId};
+use schemars::{JsonSchema, schema_for};
 use serde_json::json;
 
-use crate::emit::EmitToolCall;
+use crate::branch_name::AmbiguousBranchNameError;
+use crate::commit_id::{
+    AmbiguousCommitIdError, InvalidCommitIdError, is_abbreviated, resolve_commit_id,
+};
+use crate::emit::{EmitToolCall, record_events};
+use crate::invariants::verify_workspace_invariants;
+use crate::localization::ToolDescriptions;
+use crate::sanitize::{EmptyMessageError, EmptyTitleError, MessageSanitizer};
+use crate::session::{RecordedSnapshot, SessionSummary, ToolCallRecord};
+use crate::workspace::{
+    AssignmentsCache, BranchIntegratedError, ForeignLocksError, InvalidAuthorDateError,
+};
+
+/// The version of the tool API, as `<major>.<minor>`.
+///
+/// The major version is bumped whenever the parameters or results of a tool change incompatibly, which also bumps the
+/// [version of the tool](Tool::version()), the minor version for compatible additions.
+/// An incompatible change of the envelope all results are wrapped in bumps the major version and the version of all tools.
+/// Every bump has to be recorded in [`TOOL_API_CHANGELOG`].
+pub const TOOL_API_VERSION: &str = "10.2";
+
+/// A change to the tool API, made in a particular version of it.
+#[derive(Debug, Clone, Copy)]
+pub struct ToolApiChange {
+    /// The version of the tool API the change was made in.
+    pub api_version: &'static str,
+    /// The names of the tools that were added or changed, along with their [version](Tool::version()) after the change.
+    ///
+    /// This is never empty, as compatible changes to the toolset itself, like new error codes or result metadata,
+    /// don't bump the version.
+    pub tools: &'static [(&'static str, u32)],
+}
+
+/// All changes to the tool API, ordered from oldest to newest.
+///
+/// Whenever a tool is added or changed, it has to be listed here along with the version of the tool API it was changed in,
+/// and the latest entry is always for [`TOOL_API_VERSION`].
+pub const TOOL_API_CHANGELOG: &[ToolApiChange] = &[
+    ToolApiChange {
+        api_version: "1.0",
+        tools: &[
+            ("amend", 1),
+            ("commit", 1),
+            ("create_blank_commit", 1),
+            ("create_branch", 1),
+            ("get_branch_description", 1),
+            ("get_commit_details", 1),
+            ("get_diff_for_paths", 1),
+            ("get_project_status", 1),
+            ("move_file_changes", 1),
+            ("set_branch_description", 1),
+        ],
+    },
+    ToolApiChange {
+        api_version: "1.1",
+        tools: &[("get_stack_order", 1)],
+    },
+    ToolApiChange {
+        api_version: "1.2",
+        tools: &[("get_tool_schema", 1)],
+    },
+    ToolApiChange {
+        api_version: "1.3",
+        tools: &[("get_project_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "1.4",
+        tools: &[("move_commit_to_new_branch", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.0",
+        tools: &[
+            ("amend", 2),
+            ("commit", 2),
+            ("create_blank_commit", 2),
+            ("create_branch", 1),
+            ("set_branch_description", 1),
+        ],
+    },
+    ToolApiChange {
+        api_version: "2.1",
+        tools: &[("get_commit_ancestry", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.2",
+        tools: &[("split_rename", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.3",
+        tools: &[("get_orphaned_commits", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.4",
+        tools: &[("get_stacks_summary", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.5",
+        tools: &[("recover_commit", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.6",
+        tools: &[("get_project_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.7",
+        tools: &[("commit", 2)],
+    },
+    ToolApiChange {
+        api_version: "2.8",
+        tools: &[("get_pr_description", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.9",
+        tools: &[("get_project_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.10",
+        tools: &[("move_file_changes", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.11",
+        tools: &[("repo_query", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.12",
+        tools: &[("get_project_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.13",
+        tools: &[("get_recent_activity", 1)],
+    },
+    ToolApiChange {
+        api_version: "2.14",
+        tools: &[("amend", 2), ("commit", 2)],
+    },
+    ToolApiChange {
+        api_version: "2.15",
+        tools: &[("get_git_attributes", 1)],
+    },
+    ToolApiChange {
+        api_version: "3.0",
+        tools: &[("commit", 3)],
+    },
+    ToolApiChange {
+        api_version: "3.1",
+        tools: &[("get_commit_by_message", 1)],
+    },
+    ToolApiChange {
+        api_version: "3.2",
+        tools: &[("get_worktree_lock_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "3.3",
+        tools: &[("reassign_and_commit", 1)],
+    },
+    ToolApiChange {
+        api_version: "3.4",
+        tools: &[("get_change_stats_by_stack", 1)],
+    },
+    ToolApiChange {
+        api_version: "3.5",
+        tools: &[("get_conflicting_stack_pairs", 1)],
+    },
+    ToolApiChange {
+        api_version: "3.6",
+        tools: &[("get_diff_for_paths", 1), ("get_project_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "4.0",
+        tools: &[("amend", 3), ("commit", 4), ("create_blank_commit", 3)],
+    },
+    ToolApiChange {
+        api_version: "4.1",
+        tools: &[
+            ("get_branch_upstream_commits", 1),
+            ("get_project_status", 1),
+        ],
+    },
+    ToolApiChange {
+        api_version: "4.2",
+        tools: &[("get_stack_merge_order", 1)],
+    },
+    ToolApiChange {
+        api_version: "4.3",
+        tools: &[("get_changes_since", 1)],
+    },
+    ToolApiChange {
+        api_version: "4.4",
+        tools: &[("get_file_renames", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.0",
+        tools: &[("commit", 5)],
+    },
+    ToolApiChange {
+        api_version: "5.1",
+        tools: &[("get_project_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.2",
+        tools: &[("get_affected_tests", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.3",
+        tools: &[("get_project_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.4",
+        tools: &[("get_branch_rebase_preview", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.5",
+        tools: &[("get_commit_diff_stat", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.6",
+        tools: &[("amend", 3), ("commit", 5)],
+    },
+    ToolApiChange {
+        api_version: "5.7",
+        tools: &[("preview_integration", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.8",
+        tools: &[("get_commit_signature_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.9",
+        tools: &[("get_staged_commit_preview", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.10",
+        tools: &[("get_target_branch_commits", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.11",
+        tools: &[("reword_commits", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.12",
+        tools: &[("amend", 3), ("commit", 5)],
+    },
+    ToolApiChange {
+        api_version: "5.13",
+        tools: &[("copy_commit_to_stack", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.14",
+        tools: &[("get_related_commits", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.15",
+        tools: &[("get_workspace_health", 1)],
+    },
+    ToolApiChange {
+        api_version: "5.16",
+        tools: &[("commit", 5)],
+    },
+    ToolApiChange {
+        api_version: "6.0",
+        tools: &[("get_diff_for_paths", 2), ("get_project_status", 2)],
+    },
+    ToolApiChange {
+        api_version: "6.1",
+        tools: &[("get_stack_diff_summary", 1)],
+    },
+    ToolApiChange {
+        api_version: "6.2",
+        tools: &[("repair_workspace", 1)],
+    },
+    ToolApiChange {
+        api_version: "6.3",
+        tools: &[
+            ("add_trailer_to_commit", 1),
+            ("get_commit_signoff_trailers", 1),
+        ],
+    },
+    ToolApiChange {
+        api_version: "6.4",
+        tools: &[("restore_file_from_commit", 1)],
+    },
+    ToolApiChange {
+        api_version: "6.5",
+        tools: &[("diff_against_snapshot", 1)],
+    },
+    ToolApiChange {
+        api_version: "6.6",
+        tools: &[("get_unpushed_work", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.0",
+        tools: &[("commit", 6), ("create_branch", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.1",
+        tools: &[("get_cross_stack_diff", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.2",
+        tools: &[("get_branch_name_from_diff", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.3",
+        tools: &[("get_conflicting_hunks", 1), ("resolve_conflict", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.4",
+        tools: &[("prune_stale_assignments", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.5",
+        tools: &[("get_commit_message_body", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.6",
+        tools: &[("get_project_status", 2)],
+    },
+    ToolApiChange {
+        api_version: "7.7",
+        tools: &[("get_branch_linked_issues", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.8",
+        tools: &[("get_workspace_token_estimate", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.9",
+        tools: &[("get_file_move_history", 1)],
+    },
+    ToolApiChange {
+        api_version: "7.10",
+        tools: &[("get_project_status", 2)],
+    },
+    ToolApiChange {
+        api_version: "7.11",
+        tools: &[("amend", 3)],
+    },
+    ToolApiChange {
+        api_version: "7.12",
+        tools: &[("get_stack_commit_map", 1)],
+    },
+    ToolApiChange {
+        api_version: "8.0",
+        tools: &[("amend", 4)],
+    },
+    ToolApiChange {
+        api_version: "9.0",
+        tools: &[("get_diff_for_paths", 3)],
+    },
+    ToolApiChange {
+        api_version: "10.0",
+        tools: &[("get_commit_details", 2)],
+    },
+    ToolApiChange {
+        api_version: "10.1",
+        tools: &[("get_worktree_lock_status", 1)],
+    },
+    ToolApiChange {
+        api_version: "10.2",
+        tools: &[("reassign_and_commit", 1)],
+    },
+];
+
+/// The features of the toolset itself, which are available no matter which tools are registered.
+///
+/// Hosts detect features through [`Toolset::capabilities()`] instead of comparing versions,
+/// so new features have to be listed here as they land.
+const TOOLSET_CAPABILITIES: &[&str] = &[
+    "errors.codes",
+    "errors.context",
+    "results.resolvedCommitIds",
+    "toolset.apiVersionCheck",
+    "toolset.contextLines",
+    "toolset.hostOnlyTools",
+    "toolset.paranoid",
+    "toolset.sessionSummary",
+    "toolset.staleContext",
+    "toolset.strict",
+];
+
+/// The features a toolset supports, by their key, like `errors.codes` or `commit.forceNewBranch`.
+///
+/// The value is `true`, or metadata about the feature, like the formats it supports.
+pub type Capabilities = BTreeMap<String, serde_json::Value>;
+
+/// Return the capabilities of a toolset with `tools`.
+///
+/// Besides the features of the toolset and the ones [declared by the tools](Tool::capabilities()),
+/// each tool is listed as `tools.<name>`, along with its version.
+pub fn capabilities_of<'a>(tools: impl IntoIterator<Item = &'a Arc<dyn Tool>>) -> Capabilities {
+    let mut capabilities = TOOLSET_CAPABILITIES
+        .iter()
+        .map(|key| (key.to_string(), json!(true)))
+        .collect::<Capabilities>();
+    capabilities.insert("toolset.apiVersion".into(), TOOL_API_VERSION.into());
+    for tool in tools {
+        let mut metadata = json!({ "version": tool.version() });
+        if tool.host_only() {
+            metadata["hostOnly"] = true.into();
+        }
+        capabilities.insert(format!("tools.{}", tool.name()), metadata);
+        capabilities.extend(tool.capabilities());
+    }
+    capabilities
+}
+
+/// Return the major version of `version`, which is expected to be in the format of [`TOOL_API_VERSION`].
+fn major_version(version: &str) -> Option<u32> {
+    version.split('.').next()?.trim().parse().ok()
+}
 
 pub struct Toolset<'a> {
     ctx: &'a mut CommandContext,
     app_handle: Option<&'a tauri::AppHandle>,
-    message_id: Option<String>,
     tools: BTreeMap<String, Arc<dyn Tool>>,
+    options: ToolOptions,
+    fingerprint: ProjectFingerprint,
+    host_only_tools_allowed: bool,
+    call_metadata: bool,
+    calls: Vec<ToolCallRecord>,
+}
+
+/// Identifies the project a [`Toolset`] was created for, so hosts can tell when it doesn't match the open project anymore.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectFingerprint {
+    /// The id of the project.
+    pub project_id: ProjectId,
+    /// The path to the worktree of the project.
+    pub path: PathBuf,
+    /// The path to the directory GitButler keeps its state of the project in.
+    pub gb_dir: PathBuf,
+}
+
+impl ProjectFingerprint {
+    /// Capture the fingerprint of `project`, to compare it with the one of a toolset.
+    pub fn new(project: &Project) -> Self {
+        ProjectFingerprint {
+            project_id: project.id,
+            path: project.path.clone(),
+            gb_dir: project.gb_dir(),
+        }
+    }
+}
+
+/// The error returned if the context of a toolset doesn't refer to a usable project anymore,
+/// for instance because the project directory was moved.
+///
+/// The host should rebuild the toolset with a fresh context.
+#[derive(Debug, Clone)]
+pub struct StaleContextError {
+    reason: String,
+}
+
+impl fmt::Display for StaleContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The project context is stale and the toolset has to be rebuilt: {}",
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for StaleContextError {}
+
+/// The settings of a [`Toolset`] that are passed to the tools it calls.
+#[derive(Debug, Clone)]
+pub struct ToolOptions {
+    /// How the commit messages and branch descriptions passed to tools are sanitized.
+    pub sanitizer: MessageSanitizer,
+    /// If true, inputs and changes that tools would skip silently make them fail instead.
+    ///
+    /// See [`Toolset::set_strict()`] for the behaviors this affects.
+    pub strict: bool,
+    /// If true, the workspace invariants are verified after each tool that mutates the workspace.
+    ///
+    /// See [`Toolset::set_paranoid()`] for details.
+    pub paranoid: bool,
+    /// The ID of the message the tools are called for, which is attached to the events they emit.
+    pub message_id: Option<String>,
+    /// The number of context lines in diffs, instead of the one in the app settings.
+    ///
+    /// See [`Toolset::set_context_lines()`] for details.
+    pub context_lines: Option<u32>,
+    /// If true, hunk assignments that don't match any uncommitted change anymore are removed when listing changes.
+    ///
+    /// See [`Toolset::set_auto_prune()`] for details.
+    pub auto_prune: bool,
+    /// The descriptions of the tools and their parameters that replace the built-in English ones.
+    ///
+    /// See [`Toolset::set_descriptions()`] for details.
+    pub descriptions: ToolDescriptions,
+    /// The hunk assignments of the last call that listed the uncommitted changes, to reuse them in the next one.
+    ///
+    /// See [`Toolset::set_assignments_cache()`] for details.
+    pub assignments_cache: AssignmentsCache,
 }
 
+impl Default for ToolOptions {
+    fn default() -> Self {
+        ToolOptions {
+            sanitizer: MessageSanitizer::default(),
+            strict: false,
+            paranoid: cfg!(debug_assertions),
+            message_id: None,
+            context_lines: None,
+            auto_prune: false,
+            descriptions: ToolDescriptions::default(),
+            assignments_cache: AssignmentsCache::default(),
+        }
+    }
+}
+
+/// The error returned by tools in [strict mode](Toolset::set_strict()) instead of skipping something silently.
+#[derive(Debug, Clone)]
+pub struct StrictModeError {
+    reason: String,
+}
+
+impl StrictModeError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        StrictModeError {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for StrictModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (strict mode)", self.reason)
+    }
+}
+
+impl std::error::Error for StrictModeError {}
+
+/// The error returned if a [host-only](Tool::host_only()) tool is called without the permission of the host.
+#[derive(Debug, Clone)]
+pub struct HostOnlyToolError {
+    name: String,
+}
+
+impl fmt::Display for HostOnlyToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The tool '{}' can only be called if the host allows it",
+            self.name
+        )
+    }
+}
+
+impl std::error::Error for HostOnlyToolError {}
+
 impl<'a> Toolset<'a> {
     pub fn new(
         ctx: &'a mut CommandContext,
         app_handle: Option<&'a tauri::AppHandle>,
         message_id: Option<String>,
     ) -> Self {
+        let fingerprint = ProjectFingerprint::new(ctx.project());
         Toolset {
             ctx,
             app_handle,
-            message_id,
             tools: BTreeMap::new(),
+            options: ToolOptions {
+                message_id,
+                ..ToolOptions::default()
+            },
+            fingerprint,
+            host_only_tools_allowed: false,
+            call_metadata: false,
+            calls: vec![],
         }
     }
 
+    /// Set the id of the message the following tool calls are made for, so one toolset can be used for several messages.
+    pub fn set_message_id(&mut self, message_id: Option<String>) {
+        self.options.message_id = message_id;
+    }
+
+    /// Summarize what the tools called through this toolset did, only for the calls made for `message_id` if it's set,
+    /// or for all calls otherwise.
+    ///
+    /// Failed calls are listed as well.
+    pub fn session_summary(&self, message_id: Option<&str>) -> SessionSummary {
+        SessionSummary::from_calls(&self.calls, message_id)
+    }
+
+    /// The project the toolset was created for.
+    pub fn project_fingerprint(&self) -> &ProjectFingerprint {
+        &self.fingerprint
+    }
+
+    /// Fail with [`StaleContextError`] if the project of the context can't be used anymore.
+    fn ensure_context_is_fresh(&self) -> Result<(), StaleContextError> {
+        let project = self.ctx.project();
+        let reason = if project.id != self.fingerprint.project_id {
+            format!(
+                "the project changed from {} to {}",
+                self.fingerprint.project_id, project.id
+            )
+        } else if !project.path.is_dir() {
+            format!(
+                "the project directory '{}' doesn't exist anymore",
+                project.path.display()
+            )
+        } else if std::fs::read_dir(project.gb_dir()).is_err() {
+            format!("'{}' can't be read", project.gb_dir().display())
+        } else {
+            return Ok(());
+        };
+        Err(StaleContextError { reason })
+    }
+
+    /// Set how the commit messages and branch descriptions passed to tools are sanitized.
+    pub fn set_message_sanitizer(&mut self, sanitizer: MessageSanitizer) {
+        self.options.sanitizer = sanitizer;
+    }
+
+    pub fn message_sanitizer(&self) -> &MessageSanitizer {
+        &self.options.sanitizer
+    }
+
+    /// Set the cache of the hunk assignments that tools listing the uncommitted changes reuse if the worktree didn't change,
+    /// to share it with other toolsets of the same project. Each toolset has its own cache by default.
+    pub fn set_assignments_cache(&mut self, assignments_cache: AssignmentsCache) {
+        self.options.assignments_cache = assignments_cache;
+    }
+
+    /// The options passed to the tools called through this toolset, to call a tool obtained from [`Self::get()`] directly.
+    pub fn options(&self) -> &ToolOptions {
+        &self.options
+    }
+
+    /// Set if tools fail with a [`StrictModeError`] instead of silently skipping what they can't handle,
+    /// for callers that need deterministic, fail-fast behavior. This is off by default.
+    ///
+    /// Strict mode affects:
+    ///   - `commit` and `amend`, which skip files that have no uncommitted changes, e.g. because the path is unknown.
+    ///   - `get_project_status` and `get_diff_for_paths`, which leave out changed files that can't be read,
+    ///     and changed files without a textual diff, like binary files, files that are too large and submodules.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.options.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.options.strict
+    }
+
+    /// Set if the [workspace invariants](verify_workspace_invariants()) are verified after calling a tool that
+    /// [mutates the workspace](Tool::mutates_workspace()), to catch corruption at the operation that caused it.
+    /// This is on by default in debug builds, and off in release builds.
+    ///
+    /// Violations are attached to the result of the tool as `invariantViolations`, without failing the call.
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.options.paranoid = paranoid;
+    }
+
+    /// Set if the [metadata](ToolCallMetadata) of each call, like how long it took and which events it emitted,
+    /// is attached to the result of the tool as `metadata`.
+    ///
+    /// This is off by default, so results stay the same for hosts that don't look at them.
+    pub fn set_call_metadata(&mut self, include: bool) {
+        self.call_metadata = include;
+    }
+
+    pub fn is_paranoid(&self) -> bool {
+        self.options.paranoid
+    }
+
+    /// Set the number of unchanged lines shown around each change in the diffs of all tools called through this toolset,
+    /// in place of the one in the app settings, which remains unchanged. Pass `None` to use the app settings again.
+    ///
+    /// Tools that accept `contextLines` still use the number passed to them instead.
+    pub fn set_context_lines(&mut self, context_lines: Option<u32>) {
+        self.options.context_lines = context_lines;
+    }
+
+    /// Set if `get_project_status` removes persisted hunk assignments that don't match
+    /// any uncommitted hunk anymore before listing the changes, like the `prune_stale_assignments` tool does.
+    /// At most [`AUTO_PRUNE_LIMIT`](crate::workspace::AUTO_PRUNE_LIMIT) assignments are removed per call.
+    ///
+    /// This is off by default, so listing changes doesn't write to the assignment store.
+    pub fn set_auto_prune(&mut self, auto_prune: bool) {
+        self.options.auto_prune = auto_prune;
+    }
+
+    pub fn context_lines(&self) -> Option<u32> {
+        self.options.context_lines
+    }
+
+    /// Set the descriptions of the tools and their parameters that replace the built-in English ones,
+    /// for hosts that show them to users in another language.
+    ///
+    /// They are used by [`Self::definitions()`], by the tools returned from [`Self::get()`] and [`Self::list()`],
+    /// and by [`GetToolSchema`]. Descriptions that are missing or empty fall back to the English ones.
+    pub fn set_descriptions(&mut self, descriptions: ToolDescriptions) {
+        self.options.descriptions = descriptions;
+    }
+
+    pub fn descriptions(&self) -> &ToolDescriptions {
+        &self.options.descriptions
+    }
+
+    /// Set if [host-only](Tool::host_only()) tools are listed and can be called.
+    ///
+    /// They are hidden by default, so they aren't exposed to the LLM unless the host allows it.
+    /// Tools that describe the toolset, like [`GetToolSchema`], only know about the tools that were listed when they were created.
+    pub fn allow_host_only_tools(&mut self, allow: bool) {
+        self.host_only_tools_allowed = allow;
+    }
+
+    fn is_available(&self, tool: &dyn Tool) -> bool {
+        self.host_only_tools_allowed || !tool.host_only()
+    }
+
     pub fn register_tool<T: Tool>(&mut self, tool: T) {
         self.tools.insert(tool.name(), Arc::new(tool));
     }
 
+    /// Like [`Self::register_tool()`], for tools that are already shared.
+    pub fn register_shared_tool(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.insert(tool.name(), tool);
+    }
+
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
-        self.tools.get(name).cloned()
+        let tool = self.tools.get(name).cloned()?;
+        Some(self.options.descriptions.localize(tool))
     }
 
     pub fn list(&self) -> Vec<Arc<dyn Tool>> {
-        self.tools.values().cloned().collect()
+        self.tools
+            .values()
+            .filter(|tool| self.is_available(tool.as_ref()))
+            .map(|tool| self.options.descriptions.localize(tool.clone()))
+            .collect()
+    }
+
+    /// Return the features supported by the toolset and its available tools, for hosts to detect them at runtime.
+    pub fn capabilities(&self) -> Capabilities {
+        capabilities_of(&self.list())
+    }
+
+    /// Export the definitions of all registered tools, along with the version of the tool API they belong to.
+    pub fn definitions(&self) -> serde_json::Value {
+        let tools = self
+            .list()
+            .iter()
+            .map(|tool| {
+                let mut definition = json!({
+                    "name": tool.name(),
+                    "version": tool.version(),
+                    "description": tool.description(),
+                    "parameters": tool.parameters(),
+                });
+                if let Some(duration) = tool.estimated_duration_ms() {
+                    definition["estimatedDurationMs"] = duration.into();
+                }
+                definition
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "apiVersion": TOOL_API_VERSION,
+            "tools": tools,
+        })
     }
 
     fn call_tool_inner(
@@ -44,23 +791,86 @@ impl<'a> Toolset<'a> {
         name: &str,
         parameters: &str,
     ) -> anyhow::Result<serde_json::Value> {
+        self.ensure_context_is_fresh()?;
         let tool = self
             .get(name)
             .ok_or_else(|| anyhow::anyhow!("Tool '{}' not found", name))?;
+        if !self.is_available(tool.as_ref()) {
+            return Err(HostOnlyToolError {
+                name: name.to_owned(),
+            }
+            .into());
+        }
         let params: serde_json::Value = serde_json::from_str(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse parameters: {}", e))?;
-        tool.call(params, self.ctx, self.app_handle)
+        // The repository is opened before the call, so failing to do so can't turn the result of a tool
+        // that changed the workspace into an error. The commit ids are only resolved if it could be opened.
+        let repo = self.ctx.gix_repo().ok();
+        let mut value = tool.call(params.clone(), self.ctx, self.app_handle, &self.options)?;
+        if let Some(repo) = &repo {
+            add_resolved_commit_ids(&mut value, &params, repo);
+        }
+        if self.options.paranoid && tool.mutates_workspace() {
+            add_invariant_violations(&mut value, self.ctx);
+        }
+        Ok(value)
     }
 
+    /// Call the tool `name` with the JSON `parameters`, and return its result or error.
+    ///
+    /// A panic in the tool is turned into an error with the `internalError` code, so the toolset can still be used.
     pub fn call_tool(&mut self, name: &str, parameters: &str) -> serde_json::Value {
-        let result = self.call_tool_inner(name, parameters).unwrap_or_else(|e| {
-            serde_json::json!({
-                "error": format!("Failed to call tool '{}': {}", name, e.to_string())
-            })
+        self.call_tool_with_version(name, parameters, None)
+    }
+
+    /// Like [`Self::call_tool()`], but refuse to call the tool if the major version of `expected_api_version`,
+    /// the version of the tool API the host was built against, doesn't match the one of [`TOOL_API_VERSION`].
+    pub fn call_tool_with_version(
+        &mut self,
+        name: &str,
+        parameters: &str,
+        expected_api_version: Option<&str>,
+    ) -> serde_json::Value {
+        let oplog_head = self.ctx.oplog_head().ok().flatten().map(|id| id.to_gix());
+        let start = Instant::now();
+        let (mut result, events_emitted) = record_events(|| match expected_api_version {
+            Some(expected) if major_version(expected) != major_version(TOOL_API_VERSION) => {
+                version_mismatch_to_json(expected)
+            }
+            _ => {
+                // A panicking tool may leave the context it borrows half-updated. This is fine to observe, as the
+                // context reads the workspace from disk on each call, and the assignments cached for the
+                // workspace, whose state is unknown after a panic, are forgotten.
+                match panic::catch_unwind(AssertUnwindSafe(|| {
+                    self.call_tool_inner(name, parameters)
+                })) {
+                    Ok(result) => result
+                        .unwrap_or_else(|e| error_to_json(&e, &format!("call tool '{}'", name))),
+                    Err(payload) => {
+                        self.options.assignments_cache.forget();
+                        panic_to_json(payload.as_ref(), name)
+                    }
+                }
+            }
         });
+        let duration = start.elapsed();
+
+        // Attach the tool name and its inputs to errors, so they can be correlated in a transcript.
+        let context = ErrorContext::new(name, parameters);
+        add_error_context(&mut result, &context);
+
+        let snapshots = self.snapshots_since(oplog_head);
+        if self.call_metadata {
+            let metadata = ToolCallMetadata {
+                duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+                snapshot_created: !snapshots.is_empty(),
+                events_emitted,
+            };
+            add_call_metadata(&mut result, metadata);
+        }
 
         // Emit the tool call event if a message ID is provided
-        if let Some(message_id) = &self.message_id {
+        if let Some(message_id) = &self.options.message_id {
             if let Some(app_handle) = self.app_handle {
                 let project_id = self.ctx.project().id;
                 app_handle.emit_tool_call(
@@ -75,43 +885,520 @@ impl<'a> Toolset<'a> {
             }
         }
 
+        self.calls.push(ToolCallRecord {
+            name: name.to_owned(),
+            message_id: self.options.message_id.clone(),
+            parameters: serde_json::from_str(parameters).unwrap_or_default(),
+            result: result.clone(),
+            snapshots,
+        });
         result
     }
+
+    /// Call the tools of `steps` in order, as one all-or-nothing operation.
+    ///
+    /// A snapshot is taken before the first step. If a step fails, the remaining steps are skipped and the workspace
+    /// is restored from that snapshot. The error then has the `batchFailed` code, the index of the `failedStep`,
+    /// whether it was `rolledBack`, and the `results` of the steps up to the failed one.
+    /// Otherwise, the results of all steps are returned in order.
+    pub fn call_batch(&mut self, steps: &[BatchStep]) -> serde_json::Value {
+        let snapshot = {
+            let mut guard = self.ctx.project().exclusive_worktree_access();
+            self.ctx.create_snapshot(
+                SnapshotDetails {
+                    title: "Started a batch of tool calls".to_owned(),
+                    ..SnapshotDetails::new(OperationKind::Unknown)
+                },
+                guard.write_permission(),
+            )
+        };
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(err) => return error_to_json(&err, "snapshot the workspace before the batch"),
+        };
+
+        let mut results = vec![];
+        for (index, step) in steps.iter().enumerate() {
+            let parameters = match &step.params {
+                serde_json::Value::Null => "{}".to_owned(),
+                params => params.to_string(),
+            };
+            let result = self.call_tool(&step.tool, &parameters);
+            let error = result["error"].as_str().map(ToOwned::to_owned);
+            results.push(result);
+            let Some(error) = error else {
+                continue;
+            };
+
+            // The tools take the worktree lock themselves, so it's only held while restoring.
+            let restored = {
+                let mut guard = self.ctx.project().exclusive_worktree_access();
+                self.ctx
+                    .restore_snapshot(snapshot, guard.write_permission())
+            };
+            self.options.assignments_cache.forget();
+            let mut value = json!({
+                "error": format!(
+                    "Step {} of the batch, calling '{}', failed: {}",
+                    index + 1,
+                    step.tool,
+                    error
+                ),
+                "code": "batchFailed",
+                "failedStep": index,
+                "rolledBack": restored.is_ok(),
+                "results": results,
+                "apiVersion": TOOL_API_VERSION,
+            });
+            if let Err(err) = restored {
+                value["rollbackError"] = err.to_string().into();
+            }
+            return value;
+        }
+        json!({
+            "result": { "results": results },
+            "apiVersion": TOOL_API_VERSION,
+        })
+    }
+
+    /// Return the snapshots that were recorded after `previous_head`, the head of the oplog before a tool was called,
+    /// oldest first.
+    ///
+    /// If there was no oplog before, all of its snapshots were recorded by the tool.
+    fn snapshots_since(&self, previous_head: Option<gix::ObjectId>) -> Vec<RecordedSnapshot> {
+        let (Ok(Some(head)), Ok(repo)) = (self.ctx.oplog_head(), self.ctx.gix_repo()) else {
+            return vec![];
+        };
+        let mut snapshots = vec![];
+        let mut next = Some(head.to_gix());
+        while let Some(id) = next.filter(|id| Some(*id) != previous_head) {
+            let Ok(commit) = repo.find_commit(id) else {
+                break;
+            };
+            let message = commit.message_raw_sloppy();
+            snapshots.push(RecordedSnapshot {
+                id: id.to_string(),
+                title: message
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_str_lossy()
+                    .into_owned(),
+            });
+            next = commit.parent_ids().next().map(|id| id.detach());
+        }
+        snapshots.reverse();
+        snapshots
+    }
+}
+
+/// A step of a [batch](Toolset::call_batch()).
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchStep {
+    /// The name of the tool to call.
+    pub tool: String,
+    /// The parameters to call the tool with, which are empty if not provided.
+    #[serde(default)]
+    pub params: serde_json::Value,
 }
 
 pub trait Tool: 'static + Send + Sync {
     fn name(&self) -> String;
     fn description(&self) -> String;
     fn parameters(&self) -> serde_json::Value;
+    /// The version of the tool, bumped whenever its parameters or its result change incompatibly.
+    ///
+    /// Bumps have to be recorded in [`TOOL_API_CHANGELOG`].
+    fn version(&self) -> u32 {
+        1
+    }
+    /// Return true if the tool is only meant for hosts, and isn't exposed to the LLM unless the host
+    /// [allows it](Toolset::allow_host_only_tools()).
+    fn host_only(&self) -> bool {
+        false
+    }
+    /// Return true if the tool changes the workspace, so the workspace invariants are verified after calling it
+    /// in [paranoid mode](Toolset::set_paranoid()).
+    fn mutates_workspace(&self) -> bool {
+        false
+    }
+    /// A rough estimate of how long calling the tool takes in milliseconds, for hosts to decide whether to show progress,
+    /// or `None` if the tool is expected to return instantly.
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        None
+    }
+    /// The features of the tool that hosts may want to detect, keyed by the name of the tool and the feature,
+    /// like `commit.forceNewBranch`, along with `true` or metadata about the feature.
+    ///
+    /// Features are listed here as they land, see [`Toolset::capabilities()`].
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        vec![]
+    }
+    /// Call the tool with the JSON `parameters` and the `options` of the toolset it is called from.
+    ///
+    /// Tools that persist commit messages or branch descriptions use `options` to sanitize them,
+    /// and tools that skip inputs or changes use it to honor strict mode.
+    /// Hosts calling a tool directly should pass the options of their toolset, see [`Toolset::options()`].
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value>;
 }
 
+/// A tool to describe the other tools of a toolset.
+///
+/// It holds on to the tools that were registered when it was created, and doesn't describe itself.
+pub struct GetToolSchema {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl GetToolSchema {
+    /// Create a tool that describes all tools currently registered in `toolset`.
+    ///
+    /// Register it last so that it knows about all other tools.
+    pub fn new(toolset: &Toolset<'_>) -> Self {
+        Self::for_tools(toolset.list())
+    }
+
+    /// Create a tool that describes `tools`.
+    pub fn for_tools(tools: Vec<Arc<dyn Tool>>) -> Self {
+        GetToolSchema { tools }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetToolSchemaParameters {
+    /// The name of the tool to describe.
+    #[schemars(description = "
+    <description>
+        The name of the tool to get the description and parameter schema of.
+    </description>
+
+    <important_notes>
+        Leave this empty to get the descriptions and parameter schemas of all available tools.
+    </important_notes>
+    ")]
+    pub name: Option<String>,
+}
+
+impl Tool for GetToolSchema {
+    fn name(&self) -> String {
+        "get_tool_schema".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the description and the parameter schema of the available tools.
+        </description>
+
+        <important_notes>
+            Use this tool to find out which tools are available, and how to call them.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetToolSchemaParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        _ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetToolSchemaParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = self
+            .tool_schemas(params, &options.descriptions)
+            .to_json("get_tool_schema");
+        Ok(value)
+    }
+}
+
+impl GetToolSchema {
+    /// Describe the tools, or only the one in `params`, with `descriptions` in place of the built-in ones.
+    fn tool_schemas(
+        &self,
+        params: GetToolSchemaParameters,
+        descriptions: &ToolDescriptions,
+    ) -> anyhow::Result<Vec<ToolSchema>> {
+        let tools = self
+            .tools
+            .iter()
+            .filter(|tool| params.name.as_ref().is_none_or(|name| &tool.name() == name))
+            .map(|tool| descriptions.localize(tool.clone()));
+        let schemas = tools
+            .map(|tool| ToolSchema {
+                name: tool.name(),
+                version: tool.version(),
+                description: tool.description(),
+                parameters: tool.parameters(),
+            })
+            .collect::<Vec<_>>();
+
+        match params.name {
+            Some(name) if schemas.is_empty() => Err(anyhow::anyhow!("Tool '{}' not found", name)),
+            _ => Ok(schemas),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolSchema {
+    /// The name of the tool.
+    pub name: String,
+    /// The version of the tool.
+    pub version: u32,
+    /// The description of the tool.
+    pub description: String,
+    /// The JSON schema of the parameters of the tool.
+    pub parameters: serde_json::Value,
+}
+
+impl ToolResult for Result<Vec<ToolSchema>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<ToolSchema>")
+    }
+}
+
 pub fn error_to_json(error: &anyhow::Error, action_identifier: &str) -> serde_json::Value {
-    serde_json::json!({
-        "error": format!("Failed to {}: {}", action_identifier, error.to_string())
+    let mut value = serde_json::json!({
+        "error": format!("Failed to {}: {}", action_identifier, error.to_string()),
+        "apiVersion": TOOL_API_VERSION,
+    });
+    if error.downcast_ref::<EmptyMessageError>().is_some() {
+        value["code"] = "emptyMessage".into();
+    } else if error.downcast_ref::<EmptyTitleError>().is_some() {
+        value["code"] = "invalidInput".into();
+    } else if error.downcast_ref::<StaleContextError>().is_some() {
+        value["code"] = "staleContext".into();
+    } else if error.downcast_ref::<HostOnlyToolError>().is_some() {
+        value["code"] = "permissionDenied".into();
+    } else if error.downcast_ref::<StrictModeError>().is_some() {
+        value["code"] = "strictMode".into();
+    } else if error.downcast_ref::<InvalidCommitIdError>().is_some() {
+        value["code"] = "invalidCommitId".into();
+    } else if let Some(ambiguous) = error.downcast_ref::<AmbiguousCommitIdError>() {
+        value["code"] = "ambiguousCommitId".into();
+        value["candidates"] = ambiguous
+            .candidates
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .into();
+    } else if let Some(ambiguous) = error.downcast_ref::<AmbiguousBranchNameError>() {
+        value["code"] = "ambiguousBranchName".into();
+        value["candidates"] = ambiguous.candidates.clone().into();
+    } else if let Some(integrated) = error.downcast_ref::<BranchIntegratedError>() {
+        value["code"] = "branchIntegrated".into();
+        if let Some(next_branch_name) = &integrated.next_branch_name {
+            value["nextBranchName"] = next_branch_name.clone().into();
+        }
+    } else if error.downcast_ref::<InvalidAuthorDateError>().is_some() {
+        value["code"] = "invalidAuthorDate".into();
+    } else if let Some(foreign_locks) = error.downcast_ref::<ForeignLocksError>() {
+        value["code"] = "foreignLocks".into();
+        value["affectedForeignLocks"] =
+            serde_json::to_value(&foreign_locks.locks).unwrap_or_default();
+    }
+    value
+}
+
+/// Turn the `payload` of a panic in the tool `name` into an error result.
+fn panic_to_json(payload: &(dyn Any + Send), name: &str) -> serde_json::Value {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_owned());
+    json!({
+        "error": format!("Failed to call tool '{}': the tool panicked: {}", name, message),
+        "code": "internalError",
+        "panic": message,
+        "apiVersion": TOOL_API_VERSION,
+    })
+}
+
+fn version_mismatch_to_json(expected_api_version: &str) -> serde_json::Value {
+    json!({
+        "error": format!(
+            "The host expects version {} of the tool API, but version {} is provided",
+            expected_api_version, TOOL_API_VERSION
+        ),
+        "code": "versionMismatch",
+        "expectedApiVersion": expected_api_version,
+        "apiVersion": TOOL_API_VERSION,
     })
 }
 
+/// Add the full ids of the abbreviated commit ids in `parameters` to the successful result `value`,
+/// so they can be used in subsequent calls.
+fn add_resolved_commit_ids(
+    value: &mut serde_json::Value,
+    parameters: &serde_json::Value,
+    repo: &gix::Repository,
+) {
+    let Some(parameters) = parameters.as_object() else {
+        return;
+    };
+    let resolved = parameters
+        .iter()
+        .filter(|(key, _)| is_commit_id_parameter(key))
+        .filter_map(|(_, id)| id.as_str())
+        .filter(|id| is_abbreviated(id))
+        .filter_map(|id| {
+            let full_id = resolve_commit_id(repo, id).ok()?;
+            Some((id.to_owned(), full_id.to_string().into()))
+        })
+        .collect::<serde_json::Map<_, _>>();
+    if resolved.is_empty() {
+        return;
+    }
+    if let Some(object) = value.as_object_mut() {
+        if !object.contains_key("error") {
+            object.insert("resolvedCommitIds".to_string(), resolved.into());
+        }
+    }
+}
+
+/// Add the violations of the workspace invariants to `value`, the result of a tool that mutated the workspace.
+///
+/// The result is kept as is if the workspace is consistent.
+fn add_invariant_violations(value: &mut serde_json::Value, ctx: &CommandContext) {
+    let violations = match verify_workspace_invariants(ctx) {
+        Ok(violations) if violations.is_empty() => return,
+        Ok(violations) => serde_json::to_value(violations).unwrap_or_default(),
+        Err(err) => json!([{
+            "kind": "verificationFailed",
+            "error": err.to_string(),
+        }]),
+    };
+    if let Some(object) = value.as_object_mut() {
+        object.insert("invariantViolations".to_string(), violations);
+    }
+}
+
+/// Return true if the parameter named `key` holds a commit id, by the naming convention of tool parameters.
+fn is_commit_id_parameter(key: &str) -> bool {
+    key == "commitId" || key.ends_with("CommitId") || key == "parentId" || key.ends_with("Oid")
+}
+
+/// Strings in echoed tool inputs that are longer than this are replaced by a placeholder.
+const MAX_ECHOED_STRING_LEN: usize = 256;
+
+/// Identifies the tool call that produced an error.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorContext {
+    /// The name of the tool that failed.
+    pub tool: String,
+    /// A redacted echo of the input parameters the tool was called with.
+    pub parameters: serde_json::Value,
+}
+
+impl ErrorContext {
+    /// Create the context for a call to the tool `name` with the raw JSON `parameters`.
+    ///
+    /// Long strings, like file contents, are left out of the echoed parameters.
+    pub fn new(name: &str, parameters: &str) -> Self {
+        let parameters = serde_json::from_str(parameters)
+            .unwrap_or_else(|_| serde_json::Value::String(parameters.to_string()));
+        ErrorContext {
+            tool: name.to_string(),
+            parameters: redact(parameters),
+        }
+    }
+}
+
+fn redact(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) if s.chars().count() > MAX_ECHOED_STRING_LEN => {
+            serde_json::Value::String(format!("<{} characters omitted>", s.chars().count()))
+        }
+        serde_json::Value::Array(values) => values.into_iter().map(redact).collect(),
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| (key, redact(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        value => value,
+    }
+}
+
+/// Add `context` to `value` if it is the JSON representation of an error.
+///
+/// Values that aren't errors are left untouched.
+pub fn add_error_context(value: &mut serde_json::Value, context: &ErrorContext) {
+    if let Some(object) = value.as_object_mut() {
+        if object.contains_key("error") {
+            object.insert(
+                "context".to_string(),
+                serde_json::to_value(context).unwrap_or_default(),
+            );
+        }
+    }
+}
+
 pub fn result_to_json<T: serde::Serialize>(
     result: &Result<T, anyhow::Error>,
     action_identifier: &str,
     data_identifier: &str,
 ) -> serde_json::Value {
     match result {
-        Ok(entry) => json!({ "result": serde_json::to_value(entry).unwrap_or_else(
-            |e| json!({ "error": format!("Failed to serialize {}: {}", data_identifier, e.to_string())}),
-        )}),
+        Ok(entry) => json!({
+            "result": serde_json::to_value(entry).unwrap_or_else(
+                |e| json!({ "error": format!("Failed to serialize {}: {}", data_identifier, e.to_string())}),
+            ),
+            "apiVersion": TOOL_API_VERSION,
+        }),
         Err(e) => error_to_json(e, action_identifier),
     }
 }
 
 pub trait ToolResult: 'static + Send + Sync {
     fn to_json(&self, action_identifier: &str) -> serde_json::Value;
+
+    /// Like [`Self::to_json()`], but also attach what calling the tool did besides producing the result as `metadata`.
+    fn to_json_with_metadata(
+        &self,
+        action_identifier: &str,
+        metadata: ToolCallMetadata,
+    ) -> serde_json::Value {
+        let mut value = self.to_json(action_identifier);
+        add_call_metadata(&mut value, metadata);
+        value
+    }
+}
+
+/// What calling a tool did besides producing its result, so consumers can take its side effects into account.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallMetadata {
+    /// How long the call took in milliseconds.
+    pub duration_ms: u64,
+    /// Whether the call recorded a snapshot in the oplog, which can be restored to undo it.
+    pub snapshot_created: bool,
+    /// The names of the events the call emitted to the UI, in the order they were emitted.
+    pub events_emitted: Vec<String>,
+}
+
+/// Attach `metadata` to the result or error `value` of a tool call.
+fn add_call_metadata(value: &mut serde_json::Value, metadata: ToolCallMetadata) {
+    if let Some(object) = value.as_object_mut() {
+        object.insert("metadata".into(), json!(metadata));
+    }
 }
 
 impl ToolResult for Result<StackEntry, anyhow::Error> {
@@ -0,0 +1,48 @@
+//! Parse the trailers at the end of commit messages, like `Signed-off-by: Jane Doe <jane@example.com>`.
+
+/// A trailer of a commit message.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitTrailer {
+    /// The token of the trailer, like `Signed-off-by`.
+    pub token: String,
+    /// The value of the trailer, like the name and email of who signed off.
+    pub value: String,
+}
+
+/// Return the trailers of `message`, in the order they appear in.
+///
+/// Like git, only the last paragraph of the message is considered, and only if it isn't the title and all of its
+/// lines are trailers. Lines starting with whitespace continue the value of the trailer before them.
+pub fn parse_trailers(message: &str) -> Vec<CommitTrailer> {
+    let message = message.replace("\r\n", "\n");
+    let Some((_title, body)) = message.trim().split_once("\n\n") else {
+        return vec![];
+    };
+    let paragraph = body.rsplit("\n\n").next().unwrap_or_default();
+
+    let mut trailers: Vec<CommitTrailer> = vec![];
+    for line in paragraph.trim_matches('\n').lines() {
+        if line.starts_with([' ', '\t']) {
+            let Some(trailer) = trailers.last_mut() else {
+                return vec![];
+            };
+            trailer.value.push(' ');
+            trailer.value.push_str(line.trim());
+            continue;
+        }
+        match line.split_once(':') {
+            Some((token, value)) if is_trailer_token(token) => trailers.push(CommitTrailer {
+                token: token.to_owned(),
+                value: value.trim().to_owned(),
+            }),
+            _ => return vec![],
+        }
+    }
+    trailers
+}
+
+/// Return true if `token` can be the token of a trailer, which consists of alphanumeric characters and dashes.
+pub fn is_trailer_token(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
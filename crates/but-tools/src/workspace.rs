@@ -1,20 +1,45 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write as _;
+use std::process::Stdio;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
-use bstr::BString;
-use but_core::{TreeChange, UnifiedDiff};
+use anyhow::Context as _;
+use bstr::{BStr, BString, ByteSlice};
+use but_core::cmd::prepare_with_shell_on_windows;
+use but_core::{RepositoryExt as _, TreeChange, UnifiedDiff};
 use but_graph::VirtualBranchesTomlMetadata;
 use but_workspace::StackId;
+use but_workspace::stack_ext::StackExt;
 use but_workspace::ui::StackEntry;
+use gitbutler_branch_actions::BranchManagerExt as _;
 use gitbutler_command_context::CommandContext;
+use gitbutler_oplog::entry::{OperationKind, SnapshotDetails};
 use gitbutler_oplog::{OplogExt, SnapshotExt};
-use gitbutler_oxidize::ObjectIdExt;
+use gitbutler_oxidize::{GixRepositoryExt, ObjectIdExt, OidExt};
 use gitbutler_project::Project;
 use gitbutler_stack::{PatchReferenceUpdate, VirtualBranchesHandle};
+use gix::objs::WriteTo as _;
+use gix::prelude::ObjectIdExt as _;
 use schemars::{JsonSchema, schema_for};
 
-use crate::emit::EmitStackUpdate;
-use crate::tool::{Tool, ToolResult, Toolset, error_to_json, result_to_json};
+use crate::branch_name::{
+    ambiguous_branch_names, qualified_branch_name, resolve_branch_name, unqualified_branch_name,
+};
+use crate::branch_suggestion::suggest_branch_names;
+use crate::commit_id::resolve_commit_id;
+use crate::emit::{EmitStackUpdate, EmitWorktreeChanges};
+use crate::file_params::{NormalizedInput, normalize_file_params, normalize_path};
+use crate::linked_issues::{IssueMatcher, read_issue_patterns};
+use crate::sanitize::MessageSanitizer;
+use crate::tool::{
+    Capabilities, GetToolSchema, StrictModeError, Tool, ToolOptions, ToolResult, Toolset,
+    capabilities_of, error_to_json, result_to_json,
+};
+use crate::trailers::{CommitTrailer, is_trailer_token, parse_trailers};
 
 /// Creates a toolset for any kind of workspace operations.
 pub fn workspace_toolset<'a>(
@@ -24,17 +49,87 @@ pub fn workspace_toolset<'a>(
 ) -> anyhow::Result<Toolset<'a>> {
     let mut toolset = Toolset::new(ctx, app_handle, Some(message_id));
 
-    toolset.register_tool(Commit);
-    toolset.register_tool(CreateBranch);
-    toolset.register_tool(Amend);
-    toolset.register_tool(GetProjectStatus);
-    toolset.register_tool(CreateBlankCommit);
-    toolset.register_tool(MoveFileChanges);
-    toolset.register_tool(GetCommitDetails);
+    for tool in workspace_tools() {
+        toolset.register_shared_tool(tool);
+    }
+    toolset.register_tool(GetToolSchema::new(&toolset));
 
     Ok(toolset)
 }
 
+/// Return the capabilities of the [workspace toolset](workspace_toolset()), including its host-only tools.
+///
+/// Unlike [`Toolset::capabilities()`], this doesn't need a project, so hosts can learn about them before opening one.
+pub fn workspace_capabilities() -> Capabilities {
+    let mut tools = workspace_tools();
+    tools.push(Arc::new(GetToolSchema::for_tools(tools.clone())));
+    capabilities_of(&tools)
+}
+
+/// Return the tools of the [workspace toolset](workspace_toolset()), except for the tool schema which describes them.
+fn workspace_tools() -> Vec<Arc<dyn Tool>> {
+    vec![
+        Arc::new(Commit),
+        Arc::new(CreateBranch),
+        Arc::new(Amend),
+        Arc::new(GetProjectStatus),
+        Arc::new(CreateBlankCommit),
+        Arc::new(MoveFileChanges),
+        Arc::new(GetCommitDetails),
+        Arc::new(GetBranchDescription),
+        Arc::new(SetBranchDescription),
+        Arc::new(GetDiffForPaths),
+        Arc::new(GetStackOrder),
+        Arc::new(MoveCommitToNewBranch),
+        Arc::new(GetCommitAncestry),
+        Arc::new(SplitRename),
+        Arc::new(GetOrphanedCommits),
+        Arc::new(GetStacksSummary),
+        Arc::new(RecoverCommit),
+        Arc::new(GetPRDescription),
+        Arc::new(RepoQuery),
+        Arc::new(GetRecentActivity),
+        Arc::new(GetGitAttributes),
+        Arc::new(GetCommitByMessage),
+        Arc::new(GetWorktreeLockStatus),
+        Arc::new(ReassignAndCommit),
+        Arc::new(GetChangeStatsByStack),
+        Arc::new(GetConflictingStackPairs),
+        Arc::new(GetBranchUpstreamCommits),
+        Arc::new(GetStackMergeOrder),
+        Arc::new(GetChangesSince),
+        Arc::new(GetFileRenames),
+        Arc::new(GetAffectedTests),
+        Arc::new(GetBranchRebasePreview),
+        Arc::new(GetCommitDiffStat),
+        Arc::new(PreviewIntegration),
+        Arc::new(GetCommitSignatureStatus),
+        Arc::new(GetStagedCommitPreview),
+        Arc::new(GetTargetBranchCommits),
+        Arc::new(RewordCommits),
+        Arc::new(CopyCommitToStack),
+        Arc::new(GetRelatedCommits),
+        Arc::new(GetWorkspaceHealth),
+        Arc::new(GetStackDiffSummary),
+        Arc::new(RepairWorkspace),
+        Arc::new(GetCommitSignoffTrailers),
+        Arc::new(AddTrailerToCommit),
+        Arc::new(RestoreFileFromCommit),
+        Arc::new(DiffAgainstSnapshot),
+        Arc::new(GetUnpushedWork),
+        Arc::new(GetCrossStackDiff),
+        Arc::new(GetBranchNameFromDiff),
+        Arc::new(GetConflictingHunks),
+        Arc::new(ResolveConflict),
+        Arc::new(PruneStaleAssignments),
+        Arc::new(GetCommitMessageBody),
+        Arc::new(GetBranchLinkedIssues),
+        Arc::new(GetWorkspaceTokenEstimate),
+        Arc::new(GetFileMoveHistory),
+        Arc::new(GetStackCommitMap),
+    ]
+}
+
 /// Creates a toolset for workspace-related operations.
 pub fn commit_toolset<'a>(
     ctx: &'a mut CommandContext,
@@ -108,6 +203,7 @@ pub struct CommitParameters {
         It should not contain spaces or special characters.
         Keep it to maximum 5 words, and use hyphens to separate words.
         Don't use slashes or other special characters.
+        If the project status marks the name of an existing branch as ambiguous, use its qualified name instead.
     </important_notes>
     ")]
     pub branch_name: String,
@@ -134,9 +230,141 @@ pub struct CommitParameters {
 
         <important_notes>
             The file paths should be relative to the workspace root.
+            Directories stand for all changed files in them, and files that are listed more than once are committed once.
         </important_notes>
         ")]
     pub files: Vec<String>,
+    /// Deprecated, the index at which to place the stack of the branch in the workspace, if it's created.
+    ///
+    /// It's still accepted from callers that set it, but isn't part of the schema anymore.
+    /// If `stack_position` is set as well, `order` is ignored.
+    #[schemars(skip)]
+    #[serde(default)]
+    pub order: Option<usize>,
+    /// Whether to amend the head commit of the branch instead of creating a new commit.
+    #[schemars(description = "
+    <description>
+        Whether to add the file changes to the most recent commit of the branch, instead of creating a new commit.
+    </description>
+
+    <important_notes>
+        The branch has to exist and have at least one commit.
+        Leave the commit message title and body empty to keep the message of the amended commit.
+        The branch description is left untouched.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub amend_head: bool,
+    /// Whether to commit to a branch even if it was already integrated into the target branch.
+    #[schemars(description = "
+    <description>
+        Whether to commit to the branch even if all of its commits were already integrated into the target branch,
+        for instance because its pull request was merged.
+    </description>
+
+    <important_notes>
+        Committing to an integrated branch is refused by default, as the changes would end up on a branch that is done.
+        Prefer creating a new branch, or committing to the next branch of the stack, over setting this.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub allow_integrated: bool,
+    /// The date to use as the author date of the commit.
+    #[schemars(description = "
+    <description>
+        The date to record as author date of the commit, in ISO-8601 format like '2024-03-01 14:30:00 +0100'.
+        The committer date is always the current date.
+    </description>
+
+    <important_notes>
+        Only set this when reconstructing history, like recreating an old commit, to preserve its original author date.
+        Leave this empty to use the current date.
+        Dates in the future are refused.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub author_date: Option<String>,
+    /// Whether to always create a new branch, even if a branch with the name exists.
+    #[schemars(description = "
+    <description>
+        Whether to always commit to a new branch, even if a branch named 'branchName' already exists.
+        If the name is taken, a number is appended to it, like 'my-branch-2'.
+    </description>
+
+    <important_notes>
+        Set this if the commit is meant to start a new branch, so it doesn't end up on an unrelated branch of the same name.
+        The name of the branch that was used is returned as 'branchName'.
+        This can't be combined with 'amendHead'.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub force_new_branch: bool,
+    /// Where to place the stack of the branch, if it's created.
+    #[schemars(description = "
+    <description>
+        Where to place the stack of the branch in the workspace, if the branch has to be created.
+        Set one of 'beforeStackId', 'afterStackId' or 'index', where index 0 is the leftmost position.
+    </description>
+
+    <important_notes>
+        This is ignored if the branch already exists.
+        Leave this empty to place a new branch after all existing branches.
+        If the position can't be resolved, for instance because the stack doesn't exist, the stack is placed
+        after all existing stacks and a warning is returned.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub stack_position: Option<StackPosition>,
+}
+
+/// Where to place a new stack in the workspace, relative to the existing ones.
+///
+/// Exactly one of the fields is expected to be set.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StackPosition {
+    /// Place the stack right before the stack with this id.
+    pub before_stack_id: Option<String>,
+    /// Place the stack right after the stack with this id.
+    pub after_stack_id: Option<String>,
+    /// Place the stack at this position, with 0 being the leftmost one.
+    pub index: Option<usize>,
+}
+
+/// Return the index in the workspace at which a new stack is placed according to `position`,
+/// or a warning explaining why it can't be resolved, in which case the stack is placed after all others.
+fn resolve_stack_position(
+    ctx: &CommandContext,
+    position: &StackPosition,
+) -> anyhow::Result<Result<usize, String>> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stacks = vb_state.list_stacks_in_workspace()?;
+    stacks.sort_by_key(|stack| (stack.order, stack.id));
+    let index_of = |stack_id: &str| {
+        let position = StackId::from_str(stack_id)
+            .ok()
+            .and_then(|stack_id| stacks.iter().position(|stack| stack.id == stack_id));
+        position.ok_or_else(|| {
+            format!(
+                "Stack {stack_id} isn't in the workspace, so the new stack was placed after all existing stacks"
+            )
+        })
+    };
+    Ok(
+        match (
+            &position.before_stack_id,
+            &position.after_stack_id,
+            position.index,
+        ) {
+            (Some(before), None, None) => index_of(before),
+            (None, Some(after), None) => index_of(after).map(|index| index + 1),
+            (None, None, Some(index)) => Ok(index.min(stacks.len())),
+            _ => Err(
+                "Exactly one of beforeStackId, afterStackId and index has to be set, so the new stack was placed after all existing stacks"
+                    .to_owned(),
+            ),
+        },
+    )
 }
 
 /// Commit tool.
@@ -157,6 +385,7 @@ impl Tool for Commit {
             This tool allows you to commit changes to a specific branch in the workspace.
             You can specify the commit message, target branch name, and a list of file paths to commit.
             If the branch does not exist, it will be created.
+            To add the changes to the latest commit of the branch instead, set amendHead.
         </important_notes>
         ".to_string()
     }
@@ -166,16 +395,61 @@ impl Tool for Commit {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn version(&self) -> u32 {
+        // 2: Empty commit messages are refused.
+        // 3: Branch names shared by the heads of several stacks are refused unless they are qualified.
+        // 4: Commit messages with an empty title are refused.
+        // 5: Committing to a branch that was already integrated is refused unless `allowIntegrated` is set.
+        // 6: `order` isn't part of the parameters anymore, in favor of `stackPosition`.
+        6
+    }
+
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        vec![
+            ("commit.amendHead".into(), true.into()),
+            ("commit.authorDate".into(), true.into()),
+            ("commit.forceNewBranch".into(), true.into()),
+            ("commit.normalizedInput".into(), true.into()),
+            ("commit.stackPosition".into(), true.into()),
+        ]
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_000)
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value> {
-        let params: CommitParameters = serde_json::from_value(parameters)
+        let mut params: CommitParameters = serde_json::from_value(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
 
-        let value = create_commit(ctx, app_handle, params).to_json("create_commit");
+        let value = normalize_worktree_file_params(ctx, &params.files)
+            .and_then(|normalized_input| {
+                params.files = normalized_input.files.clone();
+                ensure_files_are_changed(ctx, &normalized_input.files, options.strict)?;
+                let committed = create_commit_on_branch(
+                    ctx,
+                    app_handle,
+                    options.message_id.as_deref(),
+                    &options.sanitizer,
+                    params,
+                )?;
+                let mut result = commit_result(ctx, committed.outcome, &normalized_input.files)?
+                    .with_normalized_input(normalized_input);
+                result.branch_name = Some(committed.branch_name);
+                result.warnings = committed.warnings;
+                Ok(result)
+            })
+            .to_json("create_commit");
         Ok(value)
     }
 }
@@ -183,67 +457,133 @@ impl Tool for Commit {
 pub fn create_commit(
     ctx: &mut CommandContext,
     app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
     params: CommitParameters,
 ) -> Result<but_workspace::commit_engine::ui::CreateCommitOutcome, anyhow::Error> {
+    create_commit_on_branch(ctx, app_handle, message_id, sanitizer, params)
+        .map(|committed| committed.outcome)
+}
+
+/// The outcome of [`create_commit_on_branch()`] and [`reassign_and_commit()`].
+pub struct CommitOnBranchOutcome {
+    /// The outcome of creating the commit.
+    pub outcome: but_workspace::commit_engine::ui::CreateCommitOutcome,
+    /// The name of the branch that was committed to.
+    pub branch_name: String,
+    /// Problems that didn't prevent the commit, like a stack position that couldn't be resolved.
+    pub warnings: Vec<String>,
+}
+
+/// Like [`create_commit()`], but also return the name of the branch that was committed to.
+pub fn create_commit_on_branch(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: CommitParameters,
+) -> anyhow::Result<CommitOnBranchOutcome> {
+    if params.amend_head {
+        if params.force_new_branch {
+            anyhow::bail!(
+                "The head of a new branch can't be amended, as the branch has no commits"
+            );
+        }
+        return amend_branch_head(ctx, app_handle, message_id, sanitizer, params);
+    }
+
+    let message = sanitizer.commit_message(&params.message_title, &params.message_body)?;
+    let author_date = params
+        .author_date
+        .as_deref()
+        .map(parse_author_date)
+        .transpose()?;
     let repo = ctx.gix_repo()?;
     let mut guard = ctx.project().exclusive_worktree_access();
     let worktree = but_core::diff::worktree_changes(&repo)?;
     let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
 
-    let file_changes: Vec<but_workspace::DiffSpec> = worktree
+    let changes = worktree
         .changes
         .iter()
         .filter(|change| params.files.contains(&change.path.to_string()))
-        .map(Into::into)
         .collect::<Vec<_>>();
+    ensure_readable(&repo, &changes)?;
+    let file_changes: Vec<but_workspace::DiffSpec> =
+        changes.into_iter().map(Into::into).collect::<Vec<_>>();
 
-    let stacks = stacks(ctx, &repo)?;
-
-    let stack_id = stacks
-        .iter()
-        .find_map(|s| {
-            let found = s.heads.iter().any(|h| h.name == params.branch_name);
-            if found { Some(s.id) } else { None }
-        })
-        .unwrap_or_else(|| {
+    let existing_branch = if params.force_new_branch {
+        None
+    } else {
+        find_branch(ctx, &repo, &params.branch_name)?
+    };
+    let mut warnings = vec![];
+    let (stack_id, branch_name) = match existing_branch {
+        Some((stack_id, branch_name)) => {
+            if !params.allow_integrated {
+                ensure_branch_not_integrated(ctx, &repo, stack_id, &branch_name)?;
+            }
+            (stack_id, branch_name)
+        }
+        None => {
+            let branch_name = if params.force_new_branch {
+                unused_branch_name(ctx, &repo, &params.branch_name)?
+            } else {
+                params.branch_name.clone()
+            };
+            let position =
+                params
+                    .stack_position
+                    .clone()
+                    .or(params.order.map(|index| StackPosition {
+                        index: Some(index),
+                        ..Default::default()
+                    }));
+            let order = match &position {
+                Some(position) => match resolve_stack_position(ctx, position)? {
+                    Ok(index) => Some(index),
+                    Err(warning) => {
+                        warnings.push(warning);
+                        None
+                    }
+                },
+                None => None,
+            };
             let perm = guard.write_permission();
 
             let branch = gitbutler_branch::BranchCreateRequest {
-                name: Some(params.branch_name.clone()),
+                name: Some(branch_name.clone()),
+                order,
                 ..Default::default()
             };
 
             let stack = gitbutler_branch_actions::create_virtual_branch(ctx, &branch, perm)
                 .expect("Failed to create virtual branch");
-            stack.id
-        });
+            (stack.id, branch_name)
+        }
+    };
 
     // Update the branch description.
     let mut stack = vb_state.get_stack(stack_id)?;
     stack.update_branch(
         ctx,
-        params.branch_name.clone(),
+        branch_name.clone(),
         &PatchReferenceUpdate {
-            description: Some(Some(params.branch_description)),
+            description: Some(Some(sanitizer.description(&params.branch_description))),
             ..Default::default()
         },
     )?;
 
     let snapshot_tree = ctx.prepare_snapshot(guard.read_permission());
 
-    let message = format!(
-        "{}\n\n{}",
-        params.message_title.trim(),
-        params.message_body.trim()
-    );
-
     let outcome = but_workspace::commit_engine::create_commit_simple(
         ctx,
         stack_id,
         None,
-        file_changes,
+        file_changes.clone(),
         message.clone(),
-        params.branch_name.clone(),
+        branch_name.clone(),
+        author_date,
         guard.write_permission(),
     );
 
@@ -260,11 +600,396 @@ pub fn create_commit(
     // If there's an app handle provided, emit an event to update the stack details in the UI.
     if let Some(app_handle) = app_handle {
         let project_id = ctx.project().id;
-        app_handle.emit_stack_update(project_id, stack_id);
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
     }
 
-    let outcome: but_workspace::commit_engine::ui::CreateCommitOutcome = outcome?.into();
-    Ok(outcome)
+    let outcome = outcome?;
+    // The commit was created already, so failing to clean up its assignments is only reported.
+    if let Err(err) = remove_committed_assignments(ctx, app_handle, &file_changes, &outcome) {
+        warnings.push(format!(
+            "The assignments of the committed hunks couldn't be removed: {}",
+            err
+        ));
+    }
+    Ok(CommitOnBranchOutcome {
+        outcome: outcome.into(),
+        branch_name,
+        warnings,
+    })
+}
+
+/// Return `name` if no branch or reference of that name exists yet, or `name` with the lowest number appended
+/// that makes it unused otherwise, like `name-2`.
+fn unused_branch_name(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    name: &str,
+) -> anyhow::Result<String> {
+    let push_remote = VirtualBranchesHandle::new(ctx.project().gb_dir())
+        .get_default_target()?
+        .push_remote_name();
+    let is_used = |candidate: &str| -> anyhow::Result<bool> {
+        Ok(find_branch(ctx, repo, candidate)?.is_some()
+            || repo
+                .try_find_reference(format!("refs/heads/{candidate}").as_str())?
+                .is_some()
+            || repo
+                .try_find_reference(format!("refs/remotes/{push_remote}/{candidate}").as_str())?
+                .is_some())
+    };
+    if !is_used(name)? {
+        return Ok(name.to_owned());
+    }
+    for suffix in 2.. {
+        let candidate = format!("{name}-{suffix}");
+        if !is_used(&candidate)? {
+            return Ok(candidate);
+        }
+    }
+    unreachable!("there are unused names")
+}
+
+/// The result of the commit and amend tools.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitResult {
+    #[serde(flatten)]
+    pub outcome: but_workspace::commit_engine::ui::CreateCommitOutcome,
+    /// The state of the requested files in the new commit, so it can be verified what exactly was committed.
+    /// This is empty if no commit was created.
+    pub committed_files: Vec<CommittedFile>,
+    /// The name of the branch that was committed to, if it's not implied by the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_name: Option<String>,
+    /// How the requested files were normalized, if any of them weren't used as they were passed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized_input: Option<NormalizedInput>,
+    /// Problems that didn't prevent the commit, like a stack position that couldn't be resolved.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// The uncommitted hunks of other stacks that depended on the amended commit, which should be checked.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub affected_foreign_locks: Vec<AffectedForeignLock>,
+    /// What happened to the amended commit if the amend left it without changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub empty_commit: Option<EmptyCommit>,
+}
+
+impl CommitResult {
+    /// Record `normalized_input` in the result if any of the requested files weren't used as they were passed.
+    pub fn with_normalized_input(mut self, normalized_input: NormalizedInput) -> Self {
+        if !normalized_input.collapsed.is_empty() {
+            self.normalized_input = Some(normalized_input);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommittedFile {
+    /// The path of the file.
+    pub path: String,
+    /// The id of the blob of the file in the new commit, or `None` if it was deleted.
+    pub blob_id: Option<String>,
+    /// The mode of the file in the new commit, like '100644', or `None` if it was deleted.
+    pub mode: Option<String>,
+    /// What the new commit did to the file compared to its parent.
+    pub action: CommittedFileAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommittedFileAction {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl ToolResult for Result<CommitResult, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "CommitResult")
+    }
+}
+
+/// Add the state of `paths` in the new commit of `outcome` to it.
+pub fn commit_result(
+    ctx: &CommandContext,
+    outcome: but_workspace::commit_engine::ui::CreateCommitOutcome,
+    paths: &[String],
+) -> anyhow::Result<CommitResult> {
+    let committed_files = match outcome.new_commit {
+        Some(commit_id) => committed_files(&ctx.gix_repo()?, commit_id, paths)?,
+        None => vec![],
+    };
+    Ok(CommitResult {
+        outcome,
+        committed_files,
+        branch_name: None,
+        normalized_input: None,
+        warnings: vec![],
+        affected_foreign_locks: vec![],
+        empty_commit: None,
+    })
+}
+
+/// Read the state of `paths` from the tree of `commit_id`, and compare it with the one of its first parent.
+///
+/// Paths that are the same in both are left out, as they weren't part of the commit.
+pub fn committed_files(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    paths: &[String],
+) -> anyhow::Result<Vec<CommittedFile>> {
+    let commit = repo.find_commit(commit_id)?;
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent_ids().next() {
+        Some(parent_id) => parent_id.object()?.peel_to_tree()?,
+        None => repo.empty_tree(),
+    };
+
+    let mut committed_files = vec![];
+    for path in paths {
+        let entry = tree
+            .lookup_entry_by_path(path)?
+            .map(|entry| (entry.object_id(), entry.mode()));
+        let parent_entry = parent_tree
+            .lookup_entry_by_path(path)?
+            .map(|entry| (entry.object_id(), entry.mode()));
+        let action = match (&entry, &parent_entry) {
+            (Some(_), None) => CommittedFileAction::Added,
+            (None, Some(_)) => CommittedFileAction::Deleted,
+            (Some(entry), Some(parent_entry)) if entry != parent_entry => {
+                CommittedFileAction::Modified
+            }
+            _ => continue,
+        };
+        committed_files.push(CommittedFile {
+            path: path.clone(),
+            blob_id: entry.map(|(id, _)| id.to_string()),
+            mode: entry.map(|(_, mode)| mode.kind().as_octal_str().to_string()),
+            action,
+        });
+    }
+    Ok(committed_files)
+}
+
+/// Amend the head commit of the branch named in `params` with the file changes in `params`,
+/// and return the outcome along with the name of the branch.
+///
+/// The message of the commit is kept if no new one is given.
+fn amend_branch_head(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: CommitParameters,
+) -> anyhow::Result<CommitOnBranchOutcome> {
+    let repo = ctx.gix_repo()?;
+    let (stack_id, branch_name) =
+        find_branch(ctx, &repo, &params.branch_name)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Branch '{}' not found, there is no commit to amend",
+                params.branch_name
+            )
+        })?;
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+    let branch = stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == &branch_name)
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' not found", params.branch_name))?;
+    if branch.commits(ctx, &stack)?.local_commits.is_empty() {
+        anyhow::bail!(
+            "Branch '{}' has no commits, there is no commit to amend",
+            params.branch_name
+        );
+    }
+    if !params.allow_integrated {
+        ensure_branch_not_integrated(ctx, &repo, stack_id, &branch_name)?;
+    }
+    let head = branch.head_oid(&repo)?;
+
+    let (message_title, message_body) =
+        if params.message_title.trim().is_empty() && params.message_body.trim().is_empty() {
+            let message = repo
+                .find_commit(head)?
+                .message_raw_sloppy()
+                .to_str_lossy()
+                .into_owned();
+            let commit = SimpleCommit::from_message(head, &message);
+            (commit.message_title, commit.message_body)
+        } else {
+            (params.message_title, params.message_body)
+        };
+
+    let amended = amend_commit_inner(
+        ctx,
+        app_handle,
+        message_id,
+        sanitizer,
+        AmendParameters {
+            commit_id: head.to_string(),
+            message_title,
+            message_body,
+            stack_id: stack_id.to_string(),
+            files: params.files,
+            predict_only: false,
+            keep_author_date: true,
+            author_date: params.author_date,
+            strict_locks: false,
+            keep_empty: true,
+        },
+    )?;
+    Ok(CommitOnBranchOutcome {
+        outcome: amended.outcome.into(),
+        branch_name,
+        warnings: amended
+            .affected_foreign_locks
+            .iter()
+            .map(ToString::to_string)
+            .chain(amended.warnings)
+            .collect(),
+    })
+}
+
+/// The error returned when committing to a branch whose commits were all integrated into the target branch.
+#[derive(Debug, Clone)]
+pub struct BranchIntegratedError {
+    /// The name of the integrated branch.
+    pub branch_name: String,
+    /// The name of the next branch of the stack that isn't integrated, if there is one.
+    pub next_branch_name: Option<String>,
+}
+
+impl fmt::Display for BranchIntegratedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The branch '{}' was already integrated into the target branch, ",
+            self.branch_name
+        )?;
+        match &self.next_branch_name {
+            Some(next_branch_name) => write!(
+                f,
+                "commit to a new branch or to the next branch of the stack, '{next_branch_name}', instead"
+            ),
+            None => f.write_str("commit to a new branch instead"),
+        }
+    }
+}
+
+impl std::error::Error for BranchIntegratedError {}
+
+/// How many seconds an author date may lie in the future, to allow for clocks that are a little off.
+const AUTHOR_DATE_FUTURE_TOLERANCE_SECONDS: i64 = 24 * 60 * 60;
+
+/// The error returned when an author date can't be parsed, or lies in the future.
+#[derive(Debug, Clone)]
+pub struct InvalidAuthorDateError {
+    /// The author date as it was passed.
+    pub date: String,
+    /// Why the date can't be used.
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidAuthorDateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid author date '{}': {}", self.date, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidAuthorDateError {}
+
+/// Parse `date` to be used as author date of a commit, failing with an [`InvalidAuthorDateError`]
+/// if it can't be parsed or lies in the future.
+pub fn parse_author_date(date: &str) -> anyhow::Result<gix::date::Time> {
+    let invalid = |reason: String| InvalidAuthorDateError {
+        date: date.to_owned(),
+        reason,
+    };
+    let time = gix::date::parse(date.trim(), Some(std::time::SystemTime::now()))
+        .map_err(|err| invalid(err.to_string()))?;
+    if time.seconds > gix::date::Time::now_utc().seconds + AUTHOR_DATE_FUTURE_TOLERANCE_SECONDS {
+        return Err(invalid("the date lies in the future".into()).into());
+    }
+    Ok(time)
+}
+
+/// Fail with a [`BranchIntegratedError`] if all commits of the branch `branch_name` in the stack `stack_id`
+/// were integrated into the target branch.
+fn ensure_branch_not_integrated(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack_id: StackId,
+    branch_name: &str,
+) -> anyhow::Result<()> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+    let branches = stack.branches();
+    let Some(position) = branches.iter().position(|b| b.name() == branch_name) else {
+        return Ok(());
+    };
+    if !is_branch_integrated(ctx, repo, &stack, &branches[position])? {
+        return Ok(());
+    }
+
+    let mut next_branch_name = None;
+    for branch in branches[position + 1..].iter().filter(|b| !b.archived) {
+        if !is_branch_integrated(ctx, repo, &stack, branch)? {
+            next_branch_name = Some(branch.name().to_owned());
+            break;
+        }
+    }
+    Err(BranchIntegratedError {
+        branch_name: branch_name.to_owned(),
+        next_branch_name,
+    }
+    .into())
+}
+
+/// Return `true` if `branch` has commits, and all of them were integrated into the target branch.
+fn is_branch_integrated(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack: &gitbutler_stack::Stack,
+    branch: &gitbutler_stack::StackBranch,
+) -> anyhow::Result<bool> {
+    let commits = but_workspace::local_and_remote_commits(ctx, repo, branch, stack)?;
+    Ok(is_integrated(&commits))
+}
+
+/// Return `true` if there are `commits`, and all of them were integrated into the target branch.
+fn is_integrated(commits: &[but_workspace::ui::Commit]) -> bool {
+    !commits.is_empty()
+        && commits
+            .iter()
+            .all(|commit| matches!(commit.state, but_workspace::ui::CommitState::Integrated))
+}
+
+/// Find the stack in the workspace with a branch named `name`, which may be [qualified](qualified_branch_name),
+/// and return its id along with the plain name of the branch.
+fn find_branch(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    name: &str,
+) -> anyhow::Result<Option<(StackId, String)>> {
+    resolve_branch_name(stack_heads(&stacks(ctx, repo)?), name)
+}
+
+/// Return the ids of `stacks` along with the names of their heads.
+fn stack_heads(stacks: &[StackEntry]) -> Vec<(StackId, String)> {
+    stacks
+        .iter()
+        .flat_map(|stack| {
+            stack
+                .heads
+                .iter()
+                .map(|head| (stack.id, head.name.to_string()))
+        })
+        .collect()
 }
 
 fn stacks(
@@ -319,6 +1044,21 @@ pub struct CreateBranchParameters {
     </important_notes>
     ")]
     pub branch_description: String,
+    /// Where to place the stack of the branch.
+    #[schemars(description = "
+    <description>
+        Where to place the stack of the new branch in the workspace.
+        Set one of 'beforeStackId', 'afterStackId' or 'index', where index 0 is the leftmost position.
+    </description>
+
+    <important_notes>
+        Leave this empty to place the stack after all existing stacks.
+        If the position can't be resolved, for instance because the stack doesn't exist, the stack is placed
+        after all existing stacks and a warning is returned.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub stack_position: Option<StackPosition>,
 }
 
 impl Tool for CreateBranch {
@@ -340,16 +1080,28 @@ impl Tool for CreateBranch {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value> {
         let params: CreateBranchParameters = serde_json::from_value(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
 
-        let stack = create_branch(ctx, app_handle, params).to_json("create branch");
+        let stack = create_branch(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .to_json("create branch");
         Ok(stack)
     }
 }
@@ -357,17 +1109,31 @@ impl Tool for CreateBranch {
 pub fn create_branch(
     ctx: &mut CommandContext,
     app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
     params: CreateBranchParameters,
-) -> Result<StackEntry, anyhow::Error> {
+) -> Result<CreatedBranch, anyhow::Error> {
     let mut guard = ctx.project().exclusive_worktree_access();
     let perm = guard.write_permission();
     let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
 
     let name = params.branch_name;
-    let description = params.branch_description;
-
+    let description = sanitizer.description(&params.branch_description);
+
+    let mut warnings = vec![];
+    let order = match &params.stack_position {
+        Some(position) => match resolve_stack_position(ctx, position)? {
+            Ok(index) => Some(index),
+            Err(warning) => {
+                warnings.push(warning);
+                None
+            }
+        },
+        None => None,
+    };
     let branch = gitbutler_branch::BranchCreateRequest {
         name: Some(name.clone()),
+        order,
         ..Default::default()
     };
 
@@ -387,10 +1153,31 @@ pub fn create_branch(
     // If there's an app handle provided, emit an event to update the stack details in the UI.
     if let Some(app_handle) = app_handle {
         let project_id = ctx.project().id;
-        app_handle.emit_stack_update(project_id, stack.id);
+        app_handle.emit_stack_update(project_id, stack.id, message_id);
     }
 
-    Ok(stack_entry)
+    Ok(CreatedBranch {
+        stack: stack_entry,
+        warnings,
+    })
+}
+
+/// The result of the create branch tool.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedBranch {
+    /// The stack the branch was created in.
+    #[serde(flatten)]
+    pub stack: StackEntry,
+    /// Problems that didn't prevent creating the branch, like a stack position that couldn't be resolved.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl ToolResult for Result<CreatedBranch, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "CreatedBranch")
+    }
 }
 
 pub struct Amend;
@@ -461,29 +1248,98 @@ pub struct AmendParameters {
 
         <important_notes>
             The file paths should be relative to the workspace root.
+            Directories stand for all changed files in them, and files that are listed more than once are included once.
             Leave this empty if you only want to edit the commit message.
         </important_notes>
         ")]
     pub files: Vec<String>,
-}
+    /// Whether to only predict the outcome of the amend.
+    #[schemars(description = "
+    <description>
+        If true, the commit is not amended. Instead, it's predicted whether the amend would apply cleanly,
+        and which commits would end up conflicted on which paths.
+    </description>
 
-impl Tool for Amend {
-    fn name(&self) -> String {
-        "amend".to_string()
-    }
+    <important_notes>
+        Use this to decide whether amending an older commit is viable, or if a follow-up commit is the better choice.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub predict_only: bool,
+    /// Whether to keep the author date of the amended commit.
+    #[schemars(description = "
+    <description>
+        Whether to keep the author date of the amended commit, instead of setting it to the current date.
+        The committer date is always set to the current date.
+    </description>
 
-    fn description(&self) -> String {
-        "
-        <description>
-            Amend an existing commit on a branch in the workspace.
-        </description>
+    <important_notes>
+        This is true by default, like it is when amending with Git.
+        This is ignored if authorDate is set.
+    </important_notes>
+    ")]
+    #[serde(default = "default_true")]
+    pub keep_author_date: bool,
+    /// The date to use as the author date of the amended commit.
+    #[schemars(description = "
+    <description>
+        The date to record as author date of the amended commit, in ISO-8601 format like '2024-03-01 14:30:00 +0100'.
+    </description>
 
-        <important_notes>
-            This tool allows you to amend a specific commit on a branch in the workspace.
-            You can specify the new commit message, target branch name, commit id, and a list of file paths to include in the amended commit.
-            Use this tool if:
-            - You want to add uncommitted changes to an existing commit.
-            - You want to update the commit message of an existing commit.
+    <important_notes>
+        Only set this when reconstructing history, to restore the original author date of a commit.
+        Leave this empty to let keepAuthorDate decide.
+        Dates in the future are refused.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub author_date: Option<String>,
+    /// Whether to refuse amending a commit that uncommitted hunks of other stacks depend on.
+    #[schemars(description = "
+    <description>
+        If true, the commit isn't amended if uncommitted hunks that are assigned to other stacks depend on it.
+        Otherwise, these hunks are listed as affectedForeignLocks in the result.
+    </description>
+
+    <important_notes>
+        Amending a commit that other hunks depend on can leave these hunks without the commit they depend on.
+        Set this to be sure that the hunks of other stacks stay committable.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub strict_locks: bool,
+    /// Whether to keep the commit if the amend leaves it without changes.
+    #[schemars(description = "
+    <description>
+        If true, the commit is kept even if amending it reverts all of its changes.
+        Otherwise, a commit that is left without changes is dropped from its branch.
+    </description>
+
+    <important_notes>
+        The result tells if the commit was left empty, and if it was dropped or kept.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub keep_empty: bool,
+}
+
+impl Tool for Amend {
+    fn name(&self) -> String {
+        "amend".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Amend an existing commit on a branch in the workspace.
+        </description>
+
+        <important_notes>
+            This tool allows you to amend a specific commit on a branch in the workspace.
+            You can specify the new commit message, target branch name, commit id, and a list of file paths to include in the amended commit.
+            Use this tool if:
+            - You want to add uncommitted changes to an existing commit.
+            - You want to update the commit message of an existing commit.
         </important_notes>
         ".to_string()
     }
@@ -493,76 +1349,495 @@ impl Tool for Amend {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn version(&self) -> u32 {
+        // 2: Empty commit messages are refused.
+        // 3: Commit messages with an empty title are refused.
+        // 4: Commits that the amend leaves without changes are dropped unless `keepEmpty` is set.
+        4
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_000)
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value> {
-        let params: AmendParameters = serde_json::from_value(parameters)
+        let mut params: AmendParameters = serde_json::from_value(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
 
-        let value = amend_commit(ctx, app_handle, params).to_json("amend_commit");
+        let normalized_input = match normalize_worktree_file_params(ctx, &params.files) {
+            Ok(normalized_input) => normalized_input,
+            Err(e) => return Ok(error_to_json(&e, "amend_commit")),
+        };
+        params.files = normalized_input.files.clone();
+
+        if params.predict_only {
+            let value = predict_amend_commit(ctx, params).to_json("predict_amend_commit");
+            return Ok(value);
+        }
+
+        let value = ensure_files_are_changed(ctx, &normalized_input.files, options.strict)
+            .and_then(|()| {
+                amend_commit(
+                    ctx,
+                    app_handle,
+                    options.message_id.as_deref(),
+                    &options.sanitizer,
+                    &options.assignments_cache,
+                    params,
+                )
+            })
+            .map(|result| result.with_normalized_input(normalized_input))
+            .to_json("amend_commit");
         Ok(value)
     }
 }
 
+/// Amend the commit in `params`, and describe the outcome along with the files of `params` in the amended commit.
+///
+/// Afterwards, the dependencies of the hunk assignments in `assignments_cache` point to the rewritten commits.
 pub fn amend_commit(
     ctx: &mut CommandContext,
     app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    assignments_cache: &AssignmentsCache,
     params: AmendParameters,
-) -> Result<but_workspace::commit_engine::ui::CreateCommitOutcome, anyhow::Error> {
-    let outcome = amend_commit_inner(ctx, app_handle, params)?;
-    Ok(outcome.into())
+) -> anyhow::Result<CommitResult> {
+    let files = params.files.clone();
+    let amended = amend_commit_inner(ctx, app_handle, message_id, sanitizer, params)?;
+    match &amended.rewritten_commits {
+        // Keeping the cache is only an optimization, so it's recomputed if it can't be kept.
+        Some(rewritten) => {
+            if repoint_cached_locks(ctx, assignments_cache, rewritten).is_err() {
+                assignments_cache.forget();
+            }
+        }
+        // The commits above the dropped one were rewritten once more, so their locks are recomputed.
+        None => assignments_cache.forget(),
+    }
+    Ok(CommitResult {
+        affected_foreign_locks: amended.affected_foreign_locks,
+        empty_commit: amended.empty_commit,
+        warnings: amended.warnings,
+        ..commit_result(ctx, amended.outcome.into(), &files)?
+    })
+}
+
+/// The outcome of [`amend_commit_inner()`].
+pub struct AmendedCommit {
+    /// The outcome of amending the commit.
+    pub outcome: but_workspace::commit_engine::CreateCommitOutcome,
+    /// The uncommitted hunks assigned to other stacks that depended on the commit before it was amended.
+    pub affected_foreign_locks: Vec<AffectedForeignLock>,
+    /// What happened to the amended commit if the amend left it without changes.
+    pub empty_commit: Option<EmptyCommit>,
+    /// Problems that didn't prevent the amend, like an emptied commit that couldn't be dropped.
+    pub warnings: Vec<String>,
+    /// The commits the amend rewrote, as `(old, new)` pairs, or `None` if the amended commit was dropped,
+    /// which rewrote the commits above it once more.
+    pub rewritten_commits: Option<Vec<(gix::ObjectId, gix::ObjectId)>>,
+}
+
+/// What happened to a commit that was left without changes by an amend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EmptyCommit {
+    /// The commit was removed from its branch.
+    Dropped,
+    /// The commit was kept, as [`AmendParameters::keep_empty`] was set.
+    Kept,
 }
 
+/// Amend the commit in `params` with the worktree changes of its files.
+///
+/// Uncommitted hunks that are assigned to other stacks but depend on the commit are listed in the outcome,
+/// or make the amend fail with a [`ForeignLocksError`] if [`AmendParameters::strict_locks`] is set.
+/// Failing to determine them only fails the amend in that case as well, and is a warning otherwise.
+/// If the amend leaves a commit that had changes without any, it's dropped unless [`AmendParameters::keep_empty`] is set.
+/// Commits that were empty before, like blank commits, are always kept.
 pub fn amend_commit_inner(
     ctx: &mut CommandContext,
     app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
     params: AmendParameters,
-) -> anyhow::Result<but_workspace::commit_engine::CreateCommitOutcome> {
+) -> anyhow::Result<AmendedCommit> {
     let repo = ctx.gix_repo()?;
+    let message = sanitizer.commit_message(&params.message_title, &params.message_body)?;
+    let author_date = match params.author_date.as_deref() {
+        Some(date) => Some(parse_author_date(date)?),
+        None if !params.keep_author_date => Some(gix::date::Time::now_local_or_utc()),
+        None => None,
+    };
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let commit_id = resolve_commit_id(&repo, &params.commit_id)?;
+
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let mut warnings = vec![];
+    // The locks are computed while the worktree is held, so they can't change until the amend is done.
+    // Unless the amend has to be refused on locks, they are only advisory, so failing to compute them is just reported.
+    let affected_foreign_locks = match affected_foreign_locks(ctx, stack_id, commit_id) {
+        Ok(locks) => locks,
+        Err(err) if params.strict_locks => return Err(err),
+        Err(err) => {
+            warnings.push(format!(
+                "The uncommitted hunks of other stacks that depend on the commit couldn't be determined: {}",
+                err
+            ));
+            vec![]
+        }
+    };
+    if params.strict_locks && !affected_foreign_locks.is_empty() {
+        return Err(ForeignLocksError {
+            commit_id,
+            locks: affected_foreign_locks,
+        }
+        .into());
+    }
+
     let project = ctx.project();
     let settings = ctx.app_settings();
-    let mut guard = ctx.project().exclusive_worktree_access();
     let worktree = but_core::diff::worktree_changes(&repo)?;
 
-    let file_changes: Vec<but_workspace::DiffSpec> = worktree
+    let changes = worktree
         .changes
         .iter()
         .filter(|change| params.files.contains(&change.path.to_string()))
-        .map(Into::into)
         .collect::<Vec<_>>();
-
-    let message = format!(
-        "{}\n\n{}",
-        params.message_title.trim(),
-        params.message_body.trim()
+    ensure_readable(&repo, &changes)?;
+    let file_changes: Vec<but_workspace::DiffSpec> =
+        changes.into_iter().map(Into::into).collect::<Vec<_>>();
+    // If it can't be told whether the commit had changes, it's treated as empty so it isn't dropped.
+    let was_empty = is_empty_commit(&repo, commit_id).unwrap_or(true);
+
+    let _ = ctx.create_snapshot(
+        SnapshotDetails::new(OperationKind::AmendCommit),
+        guard.write_permission(),
     );
-
-    let stack_id = StackId::from_str(&params.stack_id)?;
-
-    let outcome = but_workspace::commit_engine::create_commit_and_update_refs_with_project(
+    let mut outcome = but_workspace::commit_engine::create_commit_and_update_refs_with_project(
         &repo,
         project,
         Some(stack_id),
         but_workspace::commit_engine::Destination::AmendCommit {
-            commit_id: gix::ObjectId::from_str(&params.commit_id)?,
+            commit_id,
             new_message: Some(message),
+            author_date,
         },
         None,
-        file_changes,
+        file_changes.clone(),
         settings.context_lines,
         guard.write_permission(),
     );
 
+    let mut empty_commit = None;
+    let mut rewritten = None;
+    if let Ok(outcome) = &mut outcome {
+        if let Err(err) = remove_committed_assignments(ctx, app_handle, &file_changes, outcome) {
+            warnings.push(format!(
+                "The assignments of the committed hunks couldn't be removed: {}",
+                err
+            ));
+        }
+        // The amend was written already, so failing to handle an emptied commit is only reported.
+        match outcome.new_commit.filter(|_| !was_empty) {
+            None => {}
+            Some(new_commit) => match is_empty_commit(&repo, new_commit) {
+                Ok(false) => {}
+                Ok(true) if params.keep_empty => empty_commit = Some(EmptyCommit::Kept),
+                Ok(true) => {
+                    drop(guard);
+                    match gitbutler_branch_actions::undo_commit(ctx, stack_id, new_commit.to_git2())
+                    {
+                        Ok(()) => {
+                            outcome.new_commit = None;
+                            empty_commit = Some(EmptyCommit::Dropped);
+                        }
+                        Err(err) => {
+                            empty_commit = Some(EmptyCommit::Kept);
+                            warnings.push(format!(
+                                "The amended commit {} was left without changes, but couldn't be dropped: {}",
+                                new_commit, err
+                            ));
+                        }
+                    }
+                }
+                Err(err) => warnings.push(format!(
+                    "Couldn't tell if the amended commit {} was left without changes: {}",
+                    new_commit, err
+                )),
+            },
+        }
+        if empty_commit != Some(EmptyCommit::Dropped) {
+            rewritten = Some(rewritten_commits(commit_id, outcome));
+        }
+    }
+
     // If there's an app handle provided, emit an event to update the stack details in the UI.
     if let Some(app_handle) = app_handle {
         let project_id = ctx.project().id;
-        app_handle.emit_stack_update(project_id, stack_id);
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
+    }
+
+    outcome.map(|outcome| AmendedCommit {
+        outcome,
+        affected_foreign_locks,
+        empty_commit,
+        warnings,
+        rewritten_commits: rewritten,
+    })
+}
+
+/// Return `true` if the commit `commit_id` has the same tree as its first parent, so it doesn't change anything.
+fn is_empty_commit(repo: &gix::Repository, commit_id: gix::ObjectId) -> anyhow::Result<bool> {
+    let commit = repo.find_commit(commit_id)?;
+    let parent_tree_id = match commit.parent_ids().next() {
+        Some(parent_id) => repo.find_commit(parent_id)?.tree_id()?.detach(),
+        None => gix::ObjectId::empty_tree(repo.object_hash()),
+    };
+    Ok(commit.tree_id()? == parent_tree_id)
+}
+
+/// An uncommitted hunk that depends on a commit of one stack, but is assigned to another stack.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AffectedForeignLock {
+    /// The path of the file the hunk is in.
+    pub path: String,
+    /// The header of the hunk, or `None` if the assignment is for the whole file.
+    pub hunk_header: Option<but_workspace::HunkHeader>,
+    /// The stack the hunk is assigned to.
+    pub stack_id: StackId,
+    /// The commit the hunk depends on.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub commit_id: gix::ObjectId,
+}
+
+impl fmt::Display for AffectedForeignLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "An uncommitted hunk of '{}' is assigned to stack {}, but depends on commit {}",
+            self.path, self.stack_id, self.commit_id
+        )
+    }
+}
+
+/// The error returned when amending a commit that uncommitted hunks of other stacks depend on,
+/// if [`AmendParameters::strict_locks`] is set.
+#[derive(Debug, Clone)]
+pub struct ForeignLocksError {
+    /// The commit that would have been amended.
+    pub commit_id: gix::ObjectId,
+    /// The hunks of other stacks that depend on the commit.
+    pub locks: Vec<AffectedForeignLock>,
+}
+
+impl fmt::Display for ForeignLocksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} uncommitted hunk(s) assigned to other stacks depend on commit {}, which amending would rewrite",
+            self.locks.len(),
+            self.commit_id
+        )
+    }
+}
+
+impl std::error::Error for ForeignLocksError {}
+
+/// Return the uncommitted hunks that depend on the commit `commit_id` of the stack `stack_id`, but are assigned to another stack,
+/// ordered by their path.
+///
+/// The dependencies are computed without context lines, so they are matched with the assigned hunks by the lines they touch.
+pub fn affected_foreign_locks(
+    ctx: &mut CommandContext,
+    stack_id: StackId,
+    commit_id: gix::ObjectId,
+) -> anyhow::Result<Vec<AffectedForeignLock>> {
+    let dependencies =
+        but_hunk_dependency::ui::hunk_dependencies_for_workspace_changes_by_worktree_dir(
+            ctx,
+            &ctx.project().path,
+            &ctx.project().gb_dir(),
+            None,
+        )?;
+    let locked_hunks = dependencies
+        .diffs
+        .iter()
+        .filter(|(_, _, locks)| locks.iter().any(|lock| lock.commit_id == commit_id))
+        .collect::<Vec<_>>();
+    if locked_hunks.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut affected = vec![];
+    for assignment in but_hunk_assignment::persisted_assignments(ctx)? {
+        let Some(assigned_stack_id) = assignment.stack_id.filter(|id| *id != stack_id) else {
+            continue;
+        };
+        let is_locked = locked_hunks.iter().any(|(path, hunk, _)| {
+            *path == assignment.path
+                && assignment.hunk_header.is_none_or(|header| {
+                    lines_overlap(
+                        (hunk.old_start, hunk.old_lines),
+                        (header.old_start, header.old_lines),
+                    ) || lines_overlap(
+                        (hunk.new_start, hunk.new_lines),
+                        (header.new_start, header.new_lines),
+                    )
+                })
+        });
+        if is_locked {
+            affected.push(AffectedForeignLock {
+                path: assignment.path,
+                hunk_header: assignment.hunk_header,
+                stack_id: assigned_stack_id,
+                commit_id,
+            });
+        }
     }
+    affected.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(affected)
+}
 
+/// Return the commits rewritten by amending `commit_id` with `outcome`, as `(old, new)` pairs.
+fn rewritten_commits(
+    commit_id: gix::ObjectId,
+    outcome: &but_workspace::commit_engine::CreateCommitOutcome,
+) -> Vec<(gix::ObjectId, gix::ObjectId)> {
     outcome
+        .new_commit
+        .map(|new_commit| (commit_id, new_commit))
+        .into_iter()
+        .chain(outcome.rebase_output.iter().flat_map(|rebase| {
+            rebase
+                .commit_mapping
+                .iter()
+                .map(|(_, old, new)| (*old, *new))
+        }))
+        .collect()
+}
+
+/// Point the dependencies of the hunk assignments in `assignments_cache` at the commits they were rewritten to,
+/// given as `(old, new)` pairs in `rewritten`, so the cached assignments stay valid for the rewritten workspace.
+///
+/// The cache takes on the persisted assignments, which no longer include the committed hunks.
+/// If the dependencies of one of them aren't cached, the cache is dropped so all of them are recomputed.
+fn repoint_cached_locks(
+    ctx: &mut CommandContext,
+    assignments_cache: &AssignmentsCache,
+    rewritten: &[(gix::ObjectId, gix::ObjectId)],
+) -> anyhow::Result<()> {
+    let workspace = workspace_fingerprint(ctx, &ctx.gix_repo()?)?;
+    let persisted = but_hunk_assignment::persisted_assignments(ctx)?;
+
+    let mut cache = assignments_cache.lock();
+    let Some(cached) = cache.as_mut() else {
+        return Ok(());
+    };
+    let assignments = persisted
+        .into_iter()
+        .map(|mut assignment| {
+            let locks = cached
+                .assignments
+                .iter()
+                .find(|cached| {
+                    cached.path_bytes == assignment.path_bytes
+                        && cached.hunk_header == assignment.hunk_header
+                })?
+                .hunk_locks
+                .clone();
+            assignment.hunk_locks = locks.map(|locks| {
+                locks
+                    .into_iter()
+                    .map(|mut lock| {
+                        if let Some((_, new)) =
+                            rewritten.iter().find(|(old, _)| *old == lock.commit_id)
+                        {
+                            lock.commit_id = *new;
+                        }
+                        lock
+                    })
+                    .collect()
+            });
+            Some(assignment)
+        })
+        .collect::<Option<Vec<_>>>();
+    match assignments {
+        Some(assignments) => {
+            cached.assignments = assignments;
+            cached.workspace = workspace;
+        }
+        None => {
+            *cache = None;
+        }
+    }
+    Ok(())
+}
+
+/// Remove the persisted hunk assignments of the `file_changes` that ended up in the commit of `outcome`,
+/// as they would otherwise linger for changes that aren't uncommitted anymore.
+///
+/// If only some hunks of a file were committed, only the assignments of these hunks are removed.
+fn remove_committed_assignments(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    file_changes: &[but_workspace::DiffSpec],
+    outcome: &but_workspace::commit_engine::CreateCommitOutcome,
+) -> anyhow::Result<()> {
+    if outcome.new_commit.is_none() {
+        return Ok(());
+    }
+    let committed = file_changes
+        .iter()
+        .filter(|change| {
+            !outcome
+                .rejected_specs
+                .iter()
+                .any(|(_, rejected)| rejected.path == change.path)
+        })
+        .collect::<Vec<_>>();
+
+    let assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    let count = assignments.len();
+    let remaining = assignments
+        .into_iter()
+        .filter(|assignment| {
+            !committed.iter().any(|change| {
+                change.path == assignment.path_bytes
+                    && (change.hunk_headers.is_empty()
+                        || assignment
+                            .hunk_header
+                            .is_some_and(|header| change.hunk_headers.contains(&header)))
+            })
+        })
+        .collect::<Vec<_>>();
+    if remaining.len() == count {
+        return Ok(());
+    }
+    but_hunk_assignment::set_persisted_assignments(ctx, remaining.clone())?;
+
+    // If there's an app handle provided, emit an event so the UI drops the assignments as well.
+    if let Some(app_handle) = app_handle {
+        let worktree_changes: but_core::ui::WorktreeChanges =
+            but_core::diff::worktree_changes(&ctx.gix_repo()?)?.into();
+        let changes = but_hunk_assignment::WorktreeChanges {
+            assignments: remaining,
+            ..worktree_changes.into()
+        };
+        app_handle.emit_worktree_changes(ctx.project().id, &changes);
+    }
+    Ok(())
 }
 
 pub struct GetProjectStatus;
@@ -583,8 +1858,107 @@ pub struct GetProjectStatusParameters {
     </important_notes>
     ")]
     pub filter_changes: Option<Vec<String>>,
+    /// Whether to include the stacks that were recently unapplied.
+    #[schemars(description = "
+    <description>
+        Whether to also include the stacks that were recently removed from the workspace.
+        These are listed separately in the project status, along with their branches and commits.
+    </description>
+
+    <important_notes>
+        Use this to answer questions about stacks that were just unapplied.
+        Recently unapplied stacks are marked as not applied.
+        They have to be applied to the workspace again before anything can be committed to them.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub include_recently_unapplied: bool,
+    /// How many of the most recent operations to look at to find recently unapplied stacks.
+    #[schemars(description = "
+    <description>
+        The number of most recent operations to look at when finding stacks that were recently unapplied.
+    </description>
+
+    <important_notes>
+        This only has an effect if recently unapplied stacks are included.
+        If not provided, the last 10 operations are looked at.
+    </important_notes>
+    ")]
+    pub recently_unapplied_limit: Option<usize>,
+    /// Whether to include the commits that only exist on the remote branches.
+    #[schemars(description = "
+    <description>
+        Whether to also include the commits that only exist on the remote branches of the applied stacks.
+        These are commits that were pushed from somewhere else, for instance by a collaborator.
+    </description>
+
+    <important_notes>
+        Remote-only commits are listed along with the other commits of their branch, and are marked as remote only.
+        They aren't part of the workspace, so they can't be amended or moved.
+        Amending or rewriting the local commits of such a branch makes it diverge from its remote branch.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub include_remote_only: bool,
+    /// Whether to include the stacks of the workspace.
+    #[schemars(description = "
+    <description>
+        Whether to include the stacks of the workspace, along with their branches and commits.
+    </description>
+
+    <important_notes>
+        Listing the stacks takes time, so set this to false if only the uncommitted file changes are needed.
+        The stacks are then left empty, and so are the remote-only commits, even if they are requested.
+        If not provided, the stacks are included.
+    </important_notes>
+    ")]
+    #[serde(default = "default_true")]
+    pub include_stacks: bool,
+    /// The etag of a previously returned project status.
+    #[schemars(description = "
+    <description>
+        The etag of the project status that was returned by a previous call.
+    </description>
+
+    <important_notes>
+        If nothing changed since then, the status is left out of the response, and marked as unchanged.
+        Reuse the previous status in that case.
+        Leave this empty to always get the full status.
+    </important_notes>
+    ")]
+    pub etag: Option<String>,
+    /// How the diff of each hunk is represented.
+    #[schemars(description = "
+    <description>
+        How the diff of each hunk is represented, either 'unified' or 'structured'.
+    </description>
+
+    <important_notes>
+        In the 'unified' format, each hunk has a 'diff' with the unified diff text, including the hunk header.
+        In the 'structured' format, each hunk has a 'header' and 'lines' instead, where each line has a 'kind'
+        of 'context', 'added' or 'removed', and its 'content' without the leading marker.
+        The structured format takes considerably more tokens, so only use it if the lines need to be told apart reliably.
+        If not provided, the unified format is used.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub diff_format: DiffFormat,
+    /// The number of unchanged lines to show around each change.
+    #[schemars(description = "
+    <description>
+        The number of unchanged lines to show before and after each change in the diffs.
+    </description>
+
+    <important_notes>
+        If not provided, the number of lines configured for the session is used, or the one in the app settings.
+    </important_notes>
+    ")]
+    pub context_lines: Option<u32>,
 }
 
+/// The number of operations to look at for recently unapplied stacks if no limit is given.
+const DEFAULT_RECENTLY_UNAPPLIED_LIMIT: usize = 10;
+
 impl Tool for GetProjectStatus {
     fn name(&self) -> String {
         "get_project_status".to_string()
@@ -604,25 +1978,221 @@ impl Tool for GetProjectStatus {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn version(&self) -> u32 {
+        // 2: The hunks of files whose diff is disabled in `.gitattributes` are left out.
+        2
+    }
+
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        vec![
+            ("get_project_status.diffAttributes".into(), true.into()),
+            (
+                "get_project_status.diffFormats".into(),
+                serde_json::json!(["unified", "structured"]),
+            ),
+            ("get_project_status.etag".into(), true.into()),
+            ("get_project_status.recentlyUnapplied".into(), true.into()),
+            ("get_project_status.remoteOnly".into(), true.into()),
+        ]
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(2_000)
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value> {
         let repo = ctx.gix_repo()?;
-        let params: GetProjectStatusParameters = serde_json::from_value(parameters)
+        let mut params: GetProjectStatusParameters = serde_json::from_value(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+        params.context_lines = params.context_lines.or(options.context_lines);
 
-        let paths = params
-            .filter_changes
-            .map(|f| f.into_iter().map(BString::from).collect::<Vec<BString>>());
-
-        let value = get_project_status(ctx, &repo, paths).to_json("get_project_status");
+        let paths = params.filter_changes.clone();
+        let value = project_status_response(
+            ctx,
+            &repo,
+            params,
+            options.auto_prune,
+            &options.assignments_cache,
+        )
+        .and_then(|response| {
+            // Without a status, nothing changed since the etag, and there is nothing to check.
+            if let (true, Some(status)) = (options.strict, &response.status) {
+                ensure_no_changes_were_skipped(
+                    &repo,
+                    paths.as_deref(),
+                    &status.file_changes,
+                    &status.warnings,
+                )?;
+            }
+            Ok(response)
+        })
+        .to_json("get_project_status");
         Ok(value)
     }
 }
 
+/// A project status along with its etag, for conditional fetching.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectStatusResponse {
+    /// Identifies the state the status was computed from.
+    pub etag: String,
+    /// Whether the status changed since the etag passed in, which is always the case if none was passed.
+    pub changed: bool,
+    /// The status, which is left out if it didn't change.
+    ///
+    /// Its fields are inlined into the response, so it reads like a plain project status.
+    #[serde(flatten)]
+    pub status: Option<ProjectStatus>,
+}
+
+impl ToolResult for Result<ProjectStatusResponse, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "ProjectStatusResponse")
+    }
+}
+
+/// Compute the project status as requested by `params`, unless it didn't change since `params.etag`.
+///
+/// If `auto_prune` is `true`, stale hunk assignments are removed first, see [`get_filtered_changes()`].
+pub fn project_status_response(
+    ctx: &mut CommandContext,
+    repo: &gix::Repository,
+    params: GetProjectStatusParameters,
+    auto_prune: bool,
+    assignments_cache: &AssignmentsCache,
+) -> anyhow::Result<ProjectStatusResponse> {
+    let worktree_changes = but_core::diff::worktree_changes(repo)?.changes;
+    let etag = project_status_etag(ctx, repo, &params, &worktree_changes)?;
+    if params.etag.as_ref() == Some(&etag) {
+        return Ok(ProjectStatusResponse {
+            etag,
+            changed: false,
+            status: None,
+        });
+    }
+
+    let paths = params
+        .filter_changes
+        .clone()
+        .map(|f| f.into_iter().map(BString::from).collect::<Vec<BString>>());
+    let context_lines = params
+        .context_lines
+        .unwrap_or(ctx.app_settings().context_lines);
+    let mut status = project_status(
+        ctx,
+        repo,
+        worktree_changes.clone(),
+        paths,
+        context_lines,
+        params.include_stacks,
+        auto_prune,
+        assignments_cache,
+    )?;
+    set_diff_format(&mut status.file_changes, params.diff_format);
+    if params.include_recently_unapplied {
+        let limit = params
+            .recently_unapplied_limit
+            .unwrap_or(DEFAULT_RECENTLY_UNAPPLIED_LIMIT);
+        status.unapplied_stacks = recently_unapplied_stacks(ctx, repo, limit)?;
+    }
+    if params.include_remote_only {
+        add_remote_only_commits(ctx, repo, &mut status.stacks)?;
+    }
+
+    // Computing the status may persist hunk assignments, so the etag is taken again to match the next call.
+    let etag = project_status_etag(ctx, repo, &params, &worktree_changes)?;
+    Ok(ProjectStatusResponse {
+        etag,
+        changed: true,
+        status: Some(status),
+    })
+}
+
+/// Return an etag of everything the project status requested by `params` is made of,
+/// which are the heads of the stacks, their metadata, the persisted hunk assignments, the head of the oplog,
+/// and `worktree_changes`, all changed files in the worktree.
+///
+/// Changed files are identified by their size and modification time instead of their content, which is much cheaper.
+/// The etag is an MD5 hash, so it stays the same across releases.
+pub fn project_status_etag(
+    ctx: &mut CommandContext,
+    repo: &gix::Repository,
+    params: &GetProjectStatusParameters,
+    worktree_changes: &[TreeChange],
+) -> anyhow::Result<String> {
+    let mut hasher = md5::Context::new();
+    let mut hash = |bytes: &[u8]| {
+        // The length separates the fields, so their boundaries can't shift.
+        hasher.consume((bytes.len() as u64).to_le_bytes());
+        hasher.consume(bytes);
+    };
+    let options = GetProjectStatusParameters {
+        etag: None,
+        ..params.clone()
+    };
+    hash(serde_json::to_string(&options)?.as_bytes());
+
+    for (stack_id, head) in workspace_fingerprint(ctx, repo)? {
+        hash(stack_id.to_string().as_bytes());
+        hash(head.as_bytes());
+    }
+    // How far the stacks are behind depends on the target branch, which moves when fetching.
+    hash(
+        target_tip(ctx, repo)?
+            .map(|tip| tip.to_string())
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+    // Branch descriptions and the order of stacks are only kept in the metadata.
+    let metadata = std::fs::metadata(ctx.project().gb_dir().join("virtual_branches.toml")).ok();
+    hash(file_stamp(metadata).as_bytes());
+    // Recently unapplied stacks are read from the oplog.
+    hash(
+        ctx.oplog_head()?
+            .map(|head| head.to_string())
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+    // Hunks are assigned to stacks in the UI without changing anything else.
+    let assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    hash(serde_json::to_string(&assignments)?.as_bytes());
+
+    let worktree_dir = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no worktree"))?;
+    for change in worktree_changes {
+        hash(change.path.as_slice());
+        hash(format!("{:?}", change.status).as_bytes());
+        let metadata = gix::path::try_from_bstr(change.path.as_bstr())
+            .ok()
+            .and_then(|path| std::fs::symlink_metadata(worktree_dir.join(path)).ok());
+        hash(file_stamp(metadata).as_bytes());
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Identify the state of a file by its size and modification time from its `metadata`, if it exists.
+fn file_stamp(metadata: Option<std::fs::Metadata>) -> String {
+    let Some(metadata) = metadata else {
+        return String::new();
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    format!("{}:{}", metadata.len(), modified)
+}
+
 pub struct CreateBlankCommit;
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
@@ -677,12 +2247,27 @@ pub struct CreateBlankCommitParameters {
 
     <important_notes>
         This should be the ID of an existinf commit in the stack.
+        The blank commit becomes the child of this commit, so it takes the position of this commit in its branch,
+        and the commit moves one position down. Use the head of a branch to add the blank commit at position 0.
     </important_notes>
     ")]
     pub parent_id: String,
-}
+    /// The position the blank commit is expected to get in its branch.
+    #[schemars(description = "
+    <description>
+        The position the blank commit should get in its branch, with the head of the branch at 0.
+    </description>
 
-impl Tool for CreateBlankCommit {
+    <important_notes>
+        This is optional. If provided, the blank commit is only created if this is the position it would get
+        on top of the parent, and otherwise the error says which parent to use instead.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub position: Option<u32>,
+}
+
+impl Tool for CreateBlankCommit {
     fn name(&self) -> String {
         "create_blank_commit".to_string()
     }
@@ -708,16 +2293,33 @@ impl Tool for CreateBlankCommit {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn version(&self) -> u32 {
+        // 2: Empty commit messages are refused.
+        // 3: Commit messages with an empty title are refused.
+        3
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value> {
         let params: CreateBlankCommitParameters = serde_json::from_value(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
 
-        match create_blank_commit(ctx, app_handle, params) {
+        match create_blank_commit(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        ) {
             Ok(_) => Ok("Suceess".into()),
             Err(e) => Ok(error_to_json(&e, "create_blank_commit")),
         }
@@ -727,18 +2329,18 @@ impl Tool for CreateBlankCommit {
 pub fn create_blank_commit(
     ctx: &mut CommandContext,
     app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
     params: CreateBlankCommitParameters,
 ) -> Result<Vec<(gix::ObjectId, gix::ObjectId)>, anyhow::Error> {
+    let message = sanitizer.commit_message(&params.message_title, &params.message_body)?;
+
     let stack_id = StackId::from_str(&params.stack_id)?;
-    let commit_oid = gix::ObjectId::from_str(&params.parent_id)?;
+    let repo = ctx.gix_repo()?;
+    let commit_oid = resolve_commit_id(&repo, &params.parent_id)?;
+    ensure_blank_commit_parent(ctx, &repo, stack_id, commit_oid, params.position)?;
     let commit_oid = commit_oid.to_git2();
 
-    let message = format!(
-        "{}\n\n{}",
-        params.message_title.trim(),
-        params.message_body.trim()
-    );
-
     let commit_mapping = gitbutler_branch_actions::insert_blank_commit(
         ctx,
         stack_id,
@@ -750,12 +2352,66 @@ pub fn create_blank_commit(
     // If there's an app handle provided, emit an event to update the stack details in the UI.
     if let Some(app_handle) = app_handle {
         let project_id = ctx.project().id;
-        app_handle.emit_stack_update(project_id, stack_id);
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
     }
 
     Ok(commit_mapping)
 }
 
+/// Check that the blank commit in `stack_id` can be inserted on top of `parent_id`, which has to be a commit
+/// of one of its branches or its base, and that it gets `position` in its branch if that's set.
+fn ensure_blank_commit_parent(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack_id: StackId,
+    parent_id: gix::ObjectId,
+    position: Option<u32>,
+) -> anyhow::Result<()> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+    let branches = simple_branches(ctx, repo, &stack)?;
+    let Some((branch, parent)) = branches.iter().find_map(|branch| {
+        branch
+            .commits
+            .iter()
+            .find(|commit| commit.id == parent_id && !commit.remote_only)
+            .map(|commit| (branch, commit))
+    }) else {
+        let merge_base = stack.merge_base(ctx)?;
+        if parent_id == merge_base {
+            return Ok(());
+        }
+        anyhow::bail!(
+            "{parent_id} isn't a commit of the stack. Use one of the commits of its branches, \
+             which are listed from the head of each branch at position 0, or its base {merge_base}"
+        );
+    };
+
+    let Some((expected, actual)) = position.zip(parent.position) else {
+        return Ok(());
+    };
+    if expected == actual {
+        return Ok(());
+    }
+    let described = if actual == 0 {
+        format!("the head of branch '{}'", branch.name)
+    } else {
+        format!("at position {actual} of branch '{}'", branch.name)
+    };
+    let suggestion = match branch
+        .commits
+        .iter()
+        .find(|commit| commit.position == Some(expected))
+    {
+        Some(commit) => format!("use {} as parent to get position {expected}", commit.id),
+        None => format!("the branch has no commit at position {expected} to use as parent"),
+    };
+    anyhow::bail!(
+        "{parent_id} is {described}, so the blank commit would get position {actual}; \
+         did you mean position {actual}? Otherwise, {suggestion}"
+    )
+}
+
 pub struct MoveFileChanges;
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
@@ -793,6 +2449,8 @@ pub struct MoveFileChangesParameters {
 
     <important_notes>
         The commit id should refer to a commit on the specified destination stack.
+        Use 'worktree' instead of a commit id to take the file changes out of the source commit,
+        and leave them as uncommitted changes in the worktree.
     </important_notes>
     ")]
     pub destination_commit_id: String,
@@ -805,8 +2463,10 @@ pub struct MoveFileChangesParameters {
 
     <important_notes>
         The stack id should refer to a stack in the workspace.
+        This is ignored, and can be left empty, if the changes are moved to the worktree.
     </important_notes>
     ")]
+    #[serde(default)]
     pub destination_stack_id: String,
 
     /// The list of files to move.
@@ -819,9 +2479,34 @@ pub struct MoveFileChangesParameters {
         The file paths should be relative to the workspace root.
         The file paths should be contained in the source commit.
         Only the specified files will be moved.
+        Directories stand for all files in them that the source commit changed.
     </important_notes>
     ")]
     pub files: Vec<String>,
+
+    /// Whether to only predict the outcome of the move.
+    #[schemars(description = "
+    <description>
+        If true, the move is not performed. Instead, it's predicted whether it would apply cleanly,
+        and which commits would end up conflicted on which paths.
+    </description>
+
+    <important_notes>
+        Use this to decide whether moving the changes is viable before touching the repository.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub predict_only: bool,
+}
+
+/// The destination commit id that moves file changes to the worktree.
+pub const WORKTREE_DESTINATION: &str = "worktree";
+
+impl MoveFileChangesParameters {
+    /// Return true if the file changes are moved to the worktree instead of another commit.
+    pub fn moves_to_worktree(&self) -> bool {
+        self.destination_commit_id.trim() == WORKTREE_DESTINATION
+    }
 }
 
 impl Tool for MoveFileChanges {
@@ -838,6 +2523,7 @@ impl Tool for MoveFileChanges {
         <important_notes>
             Use this tool when you want to move file changes from one commit to another.
             This is useful when you want to split a commit into more parts.
+            File changes can also be moved out of a commit and back into the worktree.
         </important_notes>
         "
         .to_string()
@@ -848,17 +2534,49 @@ impl Tool for MoveFileChanges {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        vec![
+            ("move_file_changes.normalizedInput".into(), true.into()),
+            ("move_file_changes.predictOnly".into(), true.into()),
+        ]
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value> {
-        let params: MoveFileChangesParameters = serde_json::from_value(parameters)
+        let mut params: MoveFileChangesParameters = serde_json::from_value(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
 
-        match move_file_changes(ctx, app_handle, params) {
-            Ok(_) => Ok("Success".into()),
+        let normalized_input =
+            match normalize_commit_file_params(ctx, &params.source_commit_id, &params.files) {
+                Ok(normalized_input) => normalized_input,
+                Err(e) => return Ok(error_to_json(&e, "move_file_changes")),
+            };
+        params.files = normalized_input.files.clone();
+
+        if params.predict_only {
+            let value = predict_move_file_changes(ctx, params).to_json("predict_move_file_changes");
+            return Ok(value);
+        }
+
+        match move_file_changes(ctx, app_handle, options.message_id.as_deref(), params) {
+            Ok(_) if normalized_input.collapsed.is_empty() => Ok("Success".into()),
+            Ok(_) => Ok(serde_json::json!({
+                "result": "Success",
+                "normalizedInput": normalized_input,
+            })),
             Err(e) => Ok(error_to_json(&e, "move_file_changes")),
         }
     }
@@ -867,12 +2585,12 @@ impl Tool for MoveFileChanges {
 pub fn move_file_changes(
     ctx: &mut CommandContext,
     app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
     params: MoveFileChangesParameters,
 ) -> Result<Vec<(gix::ObjectId, gix::ObjectId)>, anyhow::Error> {
-    let source_commit_id = gix::ObjectId::from_str(&params.source_commit_id)?;
+    let repo = ctx.gix_repo()?;
+    let source_commit_id = resolve_commit_id(&repo, &params.source_commit_id)?;
     let source_stack_id = StackId::from_str(&params.source_stack_id)?;
-    let destination_commit_id = gix::ObjectId::from_str(&params.destination_commit_id)?;
-    let destination_stack_id = StackId::from_str(&params.destination_stack_id)?;
 
     let changes = params
         .files
@@ -884,15 +2602,30 @@ pub fn move_file_changes(
         })
         .collect::<Vec<_>>();
 
-    let result = but_workspace::move_changes_between_commits(
-        ctx,
-        source_stack_id,
-        source_commit_id,
-        destination_stack_id,
-        destination_commit_id,
-        changes,
-        ctx.app_settings().context_lines,
-    )?;
+    let (result, destination_stack_id) = if params.moves_to_worktree() {
+        // Without updating the worktree, the removed changes show up as uncommitted changes.
+        let result = but_workspace::remove_changes_from_commit_in_stack(
+            ctx,
+            source_stack_id,
+            source_commit_id,
+            changes,
+            ctx.app_settings().context_lines,
+        )?;
+        (result, None)
+    } else {
+        let destination_commit_id = resolve_commit_id(&repo, &params.destination_commit_id)?;
+        let destination_stack_id = StackId::from_str(&params.destination_stack_id)?;
+        let result = but_workspace::move_changes_between_commits(
+            ctx,
+            source_stack_id,
+            source_commit_id,
+            destination_stack_id,
+            destination_commit_id,
+            changes,
+            ctx.app_settings().context_lines,
+        )?;
+        (result, Some(destination_stack_id))
+    };
 
     let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
     gitbutler_branch_actions::update_workspace_commit(&vb_state, ctx)?;
@@ -900,13 +2633,302 @@ pub fn move_file_changes(
     // If there's an app handle provided, emit an event to update the stack details in the UI.
     if let Some(app_handle) = app_handle {
         let project_id = ctx.project().id;
-        app_handle.emit_stack_update(project_id, source_stack_id);
-        app_handle.emit_stack_update(project_id, destination_stack_id);
+        app_handle.emit_stack_update(project_id, source_stack_id, message_id);
+        if let Some(destination_stack_id) = destination_stack_id {
+            app_handle.emit_stack_update(project_id, destination_stack_id, message_id);
+        }
     }
 
     Ok(result.replaced_commits)
 }
 
+/// The predicted outcome of an operation that rewrites commits.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictPrediction {
+    /// Whether the operation would apply without any conflicts.
+    pub clean: bool,
+    /// The commits that would conflict.
+    pub conflicts: Vec<PredictedConflict>,
+}
+
+impl From<Vec<PredictedConflict>> for ConflictPrediction {
+    fn from(conflicts: Vec<PredictedConflict>) -> Self {
+        ConflictPrediction {
+            clean: conflicts.is_empty(),
+            conflicts,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PredictedConflict {
+    /// The id of the commit that would conflict, as it is now.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub commit_id: gix::ObjectId,
+    /// The paths that would conflict.
+    pub paths: Vec<String>,
+}
+
+impl ToolResult for Result<ConflictPrediction, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "ConflictPrediction")
+    }
+}
+
+/// Predict the outcome of [`amend_commit()`] without changing the repository.
+///
+/// All objects are written into memory only, and no reference is touched.
+pub fn predict_amend_commit(
+    ctx: &mut CommandContext,
+    params: AmendParameters,
+) -> anyhow::Result<ConflictPrediction> {
+    let repo = ctx.gix_repo_for_merging_non_persisting()?;
+    let worktree = but_core::diff::worktree_changes(&repo)?;
+
+    let file_changes: Vec<but_workspace::DiffSpec> = worktree
+        .changes
+        .iter()
+        .filter(|change| params.files.contains(&change.path.to_string()))
+        .map(Into::into)
+        .collect::<Vec<_>>();
+
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let commit_id = resolve_commit_id(&repo, &params.commit_id)?;
+
+    let outcome = but_workspace::commit_engine::create_commit(
+        &repo,
+        but_workspace::commit_engine::Destination::AmendCommit {
+            commit_id,
+            new_message: None,
+            author_date: None,
+        },
+        None,
+        file_changes,
+        ctx.app_settings().context_lines,
+    )?;
+
+    let mut conflicts = vec![];
+    let conflicting_paths = outcome
+        .rejected_specs
+        .iter()
+        .filter(|(reason, _)| {
+            *reason == but_workspace::commit_engine::RejectionReason::CherryPickMergeConflict
+        })
+        .map(|(_, spec)| spec.path.to_string())
+        .collect::<Vec<_>>();
+    if !conflicting_paths.is_empty() {
+        conflicts.push(PredictedConflict {
+            commit_id,
+            paths: conflicting_paths,
+        });
+    }
+
+    if let Some(new_commit) = outcome.new_commit {
+        let (stack_conflicts, _) =
+            predict_stack_rebase(ctx, &repo, stack_id, &[(commit_id, new_commit)])?;
+        conflicts.extend(stack_conflicts);
+    }
+
+    Ok(conflicts.into())
+}
+
+/// Predict the outcome of [`move_file_changes()`] without changing the repository.
+///
+/// All objects are written into memory only, and no reference is touched.
+pub fn predict_move_file_changes(
+    ctx: &mut CommandContext,
+    params: MoveFileChangesParameters,
+) -> anyhow::Result<ConflictPrediction> {
+    let repo = ctx.gix_repo_for_merging_non_persisting()?;
+    let source_commit_id = resolve_commit_id(&repo, &params.source_commit_id)?;
+    let source_stack_id = StackId::from_str(&params.source_stack_id)?;
+    let destination = if params.moves_to_worktree() {
+        None
+    } else {
+        Some((
+            resolve_commit_id(&repo, &params.destination_commit_id)?,
+            StackId::from_str(&params.destination_stack_id)?,
+        ))
+    };
+
+    if destination
+        .is_some_and(|(destination_commit_id, _)| destination_commit_id == source_commit_id)
+    {
+        return Ok(vec![].into());
+    }
+
+    // Remove the files from the source commit by restoring them to their state in its parent.
+    let source_commit = repo.find_commit(source_commit_id)?;
+    let source_tree_id = source_commit.tree_id()?.detach();
+    let parent_tree = match source_commit.parent_ids().next() {
+        Some(parent_id) => parent_id.object()?.peel_to_tree()?,
+        None => repo.empty_tree(),
+    };
+    let mut editor = repo.edit_tree(source_tree_id)?;
+    for file in &params.files {
+        match parent_tree.lookup_entry_by_path(file)? {
+            Some(entry) => {
+                editor.upsert(file.as_str(), entry.mode().kind(), entry.object_id())?;
+            }
+            None => {
+                editor.remove(file.as_str())?;
+            }
+        }
+    }
+    let source_tree_without_changes = editor.write()?.detach();
+    let rewritten_source_commit =
+        but_rebase::replace_commit_tree(&repo, source_commit_id, source_tree_without_changes)?;
+
+    let (source_conflicts, source_mapping) = predict_stack_rebase(
+        ctx,
+        &repo,
+        source_stack_id,
+        &[(source_commit_id, rewritten_source_commit)],
+    )?;
+    let Some((destination_commit_id, destination_stack_id)) = destination else {
+        // Moving the changes to the worktree only rewrites the source stack.
+        return Ok(source_conflicts.into());
+    };
+
+    let rebased_destination_commit_id = if source_stack_id == destination_stack_id {
+        source_mapping
+            .iter()
+            .find(|(old, _)| *old == destination_commit_id)
+            .map_or(destination_commit_id, |(_, new)| *new)
+    } else {
+        destination_commit_id
+    };
+    let destination_tree_id = repo.find_commit(rebased_destination_commit_id)?.tree_id()?;
+
+    // Moving the changes fails fast if they can't be merged into the destination commit.
+    let (fail_fast_options, conflict_kind) = repo.merge_options_fail_fast()?;
+    let mut merge = repo.merge_trees(
+        source_tree_without_changes,
+        source_tree_id,
+        destination_tree_id,
+        Default::default(),
+        fail_fast_options,
+    )?;
+    if merge.has_unresolved_conflicts(conflict_kind) {
+        let paths = merge
+            .conflicts
+            .iter()
+            .filter(|conflict| conflict.is_unresolved(conflict_kind))
+            .map(|conflict| conflict.theirs.location().to_string())
+            .collect();
+        // Report the conflicts of rewriting the source stack along with the one of the destination.
+        return Ok(source_conflicts
+            .into_iter()
+            .chain(Some(PredictedConflict {
+                commit_id: destination_commit_id,
+                paths,
+            }))
+            .collect::<Vec<_>>()
+            .into());
+    }
+    let destination_tree = merge.tree.write()?.detach();
+    let rewritten_destination_commit =
+        but_rebase::replace_commit_tree(&repo, rebased_destination_commit_id, destination_tree)?;
+
+    let conflicts = if source_stack_id == destination_stack_id {
+        let (conflicts, _) = predict_stack_rebase(
+            ctx,
+            &repo,
+            source_stack_id,
+            &[
+                (source_commit_id, rewritten_source_commit),
+                (destination_commit_id, rewritten_destination_commit),
+            ],
+        )?;
+        conflicts
+    } else {
+        let (destination_conflicts, _) = predict_stack_rebase(
+            ctx,
+            &repo,
+            destination_stack_id,
+            &[(destination_commit_id, rewritten_destination_commit)],
+        )?;
+        source_conflicts
+            .into_iter()
+            .chain(destination_conflicts)
+            .collect()
+    };
+
+    Ok(conflicts.into())
+}
+
+/// Rebase the stack with `stack_id` with the commits in `replacements` swapped for their rewritten versions.
+///
+/// Only objects are written, so `repo` should keep them in memory.
+/// Return the commits that would conflict, along with the mapping of the original commits to the rebased ones.
+#[allow(clippy::type_complexity)]
+fn predict_stack_rebase(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack_id: StackId,
+    replacements: &[(gix::ObjectId, gix::ObjectId)],
+) -> anyhow::Result<(Vec<PredictedConflict>, Vec<(gix::ObjectId, gix::ObjectId)>)> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack_in_workspace(stack_id)?;
+
+    let mut steps = stack.as_rebase_steps(ctx, repo)?;
+    for step in &mut steps {
+        if let but_rebase::RebaseStep::Pick { commit_id, .. } = step {
+            if let Some((_, rewritten)) = replacements.iter().find(|(old, _)| old == commit_id) {
+                *commit_id = *rewritten;
+            }
+        }
+    }
+
+    let mut rebase = but_rebase::Rebase::new(repo, stack.merge_base(ctx)?, None)?;
+    rebase.steps(steps)?;
+    rebase.rebase_noops(false);
+    let output = rebase.rebase()?;
+
+    let mut conflicts = vec![];
+    let mut mapping = vec![];
+    for (_, old, new) in output.commit_mapping {
+        // Report the commits as they are known now, not by their rewritten versions.
+        let original = replacements
+            .iter()
+            .find(|(_, rewritten)| *rewritten == old)
+            .map_or(old, |(original, _)| *original);
+        mapping.push((original, new));
+
+        if let Some(paths) = conflicting_paths(repo, new)? {
+            conflicts.push(PredictedConflict {
+                commit_id: original,
+                paths,
+            });
+        }
+    }
+
+    Ok((conflicts, mapping))
+}
+
+/// Return the sorted paths of the conflicting files of the commit `commit_id`, or `None` if it isn't conflicted.
+fn conflicting_paths(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let commit = but_core::Commit::from_id(commit_id.attach(repo))?;
+    let Some(entries) = commit.conflict_entries()? else {
+        return Ok(None);
+    };
+    let mut paths = entries
+        .ancestor_entries
+        .iter()
+        .chain(&entries.our_entries)
+        .chain(&entries.their_entries)
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    Ok(Some(paths))
+}
+
 pub struct GetCommitDetails;
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
@@ -923,6 +2945,33 @@ pub struct GetCommitDetailsParameters {
     </important_notes>
     ")]
     pub commit_id: String,
+    /// How the diff of each hunk is represented.
+    #[schemars(description = "
+    <description>
+        How the diff of each hunk is represented, either 'unified' or 'structured'.
+    </description>
+
+    <important_notes>
+        In the 'unified' format, each hunk has a 'diff' with the unified diff text, including the hunk header.
+        In the 'structured' format, each hunk has a 'header' and 'lines' instead, where each line has a 'kind'
+        of 'context', 'added' or 'removed', and its 'content' without the leading marker.
+        The structured format takes considerably more tokens, so only use it if the lines need to be told apart reliably.
+        If not provided, the unified format is used.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub diff_format: DiffFormat,
+    /// The number of unchanged lines to show around each change.
+    #[schemars(description = "
+    <description>
+        The number of unchanged lines to show before and after each change in the diffs.
+    </description>
+
+    <important_notes>
+        If not provided, the number of lines configured for the session is used, or the one in the app settings.
+    </important_notes>
+    ")]
+    pub context_lines: Option<u32>,
 }
 
 impl Tool for GetCommitDetails {
@@ -940,6 +2989,7 @@ impl Tool for GetCommitDetails {
             This tool allows you to retrieve detailed information about a specific commit in the workspace.
             Use this tool to get the information about the files changed in the commit.
             You'll want to use this tool before moving file changes from one commit to another.
+            Files whose changes can't be read are left out, and listed in the warnings instead.
         </important_notes>
         "
         .to_string()
@@ -950,14 +3000,31 @@ impl Tool for GetCommitDetails {
         serde_json::to_value(&schema).unwrap_or_default()
     }
 
+    fn version(&self) -> u32 {
+        // 2: The file changes are listed in `fileChanges`, along with the `warnings` of files that couldn't be read.
+        2
+    }
+
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        vec![
+            ("get_commit_details.diffAttributes".into(), true.into()),
+            (
+                "get_commit_details.diffFormats".into(),
+                serde_json::json!(["unified", "structured"]),
+            ),
+        ]
+    }
+
     fn call(
         self: Arc<Self>,
         parameters: serde_json::Value,
         ctx: &mut CommandContext,
         _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
     ) -> anyhow::Result<serde_json::Value> {
-        let params: GetCommitDetailsParameters = serde_json::from_value(parameters)
+        let mut params: GetCommitDetailsParameters = serde_json::from_value(parameters)
             .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+        params.context_lines = params.context_lines.or(options.context_lines);
 
         let file_changes = commit_details(ctx, params).to_json("commit_details");
 
@@ -965,12 +3032,30 @@ impl Tool for GetCommitDetails {
     }
 }
 
+/// The changes made by a commit.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitDetails {
+    /// The diffs of the files changed in the commit.
+    pub file_changes: Vec<FileChange>,
+    /// Changed files that were left out because they couldn't be read.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<DiffWarning>,
+}
+
+impl ToolResult for Result<CommitDetails, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "CommitDetails")
+    }
+}
+
+/// Return the changes of the commit in `params`, leaving out the files that can't be read along with a warning for each.
 pub fn commit_details(
     ctx: &mut CommandContext,
     params: GetCommitDetailsParameters,
-) -> anyhow::Result<Vec<FileChange>> {
+) -> anyhow::Result<CommitDetails> {
     let repo = ctx.gix_repo()?;
-    let commit_id = gix::ObjectId::from_str(&params.commit_id)?;
+    let commit_id = resolve_commit_id(&repo, &params.commit_id)?;
 
     let changes = but_core::diff::ui::commit_changes_by_worktree_dir(&repo, commit_id)?;
     let changes: Vec<but_core::TreeChange> = changes
@@ -979,70 +3064,8813 @@ pub fn commit_details(
         .map(|change| change.into())
         .collect();
 
-    let diff = unified_diff_for_changes(&repo, changes, ctx.app_settings().context_lines)?;
-    let file_changes = get_file_changes(&diff, vec![]);
+    let context_lines = params
+        .context_lines
+        .unwrap_or(ctx.app_settings().context_lines);
+    let (diff, warnings) = unified_diff_for_changes(&repo, changes, context_lines)?;
+    let mut file_changes = get_file_changes(&diff, vec![])?;
+    let tree_id = repo.find_commit(commit_id)?.tree_id()?.detach();
+    apply_diff_attributes(&repo, Some(tree_id), &mut file_changes)?;
+    set_diff_format(&mut file_changes, params.diff_format);
 
-    Ok(file_changes)
+    Ok(CommitDetails {
+        file_changes,
+        warnings,
+    })
 }
 
-fn ref_metadata_toml(project: &Project) -> anyhow::Result<VirtualBranchesTomlMetadata> {
-    VirtualBranchesTomlMetadata::from_path(project.gb_dir().join("virtual_branches.toml"))
-}
+pub struct GetBranchDescription;
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct RichHunk {
-    /// The diff string.
-    pub diff: String,
-    /// The stack ID this hunk is assigned to, if any.
-    pub assigned_to_stack: Option<but_workspace::StackId>,
-    /// The locks this hunk has, if any.
-    pub dependency_locks: Vec<but_hunk_dependency::ui::HunkLock>,
-}
+pub struct GetBranchDescriptionParameters {
+    /// The id of the stack containing the branch.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch.
+    </description>
 
-#[derive(Debug, Clone, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SimpleCommit {
-    /// The commit sha.
-    #[serde(with = "gitbutler_serde::object_id")]
-    pub id: gix::ObjectId,
-    /// The commit message.
-    pub message_title: String,
-    /// The commit message body.
-    pub message_body: String,
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The name of the branch.
+    #[schemars(description = "
+    <description>
+        The name of the branch to get the description of.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub branch_name: String,
 }
 
-impl From<but_workspace::ui::Commit> for SimpleCommit {
-    fn from(commit: but_workspace::ui::Commit) -> Self {
-        let message_str = commit.message.to_string();
-        let mut lines = message_str.lines();
-        let message_title = lines.next().unwrap_or_default().to_string();
-        let mut message_body = lines.collect::<Vec<_>>().join("\n");
-        // Remove leading empty lines from the body
-        while message_body.starts_with('\n') || message_body.starts_with("\r\n") {
-            message_body = message_body
-                .trim_start_matches('\n')
-                .trim_start_matches("\r\n")
-                .to_string();
-        }
-        SimpleCommit {
-            id: commit.id,
-            message_title,
-            message_body,
-        }
+impl Tool for GetBranchDescription {
+    fn name(&self) -> String {
+        "get_branch_description".to_string()
     }
-}
 
-#[derive(Debug, Clone, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SimpleBranch {
-    /// The name of the branch.
-    pub name: String,
-    /// The description of the branch.
-    pub description: Option<String>,
-    /// The commits in the branch.
-    pub commits: Vec<SimpleCommit>,
-}
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the description of a branch in the workspace.
+        </description>
+
+        <important_notes>
+            Use this tool if you only need to know the purpose of a branch,
+            without getting the full project status.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetBranchDescriptionParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetBranchDescriptionParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = branch_description(ctx, params).to_json("get_branch_description");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchDescription {
+    /// The name of the branch.
+    pub name: String,
+    /// The description of the branch, if it has one.
+    pub description: Option<String>,
+}
+
+impl ToolResult for Result<BranchDescription, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "BranchDescription")
+    }
+}
+
+pub fn branch_description(
+    ctx: &CommandContext,
+    params: GetBranchDescriptionParameters,
+) -> anyhow::Result<BranchDescription> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let branch_name = unqualified_branch_name(stack_id, &params.branch_name);
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+
+    let branch = stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == branch_name)
+        .ok_or_else(|| {
+            anyhow::anyhow!("Branch '{}' not found in stack {}", branch_name, stack_id)
+        })?;
+
+    Ok(BranchDescription {
+        name: branch.name().to_owned(),
+        description: branch.description,
+    })
+}
+
+pub struct SetBranchDescription;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SetBranchDescriptionParameters {
+    /// The id of the stack containing the branch.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The name of the branch.
+    #[schemars(description = "
+    <description>
+        The name of the branch to update the description of.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub branch_name: String,
+    /// The new branch description.
+    #[schemars(description = "
+    <description>
+        The new description of the branch.
+        This is a short summary of the branch's purpose.
+        The existing description will be overwritten.
+    </description>
+
+    <important_notes>
+        The branch description should be a concise summary of the branch's purpose and changes.
+        It's important to keep it clear and informative.
+        This description should also point out which kind of changes should be assigned to this branch.
+    </important_notes>
+    ")]
+    pub description: String,
+}
+
+impl Tool for SetBranchDescription {
+    fn name(&self) -> String {
+        "set_branch_description".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Update the description of a branch in the workspace.
+        </description>
+
+        <important_notes>
+            Only the description is updated, the name and the commits of the branch stay the same.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(SetBranchDescriptionParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: SetBranchDescriptionParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = set_branch_description(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .to_json("set_branch_description");
+        Ok(value)
+    }
+}
+
+pub fn set_branch_description(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: SetBranchDescriptionParameters,
+) -> anyhow::Result<BranchDescription> {
+    let description = sanitizer.description(&params.description);
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let branch_name = unqualified_branch_name(stack_id, &params.branch_name);
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stack = vb_state.get_stack(stack_id)?;
+
+    if !stack.branches().iter().any(|b| b.name() == branch_name) {
+        anyhow::bail!("Branch '{}' not found in stack {}", branch_name, stack_id);
+    }
+
+    stack.update_branch(
+        ctx,
+        branch_name.to_owned(),
+        &PatchReferenceUpdate {
+            description: Some(Some(description.clone())),
+            ..Default::default()
+        },
+    )?;
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
+    }
+
+    Ok(BranchDescription {
+        name: params.branch_name,
+        description: Some(description),
+    })
+}
+
+pub struct GetDiffForPaths;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDiffForPathsParameters {
+    /// The paths to get the diff for.
+    #[schemars(description = "
+    <description>
+        The list of file paths to get the uncommitted diff for.
+    </description>
+
+    <important_notes>
+        The file paths should be relative to the workspace root.
+        Paths without uncommitted changes are ignored.
+    </important_notes>
+    ")]
+    pub paths: Vec<String>,
+    /// How the diff of each hunk is represented.
+    #[schemars(description = "
+    <description>
+        How the diff of each hunk is represented, either 'unified' or 'structured'.
+    </description>
+
+    <important_notes>
+        In the 'unified' format, each hunk has a 'diff' with the unified diff text, including the hunk header.
+        In the 'structured' format, each hunk has a 'header' and 'lines' instead, where each line has a 'kind'
+        of 'context', 'added' or 'removed', and its 'content' without the leading marker.
+        The structured format takes considerably more tokens, so only use it if the lines need to be told apart reliably.
+        If not provided, the unified format is used.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub diff_format: DiffFormat,
+    /// The number of unchanged lines to show around each change.
+    #[schemars(description = "
+    <description>
+        The number of unchanged lines to show before and after each change in the diffs.
+    </description>
+
+    <important_notes>
+        If not provided, the number of lines configured for the session is used, or the one in the app settings.
+    </important_notes>
+    ")]
+    pub context_lines: Option<u32>,
+}
+
+impl Tool for GetDiffForPaths {
+    fn name(&self) -> String {
+        "get_diff_for_paths".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the uncommitted diff of specific files in the workspace.
+        </description>
+
+        <important_notes>
+            Use this tool if you only want to see the changes of a few files,
+            without getting the stacks and commits of the full project status.
+            The hunks aren't listed with their assignments to stacks, use the project status for those.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetDiffForPathsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn version(&self) -> u32 {
+        // 2: The hunks of files whose diff is disabled in `.gitattributes` are left out.
+        // 3: The hunks aren't listed with their assignments to stacks anymore.
+        3
+    }
+
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        vec![(
+            "get_diff_for_paths.diffFormats".into(),
+            serde_json::json!(["unified", "structured"]),
+        )]
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let repo = ctx.gix_repo()?;
+        let mut params: GetDiffForPathsParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+        params.context_lines = params.context_lines.or(options.context_lines);
+
+        let paths = params.paths.clone();
+        let value = diff_for_paths(ctx, &repo, params)
+            .and_then(|diff| {
+                if options.strict {
+                    ensure_no_changes_were_skipped(
+                        &repo,
+                        Some(&paths),
+                        &diff.file_changes,
+                        &diff.warnings,
+                    )?;
+                }
+                Ok(diff)
+            })
+            .to_json("get_diff_for_paths");
+        Ok(value)
+    }
+}
+
+/// The uncommitted changes of a set of paths.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffForPaths {
+    /// The diff changes of the requested paths.
+    pub file_changes: Vec<FileChange>,
+    /// Requested files that were left out because they couldn't be read.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<DiffWarning>,
+}
+
+impl ToolResult for Result<DiffForPaths, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "DiffForPaths")
+    }
+}
+
+/// Return the diffs of the changed files in `params`.
+///
+/// Only the diffs of the requested files are computed, and hunk assignments are left out,
+/// as they can only be computed for all changes together.
+pub fn diff_for_paths(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    params: GetDiffForPathsParameters,
+) -> anyhow::Result<DiffForPaths> {
+    let context_lines = params
+        .context_lines
+        .unwrap_or(ctx.app_settings().context_lines);
+    let changes = but_core::diff::worktree_changes(repo)?
+        .changes
+        .into_iter()
+        .filter(|change| params.paths.iter().any(|path| change.path == path.as_str()))
+        .collect();
+    let (diff, warnings) = unified_diff_for_changes(repo, changes, context_lines)?;
+    let mut file_changes = get_file_changes(&diff, vec![])?;
+    apply_diff_attributes(repo, None, &mut file_changes)?;
+    set_diff_format(&mut file_changes, params.diff_format);
+    Ok(DiffForPaths {
+        file_changes,
+        warnings,
+    })
+}
+
+pub struct GetStackOrder;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStackOrderParameters {
+    /// The id of the stack to get the branch order of.
+    #[schemars(description = "
+    <description>
+        The id of the stack to get the order of the branches of.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+}
+
+impl Tool for GetStackOrder {
+    fn name(&self) -> String {
+        "get_stack_order".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the order in which the branches of a stack are stacked on top of each other.
+        </description>
+
+        <important_notes>
+            The branch at position 0 is the bottom-most branch, which is based on the target branch.
+            Every other branch is based on the tip of the branch below it.
+            Use this tool before planning operations that depend on the order of branches, like moving commits between them.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetStackOrderParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetStackOrderParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = stack_order(ctx, params).to_json("get_stack_order");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackOrder {
+    /// The branches of the stack, ordered from the bottom-most to the top-most one.
+    pub branches: Vec<OrderedBranch>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderedBranch {
+    /// The name of the branch.
+    pub name: String,
+    /// The position of the branch in the stack, with 0 being the bottom-most branch.
+    pub position: usize,
+    /// The id of the commit at the tip of the branch.
+    pub tip_oid: String,
+    /// The id of the commit the branch is based on.
+    ///
+    /// This is the merge base with the target for the bottom-most branch, and the tip of the branch below otherwise.
+    pub base_oid: String,
+}
+
+impl ToolResult for Result<StackOrder, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "StackOrder")
+    }
+}
+
+pub fn stack_order(
+    ctx: &CommandContext,
+    params: GetStackOrderParameters,
+) -> anyhow::Result<StackOrder> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack_in_workspace(stack_id)?;
+    let repo = ctx.gix_repo()?;
+
+    let mut base = stack.merge_base(ctx)?;
+    let mut branches = vec![];
+    for (position, branch) in stack.branches().into_iter().enumerate() {
+        let tip = branch.head_oid(&repo)?;
+        branches.push(OrderedBranch {
+            name: branch.name().to_owned(),
+            position,
+            tip_oid: tip.to_string(),
+            base_oid: base.to_string(),
+        });
+        base = tip;
+    }
+
+    Ok(StackOrder { branches })
+}
+
+pub struct MoveCommitToNewBranch;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveCommitToNewBranchParameters {
+    /// The id of the stack containing the commit.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the commit to move.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The name of the branch containing the commit.
+    #[schemars(description = "
+    <description>
+        The name of the branch that currently contains the commit.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub source_branch_name: String,
+    /// The id of the commit to move.
+    #[schemars(description = "
+    <description>
+        The id of the commit to lift out of the source branch.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit on the source branch.
+        Commits that depend on the changes of other commits on the branch can't be moved.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+    /// The name of the branch to create.
+    #[schemars(description = "
+    <description>
+        The name of the new branch to move the commit to.
+    </description>
+
+    <important_notes>
+        The branch name should be a valid git reference name that doesn't exist yet.
+    </important_notes>
+    ")]
+    pub new_branch_name: String,
+    /// The description of the branch to create.
+    #[schemars(description = "
+    <description>
+        The description of the new branch.
+    </description>
+
+    <important_notes>
+        This should be a short summary of the purpose of the new branch.
+    </important_notes>
+    ")]
+    pub new_branch_description: Option<String>,
+}
+
+impl Tool for MoveCommitToNewBranch {
+    fn name(&self) -> String {
+        "move_commit_to_new_branch".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Move a commit out of its branch into a newly created branch in its own stack.
+        </description>
+
+        <important_notes>
+            Use this tool to split up a branch that contains unrelated changes.
+            The commit is removed from the source branch, and the commits above it are rebased.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(MoveCommitToNewBranchParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: MoveCommitToNewBranchParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = move_commit_to_new_branch(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .to_json("move_commit_to_new_branch");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveCommitToNewBranchOutcome {
+    /// The branch the commit was moved out of.
+    pub source_branch: SimpleBranch,
+    /// The newly created branch containing the commit.
+    pub new_branch: SimpleBranch,
+}
+
+impl ToolResult for Result<MoveCommitToNewBranchOutcome, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "MoveCommitToNewBranchOutcome")
+    }
+}
+
+/// Move the commit in `params` out of its branch into a new branch in its own stack.
+///
+/// If the commit can't be moved, the new stack is removed again, so the workspace is left as it was.
+pub fn move_commit_to_new_branch(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: MoveCommitToNewBranchParameters,
+) -> anyhow::Result<MoveCommitToNewBranchOutcome> {
+    let source_stack_id = StackId::from_str(&params.stack_id)?;
+    let source_branch_name = unqualified_branch_name(source_stack_id, &params.source_branch_name);
+    let repo = ctx.gix_repo()?;
+    let commit_id = resolve_commit_id(&repo, &params.commit_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+
+    let source_stack = vb_state.get_stack_in_workspace(source_stack_id)?;
+    let source_branch = source_stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == source_branch_name)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Branch '{}' not found in stack {}",
+                source_branch_name,
+                source_stack_id
+            )
+        })?;
+    let source_commits = simple_branch(ctx, &repo, &source_stack, &source_branch)?.commits;
+    if !source_commits.iter().any(|c| c.id == commit_id) {
+        anyhow::bail!(
+            "Commit {} is not on branch '{}'",
+            commit_id,
+            source_branch_name
+        );
+    }
+
+    let stack_entry = {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let branch = gitbutler_branch::BranchCreateRequest {
+            name: Some(params.new_branch_name.clone()),
+            ..Default::default()
+        };
+        gitbutler_branch_actions::create_virtual_branch(ctx, &branch, guard.write_permission())?
+    };
+
+    // The name of the new branch may have been normalized, so use the one it was created with.
+    let mut new_stack = vb_state.get_stack(stack_entry.id)?;
+    let new_branch_name = new_stack
+        .branches()
+        .last()
+        .map(|b| b.name().to_owned())
+        .ok_or_else(|| anyhow::anyhow!("The new stack has no branch"))?;
+
+    if let Some(description) = params.new_branch_description {
+        let description = sanitizer.description(&description);
+        new_stack.update_branch(
+            ctx,
+            new_branch_name.clone(),
+            &PatchReferenceUpdate {
+                description: Some(Some(description)),
+                ..Default::default()
+            },
+        )?;
+    }
+
+    let moved = gitbutler_branch_actions::move_commit(
+        ctx,
+        stack_entry.id,
+        commit_id.to_git2(),
+        source_stack_id,
+    );
+    if let Err(err) = moved {
+        // The commit couldn't be moved, for instance because other commits depend on it,
+        // so the stack that was created for it would be left empty.
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let removed =
+            ctx.branch_manager()
+                .unapply(stack_entry.id, guard.write_permission(), true, vec![]);
+        return Err(match removed {
+            Ok(_) => err,
+            Err(remove_err) => err.context(format!(
+                "The new branch '{new_branch_name}' couldn't be removed again: {remove_err}"
+            )),
+        });
+    }
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        app_handle.emit_stack_update(project_id, source_stack_id, message_id);
+        app_handle.emit_stack_update(project_id, stack_entry.id, message_id);
+    }
+
+    let source_stack = vb_state.get_stack_in_workspace(source_stack_id)?;
+    let source_branch = source_stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == source_branch_name)
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' disappeared", source_branch_name))?;
+    let new_stack = vb_state.get_stack_in_workspace(stack_entry.id)?;
+    let new_branch = new_stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == &new_branch_name)
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' disappeared", new_branch_name))?;
+
+    Ok(MoveCommitToNewBranchOutcome {
+        source_branch: simple_branch(ctx, &repo, &source_stack, &source_branch)?,
+        new_branch: simple_branch(ctx, &repo, &new_stack, &new_branch)?,
+    })
+}
+
+pub struct GetCommitAncestry;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCommitAncestryParameters {
+    /// The id of the commit to get the ancestors of.
+    #[schemars(description = "
+    <description>
+        The id of the commit to get the ancestors of.
+    </description>
+
+    <important_notes>
+        The commit itself isn't part of its ancestry.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+    /// The number of generations of ancestors to return.
+    #[schemars(description = "
+    <description>
+        The number of generations of ancestors to return.
+    </description>
+
+    <important_notes>
+        1 returns the parent of the commit, 2 also returns the grandparent, and so on.
+        0 returns no commits.
+    </important_notes>
+    ")]
+    pub depth: usize,
+    /// Whether to only follow the first parent of merge commits.
+    #[schemars(description = "
+    <description>
+        Whether to only follow the first parent of merge commits.
+    </description>
+
+    <important_notes>
+        This is true by default, which is what you want to walk the history of a branch.
+        Set it to false to also get the ancestors of all other parents of merge commits.
+    </important_notes>
+    ")]
+    #[serde(default = "default_true")]
+    pub follow_first_parent: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Tool for GetCommitAncestry {
+    fn name(&self) -> String {
+        "get_commit_ancestry".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the ancestors of a commit, from its parent to the oldest one.
+        </description>
+
+        <important_notes>
+            Use this tool to walk the history of a branch below a commit.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetCommitAncestryParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetCommitAncestryParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = commit_ancestry(ctx, params).to_json("get_commit_ancestry");
+        Ok(value)
+    }
+}
+
+impl ToolResult for Result<Vec<SimpleCommit>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<SimpleCommit>")
+    }
+}
+
+/// Return the ancestors of the commit up to `depth` generations, ordered from the parent to the oldest one.
+pub fn commit_ancestry(
+    ctx: &CommandContext,
+    params: GetCommitAncestryParameters,
+) -> anyhow::Result<Vec<SimpleCommit>> {
+    let repo = ctx.gix_repo()?;
+    let commit_id = resolve_commit_id(&repo, &params.commit_id)?;
+
+    let mut seen = gix::hashtable::HashSet::default();
+    let mut generation = vec![commit_id];
+    let mut ancestors = vec![];
+    for _ in 0..params.depth {
+        let mut parents = vec![];
+        for id in generation {
+            let commit = repo.find_commit(id)?;
+            let parent_ids = commit.parent_ids().map(|id| id.detach());
+            let parent_ids = if params.follow_first_parent {
+                parent_ids.take(1).collect::<Vec<_>>()
+            } else {
+                parent_ids.collect()
+            };
+            parents.extend(parent_ids.into_iter().filter(|id| seen.insert(*id)));
+        }
+        if parents.is_empty() {
+            break;
+        }
+
+        for id in &parents {
+            let commit = repo.find_commit(*id)?;
+            let message = commit.message_raw_sloppy().to_str_lossy();
+            ancestors.push(SimpleCommit::from_message(*id, &message));
+        }
+        generation = parents;
+    }
+
+    Ok(ancestors)
+}
+
+pub struct SplitRename;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitRenameParameters {
+    /// The new path of the renamed file.
+    #[schemars(description = "
+    <description>
+        The path of a file that was renamed, and possibly modified, in the worktree.
+    </description>
+
+    <important_notes>
+        This is the new path of the file, not the path it was renamed from.
+    </important_notes>
+    ")]
+    pub path: String,
+    /// The branch to commit the rename to.
+    #[schemars(description = "
+    <description>
+        The name of the branch to commit the pure rename to.
+    </description>
+
+    <important_notes>
+        The branch has to exist in the workspace.
+        The committed file has the content it had before it was renamed.
+    </important_notes>
+    ")]
+    pub branch_name: String,
+    /// The commit message title of the rename commit.
+    #[schemars(description = "
+    <description>
+        The commit message title of the rename commit.
+    </description>
+    ")]
+    pub message_title: String,
+    /// The commit message body of the rename commit.
+    #[schemars(description = "
+    <description>
+        The commit message body of the rename commit.
+    </description>
+    ")]
+    pub message_body: String,
+    /// Where to commit the content changes, if anywhere.
+    #[schemars(description = "
+    <description>
+        Where to commit the changes to the content of the renamed file.
+    </description>
+
+    <important_notes>
+        Leave this empty to keep the content changes as uncommitted changes of the renamed file.
+        The branch should be the branch of the rename commit, or a branch stacked on top of it.
+    </important_notes>
+    ")]
+    pub content_commit: Option<SplitRenameContentCommit>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitRenameContentCommit {
+    /// The branch to commit the content changes to.
+    pub branch_name: String,
+    /// The commit message title of the content commit.
+    pub message_title: String,
+    /// The commit message body of the content commit.
+    pub message_body: String,
+}
+
+impl Tool for SplitRename {
+    fn name(&self) -> String {
+        "split_rename".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Commit the rename of a file separately from the changes to its content.
+        </description>
+
+        <important_notes>
+            Use this tool if a file was renamed and modified, and the rename should be committed on its own.
+            The content changes are either left uncommitted, or committed to a second branch in the same call.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(SplitRenameParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: SplitRenameParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = split_rename(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .to_json("split_rename");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitRenameOutcome {
+    /// The commit that only renames the file.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub rename_commit_id: gix::ObjectId,
+    /// The commit with the changes to the content of the file, if it was requested.
+    #[serde(with = "gitbutler_serde::object_id_opt")]
+    pub content_commit_id: Option<gix::ObjectId>,
+}
+
+impl ToolResult for Result<SplitRenameOutcome, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "SplitRenameOutcome")
+    }
+}
+
+pub fn split_rename(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: SplitRenameParameters,
+) -> anyhow::Result<SplitRenameOutcome> {
+    let message = sanitizer.commit_message(&params.message_title, &params.message_body)?;
+    let repo = ctx.gix_repo()?;
+    let worktree = but_core::diff::worktree_changes(&repo)?;
+    let Some((previous_path, previous_state)) = worktree
+        .changes
+        .iter()
+        .filter(|change| change.path == params.path)
+        .find_map(|change| match &change.status {
+            but_core::TreeStatus::Rename {
+                previous_path,
+                previous_state,
+                ..
+            } => Some((previous_path.clone(), *previous_state)),
+            _ => None,
+        })
+    else {
+        anyhow::bail!("'{}' isn't a renamed file in the worktree", params.path);
+    };
+
+    let (stack_id, branch_name) = find_branch(ctx, &repo, &params.branch_name)?
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' not found", params.branch_name))?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stack = vb_state.get_stack_in_workspace(stack_id)?;
+    let branch_head = stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == &branch_name)
+        .map(|b| b.head_oid(&repo))
+        .transpose()?
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' not found", params.branch_name))?;
+
+    let rename_commit_id = {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let _ = ctx.create_snapshot(
+            SnapshotDetails::new(OperationKind::CreateCommit),
+            guard.write_permission(),
+        );
+
+        // The pure rename is the tree of the branch with the previous content at the new path.
+        let mut editor = repo.find_commit(branch_head)?.tree()?.edit()?;
+        editor.remove(previous_path.as_bstr())?;
+        editor.upsert(params.path.as_str(), previous_state.kind, previous_state.id)?;
+        let tree = editor.write()?.detach();
+
+        let (author, committer) = repo.commit_signatures()?;
+        let commit = gix::objs::Commit {
+            message: message.into(),
+            tree,
+            author,
+            committer,
+            encoding: None,
+            parents: [branch_head].into(),
+            extra_headers: (&but_core::commit::HeadersV2::default()).into(),
+        };
+        let commit_id =
+            but_rebase::commit::create(&repo, commit, but_rebase::commit::CommitterMode::Keep)?;
+
+        pick_into_branch(ctx, &repo, &mut stack, &branch_name, commit_id, None, false)?
+    };
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        app_handle.emit_stack_update(project_id, stack.id, message_id);
+    }
+
+    // What's left in the worktree are the content changes of the renamed file.
+    let content_commit_id = match params.content_commit {
+        Some(content_commit) => {
+            let branch_description = match find_branch(ctx, &repo, &content_commit.branch_name)? {
+                Some((stack_id, branch_name)) => vb_state
+                    .get_stack_in_workspace(stack_id)?
+                    .branches()
+                    .into_iter()
+                    .find(|b| b.name() == &branch_name)
+                    .and_then(|b| b.description),
+                None => None,
+            }
+            .unwrap_or_default();
+            let outcome = create_commit(
+                ctx,
+                app_handle,
+                message_id,
+                sanitizer,
+                CommitParameters {
+                    message_title: content_commit.message_title,
+                    message_body: content_commit.message_body,
+                    branch_name: content_commit.branch_name,
+                    branch_description,
+                    files: vec![params.path.clone()],
+                    order: None,
+                    amend_head: false,
+                    allow_integrated: false,
+                    author_date: None,
+                    force_new_branch: false,
+                    stack_position: None,
+                },
+            )?;
+            Some(
+                outcome
+                    .new_commit
+                    .ok_or_else(|| anyhow::anyhow!("The content changes couldn't be committed"))?,
+            )
+        }
+        None => None,
+    };
+
+    Ok(SplitRenameOutcome {
+        rename_commit_id,
+        content_commit_id,
+    })
+}
+
+pub struct GetOrphanedCommits;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetOrphanedCommitsParameters {
+    /// The id of the stack to find orphaned commits of.
+    #[schemars(description = "
+    <description>
+        The id of the stack to find orphaned commits of.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+}
+
+impl Tool for GetOrphanedCommits {
+    fn name(&self) -> String {
+        "get_orphaned_commits".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            List the commits that used to be on the branches of a stack, but aren't anymore.
+        </description>
+
+        <important_notes>
+            Use this tool to find commits that were dropped by accident, for example when squashing or reordering commits.
+            Commits that were rewritten, like amended commits, are listed as well, as their old version isn't part of the stack anymore.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetOrphanedCommitsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(2_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetOrphanedCommitsParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = orphaned_commits(ctx, params).to_json("get_orphaned_commits");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanedCommit {
+    /// The id of the commit that isn't reachable from the stack anymore.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub oid: gix::ObjectId,
+    /// The name of the branch the commit was on when it was last seen.
+    pub was_on_branch: String,
+    /// The commit message title.
+    pub message_title: String,
+}
+
+impl ToolResult for Result<Vec<OrphanedCommit>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<OrphanedCommit>")
+    }
+}
+
+/// The number of oplog snapshots to look for previous heads of a stack in.
+const ORPHANED_COMMITS_SNAPSHOT_LIMIT: usize = 100;
+
+/// Return the commits that were on a branch of the stack in any of the recent oplog snapshots,
+/// but aren't reachable from the current heads of the stack anymore.
+///
+/// The commits are ordered from the most recent snapshot to the oldest one.
+pub fn orphaned_commits(
+    ctx: &CommandContext,
+    params: GetOrphanedCommitsParameters,
+) -> anyhow::Result<Vec<OrphanedCommit>> {
+    let repo = ctx.gix_repo()?;
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+
+    let mut live = stack
+        .branches()
+        .iter()
+        .map(|branch| branch.head_oid(&repo))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    live.push(stack.merge_base(ctx)?);
+
+    let mut seen = gix::hashtable::HashSet::default();
+    let mut orphaned = vec![];
+    for snapshot in ctx.list_snapshots(ORPHANED_COMMITS_SNAPSHOT_LIMIT, None, vec![])? {
+        let Some(previous_stack) = snapshot_stack(&repo, snapshot.commit_id.to_gix(), stack_id)
+        else {
+            continue;
+        };
+
+        // Lower branches claim their commits first, as they are reachable from the branches above them, too.
+        for branch in previous_stack.branches() {
+            let Some(head) = branch.persisted_head_oid() else {
+                continue;
+            };
+            if !seen.insert(head) || repo.find_commit(head).is_err() {
+                continue;
+            }
+            for info in head
+                .attach(&repo)
+                .ancestors()
+                .with_hidden(live.iter().copied())
+                .all()?
+            {
+                let Ok(info) = info else {
+                    // The commit might have been garbage-collected already.
+                    break;
+                };
+                if info.id != head && !seen.insert(info.id) {
+                    continue;
+                }
+                let commit = repo.find_commit(info.id)?;
+                let message = commit.message_raw_sloppy().to_str_lossy();
+                orphaned.push(OrphanedCommit {
+                    oid: info.id,
+                    was_on_branch: branch.name().to_owned(),
+                    message_title: SimpleCommit::from_message(info.id, &message).message_title,
+                });
+            }
+        }
+    }
+    Ok(orphaned)
+}
+
+/// Read the state of the stack with `stack_id` from the oplog snapshot `snapshot_id`, if it was known back then.
+fn snapshot_stack(
+    repo: &gix::Repository,
+    snapshot_id: gix::ObjectId,
+    stack_id: StackId,
+) -> Option<gitbutler_stack::Stack> {
+    snapshot_virtual_branches(repo, snapshot_id)?
+        .branches
+        .remove(&stack_id)
+}
+
+/// Read the state of all stacks as it was recorded in the oplog snapshot `snapshot_id`,
+/// or `None` if it can't be read.
+fn snapshot_virtual_branches(
+    repo: &gix::Repository,
+    snapshot_id: gix::ObjectId,
+) -> Option<gitbutler_stack::VirtualBranchesState> {
+    let tree = repo.find_commit(snapshot_id).ok()?.tree().ok()?;
+    let entry = tree.lookup_entry_by_path("virtual_branches.toml").ok()??;
+    let blob = entry.object().ok()?;
+    toml::from_str(blob.data.to_str().ok()?).ok()
+}
+
+pub struct GetStacksSummary;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStacksSummaryParameters {}
+
+impl Tool for GetStacksSummary {
+    fn name(&self) -> String {
+        "get_stacks_summary".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get a short summary of the stacks applied to the workspace.
+        </description>
+
+        <important_notes>
+            This lists the id and name of every applied stack, along with the names of its branches and how many commits they have.
+            It doesn't include any commit messages or file changes, which makes it the cheapest way to get an overview of the workspace.
+            Use the get_project_status tool to get the details.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetStacksSummaryParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let repo = ctx.gix_repo()?;
+        let value = stacks_summary(ctx, &repo).to_json("get_stacks_summary");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackSummary {
+    /// The id of the stack.
+    pub id: StackId,
+    /// The name of the stack, which is the name of its top-most branch.
+    pub name: String,
+    /// The branches of the stack, ordered from the top-most to the bottom-most one.
+    pub branches: Vec<BranchSummary>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchSummary {
+    /// The name of the branch.
+    pub name: String,
+    /// The number of commits of the branch, not counting the ones of the branches below it.
+    pub commit_count: usize,
+}
+
+impl ToolResult for Result<Vec<StackSummary>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<StackSummary>")
+    }
+}
+
+/// Summarize the applied stacks, counting the commits of each branch without reading them.
+pub fn stacks_summary(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+) -> anyhow::Result<Vec<StackSummary>> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut summaries = vec![];
+    for entry in stacks(ctx, repo)? {
+        let merge_base = vb_state.get_stack(entry.id)?.merge_base(ctx)?;
+
+        // The heads are ordered from the top-most branch, each of which is based on the tip of the one below.
+        let bases = entry
+            .heads
+            .iter()
+            .skip(1)
+            .map(|head| head.tip)
+            .chain(Some(merge_base));
+        let branches = entry
+            .heads
+            .iter()
+            .zip(bases)
+            .map(|(head, base)| {
+                let commit_count = head
+                    .tip
+                    .attach(repo)
+                    .ancestors()
+                    .first_parent_only()
+                    .with_hidden(Some(base))
+                    .all()?
+                    .count();
+                Ok(BranchSummary {
+                    name: head.name.to_string(),
+                    commit_count,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        summaries.push(StackSummary {
+            id: entry.id,
+            name: entry.name().map(ToString::to_string).unwrap_or_default(),
+            branches,
+        });
+    }
+    Ok(summaries)
+}
+
+pub struct RecoverCommit;
+
+/// Where to place a recovered commit in its branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum RecoveryPosition {
+    /// At the tip of the branch, so it becomes the most recent commit of the branch.
+    BeforeTip,
+    /// Right above the base of the branch, so it becomes the oldest commit of the branch.
+    AfterBase,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoverCommitParameters {
+    /// The id of the orphaned commit to recover.
+    #[schemars(description = "
+    <description>
+        The id of the orphaned commit to put back into a branch.
+    </description>
+
+    <important_notes>
+        Use the get_orphaned_commits tool to find the commits that can be recovered.
+    </important_notes>
+    ")]
+    pub orphaned_oid: String,
+    /// The id of the stack to recover the commit into.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch to recover the commit into.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub target_stack_id: String,
+    /// The name of the branch to recover the commit into.
+    #[schemars(description = "
+    <description>
+        The name of the branch to recover the commit into.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub target_branch_name: String,
+    /// Where to place the commit in the branch.
+    #[schemars(description = "
+    <description>
+        Where to place the commit in the branch.
+    </description>
+
+    <important_notes>
+        'beforeTip' makes the commit the most recent commit of the branch.
+        'afterBase' makes the commit the oldest commit of the branch, and rebases all other commits of the branch on top of it.
+    </important_notes>
+    ")]
+    pub position: RecoveryPosition,
+}
+
+impl Tool for RecoverCommit {
+    fn name(&self) -> String {
+        "recover_commit".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Put an orphaned commit back into a branch.
+        </description>
+
+        <important_notes>
+            Use this tool to recover commits that were dropped by accident, as listed by the get_orphaned_commits tool.
+            The changes of the commit are applied on top of the commits below it in the branch, which can lead to conflicts.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(RecoverCommitParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: RecoverCommitParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = recover_commit(ctx, app_handle, options.message_id.as_deref(), params)
+            .to_json("recover_commit");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredCommit {
+    /// The id of the recovered commit in its branch.
+    pub new_oid: String,
+    /// The name of the branch the commit was recovered into.
+    pub branch_name: String,
+}
+
+impl ToolResult for Result<RecoveredCommit, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "RecoveredCommit")
+    }
+}
+
+pub fn recover_commit(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    params: RecoverCommitParameters,
+) -> anyhow::Result<RecoveredCommit> {
+    let repo = ctx.gix_repo()?;
+    let orphaned_oid = resolve_commit_id(&repo, &params.orphaned_oid)?;
+    repo.find_commit(orphaned_oid)
+        .map_err(|_| anyhow::anyhow!("Commit '{}' not found", params.orphaned_oid))?;
+    let stack_id = StackId::from_str(&params.target_stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stack = vb_state.get_stack_in_workspace(stack_id)?;
+
+    let is_in_stack = stack.as_rebase_steps(ctx, &repo)?.iter().any(
+        |step| matches!(step, but_rebase::RebaseStep::Pick { commit_id, .. } if *commit_id == orphaned_oid),
+    );
+    if is_in_stack {
+        anyhow::bail!(
+            "Commit '{}' is already part of the stack",
+            params.orphaned_oid
+        );
+    }
+
+    let new_oid = {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let _ = ctx.create_snapshot(
+            SnapshotDetails::new(OperationKind::MoveCommit),
+            guard.write_permission(),
+        );
+        pick_into_branch(
+            ctx,
+            &repo,
+            &mut stack,
+            &params.target_branch_name,
+            orphaned_oid,
+            None,
+            params.position == RecoveryPosition::AfterBase,
+        )?
+    };
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
+    }
+
+    Ok(RecoveredCommit {
+        new_oid: new_oid.to_string(),
+        branch_name: params.target_branch_name,
+    })
+}
+
+pub struct GetPRDescription;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPRDescriptionParameters {
+    /// The id of the stack containing the branch.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch to describe.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The name of the branch to describe.
+    #[schemars(description = "
+    <description>
+        The name of the branch to generate the pull request description for.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub branch_name: String,
+    /// The template of the pull request body.
+    #[schemars(description = "
+    <description>
+        The template to fill in to get the body of the pull request.
+    </description>
+
+    <important_notes>
+        '{title}' is replaced by the title of the pull request, and '{commits}' by the list of commits of the branch.
+        Leave this empty to use a Markdown template with the title as heading, followed by the list of commits.
+    </important_notes>
+    ")]
+    pub template: Option<String>,
+}
+
+/// The template of a pull request body if none is given.
+const DEFAULT_PR_TEMPLATE: &str = "## {title}\n\n### Changes\n\n{commits}\n";
+
+impl Tool for GetPRDescription {
+    fn name(&self) -> String {
+        "get_pr_description".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Generate the title and body of a pull request from the commits of a branch.
+        </description>
+
+        <important_notes>
+            Use this tool to get a starting point for the description of a pull request.
+            The title is the title of the only commit of the branch, or the name of the branch if it has several commits.
+            The body lists the titles and bodies of all commits of the branch, from the oldest to the most recent one.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetPRDescriptionParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetPRDescriptionParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = pr_description(ctx, params).to_json("get_pr_description");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PRDescription {
+    /// The title of the pull request.
+    pub title: String,
+    /// The body of the pull request.
+    pub body: String,
+}
+
+impl ToolResult for Result<PRDescription, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "PRDescription")
+    }
+}
+
+pub fn pr_description(
+    ctx: &CommandContext,
+    params: GetPRDescriptionParameters,
+) -> anyhow::Result<PRDescription> {
+    let repo = ctx.gix_repo()?;
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let branch_name = unqualified_branch_name(stack_id, &params.branch_name);
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack_in_workspace(stack_id)?;
+    let branch = stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == branch_name)
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' not found in stack", branch_name))?;
+
+    // The commits are ordered from the most recent one, but read best from the oldest one.
+    let mut commits = simple_branch(ctx, &repo, &stack, &branch)?.commits;
+    commits.reverse();
+
+    let title = match commits.as_slice() {
+        [commit] => commit.message_title.clone(),
+        _ => branch_name.to_owned(),
+    };
+    let commit_list = commits
+        .iter()
+        .map(|commit| {
+            let mut item = format!("- {}", commit.message_title.trim());
+            for line in commit.message_body.trim().lines() {
+                item.push('\n');
+                if !line.is_empty() {
+                    item.push_str("  ");
+                    item.push_str(line);
+                }
+            }
+            item
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let body = params
+        .template
+        .as_deref()
+        .unwrap_or(DEFAULT_PR_TEMPLATE)
+        .replace("{title}", &title)
+        .replace("{commits}", &commit_list);
+
+    Ok(PRDescription { title, body })
+}
+
+pub struct RepoQuery;
+
+/// A read-only query of the repository, as passed to [`RepoQuery`].
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RepoQueryParameters {
+    /// Resolve a reference to the object it points to.
+    ResolveRef {
+        #[schemars(description = "
+        <description>
+            The name of the reference to resolve, like 'main' or 'refs/remotes/origin/main'.
+        </description>
+        ")]
+        name: String,
+    },
+    /// Read the value of a configuration key.
+    ReadConfigKey {
+        #[schemars(description = "
+        <description>
+            The configuration key to read, like 'user.email'.
+        </description>
+        ")]
+        key: String,
+    },
+    /// Check if an object exists in the object database.
+    ObjectExists {
+        #[schemars(description = "
+        <description>
+            The full hexadecimal id of the object.
+        </description>
+        ")]
+        id: String,
+    },
+    /// Find the best common ancestor of two commits.
+    #[serde(rename_all = "camelCase")]
+    MergeBase {
+        #[schemars(description = "
+        <description>
+            The id of the first commit.
+        </description>
+        ")]
+        first_commit_id: String,
+        #[schemars(description = "
+        <description>
+            The id of the second commit.
+        </description>
+        ")]
+        second_commit_id: String,
+    },
+    /// Resolve a revision specification to a single object.
+    RevParse {
+        #[schemars(description = "
+        <description>
+            The revision specification to resolve, like 'HEAD~2' or 'main^{tree}'.
+        </description>
+        ")]
+        spec: String,
+    },
+}
+
+impl Tool for RepoQuery {
+    fn name(&self) -> String {
+        "repo_query".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Run a read-only query against the repository.
+        </description>
+
+        <important_notes>
+            Only the query kinds 'resolve_ref', 'read_config_key', 'object_exists', 'merge_base' and 'rev_parse' are supported.
+            None of them change the repository.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(RepoQueryParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn host_only(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: RepoQueryParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = repo_query(ctx, params).to_json("repo_query");
+        Ok(value)
+    }
+}
+
+/// The result of a [`RepoQueryParameters`] query, of the same kind as the query.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RepoQueryResult {
+    ResolveRef {
+        /// The full name of the reference, or the name that was queried if it doesn't exist.
+        name: String,
+        /// The id of the object the reference points to after peeling it, or `None` if it doesn't exist.
+        id: Option<String>,
+    },
+    ReadConfigKey {
+        /// The configuration key that was read.
+        key: String,
+        /// The value of the key, or `None` if it isn't set.
+        value: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    ObjectExists {
+        /// The id of the object.
+        id: String,
+        /// Whether the object exists.
+        exists: bool,
+        /// The type of the object, like 'commit' or 'blob', if it exists.
+        object_type: Option<String>,
+    },
+    MergeBase {
+        /// The id of the merge base, or `None` if the commits don't have one.
+        id: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    RevParse {
+        /// The id of the object the specification resolves to.
+        id: String,
+        /// The type of the object, like 'commit' or 'tree'.
+        object_type: String,
+    },
+}
+
+impl ToolResult for Result<RepoQueryResult, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "RepoQueryResult")
+    }
+}
+
+pub fn repo_query(
+    ctx: &CommandContext,
+    params: RepoQueryParameters,
+) -> anyhow::Result<RepoQueryResult> {
+    let repo = ctx.gix_repo()?;
+    let result = match params {
+        RepoQueryParameters::ResolveRef { name } => match repo.try_find_reference(name.as_str())? {
+            Some(mut reference) => RepoQueryResult::ResolveRef {
+                name: reference.name().as_bstr().to_string(),
+                id: Some(reference.peel_to_id_in_place()?.to_string()),
+            },
+            None => RepoQueryResult::ResolveRef { name, id: None },
+        },
+        RepoQueryParameters::ReadConfigKey { key } => {
+            let value = repo
+                .config_snapshot()
+                .string(key.as_str())
+                .map(|value| value.to_string());
+            RepoQueryResult::ReadConfigKey { key, value }
+        }
+        RepoQueryParameters::ObjectExists { id } => {
+            let id = gix::ObjectId::from_str(id.trim())?;
+            let object_type = repo
+                .try_find_object(id)?
+                .map(|object| object.kind.to_string());
+            RepoQueryResult::ObjectExists {
+                id: id.to_string(),
+                exists: object_type.is_some(),
+                object_type,
+            }
+        }
+        RepoQueryParameters::MergeBase {
+            first_commit_id,
+            second_commit_id,
+        } => {
+            let first = resolve_commit_id(&repo, &first_commit_id)?;
+            let second = resolve_commit_id(&repo, &second_commit_id)?;
+            let id = match repo.merge_base(first, second) {
+                Ok(id) => Some(id.to_string()),
+                Err(gix::repository::merge_base::Error::NotFound { .. }) => None,
+                Err(err) => return Err(err.into()),
+            };
+            RepoQueryResult::MergeBase { id }
+        }
+        RepoQueryParameters::RevParse { spec } => {
+            let id = repo.rev_parse_single(spec.as_str())?;
+            RepoQueryResult::RevParse {
+                id: id.to_string(),
+                object_type: id.object()?.kind.to_string(),
+            }
+        }
+    };
+    Ok(result)
+}
+
+pub struct GetRecentActivity;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRecentActivityParameters {
+    /// The maximum number of operations to list.
+    #[schemars(description = "
+    <description>
+        The maximum number of recent operations to list.
+    </description>
+
+    <important_notes>
+        Leave this empty to list the last 20 operations.
+    </important_notes>
+    ")]
+    pub limit: Option<usize>,
+}
+
+/// The number of operations listed by [`GetRecentActivity`] if no limit is given.
+const DEFAULT_RECENT_ACTIVITY_LIMIT: usize = 20;
+
+impl Tool for GetRecentActivity {
+    fn name(&self) -> String {
+        "get_recent_activity".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get a timeline of the operations recently performed in the project, like commits, amends, moves and rebases.
+        </description>
+
+        <important_notes>
+            Use this tool to find out what has happened recently.
+            The operations are listed from the oldest to the most recent one, each with a description and the stacks it affected.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetRecentActivityParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetRecentActivityParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = recent_activity(ctx, params).to_json("get_recent_activity");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityEntry {
+    /// The id of the oplog snapshot recorded for the operation.
+    pub snapshot_id: String,
+    /// When the operation was performed, in seconds since the Unix epoch.
+    pub created_at: i64,
+    /// The kind of operation, like 'CreateCommit'.
+    pub operation: String,
+    /// A human-readable description of the operation.
+    pub description: String,
+    /// The stacks that changed through the operation.
+    pub affected_stacks: Vec<AffectedStack>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AffectedStack {
+    /// The id of the stack.
+    pub id: StackId,
+    /// The name of the stack, which is the name of its top-most branch.
+    pub name: String,
+}
+
+impl ToolResult for Result<Vec<ActivityEntry>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<ActivityEntry>")
+    }
+}
+
+/// Return the last operations recorded in the oplog, from the oldest to the most recent one.
+pub fn recent_activity(
+    ctx: &CommandContext,
+    params: GetRecentActivityParameters,
+) -> anyhow::Result<Vec<ActivityEntry>> {
+    let repo = ctx.gix_repo()?;
+    let limit = params.limit.unwrap_or(DEFAULT_RECENT_ACTIVITY_LIMIT);
+    let snapshots = ctx.list_snapshots(limit, None, vec![])?;
+
+    // Each snapshot holds the state before its operation, so the state after it is the one of the next snapshot.
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut after = Some(vb_state.read_file()?);
+    let mut activity = vec![];
+    for snapshot in snapshots {
+        let before = snapshot_virtual_branches(&repo, snapshot.commit_id.to_gix());
+        let affected_stacks = match (&before, &after) {
+            (Some(before), Some(after)) => affected_stacks(before, after),
+            _ => vec![],
+        };
+        let (operation, description) = match &snapshot.details {
+            Some(details) => (details.operation.to_string(), activity_description(details)),
+            None => (
+                OperationKind::Unknown.to_string(),
+                "Unknown operation".to_owned(),
+            ),
+        };
+        activity.push(ActivityEntry {
+            snapshot_id: snapshot.commit_id.to_string(),
+            created_at: snapshot.created_at.seconds(),
+            operation,
+            description,
+            affected_stacks,
+        });
+        after = before;
+    }
+
+    activity.reverse();
+    Ok(activity)
+}
+
+/// Return the stacks that differ between `before` and `after`, ordered by name.
+fn affected_stacks(
+    before: &gitbutler_stack::VirtualBranchesState,
+    after: &gitbutler_stack::VirtualBranchesState,
+) -> Vec<AffectedStack> {
+    let ids = before
+        .branches
+        .keys()
+        .chain(after.branches.keys())
+        .collect::<std::collections::BTreeSet<_>>();
+    let mut affected = ids
+        .into_iter()
+        .filter_map(|id| {
+            let (before, after) = (before.branches.get(id), after.branches.get(id));
+            if let (Some(before), Some(after)) = (before, after) {
+                if before.in_workspace == after.in_workspace
+                    && before.branches() == after.branches()
+                {
+                    return None;
+                }
+            }
+            // Stacks that were removed are named as they were before the operation.
+            let stack = after.or(before)?;
+            Some(AffectedStack {
+                id: *id,
+                name: stack.derived_name().unwrap_or_else(|_| stack.name.clone()),
+            })
+        })
+        .collect::<Vec<_>>();
+    affected.sort_by(|a, b| a.name.cmp(&b.name));
+    affected
+}
+
+/// Describe the operation of a snapshot in words, using the details recorded along with it.
+fn activity_description(details: &SnapshotDetails) -> String {
+    let trailer = |key: &str| {
+        details
+            .trailers
+            .iter()
+            .find(|trailer| trailer.key == key)
+            .map(|trailer| trailer.value.lines().next().unwrap_or_default().to_owned())
+            .filter(|value| !value.is_empty())
+    };
+    let name = trailer("name")
+        .map(|name| format!(" '{name}'"))
+        .unwrap_or_default();
+    match details.operation {
+        OperationKind::CreateCommit => match trailer("message") {
+            Some(message) => format!("Created the commit '{message}'"),
+            None => "Created a commit".to_owned(),
+        },
+        OperationKind::AmendCommit => "Amended a commit".to_owned(),
+        OperationKind::SquashCommit => "Squashed commits".to_owned(),
+        OperationKind::UndoCommit => "Undid a commit".to_owned(),
+        OperationKind::UpdateCommitMessage => "Changed a commit message".to_owned(),
+        OperationKind::InsertBlankCommit => "Inserted a blank commit".to_owned(),
+        OperationKind::CherryPick => "Cherry-picked a commit".to_owned(),
+        OperationKind::MoveCommit => "Moved a commit".to_owned(),
+        OperationKind::ReorderCommit => "Reordered commits".to_owned(),
+        OperationKind::MoveCommitFile => "Moved file changes between commits".to_owned(),
+        OperationKind::MoveHunk => "Moved changes to another branch".to_owned(),
+        OperationKind::MergeUpstream => "Rebased a branch onto its upstream".to_owned(),
+        OperationKind::UpdateWorkspaceBase | OperationKind::SyncWorkspace => {
+            "Rebased the workspace onto the target branch".to_owned()
+        }
+        OperationKind::SetBaseBranch => "Set the target branch".to_owned(),
+        OperationKind::CreateBranch | OperationKind::CreateDependentBranch => {
+            format!("Created the branch{name}")
+        }
+        OperationKind::DeleteBranch | OperationKind::RemoveDependentBranch => {
+            format!("Deleted the branch{name}")
+        }
+        OperationKind::ApplyBranch => format!("Applied the branch{name}"),
+        OperationKind::UnapplyBranch => format!("Unapplied the branch{name}"),
+        OperationKind::UpdateBranchName | OperationKind::UpdateDependentBranchName => {
+            "Renamed a branch".to_owned()
+        }
+        OperationKind::DiscardLines
+        | OperationKind::DiscardHunk
+        | OperationKind::DiscardFile
+        | OperationKind::DiscardChanges => "Discarded changes".to_owned(),
+        OperationKind::RestoreFromSnapshot => "Restored a previous state".to_owned(),
+        _ => details.title.clone(),
+    }
+}
+
+pub struct GetGitAttributes;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetGitAttributesParameters {
+    /// The paths to get the attributes of.
+    #[schemars(description = "
+    <description>
+        The paths of the files to get the git attributes of, relative to the root of the repository.
+    </description>
+
+    <important_notes>
+        The files don't have to exist.
+    </important_notes>
+    ")]
+    pub paths: Vec<String>,
+}
+
+impl Tool for GetGitAttributes {
+    fn name(&self) -> String {
+        "get_git_attributes".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the git attributes that apply to the given paths, as configured in .gitattributes files.
+        </description>
+
+        <important_notes>
+            Use this tool to find out how files are treated by git, for instance their line endings ('text', 'eol'),
+            whether they are considered binary ('binary'), and which diff and merge drivers are used for them ('diff', 'merge').
+            Attributes that aren't specified for a path are left out.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetGitAttributesParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetGitAttributesParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = git_attributes(ctx, params).to_json("get_git_attributes");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathAttributes {
+    /// The path the attributes apply to.
+    pub path: String,
+    /// The attributes of the path, by their name.
+    pub attributes: BTreeMap<String, AttributeValue>,
+}
+
+/// The state of a git attribute of a path.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AttributeValue {
+    /// The attribute is set, like `text`.
+    Set,
+    /// The attribute is unset, like `-text`.
+    Unset,
+    /// The attribute is set to a value, like `eol=lf`.
+    Value(String),
+}
+
+impl ToolResult for Result<Vec<PathAttributes>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<PathAttributes>")
+    }
+}
+
+/// Return the git attributes of each of the paths in `params`, in the same order.
+pub fn git_attributes(
+    ctx: &CommandContext,
+    params: GetGitAttributesParameters,
+) -> anyhow::Result<Vec<PathAttributes>> {
+    let repo = ctx.gix_repo()?;
+    let index = repo.index_or_empty()?;
+    // The .gitattributes files in the worktree take precedence, as they may have been changed but not committed yet.
+    let mut cache = repo.attributes_only(
+        &index,
+        gix::worktree::stack::state::attributes::Source::WorktreeThenIdMapping,
+    )?;
+    let mut matches = cache.attribute_matches();
+
+    let mut path_attributes = vec![];
+    for path in params.paths {
+        let mut attributes = BTreeMap::new();
+        let entry = cache.at_entry(path.as_str(), None)?;
+        if entry.matching_attributes(&mut matches) {
+            for attribute in matches.iter() {
+                let value = match attribute.assignment.state {
+                    gix::attrs::StateRef::Set => AttributeValue::Set,
+                    gix::attrs::StateRef::Unset => AttributeValue::Unset,
+                    gix::attrs::StateRef::Value(value) => {
+                        AttributeValue::Value(value.as_bstr().to_string())
+                    }
+                    gix::attrs::StateRef::Unspecified => continue,
+                };
+                attributes.insert(attribute.assignment.name.as_str().to_owned(), value);
+            }
+        }
+        path_attributes.push(PathAttributes { path, attributes });
+    }
+    Ok(path_attributes)
+}
+
+pub struct GetCommitByMessage;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCommitByMessageParameters {
+    /// A part of the message of the commit to find.
+    #[schemars(description = "
+    <description>
+        A part of the message of the commit to find, like a few words of its title.
+    </description>
+
+    <important_notes>
+        The message is matched case-insensitively against the titles and bodies of the commits in the workspace.
+        It has to match a single commit, so make it specific enough.
+    </important_notes>
+    ")]
+    pub message: String,
+    /// The id of the stack to look for the commit in.
+    #[schemars(description = "
+    <description>
+        The id of the stack to look for the commit in.
+    </description>
+
+    <important_notes>
+        Leave this empty to look for the commit in all stacks of the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: Option<String>,
+}
+
+impl Tool for GetCommitByMessage {
+    fn name(&self) -> String {
+        "get_commit_by_message".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Find the commit in the workspace whose message contains the given text, and get its id.
+        </description>
+
+        <important_notes>
+            Use this tool to get the id of a commit that is known by its message, before passing it to the tools that take a commit id.
+            If more than one commit matches, the tool fails and lists the matching commits, so the message can be made more specific.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetCommitByMessageParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetCommitByMessageParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = commit_by_message(ctx, params).to_json("get_commit_by_message");
+        Ok(value)
+    }
+}
+
+impl ToolResult for Result<SimpleCommit, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "SimpleCommit")
+    }
+}
+
+/// Return the single commit in the workspace, or in the stack of `params` if given, whose message contains the message of `params`.
+pub fn commit_by_message(
+    ctx: &mut CommandContext,
+    params: GetCommitByMessageParameters,
+) -> anyhow::Result<SimpleCommit> {
+    let needle = params.message.trim().to_lowercase();
+    if needle.is_empty() {
+        anyhow::bail!("The message to look for is empty");
+    }
+    let repo = ctx.gix_repo()?;
+    let mut entries = stacks(ctx, &repo)?;
+    if let Some(stack_id) = &params.stack_id {
+        let stack_id = StackId::from_str(stack_id)?;
+        entries.retain(|entry| entry.id == stack_id);
+    }
+
+    let mut matches: Vec<SimpleCommit> = vec![];
+    for stack in entries_to_simple_stacks(&entries, ctx, &repo)? {
+        for commit in stack.branches.into_iter().flat_map(|b| b.commits) {
+            let found = commit.message_title.to_lowercase().contains(&needle)
+                || commit.message_body.to_lowercase().contains(&needle);
+            if found && !matches.iter().any(|c| c.id == commit.id) {
+                matches.push(commit);
+            }
+        }
+    }
+
+    match matches.len() {
+        0 => Err(anyhow::anyhow!(
+            "No commit found with a message containing '{}'",
+            params.message
+        )),
+        1 => Ok(matches.remove(0)),
+        _ => Err(anyhow::anyhow!(
+            "The message '{}' matches {} commits, use a more specific message or one of their ids instead: {}",
+            params.message,
+            matches.len(),
+            matches
+                .iter()
+                .map(|c| format!("{} ({})", c.id, c.message_title))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+pub struct GetWorktreeLockStatus;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetWorktreeLockStatusParameters {}
+
+impl Tool for GetWorktreeLockStatus {
+    fn name(&self) -> String {
+        "get_worktree_lock_status".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get whether the worktree is currently locked by an operation that changes it.
+        </description>
+
+        <important_notes>
+            Use this tool to diagnose operations that seem to hang, or before starting operations while others may still be running.
+            The lock is probed without waiting for it, so this tool never blocks.
+            If an operation that changes the worktree holds the lock, the id of its process and the time since which it holds the lock are included.
+            Only operations of this GitButler process are seen, as the worktree lock isn't shared with other processes.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetWorktreeLockStatusParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = worktree_lock_status(ctx).to_json("get_worktree_lock_status");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorktreeLockStatus {
+    /// Whether an operation holds the access to the worktree, so operations that change it have to wait.
+    pub is_locked: bool,
+    /// The id of the process holding the exclusive access, if an operation that changes the worktree holds it.
+    pub locked_by_pid: Option<u32>,
+    /// Since when the exclusive access is held, in seconds since the Unix epoch, if it's held.
+    pub locked_since: Option<i64>,
+}
+
+impl ToolResult for Result<WorktreeLockStatus, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "WorktreeLockStatus")
+    }
+}
+
+/// Return whether the worktree access of the project [is held](Project::is_worktree_access_held()),
+/// and by whom if it's the [exclusive access](Project::exclusive_worktree_access()).
+///
+/// Only holders within this process are seen.
+pub fn worktree_lock_status(ctx: &CommandContext) -> anyhow::Result<WorktreeLockStatus> {
+    let is_locked = ctx.project().is_worktree_access_held();
+    let Some(holder) = ctx.project().worktree_lock_holder().filter(|_| is_locked) else {
+        return Ok(WorktreeLockStatus {
+            is_locked,
+            locked_by_pid: None,
+            locked_since: None,
+        });
+    };
+    let locked_since = holder
+        .since
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs()
+        .try_into()?;
+    Ok(WorktreeLockStatus {
+        is_locked: true,
+        locked_by_pid: Some(holder.pid),
+        locked_since: Some(locked_since),
+    })
+}
+
+pub struct ReassignAndCommit;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReassignAndCommitParameters {
+    /// The stack to assign and commit the hunks to.
+    #[schemars(description = "
+    <description>
+        The id of the stack to assign the hunks to, and to commit them to.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+        The hunks are committed on top of its top-most branch.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The hunks to assign and commit.
+    #[schemars(description = "
+    <description>
+        The uncommitted hunks to assign to the stack and commit, by file.
+    </description>
+
+    <important_notes>
+        Only the listed hunks are committed, the other changes stay in the worktree.
+    </important_notes>
+    ")]
+    pub hunks: Vec<HunkSelection>,
+    /// The commit message title.
+    #[schemars(description = "
+    <description>
+        The commit message title.
+    </description>
+
+    <important_notes>
+        The commit message title should be concise and descriptive.
+        It should be no more than 50 characters.
+    </important_notes>
+    ")]
+    pub message_title: String,
+    /// The commit message body.
+    #[schemars(description = "
+    <description>
+        The commit message body.
+    </description>
+
+    <important_notes>
+        The commit message body should provide context and details about the changes made.
+    </important_notes>
+    ")]
+    pub message_body: String,
+}
+
+/// The hunks of a file to select.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkSelection {
+    /// The path of the file.
+    #[schemars(description = "
+    <description>
+        The path of the changed file, relative to the workspace root.
+    </description>
+    ")]
+    pub path: String,
+    /// The headers of the hunks of the file to select.
+    #[schemars(description = "
+    <description>
+        The headers of the hunks to select, like '@@ -1,3 +1,4 @@', as they are shown at the start of each hunk diff.
+    </description>
+
+    <important_notes>
+        Leave this empty to select all hunks of the file.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub hunk_headers: Vec<String>,
+}
+
+impl Tool for ReassignAndCommit {
+    fn name(&self) -> String {
+        "reassign_and_commit".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Assign uncommitted hunks to a stack and commit exactly these hunks to it, in one step.
+        </description>
+
+        <important_notes>
+            Use this tool instead of assigning hunks and committing them separately, as it doesn't leave the hunks assigned
+            to the stack if the commit fails.
+            The hunks are identified by the path of their file and their header, as shown in the project status.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(ReassignAndCommitParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: ReassignAndCommitParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let paths = params
+            .hunks
+            .iter()
+            .map(|selection| selection.path.clone())
+            .collect::<Vec<_>>();
+        let value = reassign_and_commit(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .and_then(|committed| {
+            let mut result = commit_result(ctx, committed.outcome, &paths)?;
+            result.branch_name = Some(committed.branch_name);
+            result.warnings = committed.warnings;
+            Ok(result)
+        })
+        .to_json("reassign_and_commit");
+        Ok(value)
+    }
+}
+
+/// Assign the hunks of `params` to its stack, and commit exactly these hunks to the top-most branch of the stack.
+///
+/// The previous assignments are restored if the hunks can't be assigned or committed,
+/// and nothing is committed if only some of the hunks could be.
+pub fn reassign_and_commit(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: ReassignAndCommitParameters,
+) -> anyhow::Result<CommitOnBranchOutcome> {
+    let message = sanitizer.commit_message(&params.message_title, &params.message_body)?;
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let branch_name = vb_state
+        .get_stack_in_workspace(stack_id)?
+        .branches()
+        .last()
+        .map(|b| b.name().to_owned())
+        .ok_or_else(|| anyhow::anyhow!("Stack {} has no branch", stack_id))?;
+
+    let repo = ctx.gix_repo()?;
+    // Hold the worktree for the whole operation, so the assignments can't change until the hunks are committed
+    // or the previous assignments are restored.
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let worktree = but_core::diff::worktree_changes(&repo)?;
+    let mut requests = vec![];
+    let mut file_changes = vec![];
+    for selection in &params.hunks {
+        let change = worktree
+            .changes
+            .iter()
+            .find(|change| change.path == selection.path)
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no uncommitted changes", selection.path))?;
+        let hunk_headers = match change.unified_diff(&repo, ctx.app_settings().context_lines)? {
+            Some(UnifiedDiff::Patch { hunks, .. }) => {
+                hunks.iter().map(Into::into).collect::<Vec<_>>()
+            }
+            _ => vec![],
+        };
+        let selected =
+            selected_hunk_headers(&selection.path, &hunk_headers, &selection.hunk_headers)?;
+
+        let assigned = if hunk_headers.is_empty() {
+            // Files without hunks, like binary files, are assigned as a whole.
+            vec![None]
+        } else if selected.is_empty() {
+            hunk_headers.iter().copied().map(Some).collect()
+        } else {
+            selected.iter().copied().map(Some).collect()
+        };
+        requests.extend(assigned.into_iter().map(|hunk_header| {
+            but_hunk_assignment::HunkAssignmentRequest {
+                hunk_header,
+                path_bytes: change.path.clone(),
+                stack_id: Some(stack_id),
+            }
+        }));
+
+        let mut file_change = but_workspace::DiffSpec::from(change);
+        file_change.hunk_headers = selected;
+        file_changes.push(file_change);
+    }
+
+    let previous_assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    let rejections = but_hunk_assignment::assign(ctx, requests, None)?;
+    if !rejections.is_empty() {
+        but_hunk_assignment::set_persisted_assignments(ctx, previous_assignments)?;
+        anyhow::bail!(
+            "Hunks of {} are locked to other stacks and can't be assigned to stack {}",
+            rejections
+                .iter()
+                .map(|rejection| format!("'{}'", rejection.request.path_bytes))
+                .collect::<Vec<_>>()
+                .join(", "),
+            stack_id
+        );
+    }
+
+    // Commit in memory first, so no commit is created if some of the hunks can't be committed.
+    let rejected_specs = ctx.gix_repo_for_merging_non_persisting().and_then(|repo| {
+        let parent_commit_id = repo
+            .find_reference(branch_name.as_str())?
+            .peel_to_commit()?
+            .id;
+        let outcome = but_workspace::commit_engine::create_commit(
+            &repo,
+            but_workspace::commit_engine::Destination::NewCommit {
+                parent_commit_id: Some(parent_commit_id),
+                stack_segment: None,
+                message: message.clone(),
+            },
+            None,
+            file_changes.clone(),
+            ctx.app_settings().context_lines,
+        )?;
+        Ok(outcome.rejected_specs)
+    });
+    match rejected_specs {
+        Ok(rejected_specs) if rejected_specs.is_empty() => {}
+        Ok(rejected_specs) => {
+            but_hunk_assignment::set_persisted_assignments(ctx, previous_assignments)?;
+            anyhow::bail!(
+                "Hunks of {} can't be committed to stack {}",
+                rejected_paths(&rejected_specs),
+                stack_id
+            );
+        }
+        Err(err) => {
+            but_hunk_assignment::set_persisted_assignments(ctx, previous_assignments)?;
+            return Err(err);
+        }
+    }
+
+    let snapshot_tree = ctx.prepare_snapshot(guard.read_permission());
+    let outcome = but_workspace::commit_engine::create_commit_simple(
+        ctx,
+        stack_id,
+        None,
+        file_changes.clone(),
+        message.clone(),
+        branch_name.clone(),
+        None,
+        guard.write_permission(),
+    );
+    let _ = snapshot_tree.and_then(|snapshot_tree| {
+        ctx.snapshot_commit_creation(
+            snapshot_tree,
+            outcome.as_ref().err(),
+            message,
+            None,
+            guard.write_permission(),
+        )
+    });
+
+    let outcome = match outcome {
+        Ok(outcome) if outcome.new_commit.is_some() && outcome.rejected_specs.is_empty() => outcome,
+        Ok(outcome) => {
+            but_hunk_assignment::set_persisted_assignments(ctx, previous_assignments)?;
+            match outcome.new_commit {
+                None => anyhow::bail!("None of the hunks could be committed"),
+                Some(commit_id) => anyhow::bail!(
+                    "Hunks of {} couldn't be committed, the others were committed as {}",
+                    rejected_paths(&outcome.rejected_specs),
+                    commit_id
+                ),
+            }
+        }
+        Err(err) => {
+            but_hunk_assignment::set_persisted_assignments(ctx, previous_assignments)?;
+            return Err(err);
+        }
+    };
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
+    }
+
+    let mut warnings = vec![];
+    if let Err(err) = remove_committed_assignments(ctx, app_handle, &file_changes, &outcome) {
+        warnings.push(format!(
+            "The assignments of the committed hunks couldn't be removed: {}",
+            err
+        ));
+    }
+    Ok(CommitOnBranchOutcome {
+        outcome: outcome.into(),
+        branch_name,
+        warnings,
+    })
+}
+
+/// List the paths of `rejected_specs` along with why they were rejected.
+fn rejected_paths(
+    rejected_specs: &[(
+        but_workspace::commit_engine::RejectionReason,
+        but_workspace::DiffSpec,
+    )],
+) -> String {
+    rejected_specs
+        .iter()
+        .map(|(reason, spec)| format!("'{}' ({:?})", spec.path, reason))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Return the headers among `hunk_headers`, the hunks of the file at `path`, that are selected by `selection`,
+/// or no headers if all of them are selected.
+fn selected_hunk_headers(
+    path: &str,
+    hunk_headers: &[but_workspace::HunkHeader],
+    selection: &[String],
+) -> anyhow::Result<Vec<but_workspace::HunkHeader>> {
+    selection
+        .iter()
+        .map(|header| {
+            parse_hunk_header(header)
+                .filter(|header| hunk_headers.contains(header))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "'{}' doesn't have a hunk with the header '{}', it has the hunks: {}",
+                        path,
+                        header,
+                        hunk_headers
+                            .iter()
+                            .map(format_hunk_header)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Parse a hunk header like `@@ -1,3 +1,4 @@`, as it's shown at the start of each hunk diff.
+fn parse_hunk_header(header: &str) -> Option<but_workspace::HunkHeader> {
+    fn range(range: &str) -> Option<(u32, u32)> {
+        match range.split_once(',') {
+            Some((start, lines)) => Some((start.parse().ok()?, lines.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    }
+    let mut ranges = header
+        .split_whitespace()
+        .skip_while(|token| !token.starts_with('-'));
+    let (old_start, old_lines) = range(ranges.next()?.strip_prefix('-')?)?;
+    let (new_start, new_lines) = range(ranges.next()?.strip_prefix('+')?)?;
+    Some(but_workspace::HunkHeader {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+    })
+}
+
+fn format_hunk_header(header: &but_workspace::HunkHeader) -> String {
+    format!(
+        "@@ -{},{} +{},{} @@",
+        header.old_start, header.old_lines, header.new_start, header.new_lines
+    )
+}
+
+pub struct GetChangeStatsByStack;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetChangeStatsByStackParameters {}
+
+impl Tool for GetChangeStatsByStack {
+    fn name(&self) -> String {
+        "get_change_stats_by_stack".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get how many files, added lines and removed lines of the uncommitted changes are assigned to each stack,
+            and how many aren't assigned to any stack.
+        </description>
+
+        <important_notes>
+            Use this tool to plan commits, as it shows how much work is pending for each stack without including the diffs.
+            A file whose hunks are assigned to different stacks is counted for each of them.
+            Use the get_project_status tool to get the diffs.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetChangeStatsByStackParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = change_stats_by_stack(ctx, &options.assignments_cache)
+            .to_json("get_change_stats_by_stack");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeStats {
+    /// The number of changed files.
+    pub files: usize,
+    /// The number of added lines.
+    pub insertions: usize,
+    /// The number of removed lines.
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackChangeStats {
+    /// The id of the stack.
+    pub stack_id: StackId,
+    /// The name of the stack.
+    pub stack_name: String,
+    /// The uncommitted changes assigned to the stack.
+    #[serde(flatten)]
+    pub stats: ChangeStats,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeStatsByStack {
+    /// The uncommitted changes assigned to each stack in the workspace.
+    pub stacks: Vec<StackChangeStats>,
+    /// The uncommitted changes that aren't assigned to any stack.
+    pub unassigned: ChangeStats,
+}
+
+impl ToolResult for Result<ChangeStatsByStack, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "ChangeStatsByStack")
+    }
+}
+
+/// Return the statistics of the uncommitted changes, bucketed by the stack their hunks are assigned to.
+pub fn change_stats_by_stack(
+    ctx: &mut CommandContext,
+    assignments_cache: &AssignmentsCache,
+) -> anyhow::Result<ChangeStatsByStack> {
+    let repo = ctx.gix_repo()?;
+    let worktree = but_core::diff::worktree_changes(&repo)?;
+    let (diff, _) =
+        unified_diff_for_changes(&repo, worktree.changes, ctx.app_settings().context_lines)?;
+    let assignments = cached_assignments(ctx, &repo, &diff, assignments_cache)?.assignments;
+
+    let mut stacks = stacks(ctx, &repo)?
+        .into_iter()
+        .map(|entry| StackChangeStats {
+            stack_id: entry.id,
+            stack_name: entry.name().unwrap_or_default().to_string(),
+            stats: ChangeStats::default(),
+        })
+        .collect::<Vec<_>>();
+    let mut unassigned = ChangeStats::default();
+    for (change, unified_diff) in &diff {
+        let stack_of = |hunk_header: Option<but_workspace::HunkHeader>| {
+            let stack_id = assignments
+                .iter()
+                .find(|a| a.path_bytes == change.path && a.hunk_header == hunk_header)
+                .and_then(|a| a.stack_id)?;
+            stacks.iter().position(|stack| stack.stack_id == stack_id)
+        };
+        let hunks = match unified_diff {
+            UnifiedDiff::Patch { hunks, .. } => hunks
+                .iter()
+                .map(|hunk| {
+                    let (insertions, deletions) = count_hunk_lines(hunk.diff.as_ref());
+                    (stack_of(Some(hunk.into())), insertions, deletions)
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![(stack_of(None), 0, 0)],
+        };
+
+        let mut counted_stacks = vec![];
+        for (stack, insertions, deletions) in hunks {
+            let stats = match stack {
+                Some(index) => &mut stacks[index].stats,
+                None => &mut unassigned,
+            };
+            stats.insertions += insertions;
+            stats.deletions += deletions;
+            if !counted_stacks.contains(&stack) {
+                counted_stacks.push(stack);
+                stats.files += 1;
+            }
+        }
+    }
+
+    Ok(ChangeStatsByStack { stacks, unassigned })
+}
+
+/// Return the number of added and removed lines in `diff`, the unified diff of a hunk including its header.
+fn count_hunk_lines(diff: &[u8]) -> (usize, usize) {
+    diff.lines()
+        .skip(1)
+        .fold((0, 0), |(added, removed), line| match line.first() {
+            Some(b'+') => (added + 1, removed),
+            Some(b'-') => (added, removed + 1),
+            _ => (added, removed),
+        })
+}
+
+pub struct GetConflictingStackPairs;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetConflictingStackPairsParameters {}
+
+impl Tool for GetConflictingStackPairs {
+    fn name(&self) -> String {
+        "get_conflicting_stack_pairs".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the pairs of applied stacks whose commits change the same files, and whether they change the same lines.
+        </description>
+
+        <important_notes>
+            Use this tool to find out which stacks can't be merged independently of each other.
+            Only the committed changes of the stacks are compared, uncommitted changes are ignored.
+            A severity of 'directConflict' means that both stacks change the same lines of at least one file,
+            while 'overlap' means that they only change different lines of the same files.
+            Pairs of stacks that don't change any file in common are omitted.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetConflictingStackPairsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = conflicting_stack_pairs(ctx).to_json("get_conflicting_stack_pairs");
+        Ok(value)
+    }
+}
+
+/// How severely the changes of two stacks conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictSeverity {
+    /// The stacks change the same files, but not the same lines.
+    Overlap,
+    /// The stacks change the same lines of at least one file.
+    DirectConflict,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictingPair {
+    /// The id of the first stack.
+    pub stack_a_id: StackId,
+    /// The id of the second stack.
+    pub stack_b_id: StackId,
+    /// The paths of the files changed by both stacks.
+    pub conflicting_paths: Vec<String>,
+    /// Whether the stacks change the same lines of any of the files.
+    pub severity: ConflictSeverity,
+}
+
+impl ToolResult for Result<Vec<ConflictingPair>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<ConflictingPair>")
+    }
+}
+
+/// Return all pairs of applied stacks whose commits change the same files.
+///
+/// The changes of each stack are its cumulative diff from its merge base to its tip, and two stacks
+/// conflict directly if the line ranges of their hunks intersect in the base version of a file.
+pub fn conflicting_stack_pairs(ctx: &CommandContext) -> anyhow::Result<Vec<ConflictingPair>> {
+    let repo = ctx.gix_repo()?;
+    conflicting_pairs_of(ctx, &repo, stacks(ctx, &repo)?)
+}
+
+/// Return all pairs of `stacks` whose commits change the same files, with the first stack of each pair
+/// coming before the second one in `stacks`.
+fn conflicting_pairs_of(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stacks: Vec<StackEntry>,
+) -> anyhow::Result<Vec<ConflictingPair>> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+
+    let mut changed_lines = vec![];
+    for entry in stacks {
+        let merge_base = vb_state.get_stack(entry.id)?.merge_base(ctx)?;
+        let (changes, _) = but_core::diff::tree_changes(repo, Some(merge_base), entry.tip)?;
+        let mut lines_by_path = BTreeMap::<BString, Option<Vec<(u32, u32)>>>::new();
+        for change in changes {
+            // Without context lines, the hunks cover exactly the changed lines.
+            let ranges = match change.unified_diff(repo, 0)? {
+                Some(UnifiedDiff::Patch { hunks, .. }) => Some(
+                    hunks
+                        .iter()
+                        .map(|hunk| {
+                            // Insertions don't remove any line, so they occupy the line they are inserted at.
+                            (hunk.old_start, hunk.old_start + hunk.old_lines.max(1))
+                        })
+                        .collect(),
+                ),
+                // Binary or too large files can't be compared line by line, so all of their lines are changed.
+                _ => None,
+            };
+            lines_by_path.insert(change.path, ranges);
+        }
+        changed_lines.push((entry.id, lines_by_path));
+    }
+
+    let mut pairs = vec![];
+    for (index, (stack_a_id, lines_a)) in changed_lines.iter().enumerate() {
+        for (stack_b_id, lines_b) in &changed_lines[index + 1..] {
+            let mut conflicting_paths = vec![];
+            let mut severity = ConflictSeverity::Overlap;
+            for (path, ranges_a) in lines_a {
+                let Some(ranges_b) = lines_b.get(path) else {
+                    continue;
+                };
+                let intersects = match (ranges_a, ranges_b) {
+                    (Some(ranges_a), Some(ranges_b)) => ranges_a.iter().any(|(start_a, end_a)| {
+                        ranges_b
+                            .iter()
+                            .any(|(start_b, end_b)| start_a < end_b && start_b < end_a)
+                    }),
+                    _ => true,
+                };
+                if intersects {
+                    severity = ConflictSeverity::DirectConflict;
+                }
+                conflicting_paths.push(path.to_string());
+            }
+            if !conflicting_paths.is_empty() {
+                pairs.push(ConflictingPair {
+                    stack_a_id: *stack_a_id,
+                    stack_b_id: *stack_b_id,
+                    conflicting_paths,
+                    severity,
+                });
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+pub struct GetStackMergeOrder;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStackMergeOrderParameters {
+    /// The ids of the stacks to order.
+    #[schemars(description = "
+    <description>
+        The ids of the stacks to suggest a merge order for.
+    </description>
+
+    <important_notes>
+        The stack ids should refer to stacks in the workspace.
+        If not provided, all stacks in the workspace are ordered.
+    </important_notes>
+    ")]
+    pub stack_ids: Option<Vec<String>>,
+}
+
+impl Tool for GetStackMergeOrder {
+    fn name(&self) -> String {
+        "get_stack_merge_order".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Suggest the order in which to push and merge the stacks in the workspace,
+            so stacks that change the same files as other stacks are merged after them.
+        </description>
+
+        <important_notes>
+            Use this tool when the user asks how to land several stacks, or which stack to open a pull request for first.
+            Stacks that don't change any file of another stack can be merged in any order.
+            Stacks that change the same lines as a stack they depend on will have to be rebased and have their conflicts
+            resolved after that stack was merged.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetStackMergeOrderParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetStackMergeOrderParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = stack_merge_order(ctx, params).to_json("get_stack_merge_order");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeOrderSuggestion {
+    /// The id of the stack.
+    pub stack_id: StackId,
+    /// The zero-based position at which to merge the stack.
+    pub suggested_position: usize,
+    /// The stacks that change the same files, and have to be merged before this one.
+    pub depends_on: Vec<StackId>,
+    /// Why the stack is placed at this position.
+    pub reason: String,
+}
+
+impl ToolResult for Result<Vec<MergeOrderSuggestion>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<MergeOrderSuggestion>")
+    }
+}
+
+/// Suggest the order in which to merge the stacks in `params`, ordered by their suggested position.
+///
+/// A stack depends on the stacks before it in the workspace whose commits change the same files,
+/// so stacks are merged in the order of the workspace unless they are independent of each other.
+pub fn stack_merge_order(
+    ctx: &CommandContext,
+    params: GetStackMergeOrderParameters,
+) -> anyhow::Result<Vec<MergeOrderSuggestion>> {
+    let repo = ctx.gix_repo()?;
+    let mut entries = stacks(ctx, &repo)?;
+    if let Some(stack_ids) = params.stack_ids {
+        let stack_ids = stack_ids
+            .iter()
+            .map(|id| StackId::from_str(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(missing) = stack_ids
+            .iter()
+            .find(|id| !entries.iter().any(|entry| entry.id == **id))
+        {
+            anyhow::bail!("Stack {} not found in the workspace", missing);
+        }
+        entries.retain(|entry| stack_ids.contains(&entry.id));
+    }
+    let stack_ids = entries.iter().map(|entry| entry.id).collect::<Vec<_>>();
+    let pairs = conflicting_pairs_of(ctx, &repo, entries)?;
+
+    // Dependencies only point to stacks earlier in the workspace, so its order is a valid merge order.
+    let suggestions = stack_ids
+        .iter()
+        .enumerate()
+        .map(|(suggested_position, stack_id)| {
+            let dependencies = pairs
+                .iter()
+                .filter(|pair| pair.stack_b_id == *stack_id)
+                .collect::<Vec<_>>();
+            let reason = if dependencies.is_empty() {
+                "It doesn't depend on any other stack".to_string()
+            } else {
+                let conflicting = dependencies
+                    .iter()
+                    .filter(|pair| pair.severity == ConflictSeverity::DirectConflict)
+                    .count();
+                let mut paths = dependencies
+                    .iter()
+                    .flat_map(|pair| pair.conflicting_paths.iter().map(String::as_str))
+                    .collect::<Vec<_>>();
+                paths.sort();
+                paths.dedup();
+                let mut reason = format!(
+                    "It changes files that stacks before it change too: {}",
+                    paths.join(", ")
+                );
+                if conflicting > 0 {
+                    reason.push_str(&format!(
+                        ", and the same lines as {} of them, so it has to be rebased and conflicts resolved after they are merged",
+                        conflicting
+                    ));
+                }
+                reason
+            };
+            MergeOrderSuggestion {
+                stack_id: *stack_id,
+                suggested_position,
+                depends_on: dependencies.iter().map(|pair| pair.stack_a_id).collect(),
+                reason,
+            }
+        })
+        .collect();
+    Ok(suggestions)
+}
+
+pub struct GetBranchUpstreamCommits;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBranchUpstreamCommitsParameters {
+    /// The id of the stack containing the branch.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The name of the branch.
+    #[schemars(description = "
+    <description>
+        The name of the branch to compare with its remote branch.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub branch_name: String,
+}
+
+impl Tool for GetBranchUpstreamCommits {
+    fn name(&self) -> String {
+        "get_branch_upstream_commits".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the commits of a branch, split into the ones that were pushed, the ones that only exist locally,
+            and the ones that only exist on the remote branch.
+        </description>
+
+        <important_notes>
+            Use this tool before amending, moving or otherwise rewriting commits of a branch that may have been pushed.
+            Rewriting pushed commits makes the branch diverge from its remote branch, so it has to be force-pushed,
+            which should be avoided unless the user asks for it.
+            Local-only commits can be rewritten freely.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetBranchUpstreamCommitsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetBranchUpstreamCommitsParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = branch_upstream_commits(ctx, params).to_json("get_branch_upstream_commits");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchUpstreamCommits {
+    /// The name of the branch.
+    pub name: String,
+    /// The commits that exist both locally and on the remote branch, the most recent one first.
+    pub pushed: Vec<SimpleCommit>,
+    /// The commits that only exist locally, the most recent one first.
+    pub local_only: Vec<SimpleCommit>,
+    /// The commits that only exist on the remote branch, the most recent one first.
+    pub remote_only: Vec<SimpleCommit>,
+}
+
+impl ToolResult for Result<BranchUpstreamCommits, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "BranchUpstreamCommits")
+    }
+}
+
+/// Return the commits of the branch in `params`, split by whether they exist locally, on the remote branch, or both.
+pub fn branch_upstream_commits(
+    ctx: &CommandContext,
+    params: GetBranchUpstreamCommitsParameters,
+) -> anyhow::Result<BranchUpstreamCommits> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let branch_name = unqualified_branch_name(stack_id, &params.branch_name);
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+    let branch = stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == branch_name)
+        .ok_or_else(|| {
+            anyhow::anyhow!("Branch '{}' not found in stack {}", branch_name, stack_id)
+        })?;
+
+    let (pushed, local_only) =
+        but_workspace::local_and_remote_commits(ctx, &repo, &branch, &stack)?
+            .into_iter()
+            .map(SimpleCommit::from)
+            .partition(|commit| commit.pushed);
+    let remote_only = but_workspace::stack_branch_upstream_only_commits(
+        stack_id,
+        branch.name().to_owned(),
+        ctx,
+        &repo,
+    )?
+    .into_iter()
+    .map(|commit| SimpleCommit {
+        remote_only: true,
+        ..SimpleCommit::from_message(commit.id, &commit.message.to_string())
+    })
+    .collect();
+
+    Ok(BranchUpstreamCommits {
+        name: branch.name().to_owned(),
+        pushed,
+        local_only,
+        remote_only,
+    })
+}
+
+pub struct GetChangesSince;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetChangesSinceParameters {
+    /// The id of the stack containing the branch.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The name of the branch.
+    #[schemars(description = "
+    <description>
+        The name of the branch to get the new commits of.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub branch_name: String,
+    /// The commit after which commits are new.
+    #[schemars(description = "
+    <description>
+        The id of the last commit of the branch that was already looked at.
+        Only the commits of the branch after this one are returned.
+    </description>
+
+    <important_notes>
+        If the commit was rewritten since, for instance because it was amended, no commits are returned,
+        and the commit it was rewritten to is reported instead, if it's known.
+        If not provided, all commits of the branch are returned.
+    </important_notes>
+    ")]
+    pub since_commit_id: Option<String>,
+    /// The snapshot to compare the worktree with.
+    #[schemars(description = "
+    <description>
+        The id of a snapshot, as listed by the get_recent_activity tool.
+        The paths of the files in the worktree whose content differs from this snapshot are returned.
+    </description>
+
+    <important_notes>
+        If not provided, the worktree isn't compared.
+    </important_notes>
+    ")]
+    pub since_snapshot_id: Option<String>,
+}
+
+impl Tool for GetChangesSince {
+    fn name(&self) -> String {
+        "get_changes_since".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get what changed on a branch since it was last looked at:
+            the commits added after a known commit, and the files in the worktree that changed since a snapshot.
+        </description>
+
+        <important_notes>
+            Use this tool to review only what's new on a branch, instead of looking at all of its commits again.
+            If the known commit was rewritten, review the commit it was rewritten to, along with everything after it.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetChangesSinceParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetChangesSinceParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = changes_since(ctx, params).to_json("get_changes_since");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangesSince {
+    /// The commits of the branch after the known commit, the most recent one first.
+    pub new_commits: Vec<SimpleCommit>,
+    /// Whether the known commit is still part of the branch, which is always the case if none was given.
+    pub since_commit_found: bool,
+    /// The commit of the branch the known commit was rewritten to, if it isn't part of the branch anymore.
+    #[serde(
+        with = "gitbutler_serde::object_id_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rewritten_to: Option<gix::ObjectId>,
+    /// The paths of the files in the worktree whose content differs from the snapshot, if one was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_paths: Option<Vec<String>>,
+}
+
+impl ToolResult for Result<ChangesSince, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "ChangesSince")
+    }
+}
+
+/// Return the commits of the branch in `params` after its known commit, and the worktree files that changed since its snapshot.
+///
+/// A commit that isn't part of the branch anymore was rewritten to the commit of the branch with the same change id, if any.
+pub fn changes_since(
+    ctx: &CommandContext,
+    params: GetChangesSinceParameters,
+) -> anyhow::Result<ChangesSince> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let branch_name = unqualified_branch_name(stack_id, &params.branch_name);
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+    let branch = stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == branch_name)
+        .ok_or_else(|| {
+            anyhow::anyhow!("Branch '{}' not found in stack {}", branch_name, stack_id)
+        })?;
+    let commits = but_workspace::local_and_remote_commits(ctx, &repo, &branch, &stack)?;
+
+    let (new_commits, since_commit_found, rewritten_to) = match params.since_commit_id {
+        Some(since_commit_id) => {
+            let since_commit_id = resolve_commit_id(&repo, &since_commit_id)?;
+            match commits
+                .iter()
+                .position(|commit| commit.id == since_commit_id)
+            {
+                Some(position) => (commits[..position].to_vec(), true, None),
+                None => {
+                    let since_change_id = change_id(&repo, since_commit_id);
+                    let rewritten_to = since_change_id.and_then(|since_change_id| {
+                        commits
+                            .iter()
+                            .find(|commit| {
+                                change_id(&repo, commit.id).as_ref() == Some(&since_change_id)
+                            })
+                            .map(|commit| commit.id)
+                    });
+                    (vec![], false, rewritten_to)
+                }
+            }
+        }
+        None => (commits, true, None),
+    };
+
+    let changed_paths = params
+        .since_snapshot_id
+        .map(|snapshot_id| {
+            let snapshot_id = resolve_commit_id(&repo, &snapshot_id)?;
+            paths_changed_since_snapshot(ctx, &repo, snapshot_id)
+        })
+        .transpose()?;
+
+    Ok(ChangesSince {
+        new_commits: new_commits.into_iter().map(SimpleCommit::from).collect(),
+        since_commit_found,
+        rewritten_to,
+        changed_paths,
+    })
+}
+
+/// Return the change id of the commit `id`, which is kept when the commit is rewritten.
+fn change_id(repo: &gix::Repository, id: gix::ObjectId) -> Option<String> {
+    let commit = repo.find_commit(id).ok()?;
+    let headers = but_core::Commit::from_id(commit.id()).ok()?.headers()?;
+    Some(headers.change_id)
+}
+
+/// Return the paths of the files in the worktree whose content differs from the worktree in the snapshot `snapshot_id`.
+fn paths_changed_since_snapshot(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    snapshot_id: gix::ObjectId,
+) -> anyhow::Result<Vec<String>> {
+    let snapshot_tree = repo.find_tree(ctx.snapshot_workspace_tree(snapshot_id)?)?;
+    let head = repo.head_id()?.detach();
+
+    // A file that matches `HEAD` now, and did so in the snapshot, can't have changed.
+    let (committed_changes, _) = but_core::diff::tree_changes(repo, Some(snapshot_tree.id), head)?;
+    let mut candidates = committed_changes
+        .into_iter()
+        .chain(but_core::diff::worktree_changes(repo)?.changes)
+        .map(|change| change.path)
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.dedup();
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no worktree"))?;
+    let mut changed_paths = vec![];
+    for path in candidates {
+        let in_snapshot = snapshot_tree
+            .lookup_entry_by_path(gix::path::from_bstr(path.as_bstr()))?
+            .map(|entry| entry.object_id());
+        let worktree_path = workdir.join(gix::path::from_bstr(path.as_bstr()));
+        let in_worktree = match std::fs::symlink_metadata(&worktree_path) {
+            Ok(metadata) if metadata.is_symlink() => {
+                let target =
+                    gix::path::os_string_into_bstring(std::fs::read_link(&worktree_path)?.into())?;
+                Some(gix::objs::compute_hash(
+                    repo.object_hash(),
+                    gix::object::Kind::Blob,
+                    &target,
+                )?)
+            }
+            Ok(metadata) if metadata.is_file() => Some(gix::objs::compute_hash(
+                repo.object_hash(),
+                gix::object::Kind::Blob,
+                &std::fs::read(&worktree_path)?,
+            )?),
+            _ => None,
+        };
+        if in_snapshot != in_worktree {
+            changed_paths.push(path.to_string());
+        }
+    }
+    Ok(changed_paths)
+}
+
+pub struct GetFileRenames;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetFileRenamesParameters {
+    /// The commit to detect renames from.
+    #[schemars(description = "
+    <description>
+        The id of the commit to detect renamed files from.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository.
+    </important_notes>
+    ")]
+    pub from_commit_id: String,
+    /// The commit to detect renames to.
+    #[schemars(description = "
+    <description>
+        The id of the commit to detect renamed files to.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository.
+    </important_notes>
+    ")]
+    pub to_commit_id: String,
+    /// How similar the content of two files needs to be to count as a rename.
+    #[schemars(description = "
+    <description>
+        How similar the content of a file before and after needs to be for it to count as renamed,
+        between 0.0 and 1.0.
+    </description>
+
+    <important_notes>
+        Defaults to 0.5. Use 1.0 to only detect files that were renamed without changing their content.
+    </important_notes>
+    ")]
+    pub similarity_threshold: Option<f32>,
+}
+
+impl Tool for GetFileRenames {
+    fn name(&self) -> String {
+        "get_file_renames".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the files that were renamed or copied between two commits.
+        </description>
+
+        <important_notes>
+            Use this tool to follow the history of a file across renames,
+            before looking at the changes of the file in older commits.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetFileRenamesParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetFileRenamesParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let repo = ctx.gix_repo()?;
+        let value = file_renames(&repo, params).to_json("get_file_renames");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameDetection {
+    /// The path of the file in the first commit.
+    pub old_path: String,
+    /// The path of the file in the second commit.
+    pub new_path: String,
+    /// How similar the content of both files is, from 0.0 to 1.0.
+    pub similarity: f32,
+    /// Whether the file in the first commit still exists in the second one.
+    pub is_copy: bool,
+}
+
+impl ToolResult for Result<Vec<RenameDetection>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<RenameDetection>")
+    }
+}
+
+/// The similarity files need to have to count as renamed if the caller doesn't specify one.
+const DEFAULT_RENAME_SIMILARITY: f32 = 0.5;
+
+/// Return the files renamed or copied between the commits in `params`, sorted by their new path.
+pub fn file_renames(
+    repo: &gix::Repository,
+    params: GetFileRenamesParameters,
+) -> anyhow::Result<Vec<RenameDetection>> {
+    let similarity_threshold = params
+        .similarity_threshold
+        .unwrap_or(DEFAULT_RENAME_SIMILARITY);
+    if !(0.0..=1.0).contains(&similarity_threshold) {
+        anyhow::bail!(
+            "The similarity threshold must be between 0.0 and 1.0, got {}",
+            similarity_threshold
+        );
+    }
+    let from_tree = repo
+        .find_commit(resolve_commit_id(repo, &params.from_commit_id)?)?
+        .tree()?;
+    let to_tree = repo
+        .find_commit(resolve_commit_id(repo, &params.to_commit_id)?)?
+        .tree()?;
+
+    let mut options = gix::diff::Options::default();
+    options.with_rewrites(Some(gix::diff::Rewrites {
+        copies: Some(gix::diff::rewrites::Copies {
+            source: gix::diff::rewrites::CopySource::FromSetOfModifiedFiles,
+            percentage: Some(similarity_threshold),
+        }),
+        percentage: Some(similarity_threshold),
+        ..Default::default()
+    }));
+
+    let mut renames = repo
+        .diff_tree_to_tree(Some(&from_tree), &to_tree, Some(options))?
+        .into_iter()
+        .filter_map(|change| match change {
+            gix::object::tree::diff::ChangeDetached::Rewrite {
+                source_location,
+                location,
+                entry_mode,
+                diff,
+                copy,
+                ..
+            } if !entry_mode.is_tree() => Some(RenameDetection {
+                old_path: source_location.to_string(),
+                new_path: location.to_string(),
+                // Without line stats, the content of both files is identical.
+                similarity: diff.map_or(1.0, |diff| diff.similarity),
+                is_copy: copy,
+            }),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    renames.sort_by(|a, b| a.new_path.cmp(&b.new_path));
+    Ok(renames)
+}
+
+pub struct GetFileMoveHistory;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetFileMoveHistoryParameters {
+    /// The current path of the file.
+    #[schemars(description = "
+    <description>
+        The path of the file at the tip of the stack, to get the previous paths of.
+    </description>
+
+    <important_notes>
+        The file path should be relative to the workspace root.
+    </important_notes>
+    ")]
+    pub current_path: String,
+    /// The id of the stack to look for moves in.
+    #[schemars(description = "
+    <description>
+        The id of the stack whose commits the file was moved in.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+        Only the commits of the stack are looked at, and not the ones of the target branch.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+}
+
+impl Tool for GetFileMoveHistory {
+    fn name(&self) -> String {
+        "get_file_move_history".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the renames of a file in the commits of a stack, from the oldest to the newest,
+            so the path of the file before each rename is known.
+        </description>
+
+        <important_notes>
+            Use this tool to follow the history of a file that was renamed on its branch,
+            before looking at the changes of the file in the commits before it was renamed.
+            Each rename goes from the path of the previous one, and the last one goes to the current path.
+            Renames are detected by the similarity of the content of the files, which is returned as well.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetFileMoveHistoryParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetFileMoveHistoryParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = file_move_history(ctx, params).to_json("get_file_move_history");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMoveEvent {
+    /// The path of the file before the commit.
+    pub from_path: String,
+    /// The path of the file after the commit.
+    pub to_path: String,
+    /// The commit that renamed the file.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub commit_id: gix::ObjectId,
+    /// How similar the content of the file is before and after the commit, from 0.0 to 1.0.
+    pub similarity: f32,
+}
+
+impl ToolResult for Result<Vec<FileMoveEvent>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<FileMoveEvent>")
+    }
+}
+
+/// Return the renames that led to the current path of the file in `params` in the commits of its stack,
+/// from the oldest to the newest.
+///
+/// Each commit is compared to its first parent, with renames detected at [`DEFAULT_RENAME_SIMILARITY`].
+pub fn file_move_history(
+    ctx: &CommandContext,
+    params: GetFileMoveHistoryParameters,
+) -> anyhow::Result<Vec<FileMoveEvent>> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack_in_workspace(stack_id)?;
+    let repo = ctx.gix_repo()?;
+
+    let merge_base = stack.merge_base(ctx)?;
+    let tip = stack.head_oid(&repo)?;
+    let commit_ids = tip
+        .attach(&repo)
+        .ancestors()
+        .first_parent_only()
+        .with_hidden(Some(merge_base))
+        .all()?
+        .map(|info| info.map(|info| info.id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Going from the newest commit to the oldest, each rename has to end at the path the next one started from.
+    let mut path = params.current_path;
+    let mut events = vec![];
+    for commit_id in commit_ids {
+        let commit = repo.find_commit(commit_id)?;
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parent_ids().next() {
+            Some(parent_id) => Some(parent_id.object()?.peel_to_tree()?),
+            None => None,
+        };
+        let mut options = gix::diff::Options::default();
+        options.with_rewrites(Some(gix::diff::Rewrites {
+            percentage: Some(DEFAULT_RENAME_SIMILARITY),
+            ..Default::default()
+        }));
+        let rename = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), &tree, Some(options))?
+            .into_iter()
+            .find_map(|change| match change {
+                gix::object::tree::diff::ChangeDetached::Rewrite {
+                    source_location,
+                    location,
+                    entry_mode,
+                    diff,
+                    copy: false,
+                    ..
+                } if !entry_mode.is_tree() && location == path.as_str() => Some(FileMoveEvent {
+                    from_path: source_location.to_string(),
+                    to_path: location.to_string(),
+                    commit_id,
+                    // Without line stats, the content of both files is identical.
+                    similarity: diff.map_or(1.0, |diff| diff.similarity),
+                }),
+                _ => None,
+            });
+        if let Some(rename) = rename {
+            path = rename.from_path.clone();
+            events.push(rename);
+        }
+    }
+    events.reverse();
+    Ok(events)
+}
+
+pub struct GetStackCommitMap;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStackCommitMapParameters {
+    /// The id of the stack to map the commits of.
+    #[schemars(description = "
+    <description>
+        The id of the stack to map the commits of.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+}
+
+impl Tool for GetStackCommitMap {
+    fn name(&self) -> String {
+        "get_stack_commit_map".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the ids of the commits of a stack, keyed by '<branch name>/<position>',
+            where the head commit of a branch is at position 0, its parent at position 1, and so on.
+        </description>
+
+        <important_notes>
+            Use this tool to record the commits of a stack before changing it, and to find the new ids
+            of the same commits afterwards, as rebasing or amending changes their ids but not their positions.
+            Commits that only exist on the remote branch aren't included.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetStackCommitMapParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetStackCommitMapParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = stack_commit_map(ctx, params).to_json("get_stack_commit_map");
+        Ok(value)
+    }
+}
+
+impl ToolResult for Result<BTreeMap<String, String>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "BTreeMap<String, String>")
+    }
+}
+
+/// Return the ids of the commits of the stack in `params`, keyed by the name of their branch and their position in it.
+pub fn stack_commit_map(
+    ctx: &CommandContext,
+    params: GetStackCommitMapParameters,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let repo = ctx.gix_repo()?;
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack_in_workspace(stack_id)?;
+
+    let mut commit_map = BTreeMap::new();
+    for branch in simple_branches(ctx, &repo, &stack)? {
+        for commit in branch.commits {
+            let Some(position) = commit.position else {
+                continue;
+            };
+            commit_map.insert(
+                format!("{}/{}", branch.name, position),
+                commit.id.to_string(),
+            );
+        }
+    }
+    Ok(commit_map)
+}
+
+pub struct GetAffectedTests;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAffectedTestsParameters {}
+
+impl Tool for GetAffectedTests {
+    fn name(&self) -> String {
+        "get_affected_tests".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the test files that likely test each of the files with uncommitted changes.
+        </description>
+
+        <important_notes>
+            Test files are matched by their name and location, following the conventions of common languages,
+            like 'foo_test.go' or 'tests/foo.rs' for 'foo.rs', 'foo.test.ts' or '__tests__/foo.ts' for 'foo.ts',
+            'test_foo.py' for 'foo.py' and 'FooTest.java' for 'Foo.java'.
+            This is a best-effort guess, and not an analysis of which tests depend on the changed code.
+            Changed files that are tests themselves are listed as their own test.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetAffectedTestsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let repo = ctx.gix_repo()?;
+        let value = affected_tests(&repo).to_json("get_affected_tests");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AffectedTests {
+    /// The path of the changed file.
+    pub path: String,
+    /// The paths of the test files that likely test the changed file.
+    pub test_paths: Vec<String>,
+}
+
+impl ToolResult for Result<Vec<AffectedTests>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<AffectedTests>")
+    }
+}
+
+/// Return the test files that likely test each of the files with uncommitted changes, by the path of the changed file.
+///
+/// Test files are found among the files in the index and the new files in the worktree.
+pub fn affected_tests(repo: &gix::Repository) -> anyhow::Result<Vec<AffectedTests>> {
+    let changed_paths = but_core::diff::worktree_changes(repo)?
+        .changes
+        .into_iter()
+        .map(|change| change.path.to_string())
+        .collect::<std::collections::BTreeSet<_>>();
+    let index = repo.index_or_empty()?;
+    let paths = index
+        .entries()
+        .iter()
+        .map(|entry| entry.path(&index).to_string())
+        .chain(changed_paths.iter().cloned())
+        .collect::<std::collections::BTreeSet<_>>();
+    let tests = paths
+        .iter()
+        .filter_map(|path| Some((path, test_subject(path)?)))
+        .collect::<Vec<_>>();
+
+    Ok(changed_paths
+        .into_iter()
+        .map(|path| {
+            let subject = source_subject(&path);
+            let test_paths = tests
+                .iter()
+                .filter(|(_, test_subject)| *test_subject == subject)
+                .map(|(test_path, _)| test_path.to_string())
+                .collect();
+            AffectedTests { path, test_paths }
+        })
+        .collect())
+}
+
+/// What a source or test file is about: the name of the file without test markers, along with its language.
+#[derive(Debug, PartialEq, Eq)]
+struct FileSubject<'a> {
+    name: &'a str,
+    language: &'a str,
+}
+
+/// Return the subject of the file at `path`, which is its name, or the name of its directory for module files like `mod.rs`.
+fn source_subject(path: &str) -> FileSubject<'_> {
+    let (directories, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+    let (name, extension) = file_name.split_once('.').unwrap_or((file_name, ""));
+    let name = match name {
+        "mod" | "lib" | "main" | "index" | "__init__" => directories
+            .rsplit('/')
+            .next()
+            .filter(|directory| !directory.is_empty())
+            .unwrap_or(name),
+        _ => name,
+    };
+    FileSubject {
+        name,
+        language: language_of(extension),
+    }
+}
+
+/// Return the subject of the file at `path` if it's a test file, with the test markers removed from its name.
+fn test_subject(path: &str) -> Option<FileSubject<'_>> {
+    let (directories, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+    let (name, extension) = file_name.split_once('.').unwrap_or((file_name, ""));
+    let language = language_of(extension);
+    let in_test_directory = directories
+        .split('/')
+        .any(|directory| matches!(directory, "test" | "tests" | "__tests__" | "spec"));
+
+    let name = if let Some(name) = name
+        .strip_suffix("_test")
+        .or_else(|| name.strip_suffix("_tests"))
+        .or_else(|| name.strip_prefix("test_"))
+    {
+        name
+    } else if let Some(name) = name
+        .strip_suffix("Test")
+        .or_else(|| name.strip_suffix("Tests"))
+        .filter(|name| !name.is_empty())
+    {
+        name
+    } else if extension.starts_with("test.") || extension.starts_with("spec.") {
+        name
+    } else if in_test_directory {
+        source_subject(path).name
+    } else {
+        return None;
+    };
+    Some(FileSubject { name, language })
+}
+
+/// Return the language of files with `extension`, which may include the inner extensions of a file name like `test.ts`.
+///
+/// Languages with interchangeable files, like JavaScript and TypeScript, share a name.
+fn language_of(extension: &str) -> &str {
+    let extension = extension.rsplit('.').next().unwrap_or(extension);
+    match extension {
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" | "svelte" | "vue" => {
+            "javascript"
+        }
+        "h" | "c" | "cc" | "cpp" | "cxx" | "hpp" => "c",
+        _ => extension,
+    }
+}
+
+pub struct GetBranchRebasePreview;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBranchRebasePreviewParameters {
+    /// The id of the stack to rebase.
+    #[schemars(description = "
+    <description>
+        The id of the stack to preview the rebase of.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The commit to rebase the stack onto.
+    #[schemars(description = "
+    <description>
+        The id of the commit to rebase the commits of the stack onto.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository, like the latest commit of the target branch.
+    </important_notes>
+    ")]
+    pub onto_oid: String,
+}
+
+impl Tool for GetBranchRebasePreview {
+    fn name(&self) -> String {
+        "get_branch_rebase_preview".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Preview rebasing the commits of a stack onto another commit, without changing anything.
+        </description>
+
+        <important_notes>
+            Use this tool to find out whether a rebase would conflict before performing it.
+            The commits are listed in the order they are rebased in, the oldest one first,
+            along with the id they would have after the rebase unless they stay unchanged.
+            The new commit ids aren't written to the repository, so they can't be used in other tools.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetBranchRebasePreviewParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetBranchRebasePreviewParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = branch_rebase_preview(ctx, params).to_json("get_branch_rebase_preview");
+        Ok(value)
+    }
+}
+
+/// What would happen to a commit when rebasing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RebaseStatus {
+    /// The commit would stay as it is, as its parent doesn't change.
+    Unchanged,
+    /// The commit would be rewritten without conflicts.
+    Rewritten,
+    /// The commit would be rewritten, and have conflicts.
+    Conflicted,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebaseCommitPreview {
+    /// The id of the commit before the rebase.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub original_oid: gix::ObjectId,
+    /// The id the commit would have after the rebase, if it's rewritten.
+    #[serde(with = "gitbutler_serde::object_id_opt")]
+    pub new_oid: Option<gix::ObjectId>,
+    /// What would happen to the commit.
+    pub status: RebaseStatus,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebasePreview {
+    /// The commits of the stack, in the order they would be rebased in, the oldest one first.
+    pub commits_to_rewrite: Vec<RebaseCommitPreview>,
+    /// Whether any of the commits would have conflicts.
+    pub has_conflicts: bool,
+    /// The sorted paths of the files that would conflict in any of the commits.
+    pub conflict_paths: Vec<String>,
+}
+
+impl ToolResult for Result<RebasePreview, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "RebasePreview")
+    }
+}
+
+/// Rebase the stack in `params` onto its commit in memory, and return what would happen to each of its commits.
+///
+/// All objects are written into memory only, and no reference is touched.
+pub fn branch_rebase_preview(
+    ctx: &mut CommandContext,
+    params: GetBranchRebasePreviewParameters,
+) -> anyhow::Result<RebasePreview> {
+    let repo = ctx.gix_repo_for_merging_non_persisting()?;
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let onto = resolve_commit_id(&repo, &params.onto_oid)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack_in_workspace(stack_id)?;
+
+    let mut rebase = but_rebase::Rebase::new(&repo, onto, None)?;
+    rebase.steps(stack.as_rebase_steps(ctx, &repo)?)?;
+    rebase.rebase_noops(false);
+    let output = rebase.rebase()?;
+
+    let mut commits_to_rewrite = vec![];
+    let mut conflict_paths = vec![];
+    for (_, old, new) in output.commit_mapping {
+        // The mapping is repeated for each branch that points to a commit.
+        if commits_to_rewrite
+            .last()
+            .is_some_and(|commit: &RebaseCommitPreview| commit.original_oid == old)
+        {
+            continue;
+        }
+        let status = if old == new {
+            RebaseStatus::Unchanged
+        } else if let Some(paths) = conflicting_paths(&repo, new)? {
+            conflict_paths.extend(paths);
+            RebaseStatus::Conflicted
+        } else {
+            RebaseStatus::Rewritten
+        };
+        commits_to_rewrite.push(RebaseCommitPreview {
+            original_oid: old,
+            new_oid: (old != new).then_some(new),
+            status,
+        });
+    }
+    conflict_paths.sort();
+    conflict_paths.dedup();
+
+    Ok(RebasePreview {
+        has_conflicts: commits_to_rewrite
+            .iter()
+            .any(|commit| commit.status == RebaseStatus::Conflicted),
+        commits_to_rewrite,
+        conflict_paths,
+    })
+}
+
+pub struct GetCommitDiffStat;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCommitDiffStatParameters {
+    /// The commit id to get the diffstat of.
+    #[schemars(description = "
+    <description>
+        The id of the commit to get the number of changed lines of.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+}
+
+impl Tool for GetCommitDiffStat {
+    fn name(&self) -> String {
+        "get_commit_diff_stat".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the number of added and removed lines of each file changed in a commit, like 'git diff --stat'.
+        </description>
+
+        <important_notes>
+            Use this tool to summarize a commit without looking at its diff.
+            Use the get_commit_details tool instead if the changes themselves are needed.
+            Lines aren't counted for binary files.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetCommitDiffStatParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetCommitDiffStatParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let repo = ctx.gix_repo()?;
+        let value = commit_diff_stat(&repo, params).to_json("get_commit_diff_stat");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffStatLine {
+    /// The path of the changed file.
+    pub path: String,
+    /// The number of added lines.
+    pub additions: u32,
+    /// The number of removed lines.
+    pub deletions: u32,
+    /// Whether the file is binary, so its lines aren't counted.
+    pub is_binary: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffStatSummary {
+    /// The number of changed files.
+    pub total_files: usize,
+    /// The number of added lines in all files.
+    pub total_additions: u64,
+    /// The number of removed lines in all files.
+    pub total_deletions: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitDiffStat {
+    /// The changed files, ordered by their path.
+    pub files: Vec<DiffStatLine>,
+    /// The totals of all changed files.
+    pub summary: DiffStatSummary,
+}
+
+impl ToolResult for Result<CommitDiffStat, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "CommitDiffStat")
+    }
+}
+
+/// Count the added and removed lines of each file changed by the commit in `params` compared to its first parent.
+///
+/// Only the number of lines is computed, without rendering the diffs.
+pub fn commit_diff_stat(
+    repo: &gix::Repository,
+    params: GetCommitDiffStatParameters,
+) -> anyhow::Result<CommitDiffStat> {
+    let commit = repo.find_commit(resolve_commit_id(repo, &params.commit_id)?)?;
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent_ids().next() {
+        Some(parent_id) => Some(parent_id.object()?.peel_to_tree()?),
+        None => None,
+    };
+
+    let mut resource_cache = repo.diff_resource_cache_for_tree_diff()?;
+    let mut files = vec![];
+    for change in repo.diff_tree_to_tree(parent_tree.as_ref(), &tree, None)? {
+        if change.entry_mode().is_tree() {
+            continue;
+        }
+        let change = change.attach(repo, repo);
+        resource_cache.clear_resource_cache_keep_allocation();
+        let counts = change
+            .diff(&mut resource_cache)
+            .ok()
+            .and_then(|mut platform| platform.line_counts().ok())
+            .flatten();
+        files.push(DiffStatLine {
+            path: change.location().to_string(),
+            additions: counts.as_ref().map_or(0, |counts| counts.insertions),
+            deletions: counts.as_ref().map_or(0, |counts| counts.removals),
+            is_binary: counts.is_none(),
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let summary = DiffStatSummary {
+        total_files: files.len(),
+        total_additions: files.iter().map(|file| u64::from(file.additions)).sum(),
+        total_deletions: files.iter().map(|file| u64::from(file.deletions)).sum(),
+    };
+    Ok(CommitDiffStat { files, summary })
+}
+
+pub struct PreviewIntegration;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewIntegrationParameters {}
+
+impl Tool for PreviewIntegration {
+    fn name(&self) -> String {
+        "preview_integration".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Preview integrating the latest changes of the target branch into the workspace, without changing anything.
+        </description>
+
+        <important_notes>
+            Use this tool before updating the workspace to the target branch, to find out what the update would do.
+            For each stack, it reports whether it can be rebased cleanly onto the latest commit of the target branch,
+            which of its commits would conflict on which paths, and which of its branches were integrated
+            into the target branch and could be removed.
+            The target branch isn't fetched, so its latest known commit is used.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(PreviewIntegrationParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(2_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = preview_integration(ctx).to_json("preview_integration");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictingCommit {
+    /// The id of the commit that would conflict.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub commit_id: gix::ObjectId,
+    /// The sorted paths of the files that would conflict in the commit.
+    pub conflict_paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackIntegrationPreview {
+    /// The id of the stack.
+    pub stack_id: StackId,
+    /// Whether all commits of the stack can be rebased onto the new base without conflicts.
+    pub rebases_cleanly: bool,
+    /// The commits that would conflict, the oldest one first.
+    pub conflicting_commits: Vec<ConflictingCommit>,
+    /// The names of the branches whose commits were all integrated into the target branch,
+    /// so they could be removed.
+    pub integrated_branches: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrationPreview {
+    /// The id of the commit the workspace is currently based on.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub base_id: gix::ObjectId,
+    /// The id of the latest commit of the target branch, which the workspace would be based on.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub new_base_id: gix::ObjectId,
+    /// Whether the workspace is already based on the latest commit of the target branch.
+    pub up_to_date: bool,
+    /// What would happen to each stack in the workspace, in the order of the workspace.
+    pub stacks: Vec<StackIntegrationPreview>,
+}
+
+impl ToolResult for Result<IntegrationPreview, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "IntegrationPreview")
+    }
+}
+
+/// Rebase all stacks of the workspace onto the latest commit of the target branch in memory,
+/// and return what would happen to each of them.
+///
+/// All objects are written into memory only, and no reference is touched.
+pub fn preview_integration(ctx: &mut CommandContext) -> anyhow::Result<IntegrationPreview> {
+    let repo = ctx.gix_repo_for_merging_non_persisting()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let target = vb_state.get_default_target()?;
+    let base_id = target.sha.to_gix();
+    let new_base_id = repo
+        .find_reference(&target.branch.to_string())?
+        .peel_to_commit()?
+        .id;
+
+    let mut stacks = vb_state.list_stacks_in_workspace()?;
+    stacks.sort_by_key(|stack| (stack.order, stack.id));
+    let mut previews = Vec::with_capacity(stacks.len());
+    for stack in stacks {
+        let mut integrated_branches = vec![];
+        for branch in stack.branches().iter().filter(|b| !b.archived) {
+            if is_branch_integrated(ctx, &repo, &stack, branch)? {
+                integrated_branches.push(branch.name().to_owned());
+            }
+        }
+
+        let mut rebase = but_rebase::Rebase::new(&repo, new_base_id, None)?;
+        rebase.steps(stack.as_rebase_steps(ctx, &repo)?)?;
+        rebase.rebase_noops(false);
+        let output = rebase.rebase()?;
+
+        let mut conflicting_commits: Vec<ConflictingCommit> = vec![];
+        for (_, old, new) in output.commit_mapping {
+            // The mapping is repeated for each branch that points to a commit.
+            if old == new
+                || conflicting_commits
+                    .last()
+                    .is_some_and(|commit| commit.commit_id == old)
+            {
+                continue;
+            }
+            if let Some(mut conflict_paths) = conflicting_paths(&repo, new)? {
+                conflict_paths.sort();
+                conflicting_commits.push(ConflictingCommit {
+                    commit_id: old,
+                    conflict_paths,
+                });
+            }
+        }
+
+        previews.push(StackIntegrationPreview {
+            stack_id: stack.id,
+            rebases_cleanly: conflicting_commits.is_empty(),
+            conflicting_commits,
+            integrated_branches,
+        });
+    }
+
+    Ok(IntegrationPreview {
+        base_id,
+        new_base_id,
+        up_to_date: base_id == new_base_id,
+        stacks: previews,
+    })
+}
+
+pub struct GetCommitSignatureStatus;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCommitSignatureStatusParameters {
+    /// The commit id to get the signature status of.
+    #[schemars(description = "
+    <description>
+        The id of the commit to check the signature of.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+}
+
+impl Tool for GetCommitSignatureStatus {
+    fn name(&self) -> String {
+        "get_commit_signature_status".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get whether a commit is signed, with which kind of signature, and whether the signature can be verified.
+        </description>
+
+        <important_notes>
+            Use this tool to find unsigned or unverified commits, for instance on branches that require signed commits.
+            GPG signatures are verified against the keys known to GPG, and SSH signatures against the allowed signers
+            configured in 'gpg.ssh.allowedSignersFile'.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetCommitSignatureStatusParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetCommitSignatureStatusParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let repo = ctx.gix_repo()?;
+        let value = commit_signature_status(&repo, params).to_json("get_commit_signature_status");
+        Ok(value)
+    }
+}
+
+/// The kind of signature of a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureType {
+    Gpg,
+    Ssh,
+    X509,
+    /// The signature has a format that isn't known.
+    Unknown,
+}
+
+/// The outcome of verifying the signature of a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureVerification {
+    /// The signature matches the commit, and was made with a known key.
+    Verified,
+    /// The signature doesn't match the commit, or can't be read.
+    Invalid,
+    /// The key the commit was signed with isn't known, so the signature can't be verified.
+    UnknownKey,
+    /// The signature can't be verified, as the program to do so isn't available, or the type of signature isn't supported.
+    Unverifiable,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitSignatureStatus {
+    /// The id of the commit.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub commit_id: gix::ObjectId,
+    /// Whether the commit has a signature.
+    pub is_signed: bool,
+    /// The kind of signature, if the commit is signed.
+    pub signature_type: Option<SignatureType>,
+    /// The outcome of verifying the signature, if the commit is signed.
+    pub verification: Option<SignatureVerification>,
+    /// The principal or user id that made the signature, if it could be verified.
+    pub signer: Option<String>,
+}
+
+impl ToolResult for Result<CommitSignatureStatus, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "CommitSignatureStatus")
+    }
+}
+
+/// Find the signature of the commit in `params` and verify it, using the programs configured in `repo` like Git would.
+pub fn commit_signature_status(
+    repo: &gix::Repository,
+    params: GetCommitSignatureStatusParameters,
+) -> anyhow::Result<CommitSignatureStatus> {
+    let commit_id = resolve_commit_id(repo, &params.commit_id)?;
+    let mut commit = repo.find_commit(commit_id)?.decode()?.to_owned();
+    let Some(position) = commit
+        .extra_headers()
+        .find_pos(gix::objs::commit::SIGNATURE_FIELD_NAME)
+    else {
+        return Ok(CommitSignatureStatus {
+            commit_id,
+            is_signed: false,
+            signature_type: None,
+            verification: None,
+            signer: None,
+        });
+    };
+    // The signature was made over the commit without its signature.
+    let (_, signature) = commit.extra_headers.remove(position);
+    let mut signed_data = Vec::new();
+    commit.write_to(&mut signed_data)?;
+
+    let signature_type = signature_type(signature.as_bstr());
+    let (verification, signer) = match signature_type {
+        SignatureType::Gpg => verify_gpg_signature(repo, &signature, &signed_data)?,
+        SignatureType::Ssh => verify_ssh_signature(repo, &signature, &signed_data)?,
+        SignatureType::X509 | SignatureType::Unknown => (SignatureVerification::Unverifiable, None),
+    };
+    Ok(CommitSignatureStatus {
+        commit_id,
+        is_signed: true,
+        signature_type: Some(signature_type),
+        verification: Some(verification),
+        signer,
+    })
+}
+
+fn signature_type(signature: &BStr) -> SignatureType {
+    let signature = signature.trim_start();
+    if signature.starts_with(b"-----BEGIN PGP SIGNATURE-----") {
+        SignatureType::Gpg
+    } else if signature.starts_with(b"-----BEGIN SSH SIGNATURE-----") {
+        SignatureType::Ssh
+    } else if signature.starts_with(b"-----BEGIN SIGNED MESSAGE-----") {
+        SignatureType::X509
+    } else {
+        SignatureType::Unknown
+    }
+}
+
+/// Verify the GPG `signature` of `signed_data` with `gpg.program`, and return the outcome along with the user id of the signer.
+fn verify_gpg_signature(
+    repo: &gix::Repository,
+    signature: &[u8],
+    signed_data: &[u8],
+) -> anyhow::Result<(SignatureVerification, Option<String>)> {
+    let program = configured_program(repo, "gpg.program", "gpg");
+    let mut signature_file = tempfile::NamedTempFile::new()?;
+    signature_file.write_all(signature)?;
+    let Ok(output) = run_verification(
+        prepare_with_shell_on_windows(program)
+            .args(["--status-fd=1", "--verify"])
+            .arg(signature_file.path())
+            .arg("-"),
+        Some(signed_data),
+    ) else {
+        return Ok((SignatureVerification::Unverifiable, None));
+    };
+
+    let mut outcome = (SignatureVerification::Invalid, None);
+    for status in output
+        .stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix(b"[GNUPG:] "))
+    {
+        if status.starts_with(b"BADSIG ") {
+            return Ok((SignatureVerification::Invalid, None));
+        } else if let Some(key_and_user) = status.strip_prefix(b"GOODSIG ") {
+            let user = key_and_user
+                .split_once_str(" ")
+                .map(|(_, user)| user.to_str_lossy().into_owned());
+            outcome = (SignatureVerification::Verified, user);
+        } else if status.starts_with(b"NO_PUBKEY ") || status.starts_with(b"ERRSIG ") {
+            outcome = (SignatureVerification::UnknownKey, None);
+        }
+    }
+    Ok(outcome)
+}
+
+/// Verify the SSH `signature` of `signed_data` against the signers in `gpg.ssh.allowedSignersFile`,
+/// and return the outcome along with the principal that made it.
+fn verify_ssh_signature(
+    repo: &gix::Repository,
+    signature: &[u8],
+    signed_data: &[u8],
+) -> anyhow::Result<(SignatureVerification, Option<String>)> {
+    let Some(allowed_signers) = repo
+        .config_snapshot()
+        .trusted_path("gpg.ssh.allowedSignersFile")
+        .transpose()?
+        .map(Cow::into_owned)
+    else {
+        // Without allowed signers, no key is known.
+        return Ok((SignatureVerification::UnknownKey, None));
+    };
+    let program = configured_program(repo, "gpg.ssh.program", "ssh-keygen");
+    let mut signature_file = tempfile::NamedTempFile::new()?;
+    signature_file.write_all(signature)?;
+
+    let Ok(output) = run_verification(
+        prepare_with_shell_on_windows(program.clone())
+            .args(["-Y", "find-principals", "-f"])
+            .arg(&allowed_signers)
+            .arg("-s")
+            .arg(signature_file.path()),
+        None,
+    ) else {
+        return Ok((SignatureVerification::Unverifiable, None));
+    };
+    let principal = output
+        .stdout
+        .lines()
+        .next()
+        .map(|line| line.trim().to_str_lossy().into_owned())
+        .filter(|principal| !principal.is_empty());
+    let Some(principal) = principal.filter(|_| output.status.success()) else {
+        return Ok((SignatureVerification::UnknownKey, None));
+    };
+
+    let Ok(output) = run_verification(
+        prepare_with_shell_on_windows(program)
+            .args(["-Y", "verify", "-n", "git", "-f"])
+            .arg(&allowed_signers)
+            .arg("-I")
+            .arg(&principal)
+            .arg("-s")
+            .arg(signature_file.path()),
+        Some(signed_data),
+    ) else {
+        return Ok((SignatureVerification::Unverifiable, None));
+    };
+    Ok(if output.status.success() {
+        (SignatureVerification::Verified, Some(principal))
+    } else {
+        (SignatureVerification::Invalid, None)
+    })
+}
+
+/// Return the program configured in `key`, or `default`.
+fn configured_program(repo: &gix::Repository, key: &str, default: &str) -> std::path::PathBuf {
+    repo.config_snapshot()
+        .trusted_program(key)
+        .filter(|program| !program.is_empty())
+        .map_or_else(|| default.into(), |program| program.into_owned().into())
+}
+
+/// Run `command`, passing `stdin` to it if set, and return its output.
+fn run_verification(
+    command: gix::command::Prepare,
+    stdin: Option<&[u8]>,
+) -> std::io::Result<std::process::Output> {
+    let mut command: std::process::Command = command.into();
+    command
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+    let stdin = match stdin.map(|data| child.stdin.take().map(|pipe| (pipe, data))) {
+        Some(None) => {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(std::io::Error::other(
+                "The standard input of the verification program isn't piped",
+            ));
+        }
+        stdin => stdin.flatten(),
+    };
+    // Write the input from its own thread while the output is read, as the program may not read all of
+    // its input before its output fills up the pipes.
+    std::thread::scope(|scope| {
+        let writer = stdin.map(|(mut pipe, data)| scope.spawn(move || pipe.write_all(data)));
+        let output = child.wait_with_output()?;
+        match writer.map(|writer| writer.join()) {
+            Some(Ok(Err(err))) if err.kind() != std::io::ErrorKind::BrokenPipe => Err(err),
+            Some(Err(_)) => Err(std::io::Error::other(
+                "Writing to the verification program panicked",
+            )),
+            _ => Ok(output),
+        }
+    })
+}
+
+pub struct GetStagedCommitPreview;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStagedCommitPreviewParameters {
+    /// The name of the branch to preview the next commit of.
+    #[schemars(description = "
+    <description>
+        The name of the branch to preview the next commit of.
+    </description>
+
+    <important_notes>
+        The branch has to exist in the workspace.
+        If the project status marks the name of the branch as ambiguous, use its qualified name instead.
+    </important_notes>
+    ")]
+    pub branch_name: String,
+}
+
+impl Tool for GetStagedCommitPreview {
+    fn name(&self) -> String {
+        "get_staged_commit_preview".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Preview which file changes are assigned to the stack of a branch, and would make up its next commit.
+        </description>
+
+        <important_notes>
+            Use this tool to verify which files and hunks are assigned to a branch before committing to it.
+            Only the hunks assigned to the stack of the branch are listed.
+            The number of hunks that aren't assigned to any stack is reported as well, as they may need to be assigned first.
+            Nothing is changed by this tool.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetStagedCommitPreviewParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetStagedCommitPreviewParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = staged_commit_preview(ctx, &options.assignments_cache, params)
+            .to_json("get_staged_commit_preview");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StagedPreview {
+    /// The changed files with hunks assigned to the stack of the branch, along with only these hunks.
+    pub files: Vec<FileChange>,
+    /// The number of hunks in `files`.
+    pub total_hunks: usize,
+    /// The number of hunks in the worktree that aren't assigned to any stack.
+    pub unassigned_hunks_count: usize,
+}
+
+impl ToolResult for Result<StagedPreview, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "StagedPreview")
+    }
+}
+
+/// Return the worktree changes that are assigned to the stack of the branch in `params`.
+pub fn staged_commit_preview(
+    ctx: &mut CommandContext,
+    assignments_cache: &AssignmentsCache,
+    params: GetStagedCommitPreviewParameters,
+) -> anyhow::Result<StagedPreview> {
+    let repo = ctx.gix_repo()?;
+    let (stack_id, _) = find_branch(ctx, &repo, &params.branch_name)?
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' not found", params.branch_name))?;
+
+    let worktree = but_core::diff::worktree_changes(&repo)?;
+    let (diff, _) =
+        unified_diff_for_changes(&repo, worktree.changes, ctx.app_settings().context_lines)?;
+    let assignments = cached_assignments(ctx, &repo, &diff, assignments_cache)?.assignments;
+    let file_changes = get_file_changes(&diff, assignments)?;
+
+    let unassigned_hunks_count = file_changes
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .filter(|hunk| hunk.assigned_to_stack.is_none())
+        .count();
+    let mut files = file_changes
+        .into_iter()
+        .filter_map(|mut file| {
+            file.hunks
+                .retain(|hunk| hunk.assigned_to_stack == Some(stack_id));
+            (!file.hunks.is_empty()).then_some(file)
+        })
+        .collect::<Vec<_>>();
+    let total_hunks = files.iter().map(|file| file.hunks.len()).sum();
+    apply_diff_attributes(&repo, None, &mut files)?;
+    Ok(StagedPreview {
+        total_hunks,
+        files,
+        unassigned_hunks_count,
+    })
+}
+
+pub struct GetTargetBranchCommits;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTargetBranchCommitsParameters {
+    /// The maximum number of commits to list.
+    #[schemars(description = "
+    <description>
+        The maximum number of commits of the target branch to list.
+    </description>
+
+    <important_notes>
+        Leave this empty to list the last 20 commits.
+    </important_notes>
+    ")]
+    pub limit: Option<usize>,
+    /// The commit to stop at.
+    #[schemars(description = "
+    <description>
+        The id of a commit of the target branch to stop at.
+        Only the commits that were added to the target branch after it are listed.
+    </description>
+
+    <important_notes>
+        Use this to list what landed on the target branch since a known commit, like the base of the workspace.
+        The limit still applies.
+    </important_notes>
+    ")]
+    pub since_oid: Option<String>,
+}
+
+/// The number of commits listed by [`GetTargetBranchCommits`] if no limit is given.
+const DEFAULT_TARGET_BRANCH_COMMITS_LIMIT: usize = 20;
+
+impl Tool for GetTargetBranchCommits {
+    fn name(&self) -> String {
+        "get_target_branch_commits".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the most recent commits of the target branch, the most recent one first.
+        </description>
+
+        <important_notes>
+            Use this tool to find out what landed on the target branch, for instance when looking for the cause of a regression.
+            The target branch isn't fetched, so its latest known commit is where the listing starts.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetTargetBranchCommitsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetTargetBranchCommitsParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = target_branch_commits(ctx, params).to_json("get_target_branch_commits");
+        Ok(value)
+    }
+}
+
+/// Walk the commits of the target branch from its latest known commit, the most recent one first,
+/// until the commit in `params` or the limit is reached.
+///
+/// This doesn't need access to the worktree, so no lock is acquired.
+pub fn target_branch_commits(
+    ctx: &CommandContext,
+    params: GetTargetBranchCommitsParameters,
+) -> anyhow::Result<Vec<SimpleCommit>> {
+    let repo = ctx.gix_repo()?;
+    let target = VirtualBranchesHandle::new(ctx.project().gb_dir()).get_default_target()?;
+    let head = repo
+        .find_reference(&target.branch.to_string())?
+        .peel_to_commit()?
+        .id;
+    let since = params
+        .since_oid
+        .as_deref()
+        .map(|since| resolve_commit_id(&repo, since))
+        .transpose()?;
+
+    let walk = head.attach(&repo).ancestors().with_hidden(since).all()?;
+    let mut commits = vec![];
+    for info in walk.take(params.limit.unwrap_or(DEFAULT_TARGET_BRANCH_COMMITS_LIMIT)) {
+        let commit = info?.object()?;
+        let message = commit.message_raw_sloppy().to_str_lossy();
+        commits.push(SimpleCommit::from_message(commit.id, &message));
+    }
+    Ok(commits)
+}
+
+pub struct RewordCommits;
+
+/// The new message of a commit.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitMessageUpdate {
+    /// The id of the commit to reword.
+    #[schemars(description = "
+    <description>
+        The id of the commit to reword.
+    </description>
+
+    <important_notes>
+        The commit has to be part of the stack.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+    /// The new commit title.
+    #[schemars(description = "
+    <description>
+        The new commit message title.
+        This is only a short summary of the commit.
+    </description>
+
+    <important_notes>
+        Don't exceed 50 characters in length.
+    </important_notes>
+    ")]
+    pub message_title: String,
+    /// The new commit description.
+    #[schemars(description = "
+    <description>
+        The new commit message body.
+        This is a more detailed description of the changes made in the commit.
+    </description>
+    ")]
+    pub message_body: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RewordCommitsParameters {
+    /// The id of the stack containing the commits.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the commits to reword.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The commits to reword, along with their new messages.
+    #[schemars(description = "
+    <description>
+        The commits to reword, each with its new message title and body.
+    </description>
+
+    <important_notes>
+        All commits have to be part of the stack, otherwise no commit is reworded.
+    </important_notes>
+    ")]
+    pub commits: Vec<CommitMessageUpdate>,
+}
+
+impl Tool for RewordCommits {
+    fn name(&self) -> String {
+        "reword_commits".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Change the messages of several commits of a stack at once.
+        </description>
+
+        <important_notes>
+            Use this tool instead of amending commits one by one to reword them, as all commits are rewritten in a single rebase.
+            Rewording a commit changes its id, and the ids of all commits above it.
+            The ids of all rebased commits are returned, mapped from their previous ids.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(RewordCommitsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: RewordCommitsParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = reword_commits(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .to_json("reword_commits");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewrittenCommit {
+    /// The id of the commit before the rebase.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub old_id: gix::ObjectId,
+    /// The id of the commit after the rebase.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub new_id: gix::ObjectId,
+}
+
+impl ToolResult for Result<Vec<RewrittenCommit>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<RewrittenCommit>")
+    }
+}
+
+/// Set the messages of all commits in `params` in a single rebase of their stack,
+/// and return the new ids of all commits of the stack, from the bottom-most one.
+///
+/// Nothing is changed unless all commits are part of the stack, and all messages are valid.
+pub fn reword_commits(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: RewordCommitsParameters,
+) -> anyhow::Result<Vec<RewrittenCommit>> {
+    if params.commits.is_empty() {
+        anyhow::bail!("No commits to reword were given");
+    }
+    let repo = ctx.gix_repo()?;
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let mut messages = Vec::with_capacity(params.commits.len());
+    for update in &params.commits {
+        let commit_id = resolve_commit_id(&repo, &update.commit_id)?;
+        let message = sanitizer.commit_message(&update.message_title, &update.message_body)?;
+        messages.push((commit_id, message));
+    }
+    set_commit_messages(ctx, app_handle, message_id, stack_id, messages)
+}
+
+/// Rewrite the commits of the stack `stack_id` to have the new `messages`, given by commit, and rebase the commits above them.
+fn set_commit_messages(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    stack_id: StackId,
+    messages: Vec<(gix::ObjectId, String)>,
+) -> anyhow::Result<Vec<RewrittenCommit>> {
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stack = vb_state.get_stack_in_workspace(stack_id)?;
+
+    let mut steps = stack.as_rebase_steps(ctx, &repo)?;
+    for (commit_id, _) in &messages {
+        let is_in_stack = steps.iter().any(
+            |step| matches!(step, but_rebase::RebaseStep::Pick { commit_id: id, .. } if id == commit_id),
+        );
+        if !is_in_stack {
+            anyhow::bail!("Commit '{}' is not part of stack {}", commit_id, stack_id);
+        }
+    }
+    for step in &mut steps {
+        if let but_rebase::RebaseStep::Pick {
+            commit_id,
+            new_message,
+        } = step
+        {
+            if let Some((_, message)) = messages.iter().find(|(id, _)| id == commit_id) {
+                *new_message = Some(message.as_str().into());
+            }
+        }
+    }
+
+    let commit_mapping = {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let _ = ctx.create_snapshot(
+            SnapshotDetails::new(OperationKind::UpdateCommitMessage),
+            guard.write_permission(),
+        );
+        let mut rebase = but_rebase::Rebase::new(&repo, stack.merge_base(ctx)?, None)?;
+        rebase.steps(steps)?;
+        rebase.rebase_noops(false);
+        let output = rebase.rebase()?;
+        stack.set_heads_from_rebase_output(ctx, output.references)?;
+        gitbutler_branch_actions::update_workspace_commit(&vb_state, ctx)?;
+        output.commit_mapping
+    };
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
+    }
+
+    let mut rewritten: Vec<RewrittenCommit> = vec![];
+    for (_, old_id, new_id) in commit_mapping {
+        // The mapping is repeated for each branch that points to a commit.
+        if rewritten
+            .last()
+            .is_some_and(|commit| commit.old_id == old_id)
+        {
+            continue;
+        }
+        rewritten.push(RewrittenCommit { old_id, new_id });
+    }
+    Ok(rewritten)
+}
+
+pub struct CopyCommitToStack;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyCommitToStackParameters {
+    /// The id of the commit to copy.
+    #[schemars(description = "
+    <description>
+        The id of the commit to copy.
+    </description>
+
+    <important_notes>
+        The commit stays where it is, only a copy of it is added to the destination branch.
+    </important_notes>
+    ")]
+    pub source_commit_id: String,
+    /// The id of the stack to copy the commit into.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch to copy the commit into.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace, and it shouldn't contain the source commit.
+    </important_notes>
+    ")]
+    pub destination_stack_id: String,
+    /// The name of the branch to copy the commit into.
+    #[schemars(description = "
+    <description>
+        The name of the branch to copy the commit into.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the destination stack.
+        The copy becomes the most recent commit of the branch.
+    </important_notes>
+    ")]
+    pub destination_branch_name: String,
+    /// Whether to reuse the message of the source commit.
+    #[schemars(description = "
+    <description>
+        If true, the copy gets the message of the source commit.
+        If false, it gets the message given by 'messageTitle' and 'messageBody'.
+    </description>
+
+    <important_notes>
+        Defaults to true.
+    </important_notes>
+    ")]
+    #[serde(default = "default_true")]
+    pub copy_message: bool,
+    /// The title of the message of the copy, if the message isn't copied.
+    #[schemars(description = "
+    <description>
+        The commit message title of the copy.
+    </description>
+
+    <important_notes>
+        This is required if 'copyMessage' is false, and ignored otherwise.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub message_title: Option<String>,
+    /// The body of the message of the copy, if the message isn't copied.
+    #[schemars(description = "
+    <description>
+        The commit message body of the copy.
+    </description>
+
+    <important_notes>
+        This is ignored if 'copyMessage' is true.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub message_body: Option<String>,
+}
+
+impl Tool for CopyCommitToStack {
+    fn name(&self) -> String {
+        "copy_commit_to_stack".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Copy a commit to the top of a branch in another stack.
+        </description>
+
+        <important_notes>
+            Use this tool to apply the changes of a commit to another stack as well, without removing it from its own stack.
+            The changes of the commit are applied on top of the destination branch, which can lead to conflicts.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(CopyCommitToStackParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(1_500)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: CopyCommitToStackParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = copy_commit_to_stack(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .to_json("copy_commit_to_stack");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopiedCommit {
+    /// The id of the copy in the destination branch.
+    pub new_oid: String,
+    /// The name of the branch the commit was copied into.
+    pub branch_name: String,
+}
+
+impl ToolResult for Result<CopiedCommit, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "CopiedCommit")
+    }
+}
+
+/// Cherry-pick the source commit of `params` onto the top of the destination branch.
+pub fn copy_commit_to_stack(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: CopyCommitToStackParameters,
+) -> anyhow::Result<CopiedCommit> {
+    let repo = ctx.gix_repo()?;
+    let source_commit_id = resolve_commit_id(&repo, &params.source_commit_id)?;
+    repo.find_commit(source_commit_id)
+        .map_err(|_| anyhow::anyhow!("Commit '{}' not found", params.source_commit_id))?;
+    let stack_id = StackId::from_str(&params.destination_stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stack = vb_state.get_stack_in_workspace(stack_id)?;
+
+    let is_in_stack = stack.as_rebase_steps(ctx, &repo)?.iter().any(
+        |step| matches!(step, but_rebase::RebaseStep::Pick { commit_id, .. } if *commit_id == source_commit_id),
+    );
+    if is_in_stack {
+        anyhow::bail!(
+            "Commit '{}' is already part of the destination stack, move it instead",
+            params.source_commit_id
+        );
+    }
+
+    let new_message = if params.copy_message {
+        None
+    } else {
+        let message_title = params.message_title.as_deref().ok_or_else(|| {
+            anyhow::anyhow!("A message title is required if the message isn't copied")
+        })?;
+        let message = sanitizer.commit_message(
+            message_title,
+            params.message_body.as_deref().unwrap_or_default(),
+        )?;
+        Some(message.into())
+    };
+
+    let new_oid = {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let _ = ctx.create_snapshot(
+            SnapshotDetails::new(OperationKind::CherryPick),
+            guard.write_permission(),
+        );
+        pick_into_branch(
+            ctx,
+            &repo,
+            &mut stack,
+            &params.destination_branch_name,
+            source_commit_id,
+            new_message,
+            false,
+        )?
+    };
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        app_handle.emit_stack_update(project_id, stack_id, message_id);
+    }
+
+    Ok(CopiedCommit {
+        new_oid: new_oid.to_string(),
+        branch_name: params.destination_branch_name,
+    })
+}
+
+pub struct GetRelatedCommits;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRelatedCommitsParameters {
+    /// The path of the changed file.
+    #[schemars(description = "
+    <description>
+        The path of the file with uncommitted changes to find the related commits of.
+    </description>
+
+    <important_notes>
+        The file path should be relative to the workspace root.
+    </important_notes>
+    ")]
+    pub path: String,
+    /// The header of the hunk to find the related commits of.
+    #[schemars(description = "
+    <description>
+        The header of the hunk to find the related commits of, like '@@ -1,3 +1,4 @@', as it's shown at the start of each hunk diff.
+    </description>
+
+    <important_notes>
+        Leave this out to find the related commits of all uncommitted hunks of the file.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub hunk_header: Option<String>,
+}
+
+impl Tool for GetRelatedCommits {
+    fn name(&self) -> String {
+        "get_related_commits".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            List the commits in the workspace whose changes overlap with the uncommitted changes of a file.
+        </description>
+
+        <important_notes>
+            Use this tool to decide whether uncommitted changes belong into an existing commit, which can be amended,
+            rather than into a new commit.
+            The commits of all stacks are considered, and each commit is listed along with the id of its stack.
+            Nothing is changed by this tool.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetRelatedCommitsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetRelatedCommitsParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = related_commits(ctx, params).to_json("get_related_commits");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedCommit {
+    #[serde(flatten)]
+    pub commit: SimpleCommit,
+    /// The id of the stack that contains the commit.
+    pub stack_id: StackId,
+}
+
+impl ToolResult for Result<Vec<RelatedCommit>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<RelatedCommit>")
+    }
+}
+
+/// Return the commits that the uncommitted hunks of the file in `params` depend on, in the order they're first
+/// depended on.
+pub fn related_commits(
+    ctx: &mut CommandContext,
+    params: GetRelatedCommitsParameters,
+) -> anyhow::Result<Vec<RelatedCommit>> {
+    let path = normalize_path(&params.path);
+    let repo = ctx.gix_repo()?;
+    let change = but_core::diff::worktree_changes(&repo)?
+        .changes
+        .into_iter()
+        .find(|change| change.path == path)
+        .ok_or_else(|| anyhow::anyhow!("'{}' has no uncommitted changes", path))?;
+    let hunk_header = match &params.hunk_header {
+        Some(header) => {
+            let hunk_headers = match change.unified_diff(&repo, ctx.app_settings().context_lines)? {
+                Some(UnifiedDiff::Patch { hunks, .. }) => {
+                    hunks.iter().map(Into::into).collect::<Vec<_>>()
+                }
+                _ => vec![],
+            };
+            selected_hunk_headers(&path, &hunk_headers, std::slice::from_ref(header))?
+                .into_iter()
+                .next()
+        }
+        None => None,
+    };
+
+    let dependencies =
+        but_hunk_dependency::ui::hunk_dependencies_for_workspace_changes_by_worktree_dir(
+            ctx,
+            &ctx.project().path,
+            &ctx.project().gb_dir(),
+            Some(vec![change]),
+        )?;
+
+    let mut related_commits: Vec<RelatedCommit> = vec![];
+    // The dependencies are computed without context lines, so they are matched by the lines the hunks touch.
+    let locks = dependencies
+        .diffs
+        .iter()
+        .filter(|(_, hunk, _)| {
+            hunk_header.is_none_or(|header| {
+                lines_overlap(
+                    (hunk.old_start, hunk.old_lines),
+                    (header.old_start, header.old_lines),
+                ) || lines_overlap(
+                    (hunk.new_start, hunk.new_lines),
+                    (header.new_start, header.new_lines),
+                )
+            })
+        })
+        .flat_map(|(_, _, locks)| locks);
+    for lock in locks {
+        if related_commits
+            .iter()
+            .any(|related| related.commit.id == lock.commit_id)
+        {
+            continue;
+        }
+        let message = repo
+            .find_commit(lock.commit_id)?
+            .message_raw_sloppy()
+            .to_str_lossy()
+            .into_owned();
+        related_commits.push(RelatedCommit {
+            commit: SimpleCommit::from_message(lock.commit_id, &message),
+            stack_id: lock.stack_id,
+        });
+    }
+    Ok(related_commits)
+}
+
+/// Return true if the line ranges `a` and `b`, each given by their start and number of lines, overlap.
+///
+/// Empty ranges, as they occur in hunks that only add or only remove lines, are treated as the line they start at.
+fn lines_overlap((a_start, a_lines): (u32, u32), (b_start, b_lines): (u32, u32)) -> bool {
+    a_start < b_start + b_lines.max(1) && b_start < a_start + a_lines.max(1)
+}
+
+pub struct GetWorkspaceHealth;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetWorkspaceHealthParameters {}
+
+impl Tool for GetWorkspaceHealth {
+    fn name(&self) -> String {
+        "get_workspace_health".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Check whether the workspace state is consistent, and list the issues that were found.
+        </description>
+
+        <important_notes>
+            Use this tool to diagnose the workspace when other tools fail unexpectedly.
+            It checks that the branches of all stacks point to existing commits, that the workspace commit exists,
+            that no two stacks have branches with the same tip commit, and that no lock files were left behind.
+            The workspace is healthy if there are no errors, warnings may still be worth looking into.
+            Nothing is changed by this tool, pass the ids of the issues to the repair_workspace tool to fix them.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetWorkspaceHealthParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn capabilities(&self) -> Vec<(String, serde_json::Value)> {
+        vec![("get_workspace_health.issueIds".into(), true.into())]
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = workspace_health(ctx).to_json("get_workspace_health");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    /// Whether no issue with the severity of an error was found.
+    pub healthy: bool,
+    /// All issues that were found.
+    pub issues: Vec<HealthIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthIssue {
+    /// Identifies the issue, so it can be passed to [`RepairWorkspace`].
+    pub id: String,
+    /// How severe the issue is.
+    pub severity: HealthIssueSeverity,
+    /// What is wrong.
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthIssueSeverity {
+    /// Something that may need attention, but doesn't keep the workspace from working.
+    Warning,
+    /// Something that is likely to make operations on the workspace fail.
+    Error,
+}
+
+impl ToolResult for Result<HealthReport, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "HealthReport")
+    }
+}
+
+/// Check the consistency of the stacks in `virtual_branches.toml`, the workspace commit and the repository.
+pub fn workspace_health(ctx: &CommandContext) -> anyhow::Result<HealthReport> {
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut issues = vec![];
+    let mut issue = |severity, id, description| {
+        issues.push(HealthIssue {
+            id,
+            severity,
+            description,
+        })
+    };
+
+    // Branches of stacks that aren't applied only matter once they are applied again.
+    let mut tips = BTreeMap::<gix::ObjectId, Vec<(StackId, String)>>::new();
+    for stack in vb_state.list_all_stacks()? {
+        let severity = if stack.in_workspace {
+            HealthIssueSeverity::Error
+        } else {
+            HealthIssueSeverity::Warning
+        };
+        let merge_base = if stack.in_workspace {
+            stack.merge_base(ctx).ok()
+        } else {
+            None
+        };
+        for branch in &stack.heads {
+            // Null heads are written for metadata that doesn't know the head, which is then read from the reference.
+            let persisted_head = branch.persisted_head_oid().filter(|head| !head.is_null());
+            if let Some(head) = persisted_head.filter(|head| !repo.has_object(*head)) {
+                issue(
+                    severity,
+                    format!("missing_head:{}:{}", stack.id, branch.name()),
+                    format!(
+                        "The branch '{}' of stack {} points to the commit {}, which doesn't exist",
+                        branch.name(),
+                        stack.id,
+                        head
+                    ),
+                );
+                continue;
+            }
+            if !stack.in_workspace {
+                continue;
+            }
+            // `head_oid()` isn't used as it creates the reference if it's missing.
+            let tip = match repo.try_find_reference(branch.name().as_str())? {
+                Some(mut reference) => Some(reference.peel_to_id_in_place()?.detach()),
+                None => persisted_head,
+            };
+            // Branches without commits of their own share their tip with the branch below, or the base.
+            if let Some(tip) = tip.filter(|tip| Some(*tip) != merge_base) {
+                tips.entry(tip)
+                    .or_default()
+                    .push((stack.id, branch.name().to_owned()));
+            }
+        }
+    }
+    for (tip, branches) in tips {
+        let mut stack_ids = branches
+            .iter()
+            .map(|(stack_id, _)| *stack_id)
+            .collect::<Vec<_>>();
+        stack_ids.sort();
+        stack_ids.dedup();
+        if stack_ids.len() > 1 {
+            issue(
+                HealthIssueSeverity::Error,
+                format!("shared_tip:{tip}"),
+                format!(
+                    "The branches {} of different stacks all have the tip commit {}",
+                    branches
+                        .iter()
+                        .map(|(_, name)| format!("'{name}'"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    tip
+                ),
+            );
+        }
+    }
+
+    let workspace_reference = gitbutler_branch::GITBUTLER_WORKSPACE_REFERENCE.to_string();
+    match repo.try_find_reference(workspace_reference.as_str())? {
+        None => issue(
+            HealthIssueSeverity::Error,
+            "missing_workspace_reference".to_owned(),
+            format!("The workspace reference '{workspace_reference}' doesn't exist"),
+        ),
+        Some(mut reference) => {
+            if reference.peel_to_commit().is_err() {
+                issue(
+                    HealthIssueSeverity::Error,
+                    "invalid_workspace_reference".to_owned(),
+                    format!(
+                        "The workspace reference '{workspace_reference}' doesn't point to a commit"
+                    ),
+                );
+            }
+        }
+    }
+    let head_name = repo.head_name()?.map(|name| name.as_bstr().to_string());
+    if head_name.as_deref() != Some(workspace_reference.as_str()) {
+        issue(
+            HealthIssueSeverity::Warning,
+            "head_not_at_workspace".to_owned(),
+            format!(
+                "HEAD is at {} instead of the workspace reference '{workspace_reference}'",
+                head_name.as_deref().unwrap_or("a detached commit")
+            ),
+        );
+    }
+
+    let mut lock_files = lock_files_in(repo.git_dir(), false)?;
+    lock_files.extend(lock_files_in(&repo.git_dir().join("refs"), true)?);
+    // The project lock is held through the file while the project is open, so the file is expected to exist.
+    lock_files.extend(
+        lock_files_in(&ctx.project().gb_dir(), false)?
+            .into_iter()
+            .filter(|path| path.file_name().is_none_or(|name| name != "project.lock")),
+    );
+    for lock_file in lock_files {
+        issue(
+            HealthIssueSeverity::Warning,
+            format!("lock_file:{}", lock_file.display()),
+            format!(
+                "The lock file '{}' exists, it's left behind by a crashed process unless another process is running",
+                lock_file.display()
+            ),
+        );
+    }
+
+    let healthy = !issues
+        .iter()
+        .any(|issue| issue.severity == HealthIssueSeverity::Error);
+    Ok(HealthReport { healthy, issues })
+}
+
+/// Return the paths of all files ending in `.lock` in `dir`, and in its subdirectories if `recursive` is true.
+fn lock_files_in(
+    dir: &std::path::Path,
+    recursive: bool,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let mut lock_files = vec![];
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(lock_files),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if recursive {
+                lock_files.extend(lock_files_in(&path, true)?);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "lock")
+        {
+            lock_files.push(path);
+        }
+    }
+    lock_files.sort();
+    Ok(lock_files)
+}
+
+pub struct GetStackDiffSummary;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStackDiffSummaryParameters {
+    /// The id of the stack to summarize the changes of.
+    #[schemars(description = "
+    <description>
+        The id of the stack to summarize the changes of.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+}
+
+impl Tool for GetStackDiffSummary {
+    fn name(&self) -> String {
+        "get_stack_diff_summary".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the files a stack changes compared to its base, with the number of added and removed lines of each.
+        </description>
+
+        <important_notes>
+            Use this tool to describe a pull request of the stack, as it lists the net changes of all of its commits.
+            A file that is changed by several commits is only listed once, and a change that a later commit reverts isn't counted.
+            Lines aren't counted for binary files.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetStackDiffSummaryParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetStackDiffSummaryParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = stack_diff_summary(ctx, params).to_json("get_stack_diff_summary");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackDiffFile {
+    /// The path of the changed file.
+    pub path: String,
+    /// How the file changed, like 'added' or 'renamed from <path>'.
+    pub status: String,
+    /// The number of added lines.
+    pub insertions: usize,
+    /// The number of removed lines.
+    pub deletions: usize,
+    /// Whether the file is binary, so its lines aren't counted.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_binary: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackDiffSummary {
+    /// The files changed between the base of the stack and its tip, ordered by their path.
+    pub files: Vec<StackDiffFile>,
+    /// The totals of all changed files.
+    pub totals: ChangeStats,
+}
+
+impl ToolResult for Result<StackDiffSummary, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "StackDiffSummary")
+    }
+}
+
+/// Summarize the changes of the stack in `params` from its merge base to its tip.
+pub fn stack_diff_summary(
+    ctx: &CommandContext,
+    params: GetStackDiffSummaryParameters,
+) -> anyhow::Result<StackDiffSummary> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack_in_workspace(stack_id)?;
+    let repo = ctx.gix_repo()?;
+
+    let merge_base = stack.merge_base(ctx)?;
+    let tip = stack.head_oid(&repo)?;
+    let (changes, _) = but_core::diff::tree_changes(&repo, Some(merge_base), tip)?;
+    let mut files = vec![];
+    for change in changes {
+        // Without context lines, the hunks only contain the changed lines.
+        let counts = match change.unified_diff(&repo, 0)? {
+            Some(UnifiedDiff::Patch { hunks, .. }) => {
+                Some(hunks.iter().fold((0, 0), |(insertions, deletions), hunk| {
+                    let (added, removed) = count_hunk_lines(hunk.diff.as_ref());
+                    (insertions + added, deletions + removed)
+                }))
+            }
+            _ => None,
+        };
+        files.push(StackDiffFile {
+            path: change.path.to_string(),
+            status: change_status(&change.status),
+            insertions: counts.map_or(0, |(insertions, _)| insertions),
+            deletions: counts.map_or(0, |(_, deletions)| deletions),
+            is_binary: counts.is_none(),
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let totals = ChangeStats {
+        files: files.len(),
+        insertions: files.iter().map(|file| file.insertions).sum(),
+        deletions: files.iter().map(|file| file.deletions).sum(),
+    };
+    Ok(StackDiffSummary { files, totals })
+}
+
+pub struct RepairWorkspace;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairWorkspaceParameters {
+    /// The ids of the issues to fix.
+    #[schemars(description = "
+    <description>
+        The ids of the issues to fix, as they were returned by the get_workspace_health tool.
+    </description>
+
+    <important_notes>
+        Only issues that are still reported by the get_workspace_health tool are fixed.
+    </important_notes>
+    ")]
+    pub issues_to_fix: Vec<String>,
+}
+
+impl Tool for RepairWorkspace {
+    fn name(&self) -> String {
+        "repair_workspace".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Fix issues found by the get_workspace_health tool.
+        </description>
+
+        <important_notes>
+            Stale lock files are removed, branches pointing to missing commits are reset to their git reference,
+            and the workspace commit is rewritten if it's missing or HEAD isn't at it.
+            Branches of different stacks sharing a tip can't be fixed automatically.
+            Only remove lock files if no other git process is running.
+            A snapshot is taken before anything is changed, so the repair can be undone.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(RepairWorkspaceParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: RepairWorkspaceParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = repair_workspace(ctx, app_handle, options.message_id.as_deref(), params)
+            .to_json("repair_workspace");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairReport {
+    /// The ids of the issues that were fixed.
+    pub fixed: Vec<String>,
+    /// The ids of the issues that couldn't be fixed, along with the reason.
+    pub could_not_fix: Vec<(String, String)>,
+}
+
+impl ToolResult for Result<RepairReport, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "RepairReport")
+    }
+}
+
+/// Fix the issues of the [workspace health](workspace_health()) with the ids in `params`.
+///
+/// Issues that aren't reported anymore are listed as not fixed, so stale ids can't cause any changes.
+/// Fails without changing anything if the snapshot taken beforehand can't be created.
+pub fn repair_workspace(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    params: RepairWorkspaceParameters,
+) -> anyhow::Result<RepairReport> {
+    let report = workspace_health(ctx)?;
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+
+    let mut guard = ctx.project().exclusive_worktree_access();
+    // Nothing is changed without a snapshot, as removed lock files and reset branches can't be recovered otherwise.
+    ctx.create_snapshot(
+        SnapshotDetails {
+            title: "Repaired the workspace".to_owned(),
+            ..SnapshotDetails::new(OperationKind::Unknown)
+        },
+        guard.write_permission(),
+    )
+    .context("Refusing to repair the workspace without a snapshot to undo it")?;
+
+    let mut repair = RepairReport::default();
+    let mut updated_stacks = vec![];
+    let mut update_workspace_commit = vec![];
+    for id in params.issues_to_fix {
+        if !report.issues.iter().any(|issue| issue.id == id) {
+            repair.could_not_fix.push((
+                id,
+                "The issue isn't reported anymore, it may have been fixed already".to_owned(),
+            ));
+            continue;
+        }
+        let (kind, subject) = id.split_once(':').unwrap_or((id.as_str(), ""));
+        let outcome = match kind {
+            "lock_file" => std::fs::remove_file(subject).map_err(anyhow::Error::from),
+            "missing_head" => repair_missing_head(&repo, &vb_state, subject).map(|stack_id| {
+                updated_stacks.push(stack_id);
+            }),
+            "missing_workspace_reference"
+            | "invalid_workspace_reference"
+            | "head_not_at_workspace" => {
+                // All of these are fixed by rewriting the workspace commit, which is only done once.
+                update_workspace_commit.push(id);
+                continue;
+            }
+            "shared_tip" => Err(anyhow::anyhow!(
+                "It's ambiguous which stack the commit belongs to, move or remove one of the branches instead"
+            )),
+            _ => Err(anyhow::anyhow!("The issue can't be fixed automatically")),
+        };
+        match outcome {
+            Ok(()) => repair.fixed.push(id),
+            Err(err) => repair.could_not_fix.push((id, err.to_string())),
+        }
+    }
+    if !update_workspace_commit.is_empty() {
+        match gitbutler_branch_actions::update_workspace_commit(&vb_state, ctx) {
+            Ok(_) => repair.fixed.extend(update_workspace_commit),
+            Err(err) => repair.could_not_fix.extend(
+                update_workspace_commit
+                    .into_iter()
+                    .map(|id| (id, err.to_string())),
+            ),
+        }
+    }
+
+    // If there's an app handle provided, emit an event to update the stack details in the UI.
+    if let Some(app_handle) = app_handle {
+        let project_id = ctx.project().id;
+        for stack_id in updated_stacks {
+            app_handle.emit_stack_update(project_id, stack_id, message_id);
+        }
+    }
+    Ok(repair)
+}
+
+/// Point the branch in `subject`, formatted as `<stack id>:<branch name>`, back at the commit its git reference points to.
+///
+/// Return the id of the stack of the branch.
+fn repair_missing_head(
+    repo: &gix::Repository,
+    vb_state: &VirtualBranchesHandle,
+    subject: &str,
+) -> anyhow::Result<StackId> {
+    let (stack_id, branch_name) = subject
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("The issue id is malformed"))?;
+    let mut stack = vb_state.get_stack(StackId::from_str(stack_id)?)?;
+    let branch = stack
+        .heads
+        .iter_mut()
+        .find(|branch| branch.name() == branch_name)
+        .ok_or_else(|| anyhow::anyhow!("The branch '{branch_name}' doesn't exist anymore"))?;
+    let mut reference = repo.try_find_reference(branch_name)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "The branch has no git reference to restore it from, remove the branch instead"
+        )
+    })?;
+    if reference.peel_to_id_in_place().is_err() {
+        anyhow::bail!("The git reference of the branch doesn't point to an existing commit either");
+    }
+    branch.sync_with_reference(repo)?;
+    vb_state.set_stack(stack.clone())?;
+    Ok(stack.id)
+}
+
+pub struct GetCommitSignoffTrailers;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCommitSignoffTrailersParameters {
+    /// The id of the commit to get the trailers of.
+    #[schemars(description = "
+    <description>
+        The id of the commit to get the trailers of.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+}
+
+impl Tool for GetCommitSignoffTrailers {
+    fn name(&self) -> String {
+        "get_commit_signoff_trailers".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the trailers at the end of the message of a commit, like 'Signed-off-by' or 'Reviewed-by'.
+        </description>
+
+        <important_notes>
+            Use this tool to check whether a commit is signed off, if the project requires it.
+            Only the lines of the last paragraph of the message are trailers, and only if all of them are.
+            The trailers are returned in the order they appear in.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetCommitSignoffTrailersParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetCommitSignoffTrailersParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let repo = ctx.gix_repo()?;
+        let value = resolve_commit_id(&repo, &params.commit_id)
+            .and_then(|commit_id| commit_trailers(&repo, commit_id))
+            .to_json("get_commit_signoff_trailers");
+        Ok(value)
+    }
+}
+
+impl ToolResult for Result<Vec<CommitTrailer>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<CommitTrailer>")
+    }
+}
+
+/// Return the trailers of the message of the commit `commit_id`, in the order they appear in.
+pub fn commit_trailers(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+) -> anyhow::Result<Vec<CommitTrailer>> {
+    let commit = repo.find_commit(commit_id)?;
+    Ok(parse_trailers(&commit.message_raw_sloppy().to_str_lossy()))
+}
+
+pub struct GetCommitMessageBody;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCommitMessageBodyParameters {
+    /// The id of the commit to get the message of.
+    #[schemars(description = "
+    <description>
+        The id of the commit to get the message of.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+}
+
+impl Tool for GetCommitMessageBody {
+    fn name(&self) -> String {
+        "get_commit_message_body".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the message of a commit, split into its title, its body and its trailers.
+        </description>
+
+        <important_notes>
+            Use this tool when only the message of a commit is needed, as it's much cheaper than get_commit_details,
+            which computes the diffs of the commit as well.
+            The trailers, like 'Signed-off-by', are left out of the body.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetCommitMessageBodyParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(10)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetCommitMessageBodyParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let repo = ctx.gix_repo()?;
+        let value = resolve_commit_id(&repo, &params.commit_id)
+            .and_then(|commit_id| commit_message(&repo, commit_id))
+            .to_json("get_commit_message_body");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitMessage {
+    /// The first line of the message.
+    pub title: String,
+    /// The message after the title, without the trailers and the blank lines around it.
+    pub body: String,
+    /// The trailers at the end of the message, in the order they appear in.
+    pub trailers: Vec<CommitTrailer>,
+}
+
+impl ToolResult for Result<CommitMessage, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "CommitMessage")
+    }
+}
+
+/// Return the message of the commit `commit_id`, split into title, body and trailers, without looking at its changes.
+pub fn commit_message(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+) -> anyhow::Result<CommitMessage> {
+    let commit = repo.find_commit(commit_id)?;
+    let message = commit.message_raw_sloppy().to_str_lossy();
+    let SimpleCommit {
+        message_title: title,
+        message_body: mut body,
+        ..
+    } = SimpleCommit::from_message(commit_id, &message);
+    let trailers = parse_trailers(&message);
+    if !trailers.is_empty() {
+        // The trailers are the last paragraph, which may be the whole body.
+        body = body
+            .rsplit_once("\n\n")
+            .map(|(body, _trailers)| body.trim_end().to_owned())
+            .unwrap_or_default();
+    }
+    Ok(CommitMessage {
+        title,
+        body,
+        trailers,
+    })
+}
+
+pub struct GetBranchLinkedIssues;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBranchLinkedIssuesParameters {
+    /// The id of the stack containing the branch.
+    #[schemars(description = "
+    <description>
+        The id of the stack that contains the branch.
+    </description>
+
+    <important_notes>
+        The stack id should refer to a stack in the workspace.
+    </important_notes>
+    ")]
+    pub stack_id: String,
+    /// The name of the branch.
+    #[schemars(description = "
+    <description>
+        The name of the branch to get the linked issues of.
+    </description>
+
+    <important_notes>
+        The branch name should refer to a branch in the specified stack.
+    </important_notes>
+    ")]
+    pub branch_name: String,
+}
+
+impl Tool for GetBranchLinkedIssues {
+    fn name(&self) -> String {
+        "get_branch_linked_issues".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the issues referenced in the messages of the commits of a branch, like '#123' or 'PROJ-123'.
+        </description>
+
+        <important_notes>
+            Use this tool to find the issues a branch addresses, for instance to mention them in a pull request.
+            The issues are listed in the order of the commits, starting with the newest, and once per commit.
+            The patterns of the references, and the URLs of the issues, are configured in .gitbutler/issue-patterns.toml.
+            Without this file, GitHub issues like '#123' and Jira or Linear issues like 'PROJ-123' are found, without URLs.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetBranchLinkedIssuesParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetBranchLinkedIssuesParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = branch_linked_issues(ctx, params).to_json("get_branch_linked_issues");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedIssue {
+    /// The reference to the issue, as it appears in the commit message.
+    pub ref_id: String,
+    /// The URL of the issue, if its pattern has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The commit whose message references the issue.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub commit_id: gix::ObjectId,
+}
+
+impl ToolResult for Result<Vec<LinkedIssue>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<LinkedIssue>")
+    }
+}
+
+/// Return the issues referenced in the messages of the commits of the branch in `params`, newest commit first,
+/// using the issue patterns configured in the project.
+pub fn branch_linked_issues(
+    ctx: &CommandContext,
+    params: GetBranchLinkedIssuesParameters,
+) -> anyhow::Result<Vec<LinkedIssue>> {
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let branch_name = unqualified_branch_name(stack_id, &params.branch_name);
+    let matcher = IssueMatcher::new(&read_issue_patterns(&ctx.project().path)?)?;
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state.get_stack(stack_id)?;
+    let branch = stack
+        .branches()
+        .into_iter()
+        .find(|b| b.name() == branch_name)
+        .ok_or_else(|| {
+            anyhow::anyhow!("Branch '{}' not found in stack {}", branch_name, stack_id)
+        })?;
+    let commits = but_workspace::local_and_remote_commits(ctx, &repo, &branch, &stack)?;
+
+    Ok(commits
+        .into_iter()
+        .flat_map(|commit| {
+            matcher
+                .references(&commit.message.to_str_lossy())
+                .into_iter()
+                .map(move |reference| LinkedIssue {
+                    ref_id: reference.ref_id,
+                    url: reference.url,
+                    commit_id: commit.id,
+                })
+        })
+        .collect())
+}
+
+pub struct AddTrailerToCommit;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AddTrailerToCommitParameters {
+    /// The id of the stack the commit is in.
+    #[schemars(description = "
+    <description>
+        The id of the stack the commit to add the trailer to is in.
+    </description>
+    ")]
+    pub stack_id: String,
+    /// The id of the commit to add the trailer to.
+    #[schemars(description = "
+    <description>
+        The id of the commit to add the trailer to.
+    </description>
+
+    <important_notes>
+        The commit has to be part of the stack.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+    /// The token of the trailer.
+    #[schemars(description = "
+    <description>
+        The token of the trailer, like 'Signed-off-by' or 'Reviewed-by'.
+    </description>
+
+    <important_notes>
+        The token can only consist of letters, digits and dashes.
+    </important_notes>
+    ")]
+    pub token: String,
+    /// The value of the trailer.
+    #[schemars(description = "
+    <description>
+        The value of the trailer, like 'Jane Doe <jane@example.com>'.
+    </description>
+
+    <important_notes>
+        The value has to be a single line.
+    </important_notes>
+    ")]
+    pub value: String,
+}
+
+impl Tool for AddTrailerToCommit {
+    fn name(&self) -> String {
+        "add_trailer_to_commit".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Add a trailer, like 'Signed-off-by', to the end of the message of a commit.
+        </description>
+
+        <important_notes>
+            The trailer is added to the existing trailers of the commit, or as a new paragraph if it has none.
+            Adding a trailer the commit already has fails.
+            Adding a trailer changes the id of the commit, and the ids of all commits above it.
+            The ids of all rebased commits are returned, mapped from their previous ids.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(AddTrailerToCommitParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: AddTrailerToCommitParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = add_trailer_to_commit(
+            ctx,
+            app_handle,
+            options.message_id.as_deref(),
+            &options.sanitizer,
+            params,
+        )
+        .to_json("add_trailer_to_commit");
+        Ok(value)
+    }
+}
+
+/// Append the trailer in `params` to the message of its commit, and rebase the commits above it.
+pub fn add_trailer_to_commit(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    message_id: Option<&str>,
+    sanitizer: &MessageSanitizer,
+    params: AddTrailerToCommitParameters,
+) -> anyhow::Result<Vec<RewrittenCommit>> {
+    let token = params.token.trim();
+    if !is_trailer_token(token) {
+        anyhow::bail!("'{}' isn't a valid trailer token", params.token);
+    }
+    let value = sanitizer.strip(&params.value);
+    let value = value.trim();
+    if value.is_empty() || value.contains('\n') {
+        anyhow::bail!("The value of the trailer has to be a single line");
+    }
+
+    let repo = ctx.gix_repo()?;
+    let stack_id = StackId::from_str(&params.stack_id)?;
+    let commit_id = resolve_commit_id(&repo, &params.commit_id)?;
+    let trailers = commit_trailers(&repo, commit_id)?;
+    if trailers
+        .iter()
+        .any(|trailer| trailer.token.eq_ignore_ascii_case(token) && trailer.value == value)
+    {
+        anyhow::bail!("The commit already has the trailer '{token}: {value}'");
+    }
+
+    let commit = repo.find_commit(commit_id)?;
+    let message = commit.message_raw_sloppy().to_str_lossy();
+    let separator = if trailers.is_empty() { "\n\n" } else { "\n" };
+    let message = format!("{}{separator}{token}: {value}\n", message.trim_end());
+    set_commit_messages(
+        ctx,
+        app_handle,
+        message_id,
+        stack_id,
+        vec![(commit_id, message)],
+    )
+}
+
+pub struct RestoreFileFromCommit;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreFileFromCommitParameters {
+    /// The id of the commit to restore the file from.
+    #[schemars(description = "
+    <description>
+        The id of the commit with the version of the file to restore.
+    </description>
+
+    <important_notes>
+        The commit id should refer to a commit in the repository, it doesn't have to be in the workspace.
+    </important_notes>
+    ")]
+    pub commit_id: String,
+    /// The path of the file to restore.
+    #[schemars(description = "
+    <description>
+        The path of the file to restore, relative to the root of the repository.
+    </description>
+    ")]
+    pub path: String,
+    /// Whether to delete the file if it didn't exist in the commit.
+    #[schemars(description = "
+    <description>
+        Whether to delete the file from the worktree if it didn't exist in the commit.
+    </description>
+
+    <important_notes>
+        Without this, restoring a file that didn't exist in the commit fails.
+        Set it to bring the worktree back to the state of the commit for this path.
+    </important_notes>
+    ")]
+    #[serde(default)]
+    pub delete_if_missing: bool,
+}
+
+impl Tool for RestoreFileFromCommit {
+    fn name(&self) -> String {
+        "restore_file_from_commit".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Write the version of a file in a commit into the worktree, as an uncommitted change.
+        </description>
+
+        <important_notes>
+            Use this tool to bring back the content of a file from an earlier commit.
+            Uncommitted changes to the file are overwritten, but a snapshot is taken first so they can be restored.
+            The resulting change of the file is returned, or nothing if the file now matches the workspace.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(RestoreFileFromCommitParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: RestoreFileFromCommitParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let context_lines = options
+            .context_lines
+            .unwrap_or(ctx.app_settings().context_lines);
+        let value = restore_file_from_commit(
+            ctx,
+            app_handle,
+            &options.assignments_cache,
+            params,
+            context_lines,
+        )
+        .to_json("restore_file_from_commit");
+        Ok(value)
+    }
+}
+
+impl ToolResult for Result<Option<FileChange>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Option<FileChange>")
+    }
+}
+
+/// Write the version of the file in `params` from its commit into the worktree, or delete it if it didn't exist
+/// in the commit and that's requested.
+///
+/// Return the change of the file in the worktree afterwards, with diffs with `context_lines`.
+pub fn restore_file_from_commit(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    assignments_cache: &AssignmentsCache,
+    params: RestoreFileFromCommitParameters,
+    context_lines: u32,
+) -> anyhow::Result<Option<FileChange>> {
+    let repo = ctx.gix_repo()?;
+    let path = normalize_path(&params.path);
+    if path.is_empty() || path.starts_with("..") {
+        anyhow::bail!("'{}' isn't a path inside the repository", params.path);
+    }
+    let commit_id = resolve_commit_id(&repo, &params.commit_id)?;
+    let entry = repo
+        .find_commit(commit_id)?
+        .tree()?
+        .lookup_entry_by_path(&path)?;
+    let blob = match &entry {
+        Some(entry) if entry.mode().is_blob() => {
+            Some((entry.object()?.detach().data, entry.mode().is_executable()))
+        }
+        Some(_) => anyhow::bail!("'{path}' isn't a regular file in commit {commit_id}"),
+        None if params.delete_if_missing => None,
+        None => anyhow::bail!(
+            "'{path}' didn't exist in commit {commit_id}, set deleteIfMissing to delete it from the worktree instead"
+        ),
+    };
+
+    let worktree_path = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no worktree"))?
+        .join(gix::path::from_bstr(BStr::new(&path)));
+    {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let _ = ctx.create_snapshot(
+            SnapshotDetails {
+                title: "Restored a file from a commit".to_owned(),
+                ..SnapshotDetails::new(OperationKind::FileChanges)
+            },
+            guard.write_permission(),
+        );
+        match blob {
+            Some((data, executable)) => {
+                if let Some(parent) = worktree_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&worktree_path, data)?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt as _;
+                    let mut permissions = std::fs::metadata(&worktree_path)?.permissions();
+                    let mode = permissions.mode();
+                    permissions.set_mode(if executable {
+                        mode | 0o111
+                    } else {
+                        mode & !0o111
+                    });
+                    std::fs::set_permissions(&worktree_path, permissions)?;
+                }
+                #[cfg(not(unix))]
+                let _ = executable;
+            }
+            None => match std::fs::remove_file(&worktree_path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            },
+        }
+    }
+
+    // If there's an app handle provided, emit an event to update the worktree changes in the UI.
+    if let Some(app_handle) = app_handle {
+        let worktree_changes: but_core::ui::WorktreeChanges =
+            but_core::diff::worktree_changes(&repo)?.into();
+        app_handle.emit_worktree_changes(ctx.project().id, &worktree_changes.into());
+    }
+
+    let (file_changes, warnings) = get_filtered_changes(
+        ctx,
+        &repo,
+        Some(vec![path.into()]),
+        context_lines,
+        false,
+        assignments_cache,
+    )?;
+    if let Some(warning) = warnings.first() {
+        anyhow::bail!(
+            "The file was restored, but its diff couldn't be computed: {}",
+            warning.error
+        );
+    }
+    Ok(file_changes.into_iter().next())
+}
+
+pub struct DiffAgainstSnapshot;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffAgainstSnapshotParameters {
+    /// The snapshot to compare the workspace with.
+    #[schemars(description = "
+    <description>
+        The id of a snapshot, as listed by the get_recent_activity tool.
+    </description>
+
+    <important_notes>
+        The worktree and the heads of the branches are compared with their state when the snapshot was taken.
+    </important_notes>
+    ")]
+    pub snapshot_id: String,
+    /// The number of unchanged lines to show around each change.
+    #[schemars(description = "
+    <description>
+        The number of unchanged lines to show before and after each change in the diffs.
+    </description>
+
+    <important_notes>
+        If not provided, the number of lines configured for the session is used, or the one in the app settings.
+    </important_notes>
+    ")]
+    pub context_lines: Option<u32>,
+}
+
+impl Tool for DiffAgainstSnapshot {
+    fn name(&self) -> String {
+        "diff_against_snapshot".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Show everything that changed in the workspace since a snapshot was taken.
+        </description>
+
+        <important_notes>
+            This returns the diff of each file in the worktree that differs from the snapshot,
+            and the branches whose heads moved, along with the commits that were added to or removed from them.
+            Use this tool to answer what changed since the user last looked, without changing anything.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(DiffAgainstSnapshotParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(2_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let mut params: DiffAgainstSnapshotParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+        params.context_lines = params.context_lines.or(options.context_lines);
+
+        let value = diff_against_snapshot(ctx, params).to_json("diff_against_snapshot");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    /// The changes of the files in the worktree since the snapshot, ordered by their path.
+    pub file_changes: Vec<FileChange>,
+    /// Changed files that were left out because they couldn't be read, ordered by their path.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<DiffWarning>,
+    /// The branches whose heads moved since the snapshot, ordered by the id of their stack,
+    /// then by their position in the stack.
+    pub branch_changes: Vec<BranchHeadChange>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchHeadChange {
+    /// The id of the stack the branch is in.
+    pub stack_id: StackId,
+    /// The name of the branch.
+    pub branch_name: String,
+    /// The head of the branch when the snapshot was taken, or `None` if the branch wasn't in the workspace back then.
+    #[serde(with = "gitbutler_serde::object_id_opt")]
+    pub previous_head: Option<gix::ObjectId>,
+    /// The head of the branch now, or `None` if the branch isn't in the workspace anymore.
+    #[serde(with = "gitbutler_serde::object_id_opt")]
+    pub head: Option<gix::ObjectId>,
+    /// The commits that are on the branch now, but weren't when the snapshot was taken, from the newest to the oldest.
+    pub added_commits: Vec<SimpleCommit>,
+    /// The commits that were on the branch when the snapshot was taken, but aren't anymore, from the newest to the oldest.
+    ///
+    /// Rewritten commits show up here with their old id, and in `added_commits` with their new one.
+    pub removed_commits: Vec<SimpleCommit>,
+}
+
+impl ToolResult for Result<SnapshotDiff, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "SnapshotDiff")
+    }
+}
+
+/// Compare the worktree and the heads of the branches in the workspace with the oplog snapshot `params.snapshot_id`.
+pub fn diff_against_snapshot(
+    ctx: &mut CommandContext,
+    params: DiffAgainstSnapshotParameters,
+) -> anyhow::Result<SnapshotDiff> {
+    let repo = ctx.gix_repo()?;
+    let snapshot_id = resolve_commit_id(&repo, &params.snapshot_id)?;
+    let context_lines = params
+        .context_lines
+        .unwrap_or(ctx.app_settings().context_lines);
+
+    let snapshot_worktree = ctx.snapshot_workspace_tree(snapshot_id)?;
+    // Writing the tree of the worktree only adds objects, the worktree and the references stay untouched.
+    let current_worktree = {
+        let guard = ctx.project().shared_worktree_access();
+        let tree = ctx.prepare_snapshot(guard.read_permission())?.to_gix();
+        repo.find_tree(tree)?
+            .find_entry("worktree")
+            .ok_or_else(|| anyhow::anyhow!("The tree of the worktree couldn't be written"))?
+            .object_id()
+    };
+
+    let (changes, _) =
+        but_core::diff::tree_changes(&repo, Some(snapshot_worktree), current_worktree)?;
+    let (diff, warnings) = unified_diff_for_changes(&repo, changes, context_lines)?;
+    let mut file_changes = get_file_changes(&diff, vec![])?;
+    apply_diff_attributes(&repo, None, &mut file_changes)?;
+
+    Ok(SnapshotDiff {
+        file_changes,
+        warnings,
+        branch_changes: branch_head_changes(ctx, &repo, snapshot_id)?,
+    })
+}
+
+/// Return the branches whose heads differ between the oplog snapshot `snapshot_id` and the workspace now.
+fn branch_head_changes(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    snapshot_id: gix::ObjectId,
+) -> anyhow::Result<Vec<BranchHeadChange>> {
+    let previous: BTreeMap<_, _> = snapshot_virtual_branches(repo, snapshot_id)
+        .ok_or_else(|| {
+            anyhow::anyhow!("The state of the stacks couldn't be read from snapshot {snapshot_id}")
+        })?
+        .branches
+        .into_iter()
+        .filter(|(_, stack)| stack.in_workspace)
+        .collect();
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let current: BTreeMap<_, _> = vb_state
+        .list_stacks_in_workspace()?
+        .into_iter()
+        .map(|stack| (stack.id, stack))
+        .collect();
+
+    let mut stack_ids = previous
+        .keys()
+        .chain(current.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    stack_ids.sort();
+    stack_ids.dedup();
+
+    let mut changes = vec![];
+    for stack_id in stack_ids {
+        let previous_stack = previous.get(&stack_id);
+        let current_stack = current.get(&stack_id);
+        // Commits below the merge base are never reported, even if the stack was rebased onto a newer target since.
+        let merge_base = current_stack
+            .or(previous_stack)
+            .and_then(|stack| stack.merge_base(ctx).ok());
+        let heads = |stack: Option<&gitbutler_stack::Stack>| {
+            stack
+                .map(|stack| {
+                    stack
+                        .branches()
+                        .iter()
+                        .map(|branch| (branch.name().to_owned(), branch.persisted_head_oid()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+        let previous_heads = heads(previous_stack);
+        let current_heads = heads(current_stack);
+
+        // Branches are listed from the bottom of the stack to the top, with those that are gone last.
+        let mut branch_names = current_heads
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for (name, _) in &previous_heads {
+            if !branch_names.contains(name) {
+                branch_names.push(name.clone());
+            }
+        }
+
+        for branch_name in branch_names {
+            let head_in = |heads: &[(String, Option<gix::ObjectId>)]| {
+                heads
+                    .iter()
+                    .find(|(name, _)| *name == branch_name)
+                    .and_then(|(_, head)| *head)
+            };
+            // The commits of the branches below are reachable from this branch, but don't belong to it.
+            let heads_below = |heads: &[(String, Option<gix::ObjectId>)]| {
+                heads
+                    .iter()
+                    .take_while(|(name, _)| *name != branch_name)
+                    .filter_map(|(_, head)| *head)
+                    .collect::<Vec<_>>()
+            };
+            let previous_head = head_in(&previous_heads);
+            let head = head_in(&current_heads);
+            if previous_head == head {
+                continue;
+            }
+
+            let added_commits = commits_hidden_by(
+                repo,
+                head,
+                previous_head
+                    .into_iter()
+                    .chain(heads_below(&current_heads))
+                    .chain(merge_base),
+            )?;
+            let removed_commits = commits_hidden_by(
+                repo,
+                previous_head,
+                head.into_iter()
+                    .chain(heads_below(&previous_heads))
+                    .chain(merge_base),
+            )?;
+            changes.push(BranchHeadChange {
+                stack_id,
+                branch_name,
+                previous_head,
+                head,
+                added_commits,
+                removed_commits,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+/// Return the commits reachable from `tip` that aren't reachable from any of `hidden`, from the newest to the oldest.
+///
+/// Commits that don't exist anymore, for example because they were garbage-collected, are skipped.
+fn commits_hidden_by(
+    repo: &gix::Repository,
+    tip: Option<gix::ObjectId>,
+    hidden: impl IntoIterator<Item = gix::ObjectId>,
+) -> anyhow::Result<Vec<SimpleCommit>> {
+    let Some(tip) = tip.filter(|tip| repo.find_commit(*tip).is_ok()) else {
+        return Ok(vec![]);
+    };
+    let hidden = hidden
+        .into_iter()
+        .filter(|id| repo.find_commit(*id).is_ok())
+        .collect::<Vec<_>>();
+
+    let mut commits = vec![];
+    for info in tip.attach(repo).ancestors().with_hidden(hidden).all()? {
+        let Ok(info) = info else {
+            break;
+        };
+        let commit = repo.find_commit(info.id)?;
+        let message = commit.message_raw_sloppy().to_str_lossy();
+        commits.push(SimpleCommit::from_message(info.id, &message));
+    }
+    Ok(commits)
+}
+
+pub struct GetUnpushedWork;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetUnpushedWorkParameters {
+    /// The id of the stack to limit the summary to.
+    #[schemars(description = "
+    <description>
+        The id of the stack to list the unpushed work of.
+    </description>
+
+    <important_notes>
+        If not provided, the unpushed work of all stacks in the workspace is listed.
+    </important_notes>
+    ")]
+    pub stack_id: Option<String>,
+}
+
+impl Tool for GetUnpushedWork {
+    fn name(&self) -> String {
+        "get_unpushed_work".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            List what pushing would publish: the commits of each branch that aren't on its remote branch yet.
+        </description>
+
+        <important_notes>
+            Only branches with something to push are listed.
+            A branch that diverged from its remote branch, because commits were rewritten locally or added to the remote branch,
+            can only be pushed with force, which discards the commits that only exist on the remote branch.
+            Use this tool before pushing, to tell the user what will be published.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetUnpushedWorkParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetUnpushedWorkParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = unpushed_work(ctx, params).to_json("get_unpushed_work");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnpushedBranch {
+    /// The id of the stack the branch is in.
+    pub stack_id: StackId,
+    /// The name of the branch.
+    pub branch_name: String,
+    /// The commits that pushing would publish, the most recent one first.
+    ///
+    /// This includes pushed commits that were rewritten locally since.
+    pub unpushed_commits: Vec<SimpleCommit>,
+    /// The commits that only exist on the remote branch, the most recent one first.
+    pub remote_only_commits: Vec<SimpleCommit>,
+    /// Whether the branch diverged from its remote branch, so it has to be force-pushed.
+    pub requires_force: bool,
+}
+
+impl ToolResult for Result<Vec<UnpushedBranch>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<UnpushedBranch>")
+    }
+}
+
+/// Return the branches in the workspace, or in the stack of `params`, that have commits which aren't on their
+/// remote branch, or that diverged from it.
+///
+/// Stacks are ordered by their position in the workspace, and branches by their position in the stack.
+pub fn unpushed_work(
+    ctx: &CommandContext,
+    params: GetUnpushedWorkParameters,
+) -> anyhow::Result<Vec<UnpushedBranch>> {
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stacks = match params.stack_id {
+        Some(stack_id) => vec![vb_state.get_stack(StackId::from_str(&stack_id)?)?],
+        None => vb_state.list_stacks_in_workspace()?,
+    };
+    stacks.sort_by_key(|stack| (stack.order, stack.id));
+
+    let mut unpushed = vec![];
+    for stack in stacks {
+        for branch in stack.branches().iter().filter(|branch| !branch.archived) {
+            let commits = but_workspace::local_and_remote_commits(ctx, &repo, branch, &stack)?;
+            let mut rewritten = false;
+            let unpushed_commits = commits
+                .into_iter()
+                .filter(|commit| match commit.state {
+                    but_workspace::ui::CommitState::LocalOnly => true,
+                    but_workspace::ui::CommitState::LocalAndRemote(remote_id) => {
+                        rewritten |= remote_id != commit.id;
+                        remote_id != commit.id
+                    }
+                    but_workspace::ui::CommitState::Integrated => false,
+                })
+                .map(SimpleCommit::from)
+                .collect::<Vec<_>>();
+            let remote_only_commits = but_workspace::stack_branch_upstream_only_commits(
+                stack.id,
+                branch.name().to_owned(),
+                ctx,
+                &repo,
+            )?
+            .into_iter()
+            .map(|commit| SimpleCommit {
+                remote_only: true,
+                ..SimpleCommit::from_message(commit.id, &commit.message.to_string())
+            })
+            .collect::<Vec<_>>();
+
+            let requires_force = rewritten || !remote_only_commits.is_empty();
+            if unpushed_commits.is_empty() && !requires_force {
+                continue;
+            }
+            unpushed.push(UnpushedBranch {
+                stack_id: stack.id,
+                branch_name: branch.name().to_owned(),
+                unpushed_commits,
+                remote_only_commits,
+                requires_force,
+            });
+        }
+    }
+    Ok(unpushed)
+}
+
+pub struct GetCrossStackDiff;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCrossStackDiffParameters {
+    /// The ids of the stacks to combine the changes of.
+    #[schemars(description = "
+    <description>
+        The ids of the stacks whose changes are combined into one diff.
+    </description>
+
+    <important_notes>
+        The stack ids should refer to stacks in the workspace.
+        The changes of each stack are computed against its own merge base with the target branch.
+    </important_notes>
+    ")]
+    pub stack_ids: Vec<String>,
+    /// The number of unchanged lines to show around each change.
+    #[schemars(description = "
+    <description>
+        The number of unchanged lines to show before and after each change in the diffs.
+    </description>
+
+    <important_notes>
+        If not provided, the number of lines configured for the session is used, or the one in the app settings.
+    </important_notes>
+    ")]
+    pub context_lines: Option<u32>,
+}
+
+impl Tool for GetCrossStackDiff {
+    fn name(&self) -> String {
+        "get_cross_stack_diff".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the combined diff of several stacks, as if their changes were applied together.
+        </description>
+
+        <important_notes>
+            Use this tool to review stacks that are meant to be merged or released together.
+            Hunks that several stacks contain identically are listed once.
+            Hunks of different stacks that change the same lines differently are marked with hasConflict,
+            as they can't be applied together without resolving the conflict.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetCrossStackDiffParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(2_000)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let mut params: GetCrossStackDiffParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+        params.context_lines = params.context_lines.or(options.context_lines);
+
+        let value = cross_stack_diff(ctx, params).to_json("get_cross_stack_diff");
+        Ok(value)
+    }
+}
+
+/// Return the union of the changes of the stacks in `params`, each computed against the merge base of its stack,
+/// ordered by path.
+///
+/// Identical hunks are listed once, and hunks of different stacks that change overlapping lines of the same file
+/// are marked as conflicting. The lines are compared as numbered in the merge base of each stack.
+pub fn cross_stack_diff(
+    ctx: &CommandContext,
+    params: GetCrossStackDiffParameters,
+) -> anyhow::Result<Vec<FileChange>> {
+    let repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let context_lines = params
+        .context_lines
+        .unwrap_or(ctx.app_settings().context_lines);
+
+    let mut stack_ids = params
+        .stack_ids
+        .iter()
+        .map(|stack_id| StackId::from_str(stack_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    stack_ids.sort();
+    stack_ids.dedup();
+
+    // The changes of each file, along with the stack each of its hunks comes from.
+    let mut combined: BTreeMap<String, (FileChange, Vec<StackId>)> = BTreeMap::new();
+    for stack_id in stack_ids {
+        let stack = vb_state.get_stack_in_workspace(stack_id)?;
+        let tip = stack.head_oid(&repo)?;
+        let (changes, _) = but_core::diff::tree_changes(&repo, Some(stack.merge_base(ctx)?), tip)?;
+        let (diff, warnings) = unified_diff_for_changes(&repo, changes, context_lines)?;
+        if let Some(warning) = warnings.first() {
+            anyhow::bail!(
+                "Failed to compute the diff of '{}': {}",
+                warning.path,
+                warning.error
+            );
+        }
+        let mut file_changes = get_file_changes(&diff, vec![])?;
+        let tree_id = repo.find_commit(tip)?.tree_id()?.detach();
+        apply_diff_attributes(&repo, Some(tree_id), &mut file_changes)?;
+
+        for file_change in file_changes {
+            let Some((combined_change, hunk_stacks)) = combined.get_mut(&file_change.path) else {
+                let hunk_stacks = vec![stack_id; file_change.hunks.len()];
+                combined.insert(file_change.path.clone(), (file_change, hunk_stacks));
+                continue;
+            };
+            for mut hunk in file_change.hunks {
+                let hunk_diff = unified_hunk_diff(&hunk);
+                if combined_change
+                    .hunks
+                    .iter()
+                    .any(|existing| unified_hunk_diff(existing) == hunk_diff)
+                {
+                    continue;
+                }
+                let changed_lines = changed_old_lines(hunk_diff);
+                for (existing, existing_stack) in
+                    combined_change.hunks.iter_mut().zip(&*hunk_stacks)
+                {
+                    let overlaps = match (
+                        changed_lines,
+                        changed_old_lines(unified_hunk_diff(existing)),
+                    ) {
+                        (Some((start, end)), Some((existing_start, existing_end))) => {
+                            start <= existing_end && existing_start <= end
+                        }
+                        _ => false,
+                    };
+                    if overlaps && *existing_stack != stack_id {
+                        existing.has_conflict = true;
+                        hunk.has_conflict = true;
+                    }
+                }
+                combined_change.hunks.push(hunk);
+                hunk_stacks.push(stack_id);
+            }
+            combined_change.diff_suppressed |= file_change.diff_suppressed;
+        }
+    }
+
+    Ok(combined
+        .into_values()
+        .map(|(mut file_change, _)| {
+            file_change.hunks.sort_by_key(|hunk| {
+                changed_old_lines(unified_hunk_diff(hunk)).map(|(start, _)| start)
+            });
+            file_change
+        })
+        .collect())
+}
+
+/// Return the unified diff text of `hunk`, which is empty if it's in another format.
+fn unified_hunk_diff(hunk: &RichHunk) -> &str {
+    match &hunk.diff {
+        HunkDiff::Unified { diff } => diff,
+        HunkDiff::Structured { .. } => "",
+    }
+}
+
+/// Return the first and the last line of the old version of the file that the hunk `diff` changes, ignoring its
+/// context lines, or `None` if its header can't be parsed.
+///
+/// Lines that are only added are placed at the line they are inserted before.
+fn changed_old_lines(diff: &str) -> Option<(u32, u32)> {
+    let mut lines = diff.lines();
+    let header = parse_hunk_header(lines.next()?)?;
+    let mut old_line = header.old_start;
+    let mut changed: Option<(u32, u32)> = None;
+    let mut mark = |line: u32| {
+        changed = Some(changed.map_or((line, line), |(start, end)| {
+            (start.min(line), end.max(line))
+        }));
+    };
+    for line in lines {
+        if line.starts_with('-') {
+            mark(old_line);
+            old_line += 1;
+        } else if line.starts_with('+') {
+            mark(old_line);
+        } else if !line.starts_with('\\') {
+            old_line += 1;
+        }
+    }
+    changed
+}
+
+pub struct GetBranchNameFromDiff;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBranchNameFromDiffParameters {
+    /// The paths of the changed files to name a branch after.
+    #[schemars(description = "
+    <description>
+        The paths of the files changed on the branch, relative to the root of the repository.
+    </description>
+
+    <important_notes>
+        The files don't need to exist, as only their paths are used.
+    </important_notes>
+    ")]
+    pub file_paths: Vec<String>,
+}
+
+impl Tool for GetBranchNameFromDiff {
+    fn name(&self) -> String {
+        "get_branch_name_from_diff".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Suggest kebab-case branch names for changes of the given files, from the most specific to the least specific one.
+        </description>
+
+        <important_notes>
+            The names are derived from the directories and names of the files, e.g. src/auth/login.rs suggests auth-login.
+            Generic directories like src and file names like mod.rs are ignored.
+            This is a heuristic that doesn't look at the contents of the files, so prefer a name that describes the intent of the changes if it's known.
+            The result is empty if no name can be derived from the paths.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetBranchNameFromDiffParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn estimated_duration_ms(&self) -> Option<u64> {
+        Some(10)
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        _ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetBranchNameFromDiffParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = anyhow::Ok(suggest_branch_names(&params.file_paths))
+            .to_json("get_branch_name_from_diff");
+        Ok(value)
+    }
+}
+
+impl ToolResult for Result<Vec<String>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<String>")
+    }
+}
+
+pub struct GetConflictingHunks;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetConflictingHunksParameters {}
+
+impl Tool for GetConflictingHunks {
+    fn name(&self) -> String {
+        "get_conflicting_hunks".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Get the conflicted files of the worktree, with the conflicting regions in each of them.
+        </description>
+
+        <important_notes>
+            Use this tool when the worktree is in a conflicted state, for instance after an integration that couldn't
+            be done cleanly, to find out what needs to be resolved.
+            Each region has the content of our side, their side and, if the conflict markers contain it, the common base.
+            Files without conflict markers, like binary files or files deleted on one side, are listed without regions.
+            Use the resolve_conflict tool to resolve the conflicts of a file.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetConflictingHunksParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = worktree_conflicts(ctx).to_json("get_conflicting_hunks");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictRegion {
+    /// The line of the `<<<<<<<` marker that starts the region, starting at 1.
+    pub start_line: u32,
+    /// The line of the `>>>>>>>` marker that ends the region.
+    pub end_line: u32,
+    /// The lines of our side of the conflict.
+    pub ours: String,
+    /// The lines of the common base, if the markers contain them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// The lines of their side of the conflict.
+    pub theirs: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorktreeConflict {
+    /// The path of the conflicted file, relative to the root of the repository.
+    pub path: String,
+    /// The conflicting regions of the file in the worktree, from the top of the file.
+    pub regions: Vec<ConflictRegion>,
+}
+
+impl ToolResult for Result<Vec<WorktreeConflict>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<WorktreeConflict>")
+    }
+}
+
+/// Return the files that are conflicted in the index, ordered by path, with the conflict regions
+/// of their version in the worktree.
+pub fn worktree_conflicts(ctx: &CommandContext) -> anyhow::Result<Vec<WorktreeConflict>> {
+    let repo = ctx.gix_repo()?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no worktree"))?;
+    let index = repo.index_or_empty()?;
+    let mut conflicts: Vec<WorktreeConflict> = vec![];
+    for entry in index.entries() {
+        if entry.stage() == gix::index::entry::Stage::Unconflicted {
+            continue;
+        }
+        let path = entry.path(&index).to_string();
+        // The entries are sorted by path, so the stages of one path are next to each other.
+        if conflicts
+            .last()
+            .is_some_and(|conflict| conflict.path == path)
+        {
+            continue;
+        }
+        let regions = match std::fs::read(workdir.join(gix::path::from_bstr(BStr::new(&path)))) {
+            Ok(data) => conflict_regions(&data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err.into()),
+        };
+        conflicts.push(WorktreeConflict { path, regions });
+    }
+    Ok(conflicts)
+}
+
+/// Return the regions between conflict markers in `data`, or nothing if it isn't text.
+fn conflict_regions(data: &[u8]) -> Vec<ConflictRegion> {
+    enum Section {
+        Outside,
+        Ours,
+        Base,
+        Theirs,
+    }
+    let Ok(text) = std::str::from_utf8(data) else {
+        return vec![];
+    };
+    let mut regions = vec![];
+    let mut section = Section::Outside;
+    let (mut start_line, mut ours, mut base, mut theirs) = (0, String::new(), None, String::new());
+    for (line_number, line) in (1..).zip(text.split_inclusive('\n')) {
+        match section {
+            Section::Outside if is_conflict_marker(line, '<') => {
+                section = Section::Ours;
+                start_line = line_number;
+            }
+            Section::Outside => {}
+            Section::Ours if is_conflict_marker(line, '|') => {
+                section = Section::Base;
+                base = Some(String::new());
+            }
+            Section::Ours | Section::Base if is_conflict_marker(line, '=') => {
+                section = Section::Theirs;
+            }
+            Section::Ours => ours.push_str(line),
+            Section::Base => base.get_or_insert_default().push_str(line),
+            Section::Theirs if is_conflict_marker(line, '>') => {
+                section = Section::Outside;
+                regions.push(ConflictRegion {
+                    start_line,
+                    end_line: line_number,
+                    ours: std::mem::take(&mut ours),
+                    base: base.take(),
+                    theirs: std::mem::take(&mut theirs),
+                });
+            }
+            Section::Theirs => theirs.push_str(line),
+        }
+    }
+    regions
+}
+
+/// Return `true` if `line` is a conflict marker made of seven `marker` characters, optionally followed by a label.
+fn is_conflict_marker(line: &str, marker: char) -> bool {
+    let line = line.trim_end_matches(['\n', '\r']);
+    line.len() >= 7
+        && line.chars().take(7).all(|c| c == marker)
+        && line[7..].chars().next().is_none_or(|c| c == ' ')
+}
+
+pub struct ResolveConflict;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictSide {
+    /// Keep our side of each conflict region.
+    Ours,
+    /// Keep their side of each conflict region.
+    Theirs,
+    /// Keep our side followed by their side of each conflict region.
+    Both,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveConflictParameters {
+    /// The path of the conflicted file.
+    #[schemars(description = "
+    <description>
+        The path of the conflicted file to resolve, relative to the root of the repository.
+    </description>
+    ")]
+    pub path: String,
+    /// The side to keep in each conflict region.
+    #[schemars(description = "
+    <description>
+        The side to keep in each conflict region of the file: ours, theirs, or both, with ours first.
+    </description>
+
+    <important_notes>
+        Exactly one of side and content must be provided.
+    </important_notes>
+    ")]
+    pub side: Option<ConflictSide>,
+    /// The resolved content of the whole file.
+    #[schemars(description = "
+    <description>
+        The complete content of the file after resolving its conflicts, which replaces the file in the worktree.
+    </description>
+
+    <important_notes>
+        Exactly one of side and content must be provided.
+        The content must not contain conflict markers anymore.
+    </important_notes>
+    ")]
+    pub content: Option<String>,
+}
+
+impl Tool for ResolveConflict {
+    fn name(&self) -> String {
+        "resolve_conflict".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Resolve the conflicts of a conflicted file in the worktree, and mark it as resolved.
+        </description>
+
+        <important_notes>
+            Use the get_conflicting_hunks tool first to see the conflicting regions of the file.
+            Either keep one side, or both, of all regions of the file, or provide the resolved content of the whole file.
+            A snapshot is taken first, so the conflicted state can be restored.
+            The conflicts that remain in the worktree afterwards are returned.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(ResolveConflictParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: ResolveConflictParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let value = resolve_conflict(ctx, app_handle, params).to_json("resolve_conflict");
+        Ok(value)
+    }
+}
+
+/// Resolve the conflicts of the worktree file in `params`, either by keeping a side of each conflict region
+/// or by replacing it with the given content, and replace its conflicting index entries with the result.
+///
+/// Return the conflicts that remain in the worktree.
+pub fn resolve_conflict(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+    params: ResolveConflictParameters,
+) -> anyhow::Result<Vec<WorktreeConflict>> {
+    use gix::index::entry::{Flags, Stage};
+    let repo = ctx.gix_repo()?;
+    let path = normalize_path(&params.path);
+    let worktree_path = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no worktree"))?
+        .join(gix::path::from_bstr(BStr::new(&path)));
+
+    let mut index = repo.open_index()?;
+    let mode = index
+        .entries()
+        .iter()
+        .filter(|entry| entry.stage() != Stage::Unconflicted && entry.path(&index) == path)
+        .max_by_key(|entry| entry.stage() == Stage::Ours)
+        .map(|entry| entry.mode)
+        .ok_or_else(|| anyhow::anyhow!("'{path}' isn't conflicted in the worktree"))?;
+
+    let resolved = match (params.side, params.content) {
+        (Some(side), None) => {
+            let data = std::fs::read(&worktree_path)?;
+            let text = String::from_utf8(data).map_err(|_| {
+                anyhow::anyhow!("'{path}' isn't a text file, provide its content instead")
+            })?;
+            if conflict_regions(text.as_bytes()).is_empty() {
+                anyhow::bail!("'{path}' has no conflict markers, provide its content instead");
+            }
+            keep_conflict_side(&text, side)
+        }
+        (None, Some(content)) => content,
+        _ => anyhow::bail!("Exactly one of side and content must be provided"),
+    };
+
+    {
+        let mut guard = ctx.project().exclusive_worktree_access();
+        let _ = ctx.create_snapshot(
+            SnapshotDetails {
+                title: "Resolved a conflict".to_owned(),
+                ..SnapshotDetails::new(OperationKind::FileChanges)
+            },
+            guard.write_permission(),
+        );
+        std::fs::write(&worktree_path, &resolved)?;
+        let blob_id = repo.write_blob(resolved.as_bytes())?.detach();
+        index.remove_entries(|_, entry_path, _| entry_path == path);
+        index.dangerously_push_entry(
+            Default::default(),
+            blob_id,
+            Flags::empty(),
+            mode,
+            BStr::new(&path),
+        );
+        index.sort_entries();
+        index.write(Default::default())?;
+    }
+
+    // If there's an app handle provided, emit an event to update the worktree changes in the UI.
+    if let Some(app_handle) = app_handle {
+        let worktree_changes: but_core::ui::WorktreeChanges =
+            but_core::diff::worktree_changes(&repo)?.into();
+        app_handle.emit_worktree_changes(ctx.project().id, &worktree_changes.into());
+    }
+
+    worktree_conflicts(ctx)
+}
+
+/// Return `text` with each conflict region replaced by the lines of `side`.
+fn keep_conflict_side(text: &str, side: ConflictSide) -> String {
+    let regions = conflict_regions(text.as_bytes());
+    let mut regions = regions.iter().peekable();
+    let mut out = String::with_capacity(text.len());
+    for (line_number, line) in (1..).zip(text.split_inclusive('\n')) {
+        match regions.peek() {
+            Some(region) if line_number == region.start_line => match side {
+                ConflictSide::Ours => out.push_str(&region.ours),
+                ConflictSide::Theirs => out.push_str(&region.theirs),
+                ConflictSide::Both => {
+                    out.push_str(&region.ours);
+                    out.push_str(&region.theirs);
+                }
+            },
+            Some(region) if line_number <= region.end_line && line_number > region.start_line => {
+                if line_number == region.end_line {
+                    regions.next();
+                }
+            }
+            _ => out.push_str(line),
+        }
+    }
+    out
+}
+
+pub struct PruneStaleAssignments;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneStaleAssignmentsParameters {}
+
+impl Tool for PruneStaleAssignments {
+    fn name(&self) -> String {
+        "prune_stale_assignments".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Remove the hunk assignments that don't match any uncommitted change anymore, and list them by stack.
+        </description>
+
+        <important_notes>
+            Assignments become stale when their changes are reverted, committed outside of GitButler,
+            or edited so much that their hunks don't overlap anymore. They make assignedToStack unreliable.
+            Assignments of hunks that merely moved by a few lines, so they still overlap, are kept.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(PruneStaleAssignmentsParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn mutates_workspace(&self) -> bool {
+        true
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let value = prune_stale_assignments(ctx, app_handle).to_json("prune_stale_assignments");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrunedAssignment {
+    /// The path of the file the hunk was in.
+    pub path: String,
+    /// The header of the hunk, or `None` if the assignment was for the whole file.
+    pub hunk_header: Option<but_workspace::HunkHeader>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrunedAssignments {
+    /// The stack the removed assignments assigned their hunks to, or `None` for unassigned hunks.
+    pub stack_id: Option<StackId>,
+    /// The removed assignments, ordered by path.
+    pub removed: Vec<PrunedAssignment>,
+}
+
+impl ToolResult for Result<Vec<PrunedAssignments>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<PrunedAssignments>")
+    }
+}
+
+/// The maximum number of stale hunk assignments that are removed when listing changes with auto-pruning.
+pub const AUTO_PRUNE_LIMIT: usize = 100;
+
+/// Remove the persisted hunk assignments that don't match any uncommitted hunk anymore.
+///
+/// Return the removed assignments grouped by the stack they assigned their hunk to, unassigned ones first.
+pub fn prune_stale_assignments(
+    ctx: &mut CommandContext,
+    app_handle: Option<&tauri::AppHandle>,
+) -> anyhow::Result<Vec<PrunedAssignments>> {
+    let repo = ctx.gix_repo()?;
+    let worktree = but_core::diff::worktree_changes(&repo)?;
+    let (diff, warnings) =
+        unified_diff_for_changes(&repo, worktree.changes, ctx.app_settings().context_lines)?;
+    let pruned = prune_assignments_not_in(ctx, &diff, &warnings, None)?;
+
+    // If there's an app handle provided, emit an event so the UI drops the assignments as well.
+    if let (Some(app_handle), false) = (app_handle, pruned.is_empty()) {
+        let worktree_changes: but_core::ui::WorktreeChanges =
+            but_core::diff::worktree_changes(&repo)?.into();
+        let changes = but_hunk_assignment::WorktreeChanges {
+            assignments: but_hunk_assignment::persisted_assignments(ctx)?,
+            ..worktree_changes.into()
+        };
+        app_handle.emit_worktree_changes(ctx.project().id, &changes);
+    }
+    Ok(pruned)
+}
+
+/// Remove up to `limit` persisted hunk assignments, or all, that match none of the hunks of `changes`,
+/// and return them grouped by stack.
+///
+/// Assignments of the files in `warnings` are kept, as their hunks couldn't be computed.
+/// An assignment matches a hunk of the same file if their headers are equal, or if both their old and new line ranges
+/// overlap, which is how the assignments are carried over to changed hunks as well.
+fn prune_assignments_not_in(
+    ctx: &mut CommandContext,
+    changes: &[(TreeChange, UnifiedDiff)],
+    warnings: &[DiffWarning],
+    limit: Option<usize>,
+) -> anyhow::Result<Vec<PrunedAssignments>> {
+    let is_stale = |assignment: &but_hunk_assignment::HunkAssignment| {
+        if warnings
+            .iter()
+            .any(|warning| warning.path == assignment.path_bytes)
+        {
+            return false;
+        }
+        let Some((_, diff)) = changes
+            .iter()
+            .find(|(change, _)| change.path == assignment.path_bytes)
+        else {
+            return true;
+        };
+        let (UnifiedDiff::Patch { hunks, .. }, Some(header)) = (diff, assignment.hunk_header)
+        else {
+            // Files without hunks are assigned as a whole, so the assignment is valid as long as the file is changed.
+            return false;
+        };
+        !hunks.iter().any(|hunk| {
+            let hunk = but_workspace::HunkHeader::from(hunk);
+            hunk == header
+                || (hunk.old_range().intersects(header.old_range())
+                    && hunk.new_range().intersects(header.new_range()))
+        })
+    };
+
+    let assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    let mut remaining = Vec::with_capacity(assignments.len());
+    let mut pruned: BTreeMap<Option<StackId>, Vec<PrunedAssignment>> = BTreeMap::new();
+    let mut count = 0;
+    for assignment in assignments {
+        if limit.is_some_and(|limit| count >= limit) || !is_stale(&assignment) {
+            remaining.push(assignment);
+            continue;
+        }
+        count += 1;
+        pruned
+            .entry(assignment.stack_id)
+            .or_default()
+            .push(PrunedAssignment {
+                path: assignment.path_bytes.to_string(),
+                hunk_header: assignment.hunk_header,
+            });
+    }
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    but_hunk_assignment::set_persisted_assignments(ctx, remaining)?;
+
+    Ok(pruned
+        .into_iter()
+        .map(|(stack_id, mut removed)| {
+            removed.sort_by(|a, b| a.path.cmp(&b.path));
+            PrunedAssignments { stack_id, removed }
+        })
+        .collect())
+}
+
+/// The number of characters per token used to estimate token counts, unless another ratio is given.
+pub const DEFAULT_CHARS_PER_TOKEN: f64 = 4.0;
+
+pub struct GetWorkspaceTokenEstimate;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetWorkspaceTokenEstimateParameters {
+    /// The number of characters per token.
+    #[schemars(description = "
+    <description>
+        The number of characters that make up a token on average, used to turn byte counts into token counts.
+        Defaults to 4.
+    </description>
+
+    <important_notes>
+        The ratio has to be positive.
+        Use a lower ratio for content with many symbols, like minified code, as it's split into more tokens.
+    </important_notes>
+    ")]
+    pub chars_per_token: Option<f64>,
+}
+
+impl Tool for GetWorkspaceTokenEstimate {
+    fn name(&self) -> String {
+        "get_workspace_token_estimate".to_string()
+    }
+
+    fn description(&self) -> String {
+        "
+        <description>
+            Estimate how many tokens the project status would take, without computing its diffs.
+            The estimate is split into the stacks with their commits, and the diffs of the uncommitted changes.
+        </description>
+
+        <important_notes>
+            Use this tool before get_project_status when the workspace may have large changes, to decide whether
+            to get the full status, or summaries like get_stacks_summary and get_stack_diff_summary instead.
+            The size of the diffs is estimated from the size of the changed files before and after the change,
+            so it's usually larger than the actual diffs.
+        </important_notes>
+        "
+        .to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        let schema = schema_for!(GetWorkspaceTokenEstimateParameters);
+        serde_json::to_value(&schema).unwrap_or_default()
+    }
+
+    fn call(
+        self: Arc<Self>,
+        parameters: serde_json::Value,
+        ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        let params: GetWorkspaceTokenEstimateParameters = serde_json::from_value(parameters)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input parameters: {}", e))?;
+
+        let repo = ctx.gix_repo()?;
+        let value = workspace_token_estimate(ctx, &repo, params.chars_per_token)
+            .to_json("get_workspace_token_estimate");
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceTokenEstimate {
+    /// The number of characters per token the estimate is based on.
+    pub chars_per_token: f64,
+    /// The number of stacks in the workspace.
+    pub stacks: usize,
+    /// The number of commits on the branches of the stacks.
+    pub commits: usize,
+    /// The number of bytes the stacks take in the project status.
+    pub stacks_bytes: u64,
+    /// The number of files with uncommitted changes.
+    pub changed_files: usize,
+    /// The number of bytes of the changed files, before and after their change, as an upper bound of the size of their diffs.
+    pub diff_bytes: u64,
+    /// The estimated number of tokens of the stacks.
+    pub stacks_tokens: u64,
+    /// The estimated number of tokens of the diffs.
+    pub diff_tokens: u64,
+    /// The estimated number of tokens of the whole project status.
+    pub total_tokens: u64,
+}
+
+impl ToolResult for Result<WorkspaceTokenEstimate, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "WorkspaceTokenEstimate")
+    }
+}
+
+/// Estimate the number of tokens of the project status with `chars_per_token`, or [`DEFAULT_CHARS_PER_TOKEN`],
+/// from the size of its stacks and of the files with uncommitted changes, without computing their diffs.
+pub fn workspace_token_estimate(
+    ctx: &mut CommandContext,
+    repo: &gix::Repository,
+    chars_per_token: Option<f64>,
+) -> anyhow::Result<WorkspaceTokenEstimate> {
+    let chars_per_token = chars_per_token.unwrap_or(DEFAULT_CHARS_PER_TOKEN);
+    if !chars_per_token.is_finite() || chars_per_token <= 0.0 {
+        anyhow::bail!(
+            "The number of characters per token has to be positive, got {chars_per_token}"
+        );
+    }
+
+    let stacks = entries_to_simple_stacks(&stacks(ctx, repo)?, ctx, repo)?;
+    let commits = stacks
+        .iter()
+        .flat_map(|stack| &stack.branches)
+        .map(|branch| branch.commits.len())
+        .sum();
+    let stacks_bytes = serde_json::to_vec(&stacks)?.len() as u64;
+
+    let worktree_dir = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no worktree"))?;
+    let changes = but_core::diff::worktree_changes(repo)?.changes;
+    let mut diff_bytes = 0;
+    for change in &changes {
+        // The path is part of the diff as well, in its header.
+        diff_bytes += change.path.len() as u64;
+        if let Some((previous_state, _)) = change.status.previous_state_and_path() {
+            diff_bytes += repo
+                .find_header(previous_state.id)
+                .map(|header| header.size())
+                .unwrap_or_default();
+        }
+        if change.status.state().is_some() {
+            diff_bytes += gix::path::try_from_bstr(change.path.as_bstr())
+                .ok()
+                .and_then(|path| std::fs::symlink_metadata(worktree_dir.join(path)).ok())
+                .map(|metadata| metadata.len())
+                .unwrap_or_default();
+        }
+    }
+
+    let tokens = |bytes: u64| (bytes as f64 / chars_per_token).ceil() as u64;
+    let (stacks_tokens, diff_tokens) = (tokens(stacks_bytes), tokens(diff_bytes));
+    Ok(WorkspaceTokenEstimate {
+        chars_per_token,
+        stacks: stacks.len(),
+        commits,
+        stacks_bytes,
+        changed_files: changes.len(),
+        diff_bytes,
+        stacks_tokens,
+        diff_tokens,
+        total_tokens: stacks_tokens + diff_tokens,
+    })
+}
+
+/// Pick `commit_id` into the branch `branch_name` of `stack`, and rebase the commits above it.
+///
+/// The commit is placed on top of the branch, or right above the base of the branch if `at_base` is true,
+/// and gets `new_message` if it's set.
+/// Return the id of the commit after the rebase.
+fn pick_into_branch(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack: &mut gitbutler_stack::Stack,
+    branch_name: &str,
+    commit_id: gix::ObjectId,
+    new_message: Option<BString>,
+    at_base: bool,
+) -> anyhow::Result<gix::ObjectId> {
+    // The steps are ordered from the bottom-most branch, with each branch's picks followed by its reference.
+    let mut steps = stack.as_rebase_steps(ctx, repo)?;
+    let is_reference =
+        |step: &but_rebase::RebaseStep| matches!(step, but_rebase::RebaseStep::Reference(_));
+    let tip = steps
+        .iter()
+        .position(|step| {
+            matches!(step, but_rebase::RebaseStep::Reference(reference) if reference.to_string() == branch_name)
+        })
+        .ok_or_else(|| anyhow::anyhow!("Branch '{}' not found in stack", branch_name))?;
+    let position = if at_base {
+        steps[..tip]
+            .iter()
+            .rposition(is_reference)
+            .map_or(0, |below| below + 1)
+    } else {
+        tip
+    };
+    steps.insert(
+        position,
+        but_rebase::RebaseStep::Pick {
+            commit_id,
+            new_message,
+        },
+    );
+
+    let mut rebase = but_rebase::Rebase::new(repo, stack.merge_base(ctx)?, None)?;
+    rebase.steps(steps)?;
+    rebase.rebase_noops(false);
+    let output = rebase.rebase()?;
+    stack.set_heads_from_rebase_output(ctx, output.references)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    gitbutler_branch_actions::update_workspace_commit(&vb_state, ctx)?;
+
+    Ok(output
+        .commit_mapping
+        .iter()
+        .find(|(_, old, _)| *old == commit_id)
+        .map(|(_, _, new)| *new)
+        .unwrap_or(commit_id))
+}
+
+fn ref_metadata_toml(project: &Project) -> anyhow::Result<VirtualBranchesTomlMetadata> {
+    VirtualBranchesTomlMetadata::from_path(project.gb_dir().join("virtual_branches.toml"))
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RichHunk {
+    /// Identifies the hunk by its content, so it's the same as long as the hunk doesn't change.
+    pub change_ref: String,
+    /// The diff of the hunk, in the requested format.
+    #[serde(flatten)]
+    pub diff: HunkDiff,
+    /// The stack ID this hunk is assigned to, if any.
+    pub assigned_to_stack: Option<but_workspace::StackId>,
+    /// The locks this hunk has, if any.
+    pub dependency_locks: Vec<but_hunk_dependency::ui::HunkLock>,
+    /// How certain the assignment of this hunk is, from 0.0 if it's unassigned to 1.0 if it's locked to exactly one commit.
+    /// This is `None` if the hunk has no assignment.
+    pub assignment_confidence: Option<f32>,
+    /// Whether the hunk conflicts with a hunk of another stack that changes the same lines,
+    /// which is only determined when the changes of several stacks are combined.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub has_conflict: bool,
+}
+
+/// How the diff of a hunk is represented in the results of tools.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffFormat {
+    /// The unified diff text of the hunk.
+    #[default]
+    Unified,
+    /// The header of the hunk along with each of its lines.
+    Structured,
+}
+
+/// The diff of a hunk, as unified diff text or split into its lines.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(untagged)]
+pub enum HunkDiff {
+    Unified {
+        /// The unified diff of the hunk, including its header.
+        diff: String,
+    },
+    Structured {
+        /// The header of the hunk, like `@@ -1,2 +1,3 @@`.
+        header: String,
+        /// The lines of the hunk, in order.
+        lines: Vec<DiffLine>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    /// Whether the line was added, removed or is unchanged.
+    pub kind: DiffLineKind,
+    /// The line without its leading marker and trailing newline.
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+impl HunkDiff {
+    /// Return this diff in `format`, splitting or joining the lines of the hunk as needed.
+    pub fn into_format(self, format: DiffFormat) -> Self {
+        match (self, format) {
+            (HunkDiff::Unified { diff }, DiffFormat::Structured) => {
+                let mut lines = diff.split_terminator('\n');
+                let header = lines.next().unwrap_or_default().to_owned();
+                let lines = lines
+                    .map(|line| {
+                        let (kind, content) = match line.split_at_checked(1) {
+                            Some(("+", content)) => (DiffLineKind::Added, content),
+                            Some(("-", content)) => (DiffLineKind::Removed, content),
+                            Some((_, content)) => (DiffLineKind::Context, content),
+                            None => (DiffLineKind::Context, line),
+                        };
+                        DiffLine {
+                            kind,
+                            content: content.to_owned(),
+                        }
+                    })
+                    .collect();
+                HunkDiff::Structured { header, lines }
+            }
+            (HunkDiff::Structured { header, lines }, DiffFormat::Unified) => {
+                let mut diff = format!("{header}\n");
+                for line in lines {
+                    let marker = match line.kind {
+                        DiffLineKind::Context => ' ',
+                        DiffLineKind::Added => '+',
+                        DiffLineKind::Removed => '-',
+                    };
+                    diff.push(marker);
+                    diff.push_str(&line.content);
+                    diff.push('\n');
+                }
+                HunkDiff::Unified { diff }
+            }
+            (diff, _) => diff,
+        }
+    }
+}
+
+/// Represent the hunks of all `file_changes` in `format`.
+fn set_diff_format(file_changes: &mut [FileChange], format: DiffFormat) {
+    for hunk in file_changes.iter_mut().flat_map(|change| &mut change.hunks) {
+        hunk.diff = std::mem::replace(
+            &mut hunk.diff,
+            HunkDiff::Unified {
+                diff: String::new(),
+            },
+        )
+        .into_format(format);
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimpleCommit {
+    /// The commit sha.
+    #[serde(with = "gitbutler_serde::object_id")]
+    pub id: gix::ObjectId,
+    /// The commit message.
+    pub message_title: String,
+    /// The commit message body.
+    pub message_body: String,
+    /// Whether the commit only exists on the remote branch, and isn't part of the workspace.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub remote_only: bool,
+    /// Whether the commit was pushed, so it exists both locally and on the remote branch.
+    ///
+    /// Rewriting a pushed commit makes the branch diverge from its remote branch.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub pushed: bool,
+    /// The ids of the parents of the commit, the first parent first.
+    ///
+    /// This is empty if the parents aren't known, like for commits that only exist on the remote branch.
+    #[serde(
+        with = "gitbutler_serde::object_id_vec",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub parent_ids: Vec<gix::ObjectId>,
+    /// The position of the commit in its branch, with the head of the branch at 0, its parent at 1, and so on.
+    ///
+    /// This is only set for the commits of branches, and not for commits that only exist on the remote branch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<u32>,
+}
+
+impl From<but_workspace::ui::Commit> for SimpleCommit {
+    fn from(commit: but_workspace::ui::Commit) -> Self {
+        SimpleCommit {
+            parent_ids: commit.parent_ids,
+            pushed: matches!(
+                commit.state,
+                but_workspace::ui::CommitState::LocalAndRemote(_)
+            ),
+            ..SimpleCommit::from_message(commit.id, &commit.message.to_string())
+        }
+    }
+}
+
+impl SimpleCommit {
+    /// Create the commit with `id` by splitting its `message` into title and body.
+    ///
+    /// The body is empty if the message is only a title, even if it's followed by blank lines.
+    fn from_message(id: gix::ObjectId, message_str: &str) -> Self {
+        let mut lines = message_str.lines();
+        let message_title = lines.next().unwrap_or_default().trim_end().to_string();
+        let message_body = lines.collect::<Vec<_>>().join("\n");
+        // Remove the empty lines separating the body from the title, and trailing whitespace.
+        let message_body = message_body
+            .trim_start_matches(['\r', '\n'])
+            .trim_end()
+            .to_string();
+        SimpleCommit {
+            id,
+            message_title,
+            message_body,
+            remote_only: false,
+            pushed: false,
+            parent_ids: vec![],
+            position: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimpleBranch {
+    /// The name of the branch.
+    pub name: String,
+    /// The name that selects the branch even if other stacks have a branch of the same name.
+    pub qualified_name: String,
+    /// Whether other stacks have a branch of the same name, so it has to be selected by its qualified name.
+    pub ambiguous_name: bool,
+    /// The description of the branch.
+    pub description: Option<String>,
+    /// The commits in the branch, ordered from its head, the newest commit, to its oldest commit.
+    ///
+    /// Commits that only exist on the remote branch come first. The others each have their position,
+    /// which is also their index among the commits that aren't remote-only.
+    pub commits: Vec<SimpleCommit>,
+    /// Whether all commits of the branch were integrated into the target branch, so it shouldn't be committed to.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub integrated: bool,
+}
 
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -1053,6 +11881,13 @@ pub struct SimpleStack {
     pub name: String,
     /// The branches in the stack.
     pub branches: Vec<SimpleBranch>,
+    /// Whether the stack is applied to the workspace.
+    ///
+    /// Stacks that aren't applied have to be applied before they can be committed to.
+    pub applied: bool,
+    /// How many commits the stack is ahead and behind the target branch, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_ahead_behind: Option<AheadBehind>,
 }
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -1061,75 +11896,577 @@ pub struct FileChange {
     pub path: String,
     /// The file change status
     pub status: String,
-    /// The hunk changes in the file.
+    /// The hunk changes in the file, ordered by the line they start at in the new version of the file.
+    ///
+    /// This is empty if the diff of the file is suppressed.
     pub hunks: Vec<RichHunk>,
+    /// Whether the diff of the file is disabled with `-diff` in `.gitattributes`, so it's treated like a binary file
+    /// and its hunks are left out.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub diff_suppressed: bool,
+    /// The custom diff driver set with `diff=<driver>` in `.gitattributes`, if there is one.
+    ///
+    /// The hunks are the plain text diff, which may not be how the driver would show the changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_driver: Option<String>,
+}
+
+impl ToolResult for Result<Vec<FileChange>, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "Vec<FileChange>")
+    }
+}
+
+/// Represents the status of a project, including applied stacks and file changes.
+///
+/// The shape of this struct is designed to be serializable and as simple as possible for use in LLM context.
+///
+/// Everything in it is ordered deterministically, so the status of an unchanged project serializes identically.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectStatus {
+    /// List of stacks applied to the project's workspace, ordered by their position in the workspace, then by their id.
+    ///
+    /// The branches of each stack are ordered by their position in the stack.
+    pub stacks: Vec<SimpleStack>,
+    /// Unified diff changes that could be committed, ordered by their path.
+    pub file_changes: Vec<FileChange>,
+    /// Changed files that were left out because they couldn't be read, ordered by their path.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<DiffWarning>,
+    /// Stacks that were recently removed from the workspace, if requested.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unapplied_stacks: Vec<SimpleStack>,
+}
+
+impl ToolResult for Result<ProjectStatus, anyhow::Error> {
+    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
+        result_to_json(self, action_identifier, "ProjectStatus")
+    }
+}
+
+/// Return the stacks and the uncommitted changes of the project, only of the paths in `filter_changes` if set.
+///
+/// No assignments are cached between calls, see [`project_status_response()`] for that.
+pub fn get_project_status(
+    ctx: &mut CommandContext,
+    repo: &gix::Repository,
+    filter_changes: Option<Vec<BString>>,
+) -> anyhow::Result<ProjectStatus> {
+    let context_lines = ctx.app_settings().context_lines;
+    let worktree_changes = but_core::diff::worktree_changes(repo)?.changes;
+    project_status(
+        ctx,
+        repo,
+        worktree_changes,
+        filter_changes,
+        context_lines,
+        true,
+        false,
+        &AssignmentsCache::default(),
+    )
+}
+
+/// Like [`get_project_status()`], but for `worktree_changes`, all changes in the worktree,
+/// with `context_lines` around the changes in the diffs instead of the ones in the app settings.
+///
+/// If `include_stacks` is `false`, the stacks aren't listed, which is considerably faster.
+/// If `auto_prune` is `true`, stale hunk assignments are removed first, see [`get_filtered_changes()`].
+#[allow(clippy::too_many_arguments)]
+fn project_status(
+    ctx: &mut CommandContext,
+    repo: &gix::Repository,
+    worktree_changes: Vec<TreeChange>,
+    filter_changes: Option<Vec<BString>>,
+    context_lines: u32,
+    include_stacks: bool,
+    auto_prune: bool,
+    assignments_cache: &AssignmentsCache,
+) -> anyhow::Result<ProjectStatus> {
+    let stacks = if include_stacks {
+        let stacks = stacks(ctx, repo)?;
+        let mut stacks = entries_to_simple_stacks(&stacks, ctx, repo)?;
+        add_target_ahead_behind(ctx, repo, &mut stacks)?;
+        stacks
+    } else {
+        vec![]
+    };
+
+    let (file_changes, warnings) = filtered_changes(
+        ctx,
+        repo,
+        worktree_changes,
+        filter_changes,
+        context_lines,
+        auto_prune,
+        assignments_cache,
+    )?;
+
+    Ok(ProjectStatus {
+        stacks,
+        file_changes,
+        warnings,
+        unapplied_stacks: vec![],
+    })
+}
+
+/// How many commits a stack has that its target doesn't have, and the other way around.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AheadBehind {
+    /// The number of commits of the stack that aren't in the target.
+    pub ahead: usize,
+    /// The number of commits of the target that aren't in the stack.
+    pub behind: usize,
 }
 
-impl ToolResult for Result<Vec<FileChange>, anyhow::Error> {
-    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
-        result_to_json(self, action_identifier, "Vec<FileChange>")
-    }
+/// Return the commit at the tip of the target branch of the project, or the commit the workspace is based on
+/// if the target branch can't be found, or `None` if the project has no target.
+fn target_tip(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+) -> anyhow::Result<Option<gix::ObjectId>> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let Some(target) = vb_state.maybe_get_default_target()? else {
+        return Ok(None);
+    };
+    let tip = repo
+        .try_find_reference(&target.branch.to_string())?
+        .map(|mut reference| reference.peel_to_id_in_place().map(|id| id.detach()))
+        .transpose()?
+        .unwrap_or_else(|| target.sha.to_gix());
+    Ok(Some(tip))
 }
 
-/// Represents the status of a project, including applied stacks and file changes.
+/// Set how far each of `stacks` is ahead and behind the tip of the target branch, computed with a single walk
+/// for all of them by [`ahead_behind_per_tip()`].
 ///
-/// The shape of this struct is designed to be serializable and as simple as possible for use in LLM context.
-#[derive(Debug, Clone, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ProjectStatus {
-    /// List of stacks applied to the project's workspace
-    pub stacks: Vec<SimpleStack>,
-    /// Unified diff changes that could be committed.
-    pub file_changes: Vec<FileChange>,
+/// Nothing is set if the project has no target.
+pub fn add_target_ahead_behind(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stacks: &mut [SimpleStack],
+) -> anyhow::Result<()> {
+    let Some(target) = target_tip(ctx, repo)? else {
+        return Ok(());
+    };
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let tips = stacks
+        .iter()
+        .map(|stack| vb_state.get_stack(stack.id)?.head_oid(repo))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (stack, ahead_behind) in stacks
+        .iter_mut()
+        .zip(ahead_behind_per_tip(repo, target, &tips)?)
+    {
+        stack.target_ahead_behind = Some(ahead_behind);
+    }
+    Ok(())
 }
 
-impl ToolResult for Result<ProjectStatus, anyhow::Error> {
-    fn to_json(&self, action_identifier: &str) -> serde_json::Value {
-        result_to_json(self, action_identifier, "ProjectStatus")
+/// Return how far each of the commits in `tips` is ahead and behind `target`, in the order of `tips`.
+///
+/// Instead of walking the commit graph twice per tip, it's walked once for all tips and the target together,
+/// down to the commit all of them are based on. Each commit is marked with the tips it's reachable from,
+/// which tells which tips it counts for.
+///
+/// Fails if the tips and `target` have no commit in common.
+pub fn ahead_behind_per_tip(
+    repo: &gix::Repository,
+    target: gix::ObjectId,
+    tips: &[gix::ObjectId],
+) -> anyhow::Result<Vec<AheadBehind>> {
+    if tips.is_empty() {
+        return Ok(vec![]);
+    }
+    let all = tips.iter().copied().chain(Some(target)).collect::<Vec<_>>();
+    // The merge base and its ancestors are reachable from all tips and the target, so they don't count for any tip.
+    // It also bounds the walk, which would otherwise go through all of the history.
+    let base = repo
+        .merge_base_octopus(all.iter().copied())
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "The stacks don't share any history with the target branch {}: {}",
+                target,
+                err
+            )
+        })?
+        .detach();
+    let mut parents = HashMap::new();
+    for info in repo
+        .rev_walk(all.iter().copied())
+        .with_hidden(Some(base))
+        .all()?
+    {
+        let info = info?;
+        parents.insert(info.id, info.parent_ids.to_vec());
+    }
+
+    // Bit `i` of a mask is set if the commit is reachable from the tip `i`, and the last bit if it's reachable from the target.
+    let target_bit = tips.len();
+    let words = target_bit / 64 + 1;
+    let is_set = |mask: &[u64], bit: usize| mask[bit / 64] & (1u64 << (bit % 64)) != 0;
+    let mut masks: HashMap<gix::ObjectId, Vec<u64>> = HashMap::new();
+    for (bit, id) in all.iter().enumerate() {
+        if parents.contains_key(id) {
+            masks.entry(*id).or_insert_with(|| vec![0; words])[bit / 64] |= 1u64 << (bit % 64);
+        }
+    }
+
+    // Masks are passed from children to parents, and a commit passes its mask on once all its children did.
+    let mut pending_children: HashMap<gix::ObjectId, usize> = HashMap::new();
+    for parent in parents.values().flatten() {
+        if parents.contains_key(parent) {
+            *pending_children.entry(*parent).or_default() += 1;
+        }
+    }
+    let mut ready = parents
+        .keys()
+        .filter(|id| !pending_children.contains_key(id))
+        .copied()
+        .collect::<Vec<_>>();
+    while let Some(id) = ready.pop() {
+        let mask = masks.get(&id).cloned().unwrap_or_else(|| vec![0; words]);
+        for parent in &parents[&id] {
+            let Some(pending) = pending_children.get_mut(parent) else {
+                continue;
+            };
+            let parent_mask = masks.entry(*parent).or_insert_with(|| vec![0; words]);
+            for (word, child_word) in parent_mask.iter_mut().zip(&mask) {
+                *word |= child_word;
+            }
+            *pending -= 1;
+            if *pending == 0 {
+                ready.push(*parent);
+            }
+        }
+    }
+
+    let mut counts = vec![AheadBehind::default(); tips.len()];
+    for mask in masks.values() {
+        let in_target = is_set(mask, target_bit);
+        for (bit, count) in counts.iter_mut().enumerate() {
+            match (is_set(mask, bit), in_target) {
+                (true, false) => count.ahead += 1,
+                (false, true) => count.behind += 1,
+                _ => {}
+            }
+        }
     }
+    Ok(counts)
 }
 
-pub fn get_project_status(
+/// Return the stacks that aren't in the workspace and were unapplied within the last `limit` operations,
+/// the most recently unapplied one first.
+pub fn recently_unapplied_stacks(
     ctx: &mut CommandContext,
     repo: &gix::Repository,
-    filter_changes: Option<Vec<BString>>,
-) -> anyhow::Result<ProjectStatus> {
-    let stacks = stacks(ctx, repo)?;
-    let stacks = entries_to_simple_stacks(&stacks, ctx, repo)?;
+    limit: usize,
+) -> anyhow::Result<Vec<SimpleStack>> {
+    // Unapplying a stack records the name of the stack or of its reference in the oplog.
+    let names = ctx
+        .list_snapshots(limit, None, vec![])?
+        .into_iter()
+        .filter_map(|snapshot| snapshot.details)
+        .filter(|details| {
+            matches!(
+                details.operation,
+                OperationKind::DeleteBranch | OperationKind::UnapplyBranch
+            )
+        })
+        .flat_map(|details| details.trailers)
+        .filter(|trailer| trailer.key == "name")
+        .map(|trailer| trailer.value)
+        .collect::<Vec<_>>();
 
-    let file_changes = get_filtered_changes(ctx, repo, filter_changes)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let unapplied = vb_state
+        .list_all_stacks()?
+        .into_iter()
+        .filter(|stack| !stack.in_workspace)
+        .collect::<Vec<_>>();
 
-    Ok(ProjectStatus {
-        stacks,
-        file_changes,
-    })
+    let mut stacks: Vec<SimpleStack> = vec![];
+    for name in names {
+        let Some(stack) = unapplied.iter().find(|stack| {
+            stack.name == name
+                || stack.branches().iter().any(|branch| {
+                    branch.name() == &name
+                        || branch.full_name().is_ok_and(|n| n.to_string() == name)
+                })
+        }) else {
+            continue;
+        };
+        if stacks.iter().any(|s| s.id == stack.id) {
+            continue;
+        }
+
+        let branches = simple_branches(ctx, repo, stack)?;
+        if branches.is_empty() {
+            continue;
+        }
+        stacks.push(SimpleStack {
+            id: stack.id,
+            name: stack
+                .branches()
+                .last()
+                .map(|branch| branch.name().to_owned())
+                .unwrap_or_else(|| stack.name.clone()),
+            branches,
+            applied: false,
+            target_ahead_behind: None,
+        });
+    }
+    Ok(stacks)
 }
 
+/// Return the uncommitted changes of the paths in `filter_changes`, or of all paths, with their hunk assignments
+/// and diffs with `context_lines`, along with warnings for changes that couldn't be read.
+///
+/// If `auto_prune` is `true`, up to [`AUTO_PRUNE_LIMIT`] persisted hunk assignments that don't match any of
+/// the changes anymore are removed before the assignments are joined with the changes.
+/// The assignments are reused from and kept in `assignments_cache`, see [`cached_assignments()`].
 pub fn get_filtered_changes(
     ctx: &mut CommandContext,
     repo: &gix::Repository,
     filter_changes: Option<Vec<BString>>,
-) -> Result<Vec<FileChange>, anyhow::Error> {
+    context_lines: u32,
+    auto_prune: bool,
+    assignments_cache: &AssignmentsCache,
+) -> Result<(Vec<FileChange>, Vec<DiffWarning>), anyhow::Error> {
     let worktree = but_core::diff::worktree_changes(repo)?;
-    let changes = if let Some(filter) = filter_changes {
-        worktree
-            .changes
-            .into_iter()
-            .filter(|change| filter.iter().any(|f| *f == change.path))
-            .collect::<Vec<_>>()
-    } else {
-        worktree.changes.clone()
-    };
-    let diff = unified_diff_for_changes(repo, changes, ctx.app_settings().context_lines)?;
-    let (assignments, _) = but_hunk_assignment::assignments_with_fallback(
+    filtered_changes(
         ctx,
-        true,
-        None::<Vec<but_core::TreeChange>>,
-        None,
+        repo,
+        worktree.changes,
+        filter_changes,
+        context_lines,
+        auto_prune,
+        assignments_cache,
     )
-    .map_err(|err| serde_error::Error::new(&*err))?;
-    let file_changes = get_file_changes(&diff, assignments.clone());
-    Ok(file_changes)
+}
+
+/// Like [`get_filtered_changes()`], for `worktree_changes`, all changes in the worktree, which were listed already.
+fn filtered_changes(
+    ctx: &mut CommandContext,
+    repo: &gix::Repository,
+    worktree_changes: Vec<TreeChange>,
+    filter_changes: Option<Vec<BString>>,
+    context_lines: u32,
+    auto_prune: bool,
+    assignments_cache: &AssignmentsCache,
+) -> Result<(Vec<FileChange>, Vec<DiffWarning>), anyhow::Error> {
+    // Assignments are computed for all changes, as they depend on each other.
+    let (diff, warnings) = unified_diff_for_changes(repo, worktree_changes, context_lines)?;
+    if auto_prune {
+        // The hunks match their assignments even with a different number of context lines, as they overlap.
+        prune_assignments_not_in(ctx, &diff, &warnings, Some(AUTO_PRUNE_LIMIT))?;
+    }
+    let assignments = cached_assignments(ctx, repo, &diff, assignments_cache)
+        .map_err(|err| serde_error::Error::new(&*err))?
+        .assignments;
+
+    let (diff, warnings) = if let Some(filter) = filter_changes {
+        (
+            diff.into_iter()
+                .filter(|(change, _)| filter.iter().any(|f| *f == change.path))
+                .collect::<Vec<_>>(),
+            warnings
+                .into_iter()
+                .filter(|warning| filter.iter().any(|f| *f == warning.path))
+                .collect::<Vec<_>>(),
+        )
+    } else {
+        (diff, warnings)
+    };
+    let mut file_changes = get_file_changes(&diff, assignments)?;
+    apply_diff_attributes(repo, None, &mut file_changes)?;
+    Ok((file_changes, warnings))
+}
+
+/// The hunk assignments computed by the last status call of a [`Toolset`](crate::tool::Toolset),
+/// so the next one can reuse them if the worktree didn't change.
+///
+/// A toolset works on a single project, so it only ever caches the assignments of that project.
+/// Clones share the cached assignments.
+#[derive(Default, Clone)]
+pub struct AssignmentsCache(Arc<Mutex<Option<CachedAssignments>>>);
+
+impl fmt::Debug for AssignmentsCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssignmentsCache").finish_non_exhaustive()
+    }
+}
+
+impl AssignmentsCache {
+    /// Forget the cached assignments, so they are recomputed by the next status call.
+    pub fn forget(&self) {
+        *self.lock() = None;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<CachedAssignments>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+struct CachedAssignments {
+    /// The id and head of each stack in the workspace at the time the assignments were computed.
+    workspace: Vec<(StackId, gix::ObjectId)>,
+    /// A fingerprint of the diff of each changed file the assignments were computed for.
+    fingerprints: BTreeMap<BString, u64>,
+    /// The assignments, as they were persisted.
+    assignments: Vec<but_hunk_assignment::HunkAssignment>,
+}
+
+/// The outcome of [`cached_assignments()`].
+pub struct CachedAssignmentsOutcome {
+    /// The hunk assignments of all changes.
+    pub assignments: Vec<but_hunk_assignment::HunkAssignment>,
+    /// The paths of the changes whose assignments had to be recomputed.
+    pub recomputed_paths: Vec<BString>,
+}
+
+/// Compute the hunk assignments of `changes`, which are all changes in the worktree along with their diffs.
+///
+/// The assignments in `assignments_cache` are reused if no file is new or has a different diff since they were
+/// computed, and the files whose changes are gone only lose their assignments. Otherwise, the assignments of
+/// all changes are recomputed, as they depend on each other.
+/// Everything is recomputed as well if the stacks in the workspace or the persisted assignments changed in the meantime,
+/// which is the case after mutations of the workspace.
+pub fn cached_assignments(
+    ctx: &mut CommandContext,
+    repo: &gix::Repository,
+    changes: &[(TreeChange, UnifiedDiff)],
+    assignments_cache: &AssignmentsCache,
+) -> anyhow::Result<CachedAssignmentsOutcome> {
+    let workspace = workspace_fingerprint(ctx, repo)?;
+    let fingerprints = changes
+        .iter()
+        .map(|(change, diff)| (change.path.clone(), diff_fingerprint(change, diff)))
+        .collect::<BTreeMap<_, _>>();
+
+    let cached = assignments_cache.lock().take();
+    let cached = match cached {
+        Some(cached)
+            if cached.workspace == workspace
+                && same_assignments(
+                    &but_hunk_assignment::persisted_assignments(ctx)?,
+                    &cached.assignments,
+                ) =>
+        {
+            Some(cached)
+        }
+        _ => None,
+    };
+    // Files without a fingerprint, like binary files, can't be told apart from their previous version.
+    let cached = cached.filter(|cached| {
+        fingerprints.iter().all(|(path, fingerprint)| {
+            fingerprint
+                .as_ref()
+                .is_some_and(|fingerprint| cached.fingerprints.get(path) == Some(fingerprint))
+        })
+    });
+
+    let (assignments, recomputed_paths) = match cached {
+        Some(cached) => {
+            let count = cached.assignments.len();
+            let assignments = cached
+                .assignments
+                .into_iter()
+                .filter(|assignment| fingerprints.contains_key(&assignment.path_bytes))
+                .collect::<Vec<_>>();
+            if assignments.len() != count {
+                but_hunk_assignment::set_persisted_assignments(ctx, assignments.clone())?;
+            }
+            (assignments, vec![])
+        }
+        None => {
+            let (assignments, _) = but_hunk_assignment::assignments_with_fallback(
+                ctx,
+                true,
+                Some(
+                    changes
+                        .iter()
+                        .map(|(change, _)| change.clone())
+                        .collect::<Vec<_>>(),
+                ),
+                None,
+            )?;
+            let paths = changes
+                .iter()
+                .map(|(change, _)| change.path.clone())
+                .collect();
+            (assignments, paths)
+        }
+    };
+
+    *assignments_cache.lock() = Some(CachedAssignments {
+        workspace,
+        fingerprints: fingerprints
+            .into_iter()
+            .filter_map(|(path, fingerprint)| Some((path, fingerprint?)))
+            .collect(),
+        assignments: assignments.clone(),
+    });
+
+    Ok(CachedAssignmentsOutcome {
+        assignments,
+        recomputed_paths,
+    })
+}
+
+fn workspace_fingerprint(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+) -> anyhow::Result<Vec<(StackId, gix::ObjectId)>> {
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stacks = vb_state
+        .list_stacks_in_workspace()?
+        .into_iter()
+        .map(|stack| Ok((stack.id, stack.head_oid(repo)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    stacks.sort();
+    Ok(stacks)
+}
+
+/// Return a fingerprint of the diff of `change`, or `None` if its content isn't known, like for binary files.
+fn diff_fingerprint(change: &TreeChange, diff: &UnifiedDiff) -> Option<u64> {
+    let UnifiedDiff::Patch { hunks, .. } = diff else {
+        return None;
+    };
+    let mut hasher = DefaultHasher::new();
+    change.path.hash(&mut hasher);
+    format!("{:?}", change.status).hash(&mut hasher);
+    for hunk in hunks {
+        (
+            hunk.old_start,
+            hunk.old_lines,
+            hunk.new_start,
+            hunk.new_lines,
+        )
+            .hash(&mut hasher);
+        hunk.diff.hash(&mut hasher);
+    }
+    Some(hasher.finish())
+}
+
+/// Return `true` if `a` and `b` contain the same assignments, in any order, ignoring derived data.
+fn same_assignments(
+    a: &[but_hunk_assignment::HunkAssignment],
+    b: &[but_hunk_assignment::HunkAssignment],
+) -> bool {
+    let keys = |assignments: &[but_hunk_assignment::HunkAssignment]| {
+        let mut keys = assignments
+            .iter()
+            .map(|a| {
+                serde_json::to_string(&(a.id, &a.path_bytes, a.hunk_header, a.stack_id))
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    };
+    a.len() == b.len() && keys(a) == keys(b)
 }
 
 fn entries_to_simple_stacks(
@@ -1139,113 +12476,420 @@ fn entries_to_simple_stacks(
 ) -> anyhow::Result<Vec<SimpleStack>> {
     let mut stacks = vec![];
     let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
-    for entry in entries {
-        let stack = vb_state.get_stack(entry.id)?;
-        let branches = stack.branches();
-        let branches = branches.iter().filter(|b| !b.archived);
-        let mut simple_branches = vec![];
-        for branch in branches {
-            let commits = but_workspace::local_and_remote_commits(ctx, repo, branch, &stack)?;
-
-            if commits.is_empty() {
-                continue;
-            }
-
-            let simple_commits = commits
-                .into_iter()
-                .map(SimpleCommit::from)
-                .collect::<Vec<_>>();
-
-            simple_branches.push(SimpleBranch {
-                name: branch.name.to_string(),
-                description: branch.description.clone(),
-                commits: simple_commits,
-            });
-        }
+    let ambiguous_names = ambiguous_branch_names(stack_heads(entries));
+    let mut workspace_stacks = entries
+        .iter()
+        .map(|entry| Ok((entry, vb_state.get_stack(entry.id)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    workspace_stacks.sort_by_key(|(entry, stack)| (stack.order, entry.id));
+    for (entry, stack) in workspace_stacks {
+        let mut simple_branches = simple_branches(ctx, repo, &stack)?;
         if simple_branches.is_empty() {
             continue;
         }
+        for branch in &mut simple_branches {
+            branch.ambiguous_name = ambiguous_names.contains(&branch.name);
+        }
 
         stacks.push(SimpleStack {
             id: entry.id,
             name: entry.name().unwrap_or_default().to_string(),
             branches: simple_branches,
+            applied: true,
+            target_ahead_behind: None,
         });
     }
     Ok(stacks)
 }
 
+/// Add the commits that only exist on the remote branches of `stacks` on top of the commits of each branch.
+pub fn add_remote_only_commits(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stacks: &mut [SimpleStack],
+) -> anyhow::Result<()> {
+    for stack in stacks {
+        for branch in &mut stack.branches {
+            let upstream_only = but_workspace::stack_branch_upstream_only_commits(
+                stack.id,
+                branch.name.clone(),
+                ctx,
+                repo,
+            )?;
+            let remote_only = upstream_only.into_iter().map(|commit| SimpleCommit {
+                remote_only: true,
+                ..SimpleCommit::from_message(commit.id, &commit.message.to_string())
+            });
+            branch.commits.splice(0..0, remote_only);
+        }
+    }
+    Ok(())
+}
+
+/// Return the branches of `stack` that aren't archived and have commits.
+fn simple_branches(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack: &gitbutler_stack::Stack,
+) -> anyhow::Result<Vec<SimpleBranch>> {
+    let branches = stack.branches();
+    let branches = branches.iter().filter(|b| !b.archived);
+    let mut simple_branches = vec![];
+    for branch in branches {
+        let simple_branch = simple_branch(ctx, repo, stack, branch)?;
+        if simple_branch.commits.is_empty() {
+            continue;
+        }
+        simple_branches.push(simple_branch);
+    }
+    Ok(simple_branches)
+}
+
+fn simple_branch(
+    ctx: &CommandContext,
+    repo: &gix::Repository,
+    stack: &gitbutler_stack::Stack,
+    branch: &gitbutler_stack::StackBranch,
+) -> anyhow::Result<SimpleBranch> {
+    let commits = order_from_head(but_workspace::local_and_remote_commits(
+        ctx, repo, branch, stack,
+    )?);
+    let integrated = is_integrated(&commits);
+    let simple_commits = commits
+        .into_iter()
+        .zip(0..)
+        .map(|(commit, position)| SimpleCommit {
+            position: Some(position),
+            ..SimpleCommit::from(commit)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(SimpleBranch {
+        name: branch.name.to_string(),
+        qualified_name: qualified_branch_name(stack.id, &branch.name),
+        ambiguous_name: false,
+        description: branch.description.clone(),
+        commits: simple_commits,
+        integrated,
+    })
+}
+
+/// Return the `commits` of a branch ordered from its head to its oldest commit, by following their first parents.
+///
+/// Commits that aren't connected to the others by their first parents are kept at the end, in their original order.
+fn order_from_head(mut commits: Vec<but_workspace::ui::Commit>) -> Vec<but_workspace::ui::Commit> {
+    let head = commits.iter().position(|commit| {
+        !commits
+            .iter()
+            .any(|other| other.parent_ids.first() == Some(&commit.id))
+    });
+    let mut ordered = Vec::with_capacity(commits.len());
+    let mut next = head;
+    while let Some(index) = next {
+        let commit = commits.remove(index);
+        next = commit
+            .parent_ids
+            .first()
+            .and_then(|parent| commits.iter().position(|c| c.id == *parent));
+        ordered.push(commit);
+    }
+    ordered.extend(commits);
+    ordered
+}
+
+/// Return the file changes of `changes` along with their `assignments`, ordered by their path, with their hunks ordered
+/// by the line they start at.
 fn get_file_changes(
     changes: &[(TreeChange, UnifiedDiff)],
     assingments: Vec<but_hunk_assignment::HunkAssignment>,
-) -> Vec<FileChange> {
+) -> anyhow::Result<Vec<FileChange>> {
+    let mut changes = changes.iter().collect::<Vec<_>>();
+    changes.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
     let mut file_changes = vec![];
-    for (change, unified_diff) in changes.iter() {
+    for (change, unified_diff) in changes {
         match unified_diff {
             but_core::UnifiedDiff::Patch { hunks, .. } => {
                 let path = change.path.to_string();
-                let status = match &change.status {
-                    but_core::TreeStatus::Addition { .. } => "added".to_string(),
-                    but_core::TreeStatus::Deletion { .. } => "deleted".to_string(),
-                    but_core::TreeStatus::Modification { .. } => "modified".to_string(),
-                    but_core::TreeStatus::Rename { previous_path, .. } => {
-                        format!("renamed from {}", previous_path)
-                    }
-                };
+                let status = change_status(&change.status);
 
+                let mut hunks = hunks.iter().collect::<Vec<_>>();
+                hunks.sort_by_key(|hunk| (hunk.new_start, hunk.old_start));
                 let hunks = hunks
-                    .iter()
+                    .into_iter()
                     .map(|hunk| {
+                        let change_ref = hunk_change_ref(&change.path, &hunk.diff)?;
                         let diff = hunk.diff.to_string();
                         let assignment = assingments
                             .iter()
                             .find(|a| {
                                 a.path_bytes == change.path && a.hunk_header == Some(hunk.into())
                             })
-                            .map(|a| (a.stack_id, a.hunk_locks.clone()));
+                            .map(|a| (a.stack_id, a.hunk_locks.clone(), a.confidence));
 
-                        let (assigned_to_stack, dependency_locks) =
-                            if let Some((stack_id, locks)) = assignment {
+                        let (assigned_to_stack, dependency_locks, assignment_confidence) =
+                            if let Some((stack_id, locks, confidence)) = assignment {
                                 let locks = locks.unwrap_or_default();
-                                (stack_id, locks)
+                                (stack_id, locks, Some(confidence))
                             } else {
-                                (None, vec![])
+                                (None, vec![], None)
                             };
 
-                        RichHunk {
-                            diff,
+                        Ok(RichHunk {
+                            change_ref,
+                            diff: HunkDiff::Unified { diff },
                             assigned_to_stack,
                             dependency_locks,
-                        }
+                            assignment_confidence,
+                            has_conflict: false,
+                        })
                     })
-                    .collect::<Vec<_>>();
+                    .collect::<anyhow::Result<Vec<_>>>()?;
 
                 file_changes.push(FileChange {
                     path,
                     status,
                     hunks,
+                    diff_suppressed: false,
+                    diff_driver: None,
                 });
             }
             _ => continue,
         }
     }
 
-    file_changes
+    Ok(file_changes)
+}
+
+/// Describe `status` for the results of tools.
+fn change_status(status: &but_core::TreeStatus) -> String {
+    match status {
+        but_core::TreeStatus::Addition { .. } => "added".to_string(),
+        but_core::TreeStatus::Deletion { .. } => "deleted".to_string(),
+        but_core::TreeStatus::Modification { .. } => "modified".to_string(),
+        but_core::TreeStatus::Rename { previous_path, .. } => {
+            format!("renamed from {}", previous_path)
+        }
+    }
+}
+
+/// Apply the `diff` attribute of the files in `file_changes` like git does.
+///
+/// The hunks of files with `-diff` are left out, and files with a custom diff driver are annotated with it.
+/// The attributes are read from the tree `tree_id` if it's set, and from the worktree otherwise.
+fn apply_diff_attributes(
+    repo: &gix::Repository,
+    tree_id: Option<gix::ObjectId>,
+    file_changes: &mut [FileChange],
+) -> anyhow::Result<()> {
+    use gix::worktree::stack::state::attributes::Source;
+    if file_changes.is_empty() {
+        return Ok(());
+    }
+    let mut cache = match tree_id {
+        Some(tree_id) => {
+            let index = repo.index_from_tree(&tree_id)?;
+            repo.attributes_only(&index, Source::IdMapping)?
+        }
+        None => {
+            let index = repo.index_or_empty()?;
+            // Changes to .gitattributes files apply right away, like they do for `git diff`.
+            repo.attributes_only(&index, Source::WorktreeThenIdMapping)?
+        }
+    };
+    let mut matches = cache.selected_attribute_matches(Some("diff"));
+    for file in file_changes {
+        let entry = cache.at_entry(file.path.as_str(), None)?;
+        if !entry.matching_attributes(&mut matches) {
+            continue;
+        }
+        let Some(diff) = matches.iter().next() else {
+            continue;
+        };
+        match diff.assignment.state {
+            gix::attrs::StateRef::Unset => {
+                file.hunks.clear();
+                file.diff_suppressed = true;
+            }
+            gix::attrs::StateRef::Value(driver) => {
+                file.diff_driver = Some(driver.as_bstr().to_string());
+            }
+            gix::attrs::StateRef::Set | gix::attrs::StateRef::Unspecified => {}
+        }
+    }
+    Ok(())
+}
+
+/// Return an identifier of the hunk with `diff` in the file at `path` that only depends on their content.
+fn hunk_change_ref(path: &BStr, diff: &BStr) -> anyhow::Result<String> {
+    let mut content = BString::from(path);
+    content.push(0);
+    content.extend_from_slice(diff);
+    let id = gix::objs::compute_hash(gix::hash::Kind::Sha1, gix::object::Kind::Blob, &content)?;
+    Ok(id.to_string())
+}
+
+/// A changed file that was skipped because its diff couldn't be computed.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffWarning {
+    /// The path of the file that was skipped.
+    pub path: String,
+    /// The reason the file was skipped.
+    pub error: String,
 }
 
-fn unified_diff_for_changes(
+/// Compute the unified diff for each of the `changes`.
+///
+/// Files that can't be read, e.g. because they vanished or aren't accessible, and submodules, which have no unified diff,
+/// don't fail the whole operation but are skipped and reported as warnings instead.
+pub fn unified_diff_for_changes(
     repo: &gix::Repository,
     changes: Vec<but_core::TreeChange>,
     context_lines: u32,
-) -> anyhow::Result<Vec<(but_core::TreeChange, but_core::UnifiedDiff)>> {
-    changes
-        .into_iter()
-        .map(|tree_change| {
-            tree_change
-                .unified_diff(repo, context_lines)
-                .map(|diff| (tree_change, diff.expect("no submodule")))
+) -> anyhow::Result<(
+    Vec<(but_core::TreeChange, but_core::UnifiedDiff)>,
+    Vec<DiffWarning>,
+)> {
+    let mut diffs = vec![];
+    let mut warnings = vec![];
+    for tree_change in changes {
+        match tree_change.unified_diff(repo, context_lines) {
+            Ok(Some(diff)) => diffs.push((tree_change, diff)),
+            Ok(None) => warnings.push(DiffWarning {
+                path: tree_change.path.to_string(),
+                error: "It's a submodule, which has no textual diff".to_owned(),
+            }),
+            Err(err) => warnings.push(DiffWarning {
+                path: tree_change.path.to_string(),
+                error: err.to_string(),
+            }),
+        }
+    }
+    warnings.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((diffs, warnings))
+}
+
+/// Make sure the worktree files of all `changes` can be read, failing with an error naming the first one that can't.
+fn ensure_readable(repo: &gix::Repository, changes: &[&TreeChange]) -> anyhow::Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no worktree"))?;
+    for change in changes {
+        if matches!(change.status, but_core::TreeStatus::Deletion { .. }) {
+            continue;
+        }
+        let path = workdir.join(gix::path::from_bstr(change.path.as_bstr()));
+        let readable = std::fs::symlink_metadata(&path).and_then(|metadata| {
+            if metadata.is_file() {
+                std::fs::File::open(&path).map(|_| ())
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = readable {
+            anyhow::bail!("Cannot read '{}': {}", change.path, err);
+        }
+    }
+    Ok(())
+}
+
+/// Normalize `files` against the paths of all uncommitted changes.
+fn normalize_worktree_file_params(
+    ctx: &CommandContext,
+    files: &[String],
+) -> anyhow::Result<NormalizedInput> {
+    let worktree = but_core::diff::worktree_changes(&ctx.gix_repo()?)?;
+    let changed_paths = worktree
+        .changes
+        .iter()
+        .map(|change| change.path.to_string())
+        .collect::<Vec<_>>();
+    Ok(normalize_file_params(files, &changed_paths))
+}
+
+/// Normalize `files` against the paths changed by the commit with the possibly abbreviated `commit_id`.
+fn normalize_commit_file_params(
+    ctx: &CommandContext,
+    commit_id: &str,
+    files: &[String],
+) -> anyhow::Result<NormalizedInput> {
+    let repo = ctx.gix_repo()?;
+    let commit_id = resolve_commit_id(&repo, commit_id)?;
+    let parent_id = repo
+        .find_commit(commit_id)?
+        .parent_ids()
+        .next()
+        .map(|id| id.detach());
+    let (changes, _) = but_core::diff::tree_changes(&repo, parent_id, commit_id)?;
+    let changed_paths = changes
+        .iter()
+        .map(|change| change.path.to_string())
+        .collect::<Vec<_>>();
+    Ok(normalize_file_params(files, &changed_paths))
+}
+
+/// In strict mode, fail if any of `files` has no uncommitted changes, instead of leaving it out of the commit.
+fn ensure_files_are_changed(
+    ctx: &CommandContext,
+    files: &[String],
+    strict: bool,
+) -> anyhow::Result<()> {
+    if !strict {
+        return Ok(());
+    }
+    let worktree = but_core::diff::worktree_changes(&ctx.gix_repo()?)?;
+    let unchanged = files
+        .iter()
+        .filter(|file| {
+            !worktree
+                .changes
+                .iter()
+                .any(|change| change.path.to_string() == **file)
         })
-        .collect::<Result<Vec<_>, _>>()
+        .map(|file| format!("'{file}'"))
+        .collect::<Vec<_>>();
+    if unchanged.is_empty() {
+        Ok(())
+    } else {
+        Err(StrictModeError::new(format!(
+            "There are no uncommitted changes in {}",
+            unchanged.join(", ")
+        ))
+        .into())
+    }
+}
+
+/// Fail if any changed file in `paths`, or any changed file at all if `None`, was left out of `file_changes`,
+/// either because it's listed in `warnings` or because it has no textual diff.
+fn ensure_no_changes_were_skipped(
+    repo: &gix::Repository,
+    paths: Option<&[String]>,
+    file_changes: &[FileChange],
+    warnings: &[DiffWarning],
+) -> anyhow::Result<()> {
+    if let Some(warning) = warnings.first() {
+        return Err(StrictModeError::new(format!(
+            "'{}' was left out: {}",
+            warning.path, warning.error
+        ))
+        .into());
+    }
+    for change in but_core::diff::worktree_changes(repo)?.changes {
+        let path = change.path.to_string();
+        if paths.is_some_and(|paths| !paths.contains(&path)) {
+            continue;
+        }
+        if !file_changes
+            .iter()
+            .any(|file_change| file_change.path == path)
+        {
+            return Err(StrictModeError::new(format!(
+                "'{path}' was left out as it has no textual diff, because it's binary or too large"
+            ))
+            .into());
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, JsonSchema)]
@@ -0,0 +1,65 @@
+use but_tools::branch_suggestion::suggest_branch_names;
+use but_tools::workspace::workspace_toolset;
+use serde_json::json;
+
+use super::Test;
+
+#[test]
+fn names_are_derived_from_meaningful_path_components() {
+    assert_eq!(
+        suggest_branch_names(&["src/auth/login.rs"]),
+        ["auth-login", "auth"]
+    );
+    assert_eq!(
+        suggest_branch_names(&["crates/but-tools/src/workspace/mod.rs"]),
+        ["but-tools-workspace", "but-tools"]
+    );
+    assert_eq!(
+        suggest_branch_names(&["app/components/UserProfile.tsx"]),
+        ["app-components-user-profile", "app-components", "app"]
+    );
+    assert!(suggest_branch_names(&["src/lib.rs"]).is_empty());
+    assert!(suggest_branch_names(&[] as &[&str]).is_empty());
+}
+
+#[test]
+fn sibling_files_share_their_directory() {
+    assert_eq!(
+        suggest_branch_names(&["src/auth/login.rs", "src/auth/logout.rs"]),
+        ["auth-login-logout", "auth"]
+    );
+    assert_eq!(
+        suggest_branch_names(&[
+            "src/auth/login.rs",
+            "src/auth/logout.rs",
+            "src/auth/session.rs"
+        ]),
+        ["auth"]
+    );
+}
+
+#[test]
+fn unrelated_files_fall_back_to_the_most_changed_top_level_components() {
+    assert_eq!(
+        suggest_branch_names(&[
+            "docs/intro.md",
+            "api/users.rs",
+            "api/teams.rs",
+            "web/app.ts"
+        ]),
+        ["api-docs", "api"]
+    );
+}
+
+#[test]
+fn tool_returns_the_suggestions() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+
+    let value = toolset.call_tool(
+        "get_branch_name_from_diff",
+        &json!({ "filePaths": ["src/auth/login.rs"] }).to_string(),
+    );
+    assert_eq!(value["result"], json!(["auth-login", "auth"]));
+    Ok(())
+}
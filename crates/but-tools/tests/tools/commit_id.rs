@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::fs;
+
+use but_tools::commit_id::{AmbiguousCommitIdError, InvalidCommitIdError, resolve_commit_id};
+use but_tools::tool::error_to_json;
+use but_tools::workspace::workspace_toolset;
+use serde_json::json;
+
+use super::{Test, commit_files};
+
+#[test]
+fn unique_abbreviations_resolve_to_the_full_id() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, commit) = commit_files(ctx, "branch", &["a.txt"])?;
+    let gix_repo = ctx.gix_repo()?;
+
+    let abbreviated = &commit.to_string()[..10];
+    assert_eq!(resolve_commit_id(&gix_repo, abbreviated)?, commit);
+    assert_eq!(resolve_commit_id(&gix_repo, &commit.to_string())?, commit);
+    Ok(())
+}
+
+#[test]
+fn invalid_ids_are_reported() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let gix_repo = ctx.gix_repo()?;
+
+    for id in ["not-a-commit-id", "abc", "HEAD"] {
+        let err = resolve_commit_id(&gix_repo, id).expect_err("the id is invalid");
+        assert!(err.downcast_ref::<InvalidCommitIdError>().is_some());
+        assert_eq!(error_to_json(&err, "resolve")["code"], "invalidCommitId");
+    }
+    Ok(())
+}
+
+#[test]
+fn ambiguous_abbreviations_list_the_candidates() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let gix_repo = ctx.gix_repo()?;
+
+    // Write commits until two of them share the shortest possible prefix.
+    let signature = gix::actor::Signature {
+        name: "test".into(),
+        email: "test@example.com".into(),
+        time: gix::date::Time::new(0, 0),
+    };
+    let tree = gix_repo.empty_tree().id;
+    let mut by_prefix = HashMap::new();
+    let (prefix, candidates) = (0..)
+        .find_map(|i| {
+            let commit = gix::objs::Commit {
+                tree,
+                parents: Default::default(),
+                author: signature.clone(),
+                committer: signature.clone(),
+                encoding: None,
+                message: format!("commit {i}").into(),
+                extra_headers: vec![],
+            };
+            let id = gix_repo.write_object(&commit).ok()?.detach();
+            let prefix = id.to_string()[..4].to_owned();
+            let other = by_prefix.insert(prefix.clone(), id)?;
+            let mut candidates = vec![id, other];
+            candidates.sort();
+            Some((prefix, candidates))
+        })
+        .expect("two commits share a prefix eventually");
+
+    let err = resolve_commit_id(&gix_repo, &prefix).expect_err("the prefix is ambiguous");
+    let ambiguous = err
+        .downcast_ref::<AmbiguousCommitIdError>()
+        .expect("an ambiguous id is reported as such");
+    assert_eq!(ambiguous.candidates, candidates);
+
+    let value = error_to_json(&err, "resolve");
+    assert_eq!(value["code"], "ambiguousCommitId");
+    assert_eq!(
+        value["candidates"],
+        json!(
+            candidates
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn resolved_ids_are_echoed_in_the_result() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, commit) = commit_files(ctx, "branch", &["a.txt"])?;
+    let abbreviated = commit.to_string()[..7].to_owned();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "get_commit_details",
+        &json!({ "commitId": abbreviated }).to_string(),
+    );
+
+    assert!(result["error"].is_null());
+    assert_eq!(
+        result["resolvedCommitIds"][&abbreviated],
+        commit.to_string()
+    );
+    Ok(())
+}
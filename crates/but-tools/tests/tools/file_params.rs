@@ -0,0 +1,219 @@
+use std::collections::BTreeSet;
+use std::fs;
+
+use but_tools::file_params::{CollapseReason, normalize_file_params, normalize_path};
+use but_tools::workspace::workspace_toolset;
+use rand::rngs::StdRng;
+use rand::seq::{IndexedRandom as _, SliceRandom as _};
+use rand::{Rng as _, SeedableRng as _};
+use serde_json::json;
+
+use super::Test;
+
+const CHANGED_PATHS: &[&str] = &[
+    "a.txt",
+    "docs/readme.md",
+    "src/lib.rs",
+    "src/main.rs",
+    "src/nested/mod.rs",
+];
+
+fn changed_paths() -> Vec<String> {
+    CHANGED_PATHS.iter().map(ToString::to_string).collect()
+}
+
+/// Return the changed files that `target`, a changed file or a directory, stands for.
+fn files_of(target: &str) -> Vec<&'static str> {
+    CHANGED_PATHS
+        .iter()
+        .copied()
+        .filter(|path| *path == target || path.starts_with(&format!("{target}/")))
+        .collect()
+}
+
+/// Return a random spelling of `path` that normalizes to it.
+fn spelling(rng: &mut StdRng, path: &str) -> String {
+    match rng.random_range(0..6) {
+        0 => path.to_owned(),
+        1 => format!("./{path}"),
+        2 => format!(" {path}/ "),
+        3 => path.replace('/', "//"),
+        4 => path.replace('/', "\\"),
+        _ => format!("tmp/../{path}"),
+    }
+}
+
+#[test]
+fn paths_are_normalized() {
+    for (path, expected) in [
+        ("src/lib.rs", "src/lib.rs"),
+        ("./src/lib.rs", "src/lib.rs"),
+        ("  src//lib.rs  ", "src/lib.rs"),
+        ("src\\lib.rs", "src/lib.rs"),
+        ("src/./nested/../lib.rs", "src/lib.rs"),
+        ("src/", "src"),
+        ("../outside.rs", "../outside.rs"),
+        (".", ""),
+    ] {
+        assert_eq!(normalize_path(path), expected, "{path:?}");
+    }
+}
+
+#[test]
+fn duplicates_and_directories_are_collapsed_in_first_seen_order() {
+    let files = ["src/main.rs", "./a.txt", "src", "a.txt", "unknown.rs"]
+        .map(ToString::to_string)
+        .to_vec();
+    let normalized = normalize_file_params(&files, &changed_paths());
+
+    assert_eq!(
+        normalized.files,
+        [
+            "src/main.rs",
+            "a.txt",
+            "src/lib.rs",
+            "src/nested/mod.rs",
+            "unknown.rs"
+        ]
+    );
+    let collapsed = normalized
+        .collapsed
+        .iter()
+        .map(|collapsed| (collapsed.input.as_str(), collapsed.reason))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        collapsed,
+        [
+            ("./a.txt", CollapseReason::Normalized),
+            ("src", CollapseReason::Directory),
+            ("a.txt", CollapseReason::Duplicate),
+        ],
+        "paths that aren't changed are kept as they are, so they can be reported"
+    );
+}
+
+#[test]
+fn randomized_inputs_normalize_to_a_canonical_set() {
+    let targets = [
+        "a.txt",
+        "docs",
+        "src",
+        "src/lib.rs",
+        "src/nested",
+        "src/nested/mod.rs",
+    ];
+    let changed_paths = changed_paths();
+    for seed in 0..500 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let amount = rng.random_range(1..=targets.len());
+        let chosen = targets
+            .choose_multiple(&mut rng, amount)
+            .copied()
+            .collect::<Vec<_>>();
+        let mut files = vec![];
+        for target in &chosen {
+            for _ in 0..rng.random_range(1..=3) {
+                files.push(spelling(&mut rng, target));
+            }
+        }
+        files.shuffle(&mut rng);
+
+        let normalized = normalize_file_params(&files, &changed_paths);
+
+        let expected = chosen
+            .iter()
+            .flat_map(|target| files_of(target))
+            .collect::<BTreeSet<_>>();
+        let actual = normalized
+            .files
+            .iter()
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(actual, expected, "seed {seed}: {files:?}");
+        assert_eq!(
+            actual.len(),
+            normalized.files.len(),
+            "seed {seed}: there are no duplicates in {:?}",
+            normalized.files
+        );
+
+        let first_input_of = |file: &str| {
+            files
+                .iter()
+                .position(|input| files_of(&normalize_path(input)).contains(&file))
+                .expect("every file was named")
+        };
+        assert!(
+            normalized
+                .files
+                .windows(2)
+                .all(|pair| first_input_of(&pair[0]) <= first_input_of(&pair[1])),
+            "seed {seed}: {:?} is in the order of {files:?}",
+            normalized.files
+        );
+
+        let mut collapsed = normalized.collapsed.iter().peekable();
+        for input in &files {
+            if collapsed
+                .next_if(|collapsed| collapsed.input == *input)
+                .is_none()
+            {
+                assert!(
+                    normalized.files.contains(input),
+                    "seed {seed}: {input:?} was either used as is, or recorded"
+                );
+            }
+        }
+        assert!(collapsed.next().is_none(), "seed {seed}");
+
+        let renormalized = normalize_file_params(&normalized.files, &changed_paths);
+        assert_eq!(renormalized.files, normalized.files, "seed {seed}");
+        assert!(
+            renormalized.collapsed.is_empty(),
+            "seed {seed}: normalized files are canonical"
+        );
+    }
+}
+
+#[test]
+fn commits_record_how_the_files_were_normalized() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::create_dir_all(repo.path().join("src"))?;
+    fs::write(repo.path().join("src/lib.rs"), "lib\n")?;
+    fs::write(repo.path().join("src/main.rs"), "main\n")?;
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "commit",
+        &json!({
+            "messageTitle": "Add files",
+            "messageBody": "",
+            "branchName": "branch",
+            "branchDescription": "",
+            "files": ["./src/lib.rs", "src/", "src/lib.rs"],
+        })
+        .to_string(),
+    );
+
+    let committed = result["result"]["committedFiles"]
+        .as_array()
+        .expect("the commit was created")
+        .iter()
+        .map(|file| file["path"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(committed, ["src/lib.rs", "src/main.rs"]);
+    assert_eq!(
+        result["result"]["normalizedInput"]["files"],
+        json!(["src/lib.rs", "src/main.rs"])
+    );
+    assert_eq!(
+        result["result"]["normalizedInput"]["collapsed"],
+        json!([
+            {"input": "./src/lib.rs", "reason": "normalized", "paths": ["src/lib.rs"]},
+            {"input": "src/", "reason": "directory", "paths": ["src/lib.rs", "src/main.rs"]},
+            {"input": "src/lib.rs", "reason": "duplicate", "paths": ["src/lib.rs"]},
+        ])
+    );
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::fs;
+
+use but_tools::invariants::{InvariantViolation, verify_workspace_invariants};
+use but_tools::workspace::workspace_toolset;
+use gitbutler_stack::VirtualBranchesHandle;
+use serde_json::json;
+
+use super::{Test, commit_files};
+
+#[test]
+fn a_consistent_workspace_has_no_violations() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "second", &["b.txt"])?;
+
+    assert_eq!(verify_workspace_invariants(ctx)?, vec![]);
+    Ok(())
+}
+
+#[test]
+fn heads_that_do_not_exist_are_detected() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, commit) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    // Make the metadata refer to a commit that doesn't exist.
+    let missing = gix::ObjectId::from_hex(b"1111111111111111111111111111111111111111")?;
+    let toml_path = ctx.project().gb_dir().join("virtual_branches.toml");
+    let toml = fs::read_to_string(&toml_path)?;
+    fs::write(
+        &toml_path,
+        toml.replace(&commit.to_string(), &missing.to_string()),
+    )?;
+
+    let violations = verify_workspace_invariants(ctx)?;
+    assert!(violations.contains(&InvariantViolation::MissingHead {
+        stack_id,
+        branch_name: "branch".into(),
+        head: missing,
+    }));
+    Ok(())
+}
+
+#[test]
+fn heads_shared_by_stacks_are_detected() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (first_stack_id, _) = commit_files(ctx, "shared", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (second_stack_id, _) = commit_files(ctx, "other", &["b.txt"])?;
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut second_stack = vb_state.get_stack(second_stack_id)?;
+    second_stack.heads[0].name = "shared".into();
+    vb_state.set_stack(second_stack)?;
+
+    let violations = verify_workspace_invariants(ctx)?;
+    let shared = violations
+        .iter()
+        .find_map(|violation| match violation {
+            InvariantViolation::SharedHead {
+                branch_name,
+                stack_ids,
+            } => Some((branch_name, stack_ids)),
+            _ => None,
+        })
+        .expect("the shared head is detected");
+    assert_eq!(shared.0, "shared");
+    assert_eq!(shared.1.len(), 2);
+    assert!(shared.1.contains(&first_stack_id) && shared.1.contains(&second_stack_id));
+    Ok(())
+}
+
+#[test]
+fn violations_are_attached_to_mutating_tools_in_paranoid_mode() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    commit_files(ctx, "shared", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (stack_id, _) = commit_files(ctx, "other", &["b.txt"])?;
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stack = vb_state.get_stack(stack_id)?;
+    stack.heads[0].name = "shared".into();
+    vb_state.set_stack(stack)?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({
+        "stackId": stack_id.to_string(),
+        "branchName": "shared",
+        "description": "A description",
+    })
+    .to_string();
+
+    toolset.set_paranoid(false);
+    let result = toolset.call_tool("set_branch_description", &parameters);
+    assert!(result["result"].is_object());
+    assert!(result["invariantViolations"].is_null());
+
+    toolset.set_paranoid(true);
+    let result = toolset.call_tool("set_branch_description", &parameters);
+    assert!(result["result"].is_object(), "the call doesn't fail");
+    assert_eq!(result["invariantViolations"][0]["kind"], "sharedHead");
+    assert_eq!(result["invariantViolations"][0]["branchName"], "shared");
+
+    let result = toolset.call_tool("get_stacks_summary", "{}");
+    assert!(
+        result["invariantViolations"].is_null(),
+        "only mutating tools are verified"
+    );
+    Ok(())
+}
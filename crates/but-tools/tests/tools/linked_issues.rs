@@ -0,0 +1,49 @@
+use but_tools::linked_issues::{IssueMatcher, IssuePattern, default_issue_patterns};
+
+/// Return the references of `patterns` in `message`, as `<ref>` or `<ref> <url>`.
+fn references(patterns: &[IssuePattern], message: &str) -> Vec<String> {
+    IssueMatcher::new(patterns)
+        .unwrap()
+        .references(message)
+        .into_iter()
+        .map(|reference| match reference.url {
+            Some(url) => format!("{} {url}", reference.ref_id),
+            None => reference.ref_id,
+        })
+        .collect()
+}
+
+#[test]
+fn default_patterns_find_github_and_tracker_issues() {
+    assert_eq!(
+        references(
+            &default_issue_patterns(),
+            "Fix PROJ-7 and LIN-42\n\nFixes #12, see #12 and owner/repo#3.\nNot an issue: abc#5."
+        ),
+        ["PROJ-7", "LIN-42", "#12"],
+        "references are listed once, in the order they appear in"
+    );
+}
+
+#[test]
+fn urls_are_filled_in_with_the_reference_and_its_number() {
+    let patterns = [IssuePattern {
+        regex: r"\b(?P<ref>GB-\d+)\b".into(),
+        url: Some("https://tracker.example.com/{number}?ref={ref}".into()),
+    }];
+    assert_eq!(
+        references(&patterns, "Closes GB-101"),
+        ["GB-101 https://tracker.example.com/101?ref=GB-101"]
+    );
+}
+
+#[test]
+fn invalid_patterns_are_rejected() {
+    let err = IssueMatcher::new(&[IssuePattern {
+        regex: "(".into(),
+        url: None,
+    }])
+    .err()
+    .expect("the pattern doesn't compile");
+    assert_eq!(err.to_string(), "'(' isn't a valid issue pattern");
+}
@@ -0,0 +1,93 @@
+use but_settings::AppSettings;
+use but_tools::sanitize::MessageSanitizer;
+use but_tools::workspace::{CommitParameters, create_commit};
+use but_workspace::StackId;
+use gitbutler_command_context::CommandContext;
+use gitbutler_project as projects;
+use gitbutler_stack::VirtualBranchesHandle;
+use gitbutler_testsupport::{TestProject, paths};
+use tempfile::TempDir;
+
+struct Test {
+    repo: TestProject,
+    ctx: CommandContext,
+    _data_dir: TempDir,
+}
+
+impl Default for Test {
+    fn default() -> Self {
+        let data_dir = paths::data_dir();
+        let projects = projects::Controller::from_path(data_dir.path());
+
+        let test_project = TestProject::default();
+        let project = projects
+            .add(test_project.path(), None, None)
+            .expect("failed to add project");
+        let ctx = CommandContext::open(&project, AppSettings::default()).unwrap();
+
+        gitbutler_branch_actions::set_base_branch(
+            &ctx,
+            &"refs/remotes/origin/master".parse().unwrap(),
+            false,
+            ctx.project().exclusive_worktree_access().write_permission(),
+        )
+        .expect("failed to set the base branch");
+
+        Self {
+            repo: test_project,
+            ctx,
+            _data_dir: data_dir,
+        }
+    }
+}
+
+/// Commit the worktree changes of `files` to `branch_name`, creating the branch if it doesn't exist yet.
+///
+/// Return the id of the stack the branch belongs to, along with the new commit.
+fn commit_files(
+    ctx: &mut CommandContext,
+    branch_name: &str,
+    files: &[&str],
+) -> anyhow::Result<(StackId, gix::ObjectId)> {
+    let outcome = create_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        CommitParameters {
+            message_title: format!("Change {}", files.join(", ")),
+            message_body: String::new(),
+            branch_name: branch_name.to_owned(),
+            branch_description: String::new(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            order: None,
+            amend_head: false,
+            allow_integrated: false,
+            author_date: None,
+            force_new_branch: false,
+            stack_position: None,
+        },
+    )?;
+    let commit_id = outcome
+        .new_commit
+        .ok_or_else(|| anyhow::anyhow!("No commit was created"))?;
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = vb_state
+        .list_stacks_in_workspace()?
+        .into_iter()
+        .find(|s| s.heads.iter().any(|h| h.name() == branch_name))
+        .ok_or_else(|| anyhow::anyhow!("No stack contains {branch_name}"))?;
+    Ok((stack.id, commit_id))
+}
+
+mod branch_suggestion;
+mod commit_id;
+mod file_params;
+mod invariants;
+mod linked_issues;
+mod sanitize;
+mod session;
+mod tool;
+mod trailers;
+mod workspace;
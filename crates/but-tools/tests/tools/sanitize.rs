@@ -0,0 +1,201 @@
+use but_tools::sanitize::{EmptyMessageError, EmptyTitleError, MessageSanitizer};
+use but_tools::workspace::workspace_toolset;
+use but_workspace::StackId;
+use serde_json::json;
+
+use super::Test;
+
+#[test]
+fn scaffolding_markers_are_stripped() -> anyhow::Result<()> {
+    let sanitizer = MessageSanitizer::default();
+
+    let message = sanitizer.commit_message(
+        "<description>Add feature</description>",
+        "<important_notes>\nDetails\n</important_notes>",
+    )?;
+    assert_eq!(message, "Add feature\n\nDetails");
+    assert_eq!(
+        sanitizer.description("<description> The purpose </description>"),
+        "The purpose"
+    );
+    Ok(())
+}
+
+#[test]
+fn stripped_patterns_are_configurable() -> anyhow::Result<()> {
+    let sanitizer = MessageSanitizer {
+        stripped_patterns: vec!["[scaffold]".into()],
+        ..Default::default()
+    };
+
+    let message = sanitizer.commit_message("[scaffold]Title", "<description>kept</description>")?;
+    assert_eq!(message, "Title\n\n<description>kept</description>");
+    Ok(())
+}
+
+#[test]
+fn long_descriptions_are_truncated() {
+    let sanitizer = MessageSanitizer {
+        max_description_len: 5,
+        ..Default::default()
+    };
+
+    assert_eq!(sanitizer.description("short"), "short");
+    assert_eq!(
+        sanitizer.description("too long"),
+        "too l\n[3 characters truncated]"
+    );
+}
+
+#[test]
+fn long_message_bodies_are_truncated() -> anyhow::Result<()> {
+    let sanitizer = MessageSanitizer {
+        max_message_body_len: 4,
+        ..Default::default()
+    };
+
+    let message = sanitizer.commit_message("A title that is longer than the cap", "äöüßx")?;
+    assert_eq!(
+        message, "A title that is longer than the cap\n\näöüß\n[1 characters truncated]",
+        "the title has its own cap, and the body is cut at character boundaries"
+    );
+    Ok(())
+}
+
+#[test]
+fn long_message_titles_are_truncated_on_their_line() -> anyhow::Result<()> {
+    let sanitizer = MessageSanitizer {
+        max_message_title_len: 7,
+        ..Default::default()
+    };
+
+    let message = sanitizer.commit_message("A title\n+pasted diff", "The body")?;
+    assert_eq!(
+        message, "A title [13 characters truncated]\n\nThe body",
+        "the marker stays on the title line"
+    );
+    assert_eq!(sanitizer.commit_message("Short", "")?, "Short");
+    Ok(())
+}
+
+#[test]
+fn messages_with_only_whitespace_are_rejected() {
+    let sanitizer = MessageSanitizer::default();
+
+    let err = sanitizer
+        .commit_message("  <description> </description>", "\n\t")
+        .unwrap_err();
+    assert!(err.downcast_ref::<EmptyMessageError>().is_some());
+}
+
+#[test]
+fn messages_with_only_a_body_are_rejected() {
+    let sanitizer = MessageSanitizer::default();
+
+    let err = sanitizer
+        .commit_message("<description> </description>", "The body")
+        .unwrap_err();
+    assert!(err.downcast_ref::<EmptyTitleError>().is_some());
+}
+
+#[test]
+fn empty_messages_are_reported_with_a_code() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+
+    let parameters = json!({
+        "commitId": "0000000000000000000000000000000000000000",
+        "stackId": StackId::generate().to_string(),
+        "messageTitle": "<important_notes></important_notes>",
+        "messageBody": " ",
+        "files": [],
+    });
+    let result = toolset.call_tool("amend", &parameters.to_string());
+
+    assert_eq!(result["code"], "emptyMessage");
+    Ok(())
+}
+
+#[test]
+fn empty_titles_are_rejected_by_all_committing_tools() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+
+    let commit_id = "0000000000000000000000000000000000000000";
+    let stack_id = StackId::generate().to_string();
+    for (tool, parameters) in [
+        (
+            "commit",
+            json!({
+                "messageTitle": " ",
+                "messageBody": "The body",
+                "branchName": "branch",
+                "branchDescription": "",
+                "files": [],
+            }),
+        ),
+        (
+            "amend",
+            json!({
+                "commitId": commit_id,
+                "stackId": stack_id,
+                "messageTitle": "",
+                "messageBody": "The body",
+                "files": [],
+            }),
+        ),
+        (
+            "create_blank_commit",
+            json!({
+                "messageTitle": "\n\t",
+                "messageBody": "The body",
+                "stackId": stack_id,
+                "parentId": commit_id,
+            }),
+        ),
+    ] {
+        let result = toolset.call_tool(tool, &parameters.to_string());
+        assert_eq!(result["code"], "invalidInput", "{tool}: {result}");
+    }
+    Ok(())
+}
+
+#[test]
+fn title_only_messages_round_trip_without_trailing_whitespace() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    std::fs::write(repo.path().join("a.txt"), "a\n")?;
+    let mut parameters = json!({
+        "messageTitle": "Add a.txt",
+        "messageBody": "",
+        "branchName": "branch",
+        "branchDescription": "",
+        "files": ["a.txt"],
+    });
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert!(result["error"].is_null(), "{result}");
+    // Amending without a message keeps the message of the amended commit.
+    std::fs::write(repo.path().join("a.txt"), "amended\n")?;
+    parameters["messageTitle"] = "".into();
+    parameters["amendHead"] = true.into();
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert!(result["error"].is_null(), "{result}");
+    let amended: gix::ObjectId = result["result"]["newCommit"]
+        .as_str()
+        .expect("the head commit was amended")
+        .parse()?;
+
+    let status = toolset.call_tool("get_project_status", "{}");
+    let commit = &status["result"]["stacks"][0]["branches"][0]["commits"][0];
+    assert_eq!(commit["messageTitle"], "Add a.txt");
+    assert_eq!(commit["messageBody"], "");
+    drop(toolset);
+
+    let gix_repo = ctx.gix_repo()?;
+    assert_eq!(
+        gix_repo.find_commit(amended)?.message_raw_sloppy(),
+        "Add a.txt"
+    );
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use std::fs;
+
+use but_tools::session::{SessionSummary, ToolCallRecord};
+use but_tools::workspace::workspace_toolset;
+use serde_json::json;
+
+use super::Test;
+
+fn record(
+    name: &str,
+    message_id: &str,
+    parameters: serde_json::Value,
+    result: serde_json::Value,
+) -> ToolCallRecord {
+    ToolCallRecord {
+        name: name.to_owned(),
+        message_id: Some(message_id.to_owned()),
+        parameters,
+        result,
+        snapshots: vec![],
+    }
+}
+
+#[test]
+fn calls_are_aggregated_into_a_summary() {
+    let calls = [
+        record(
+            "create_branch",
+            "first",
+            json!({ "branchName": "feature", "branchDescription": "" }),
+            json!({ "result": {} }),
+        ),
+        record(
+            "commit",
+            "first",
+            json!({ "messageTitle": "Add a", "branchName": "feature", "files": ["a.txt"] }),
+            json!({ "result": {
+                "newCommit": "1111111111111111111111111111111111111111",
+                "pathsToRejectedChanges": [["noEffectiveChanges", "b.txt"]],
+                "committedFiles": [{ "path": "a.txt" }],
+            }}),
+        ),
+        record(
+            "amend",
+            "first",
+            json!({ "commitId": "1111111", "messageTitle": "", "files": ["c.txt"] }),
+            json!({ "error": "The commit can't be amended", "code": "internalError" }),
+        ),
+        record(
+            "reword_commits",
+            "second",
+            json!({ "commits": [{ "commitId": "1111111", "messageTitle": "Add the a file" }] }),
+            json!({ "result": [
+                { "oldId": "1111111111111111111111111111111111111111", "newId": "2222222222222222222222222222222222222222" },
+                { "oldId": "3333333333333333333333333333333333333333", "newId": "4444444444444444444444444444444444444444" },
+            ]}),
+        ),
+        record(
+            "move_file_changes",
+            "second",
+            json!({ "files": ["./d.txt"] }),
+            json!("Success"),
+        ),
+    ];
+
+    let summary = serde_json::to_value(SessionSummary::from_calls(&calls, None)).unwrap();
+    assert_eq!(
+        summary,
+        json!({
+            "messageId": null,
+            "toolCalls": [
+                { "name": "create_branch", "succeeded": true },
+                { "name": "commit", "succeeded": true },
+                { "name": "amend", "succeeded": false, "error": "The commit can't be amended" },
+                { "name": "reword_commits", "succeeded": true },
+                { "name": "move_file_changes", "succeeded": true },
+            ],
+            "branchesCreated": ["feature"],
+            "commits": [
+                {
+                    "action": "created",
+                    "id": "1111111111111111111111111111111111111111",
+                    "title": "Add a",
+                    "branch": "feature",
+                },
+                {
+                    "action": "reworded",
+                    "id": "2222222222222222222222222222222222222222",
+                    "title": "Add the a file",
+                },
+            ],
+            "filesTouched": ["a.txt", "d.txt"],
+            "snapshots": [],
+            "warnings": ["commit: changes to 'b.txt' were rejected: noEffectiveChanges"],
+        })
+    );
+
+    let summary = SessionSummary::from_calls(&calls, Some("second"));
+    assert_eq!(summary.message_id.as_deref(), Some("second"));
+    assert_eq!(
+        summary
+            .tool_calls
+            .iter()
+            .map(|call| call.name.as_str())
+            .collect::<Vec<_>>(),
+        ["reword_commits", "move_file_changes"],
+        "only the calls for the message are considered"
+    );
+    assert!(summary.branches_created.is_empty());
+}
+
+#[test]
+fn toolsets_record_their_calls_per_message() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "first".into())?;
+    let result = toolset.call_tool(
+        "commit",
+        &json!({
+            "messageTitle": "Add a",
+            "messageBody": "",
+            "branchName": "branch",
+            "branchDescription": "",
+            "files": ["a.txt"],
+        })
+        .to_string(),
+    );
+    let commit_id = result["result"]["newCommit"]
+        .as_str()
+        .expect("the commit was created")
+        .to_owned();
+
+    toolset.set_message_id(Some("second".into()));
+    let result = toolset.call_tool("commit", "not json");
+    assert!(result["error"].is_string(), "{result}");
+
+    let summary = toolset.session_summary(Some("first"));
+    assert_eq!(summary.tool_calls.len(), 1);
+    assert_eq!(summary.commits.len(), 1);
+    assert_eq!(summary.commits[0].id, commit_id);
+    assert_eq!(summary.files_touched, ["a.txt"]);
+    assert!(
+        !summary.snapshots.is_empty(),
+        "committing records a snapshot"
+    );
+
+    let summary = toolset.session_summary(None);
+    assert_eq!(summary.tool_calls.len(), 2);
+    assert!(
+        !summary.tool_calls[1].succeeded,
+        "failed calls are listed as well"
+    );
+    Ok(())
+}
@@ -0,0 +1,614 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use but_tools::localization::ToolDescriptions;
+use but_tools::tool::{
+    BatchStep, ProjectFingerprint, TOOL_API_CHANGELOG, TOOL_API_VERSION, Tool, ToolCallMetadata,
+    ToolOptions, ToolResult, Toolset,
+};
+use but_tools::trailers::CommitTrailer;
+use but_tools::workspace::{workspace_capabilities, workspace_toolset};
+use but_workspace::StackId;
+use gitbutler_command_context::CommandContext;
+use gitbutler_stack::VirtualBranchesHandle;
+use serde_json::json;
+
+use super::{Test, commit_files};
+
+#[test]
+fn failing_amend_error_includes_context() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let stack_id = StackId::generate().to_string();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({
+        "commitId": "not-a-commit-id",
+        "messageTitle": "title",
+        "messageBody": "body",
+        "stackId": stack_id,
+        "files": [],
+    });
+    let result = toolset.call_tool("amend", &parameters.to_string());
+
+    assert!(result["error"].is_string());
+    assert_eq!(result["context"]["tool"], "amend");
+    assert_eq!(result["context"]["parameters"]["stackId"], stack_id);
+    Ok(())
+}
+
+#[test]
+fn error_context_omits_long_strings() {
+    let content = "a".repeat(1000);
+    let parameters = json!({ "content": content, "paths": ["a.txt"] });
+    let context = but_tools::tool::ErrorContext::new("tool", &parameters.to_string());
+
+    assert_eq!(context.parameters["content"], "<1000 characters omitted>");
+    assert_eq!(context.parameters["paths"], json!(["a.txt"]));
+}
+
+#[test]
+fn version_mismatch_is_reported() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool_with_version("get_project_status", "{}", Some("0.9"));
+
+    assert_eq!(result["code"], "versionMismatch");
+    assert_eq!(result["expectedApiVersion"], "0.9");
+    assert_eq!(result["apiVersion"], TOOL_API_VERSION);
+    Ok(())
+}
+
+#[test]
+fn tool_versions_are_recorded_in_the_changelog() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.allow_host_only_tools(true);
+
+    let latest = TOOL_API_CHANGELOG
+        .last()
+        .expect("the changelog is never empty");
+    assert_eq!(
+        latest.api_version, TOOL_API_VERSION,
+        "the latest change must be for the current version of the tool API"
+    );
+    let mut api_versions = TOOL_API_CHANGELOG
+        .iter()
+        .map(|change| change.api_version)
+        .collect::<Vec<_>>();
+    api_versions.dedup();
+    assert_eq!(
+        api_versions.len(),
+        TOOL_API_CHANGELOG.len(),
+        "each change bumps the version"
+    );
+
+    let major_version = |api_version: &str| -> u32 {
+        api_version
+            .split('.')
+            .next()
+            .and_then(|major| major.parse().ok())
+            .expect("the version is formatted as <major>.<minor>")
+    };
+    let mut tool_versions = BTreeMap::new();
+    let mut previous_major = 0;
+    for change in TOOL_API_CHANGELOG {
+        assert!(
+            !change.tools.is_empty(),
+            "change {} names the tools it affects",
+            change.api_version
+        );
+        let mut is_incompatible = previous_major == 0;
+        for (name, version) in change.tools {
+            let previous = tool_versions.insert(*name, *version);
+            is_incompatible |= previous.is_some_and(|previous| previous < *version);
+        }
+        let major = major_version(change.api_version);
+        assert_eq!(
+            major,
+            if is_incompatible {
+                previous_major + 1
+            } else {
+                previous_major
+            },
+            "change {} bumps the major version exactly if it bumps the version of a tool",
+            change.api_version
+        );
+        previous_major = major;
+    }
+
+    for tool in toolset.list() {
+        let recorded = TOOL_API_CHANGELOG
+            .iter()
+            .rev()
+            .flat_map(|change| change.tools)
+            .find(|(name, _)| *name == tool.name())
+            .map(|(_, version)| *version);
+        assert_eq!(
+            recorded,
+            Some(tool.version()),
+            "the latest change of tool '{}' in the changelog must be for its current version",
+            tool.name()
+        );
+    }
+
+    let definitions = toolset.definitions();
+    assert_eq!(definitions["apiVersion"], TOOL_API_VERSION);
+    Ok(())
+}
+
+#[test]
+fn tool_schema_of_commit_includes_files() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+
+    let result = toolset.call_tool("get_tool_schema", &json!({ "name": "commit" }).to_string());
+    let schemas = result["result"].as_array().expect("a list of schemas");
+    assert_eq!(schemas.len(), 1);
+    assert_eq!(schemas[0]["name"], "commit");
+    assert!(schemas[0]["parameters"]["properties"]["files"].is_object());
+
+    let result = toolset.call_tool("get_tool_schema", "{}");
+    let names = result["result"]
+        .as_array()
+        .expect("a list of schemas")
+        .iter()
+        .map(|schema| schema["name"].as_str().unwrap_or_default().to_owned())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"commit".to_owned()));
+    assert!(
+        !names.contains(&"get_tool_schema".to_owned()),
+        "the schema tool doesn't describe itself"
+    );
+    Ok(())
+}
+
+#[test]
+fn moved_project_is_reported_as_stale_context() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let fingerprint = ProjectFingerprint::new(ctx.project());
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    assert_eq!(toolset.project_fingerprint(), &fingerprint);
+    let result = toolset.call_tool("get_project_status", "{}");
+    assert!(result["error"].is_null());
+
+    let moved = repo.path().with_extension("moved");
+    std::fs::rename(repo.path(), &moved)?;
+    let result = toolset.call_tool("get_project_status", "{}");
+    std::fs::rename(&moved, repo.path())?;
+
+    assert_eq!(result["code"], "staleContext");
+    assert_eq!(result["context"]["tool"], "get_project_status");
+    Ok(())
+}
+
+#[test]
+fn host_only_tools_need_the_permission_of_the_host() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({ "kind": "rev_parse", "spec": "HEAD" }).to_string();
+
+    let is_listed = |toolset: &Toolset<'_>| {
+        toolset
+            .list()
+            .iter()
+            .any(|tool| tool.name() == "repo_query")
+    };
+    assert!(!is_listed(&toolset), "it isn't exposed by default");
+    let result = toolset.call_tool("repo_query", &parameters);
+    assert_eq!(result["code"], "permissionDenied");
+
+    toolset.allow_host_only_tools(true);
+    assert!(is_listed(&toolset));
+    let result = toolset.call_tool("repo_query", &parameters);
+    assert!(result["error"].is_null());
+    assert_eq!(result["result"]["kind"], "rev_parse");
+    assert_eq!(result["result"]["objectType"], "commit");
+    Ok(())
+}
+
+#[test]
+fn unknown_files_fail_a_commit_only_in_strict_mode() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    std::fs::write(repo.path().join("a.txt"), "a\n")?;
+    let parameters = json!({
+        "messageTitle": "Add a.txt",
+        "messageBody": "",
+        "branchName": "branch",
+        "branchDescription": "",
+        "files": ["a.txt", "unknown.txt"],
+    })
+    .to_string();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.set_strict(true);
+    let result = toolset.call_tool("commit", &parameters);
+    assert_eq!(result["code"], "strictMode");
+    assert!(
+        result["error"]
+            .as_str()
+            .is_some_and(|error| error.contains("'unknown.txt'")),
+        "{result}"
+    );
+
+    toolset.set_strict(false);
+    let result = toolset.call_tool("commit", &parameters);
+    assert!(result["error"].is_null(), "{result}");
+    assert!(result["result"]["newCommit"].is_string());
+    assert_eq!(result["result"]["committedFiles"][0]["path"], "a.txt");
+    Ok(())
+}
+
+#[test]
+fn changed_submodules_fail_the_status_only_in_strict_mode() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let submodule = gitbutler_testsupport::TestProject::default();
+    let url: gitbutler_url::Url = submodule
+        .path()
+        .display()
+        .to_string()
+        .parse()
+        .expect("the path is a valid url");
+    repo.add_submodule(&url, std::path::Path::new("submodule"));
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert!(status["error"].is_null(), "{status}");
+    assert_eq!(
+        status["result"]["warnings"][0]["path"], "submodule",
+        "{status}"
+    );
+    assert_eq!(status["result"]["fileChanges"][0]["path"], ".gitmodules");
+
+    toolset.set_strict(true);
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert_eq!(status["code"], "strictMode", "{status}");
+    assert!(
+        status["error"]
+            .as_str()
+            .is_some_and(|error| error.contains("'submodule'")),
+        "{status}"
+    );
+    Ok(())
+}
+
+#[test]
+fn definitions_include_the_estimated_duration_of_slow_tools() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let toolset = workspace_toolset(ctx, None, "message".into())?;
+
+    let definitions = toolset.definitions();
+    let definition = |name: &str| {
+        definitions["tools"]
+            .as_array()
+            .and_then(|tools| tools.iter().find(|tool| tool["name"] == name))
+            .cloned()
+            .unwrap_or_default()
+    };
+    assert!(definition("get_project_status")["estimatedDurationMs"].is_u64());
+    assert!(definition("get_tool_schema")["estimatedDurationMs"].is_null());
+    Ok(())
+}
+
+#[test]
+fn session_context_lines_are_used_for_diffs() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let lines = (1..=9).map(|n| format!("{n}\n")).collect::<String>();
+    std::fs::write(repo.path().join("a.txt"), &lines)?;
+    commit_files(ctx, "branch", &["a.txt"])?;
+    std::fs::write(repo.path().join("a.txt"), lines.replace("5\n", "five\n"))?;
+    let app_context_lines = ctx.app_settings().context_lines;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let mut diff_with = |parameters: serde_json::Value| {
+        let result = toolset.call_tool("get_project_status", &parameters.to_string());
+        result["result"]["fileChanges"][0]["hunks"][0]["diff"].clone()
+    };
+    assert_ne!(app_context_lines, 0);
+    toolset.set_context_lines(Some(0));
+    assert_eq!(diff_with(json!({})), "@@ -5,1 +5,1 @@\n-5\n+five\n");
+    assert_eq!(
+        diff_with(json!({ "contextLines": 1 })),
+        "@@ -4,3 +4,3 @@\n 4\n-5\n+five\n 6\n",
+        "the number passed to the tool takes precedence"
+    );
+    drop(toolset);
+
+    assert_eq!(
+        ctx.app_settings().context_lines,
+        app_context_lines,
+        "the app settings are unchanged"
+    );
+    Ok(())
+}
+
+/// A tool that panics when called.
+struct PanickingTool;
+
+impl Tool for PanickingTool {
+    fn name(&self) -> String {
+        "panic".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Panic".to_string()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        json!({})
+    }
+
+    fn call(
+        self: Arc<Self>,
+        _parameters: serde_json::Value,
+        _ctx: &mut CommandContext,
+        _app_handle: Option<&tauri::AppHandle>,
+        _options: &ToolOptions,
+    ) -> anyhow::Result<serde_json::Value> {
+        panic!("the tool is broken")
+    }
+}
+
+#[test]
+fn panicking_tools_fail_without_breaking_the_toolset() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.register_tool(PanickingTool);
+
+    let result = toolset.call_tool("panic", "{}");
+    assert_eq!(result["code"], "internalError");
+    assert_eq!(result["panic"], "the tool is broken");
+    assert_eq!(result["context"]["tool"], "panic");
+    assert!(
+        result["error"]
+            .as_str()
+            .is_some_and(|error| error.contains("the tool is broken")),
+        "{result}"
+    );
+
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert!(status["error"].is_null(), "{status}");
+    Ok(())
+}
+
+#[test]
+fn every_tool_contributes_its_capabilities() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.allow_host_only_tools(true);
+
+    let capabilities = toolset.capabilities();
+    for tool in toolset.list() {
+        assert_eq!(
+            capabilities[&format!("tools.{}", tool.name())]["version"],
+            tool.version(),
+            "tool '{}' is listed along with its version",
+            tool.name()
+        );
+        for (key, _) in tool.capabilities() {
+            assert!(
+                key.starts_with(&format!("{}.", tool.name())),
+                "the capability '{key}' is keyed by the name of tool '{}'",
+                tool.name()
+            );
+            assert!(capabilities.contains_key(&key));
+        }
+    }
+    for key in [
+        "errors.codes",
+        "commit.forceNewBranch",
+        "toolset.apiVersion",
+    ] {
+        assert!(capabilities.contains_key(key), "{key} is listed");
+    }
+    assert_eq!(
+        capabilities,
+        workspace_capabilities(),
+        "the capabilities are known without a project"
+    );
+
+    toolset.allow_host_only_tools(false);
+    let capabilities = toolset.capabilities();
+    assert!(
+        capabilities
+            .values()
+            .all(|metadata| metadata.get("hostOnly").is_none()),
+        "unavailable host-only tools aren't listed"
+    );
+    Ok(())
+}
+
+#[test]
+fn call_metadata_reports_created_snapshots() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    std::fs::write(repo.path().join("a.txt"), "a\n")?;
+    let parameters = json!({
+        "messageTitle": "Add a.txt",
+        "messageBody": "",
+        "branchName": "branch",
+        "branchDescription": "",
+        "files": ["a.txt"],
+    })
+    .to_string();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("get_project_status", "{}");
+    assert!(result["metadata"].is_null(), "metadata is opt-in: {result}");
+
+    toolset.set_call_metadata(true);
+    let result = toolset.call_tool("get_project_status", "{}");
+    assert_eq!(result["metadata"]["snapshotCreated"], false, "{result}");
+    assert!(result["metadata"]["durationMs"].is_u64());
+    assert_eq!(result["metadata"]["eventsEmitted"], json!([]));
+
+    let result = toolset.call_tool("commit", &parameters);
+    assert!(result["error"].is_null(), "{result}");
+    assert_eq!(result["metadata"]["snapshotCreated"], true, "{result}");
+    Ok(())
+}
+
+#[test]
+fn metadata_is_attached_to_results_and_errors() {
+    let metadata = ToolCallMetadata {
+        duration_ms: 12,
+        snapshot_created: true,
+        events_emitted: vec!["project://id/worktree_changes".into()],
+    };
+    let ok: anyhow::Result<Vec<CommitTrailer>> = Ok(vec![CommitTrailer {
+        token: "Signed-off-by".into(),
+        value: "A <a@example.com>".into(),
+    }]);
+    let value = ok.to_json_with_metadata("get_commit_signoff_trailers", metadata.clone());
+    assert_eq!(value["result"][0]["token"], "Signed-off-by");
+    assert_eq!(
+        value["metadata"],
+        json!({
+            "durationMs": 12,
+            "snapshotCreated": true,
+            "eventsEmitted": ["project://id/worktree_changes"],
+        })
+    );
+
+    let err: anyhow::Result<Vec<CommitTrailer>> = Err(anyhow::anyhow!("failed"));
+    let value = err.to_json_with_metadata("get_commit_signoff_trailers", metadata);
+    assert!(value["error"].is_string());
+    assert_eq!(value["metadata"]["durationMs"], 12);
+}
+
+#[test]
+fn failing_batch_step_rolls_back_the_whole_batch() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    std::fs::write(repo.path().join("a.txt"), "a\n")?;
+    let create_branch = |name: &str| BatchStep {
+        tool: "create_branch".into(),
+        params: json!({ "branchName": name, "branchDescription": "" }),
+    };
+    let steps = [
+        create_branch("batched"),
+        BatchStep {
+            tool: "commit".into(),
+            params: json!({
+                "messageTitle": "Add a.txt",
+                "messageBody": "",
+                "branchName": "batched",
+                "branchDescription": "",
+                "files": ["a.txt"],
+                "amendHead": true,
+            }),
+        },
+        create_branch("skipped"),
+    ];
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_batch(&steps);
+    assert_eq!(result["code"], "batchFailed", "{result}");
+    assert_eq!(result["failedStep"], 1);
+    assert_eq!(result["rolledBack"], true);
+    assert_eq!(result["results"].as_array().map(Vec::len), Some(2));
+    assert!(result["results"][0]["error"].is_null());
+
+    let branch_names = |ctx: &CommandContext| -> anyhow::Result<Vec<String>> {
+        let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+        Ok(vb_state
+            .list_stacks_in_workspace()?
+            .iter()
+            .flat_map(|stack| stack.branches())
+            .map(|branch| branch.name().to_owned())
+            .collect())
+    };
+    drop(toolset);
+    assert!(branch_names(ctx)?.is_empty(), "the created branch is gone");
+    assert!(repo.path().join("a.txt").exists(), "the worktree is kept");
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_batch(&[create_branch("first"), create_branch("second")]);
+    assert!(result["error"].is_null(), "{result}");
+    assert_eq!(
+        result["result"]["results"].as_array().map(Vec::len),
+        Some(2)
+    );
+    drop(toolset);
+    assert_eq!(branch_names(ctx)?, ["first", "second"]);
+    Ok(())
+}
+
+#[test]
+fn partial_translations_fall_back_to_english() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let definition = |definitions: &serde_json::Value, name: &str| {
+        definitions["tools"]
+            .as_array()
+            .and_then(|tools| tools.iter().find(|tool| tool["name"] == name))
+            .cloned()
+            .unwrap_or_default()
+    };
+    let english = toolset.definitions();
+    let template = ToolDescriptions::of_tools(&toolset.list());
+    let commit = template.get("commit").expect("commit is described");
+    assert_eq!(
+        commit.description.as_deref(),
+        definition(&english, "commit")["description"].as_str(),
+        "the template holds the built-in English descriptions"
+    );
+
+    let german = ToolDescriptions::from_json(
+        &json!({
+            "commit": {
+                "description": "Erstellt einen Commit mit den angegebenen Dateien.",
+                "parameters": {
+                    "messageTitle": "Der Titel der Commit-Nachricht.",
+                    "messageBody": "  ",
+                    "unknownParameter": "Wird ignoriert.",
+                },
+            },
+            "get_stacks_summary": { "description": "" },
+        })
+        .to_string(),
+    )?;
+    toolset.set_descriptions(german);
+    let definitions = toolset.definitions();
+
+    let (english_commit, german_commit) = (
+        definition(&english, "commit"),
+        definition(&definitions, "commit"),
+    );
+    assert_eq!(
+        german_commit["description"],
+        "Erstellt einen Commit mit den angegebenen Dateien."
+    );
+    assert_eq!(
+        german_commit["parameters"]["properties"]["messageTitle"]["description"],
+        "Der Titel der Commit-Nachricht."
+    );
+    assert_eq!(
+        german_commit["parameters"]["properties"]["messageBody"],
+        english_commit["parameters"]["properties"]["messageBody"],
+        "blank translations fall back to English"
+    );
+    assert!(german_commit["parameters"]["properties"]["unknownParameter"].is_null());
+
+    let mut schema = german_commit["parameters"].clone();
+    schema["properties"]["messageTitle"]["description"] =
+        english_commit["parameters"]["properties"]["messageTitle"]["description"].clone();
+    assert_eq!(
+        schema, english_commit["parameters"],
+        "only the descriptions change"
+    );
+    for name in ["get_stacks_summary", "get_project_status"] {
+        assert_eq!(definition(&definitions, name), definition(&english, name));
+    }
+
+    let result = toolset.call_tool("get_tool_schema", &json!({ "name": "commit" }).to_string());
+    assert_eq!(
+        result["result"][0]["description"],
+        german_commit["description"]
+    );
+    assert_eq!(
+        result["result"][0]["parameters"],
+        german_commit["parameters"]
+    );
+
+    Ok(())
+}
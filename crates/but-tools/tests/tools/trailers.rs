@@ -0,0 +1,40 @@
+use but_tools::trailers::parse_trailers;
+
+/// Return the trailers of `message` as `<token>: <value>`.
+fn trailers(message: &str) -> Vec<String> {
+    parse_trailers(message)
+        .into_iter()
+        .map(|trailer| format!("{}: {}", trailer.token, trailer.value))
+        .collect()
+}
+
+#[test]
+fn trailers_are_parsed_from_the_last_paragraph() {
+    assert_eq!(
+        trailers(
+            "Title\n\nBody: not a trailer.\n\nSigned-off-by: A <a@example.com>\nCo-authored-by: B\n  <b@example.com>\n"
+        ),
+        [
+            "Signed-off-by: A <a@example.com>",
+            "Co-authored-by: B <b@example.com>"
+        ],
+        "continuation lines are joined"
+    );
+    assert_eq!(
+        trailers("Title\r\n\r\nReviewed-by: C\r\n"),
+        ["Reviewed-by: C"]
+    );
+}
+
+#[test]
+fn paragraphs_with_other_lines_have_no_trailers() {
+    for message in [
+        "Signed-off-by: A",
+        "Title\n\nSigned-off-by: A\nThis isn't a trailer",
+        "Title\n\nSigned-off-by: A\n\nMore text",
+        "Title\n\nNot a token: A",
+        "Title\n\n  Signed-off-by: A",
+    ] {
+        assert!(trailers(message).is_empty(), "{message:?}");
+    }
+}
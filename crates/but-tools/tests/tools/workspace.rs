@@ -0,0 +1,3818 @@
+use std::fs;
+
+use but_settings::AppSettings;
+use but_tools::branch_name::{qualified_branch_name, resolve_branch_name};
+use but_tools::sanitize::MessageSanitizer;
+use but_tools::tool::{Toolset, error_to_json};
+use but_tools::workspace::{
+    AffectedTests, AheadBehind, AssignmentsCache, AttributeValue, CachedAssignmentsOutcome,
+    ChangeStats, CommitParameters, ConflictSeverity, DiffFormat, DiffLineKind, DiffStatLine,
+    DiffStatSummary, GetBranchRebasePreviewParameters, GetBranchUpstreamCommitsParameters,
+    GetChangesSinceParameters, GetCommitByMessageParameters, GetCommitDiffStatParameters,
+    GetCommitSignatureStatusParameters, GetDiffForPathsParameters, GetFileRenamesParameters,
+    GetGitAttributesParameters, GetOrphanedCommitsParameters, GetPRDescriptionParameters,
+    GetRecentActivityParameters, GetStackMergeOrderParameters, GetStagedCommitPreviewParameters,
+    GetTargetBranchCommitsParameters, HunkDiff, HunkSelection, MoveFileChangesParameters,
+    ReassignAndCommitParameters, RebaseStatus, RecoverCommitParameters, RecoveryPosition,
+    RenameDetection, RepoQueryParameters, RepoQueryResult, SignatureType, SignatureVerification,
+    SplitRenameContentCommit, SplitRenameParameters, WORKTREE_DESTINATION, affected_tests,
+    ahead_behind_per_tip, branch_rebase_preview, branch_upstream_commits, cached_assignments,
+    change_stats_by_stack, changes_since, commit_by_message, commit_diff_stat,
+    commit_signature_status, conflicting_stack_pairs, create_commit, diff_for_paths, file_renames,
+    git_attributes, move_file_changes, predict_move_file_changes, preview_integration,
+    reassign_and_commit, recent_activity, repo_query, split_rename, stack_merge_order,
+    staged_commit_preview, target_branch_commits, unified_diff_for_changes, workspace_toolset,
+    worktree_lock_status,
+};
+use but_workspace::StackId;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oxidize::ObjectIdExt;
+use gitbutler_stack::VirtualBranchesHandle;
+use serde_json::json;
+
+use super::{Test, commit_files};
+
+#[test]
+fn unreadable_files_are_skipped_with_a_warning() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("kept.txt"), "kept\n")?;
+    fs::write(repo.path().join("vanished.txt"), "vanished\n")?;
+
+    let gix_repo = ctx.gix_repo()?;
+    let worktree = but_core::diff::worktree_changes(&gix_repo)?;
+    // The file disappears after the status was computed, but before its diff is rendered.
+    fs::remove_file(repo.path().join("vanished.txt"))?;
+
+    let (diffs, warnings) = unified_diff_for_changes(&gix_repo, worktree.changes, 3)?;
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].0.path, "kept.txt");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].path, "vanished.txt");
+    Ok(())
+}
+
+#[test]
+fn diff_for_paths_only_contains_requested_paths() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("requested.txt"), "requested\n")?;
+    fs::write(repo.path().join("other.txt"), "other\n")?;
+
+    let gix_repo = ctx.gix_repo()?;
+    let diff = diff_for_paths(
+        ctx,
+        &gix_repo,
+        GetDiffForPathsParameters {
+            paths: vec!["requested.txt".into()],
+            diff_format: DiffFormat::Unified,
+            context_lines: None,
+        },
+    )?;
+
+    let paths = diff
+        .file_changes
+        .iter()
+        .map(|change| change.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, ["requested.txt"]);
+    Ok(())
+}
+
+#[test]
+fn diff_attributes_suppress_or_annotate_hunks() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(
+        repo.path().join(".gitattributes"),
+        "bundle.min.js -diff\n*.proto diff=proto\n",
+    )?;
+    fs::write(repo.path().join("bundle.min.js"), "var a=1;\n")?;
+    fs::write(repo.path().join("api.proto"), "message A {}\n")?;
+    let (_, commit) = commit_files(
+        ctx,
+        "branch",
+        &[".gitattributes", "bundle.min.js", "api.proto"],
+    )?;
+    fs::write(repo.path().join("bundle.min.js"), "var a=2;\n")?;
+    fs::write(repo.path().join("api.proto"), "message B {}\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let details = toolset.call_tool(
+        "get_commit_details",
+        &json!({ "commitId": commit.to_string() }).to_string(),
+    );
+    for file_changes in [
+        &status["result"]["fileChanges"],
+        &details["result"]["fileChanges"],
+    ] {
+        let file = |path: &str| {
+            file_changes
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|file| file["path"] == path)
+                .cloned()
+                .unwrap_or_else(|| panic!("{path} is listed in {file_changes}"))
+        };
+        let bundle = file("bundle.min.js");
+        assert_eq!(bundle["diffSuppressed"], true);
+        assert_eq!(bundle["hunks"], json!([]), "no hunk text is shown");
+        let proto = file("api.proto");
+        assert_eq!(proto["diffDriver"], "proto");
+        assert!(proto["diffSuppressed"].is_null());
+        assert_eq!(proto["hunks"].as_array().map(Vec::len), Some(1));
+    }
+    Ok(())
+}
+
+#[test]
+fn commit_details_leave_out_unreadable_files_with_a_warning() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, base) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    // A submodule has no diff, so its change can't be shown.
+    let gix_repo = ctx.gix_repo()?;
+    let blob = gix_repo.write_blob("b\n")?;
+    let mut editor = gix_repo.edit_tree(gix_repo.find_commit(base)?.tree_id()?)?;
+    editor.upsert("b.txt", gix::objs::tree::EntryKind::Blob, blob)?;
+    editor.upsert("submodule", gix::objs::tree::EntryKind::Commit, base)?;
+    let tree = editor.write()?;
+    let commit = gix_repo
+        .commit("refs/heads/with-submodule", "Add a submodule", tree, [base])?
+        .detach();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let details = toolset.call_tool(
+        "get_commit_details",
+        &json!({ "commitId": commit.to_string() }).to_string(),
+    );
+    assert!(details["error"].is_null(), "{details}");
+    assert_eq!(details["result"]["fileChanges"][0]["path"], "b.txt");
+    assert_eq!(
+        details["result"]["fileChanges"].as_array().map(Vec::len),
+        Some(1)
+    );
+    assert_eq!(details["result"]["warnings"][0]["path"], "submodule");
+    Ok(())
+}
+
+#[test]
+fn predicting_a_conflicting_move_does_not_change_the_stacks() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("file.txt"), "one\n")?;
+    let (source_stack_id, added) = commit_files(ctx, "source", &["file.txt"])?;
+    fs::write(repo.path().join("file.txt"), "two\n")?;
+    let (_, modified) = commit_files(ctx, "source", &["file.txt"])?;
+    fs::write(repo.path().join("other.txt"), "other\n")?;
+    let (destination_stack_id, destination) = commit_files(ctx, "destination", &["other.txt"])?;
+
+    // Moving the addition of the file out of the first commit makes the modification in the second one conflict.
+    let prediction = predict_move_file_changes(
+        ctx,
+        MoveFileChangesParameters {
+            source_commit_id: added.to_string(),
+            source_stack_id: source_stack_id.to_string(),
+            destination_commit_id: destination.to_string(),
+            destination_stack_id: destination_stack_id.to_string(),
+            files: vec!["file.txt".into()],
+            predict_only: true,
+        },
+    )?;
+
+    assert!(!prediction.clean);
+    assert_eq!(prediction.conflicts.len(), 1);
+    assert_eq!(prediction.conflicts[0].commit_id, modified);
+    assert_eq!(prediction.conflicts[0].paths, ["file.txt"]);
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let gix_repo = ctx.gix_repo()?;
+    let source_stack = vb_state.get_stack(source_stack_id)?;
+    assert_eq!(source_stack.head_oid(&gix_repo)?, modified);
+    let destination_stack = vb_state.get_stack(destination_stack_id)?;
+    assert_eq!(destination_stack.head_oid(&gix_repo)?, destination);
+    Ok(())
+}
+
+#[test]
+fn commit_places_a_new_branch_at_the_requested_position() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("first.txt"), "first\n")?;
+    commit_files(ctx, "first", &["first.txt"])?;
+    fs::write(repo.path().join("second.txt"), "second\n")?;
+    commit_files(ctx, "second", &["second.txt"])?;
+
+    fs::write(repo.path().join("new.txt"), "new\n")?;
+    create_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        CommitParameters {
+            message_title: "Add new".into(),
+            message_body: String::new(),
+            branch_name: "new".into(),
+            branch_description: String::new(),
+            files: vec!["new.txt".into()],
+            order: Some(1),
+            amend_head: false,
+            allow_integrated: false,
+            author_date: None,
+            force_new_branch: false,
+            stack_position: None,
+        },
+    )?;
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut stacks = vb_state.list_stacks_in_workspace()?;
+    stacks.sort_by_key(|s| s.order);
+    let names = stacks
+        .iter()
+        .map(|s| s.heads.last().unwrap().name().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["first", "new", "second"]);
+    Ok(())
+}
+
+#[test]
+fn recently_unapplied_stacks_are_only_listed_on_request() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("file.txt"), "content\n")?;
+    let (stack_id, _) = commit_files(ctx, "unapplied-branch", &["file.txt"])?;
+    gitbutler_branch_actions::unapply_stack(ctx, stack_id, vec![])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert!(status["result"]["unappliedStacks"].is_null());
+
+    let status = toolset.call_tool(
+        "get_project_status",
+        &json!({ "includeRecentlyUnapplied": true }).to_string(),
+    );
+    let unapplied = status["result"]["unappliedStacks"]
+        .as_array()
+        .expect("unapplied stacks are listed");
+    assert_eq!(unapplied.len(), 1);
+    assert_eq!(unapplied[0]["id"], stack_id.to_string());
+    assert_eq!(unapplied[0]["applied"], false);
+    assert_eq!(unapplied[0]["branches"][0]["name"], "unapplied-branch");
+    assert!(
+        status["result"]["stacks"]
+            .as_array()
+            .expect("applied stacks are listed")
+            .iter()
+            .all(|stack| stack["id"] != stack_id.to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn amends_report_the_hunks_of_other_stacks_that_depend_on_the_commit() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("f.txt"), "1\n2\n3\n4\n5\n")?;
+    let (stack_a, commit) = commit_files(ctx, "a", &["f.txt"])?;
+    fs::write(repo.path().join("g.txt"), "g\n")?;
+    let (stack_b, _) = commit_files(ctx, "b", &["g.txt"])?;
+
+    // The change to f.txt depends on the commit of stack a, but is assigned to stack b.
+    fs::write(repo.path().join("f.txt"), "1\n2\nthree\n4\n5\n")?;
+    fs::write(repo.path().join("h.txt"), "h\n")?;
+    let gix_repo = ctx.gix_repo()?;
+    let changes = |gix_repo: &gix::Repository| -> anyhow::Result<_> {
+        let worktree = but_core::diff::worktree_changes(gix_repo)?;
+        Ok(unified_diff_for_changes(gix_repo, worktree.changes, 3)?.0)
+    };
+    let cache = AssignmentsCache::default();
+    cached_assignments(ctx, &gix_repo, &changes(&gix_repo)?, &cache)?;
+    let mut assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    for assignment in &mut assignments {
+        if assignment.path_bytes == "f.txt" {
+            assignment.stack_id = Some(stack_b);
+        }
+    }
+    but_hunk_assignment::set_persisted_assignments(ctx, assignments)?;
+
+    let mut parameters = json!({
+        "commitId": commit.to_string(),
+        "stackId": stack_a.to_string(),
+        "messageTitle": "Change f.txt and h.txt",
+        "messageBody": "",
+        "files": ["h.txt"],
+        "strictLocks": true,
+    });
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.set_assignments_cache(cache.clone());
+    let refused = toolset.call_tool("amend", &parameters.to_string());
+    assert_eq!(refused["code"], "foreignLocks");
+    assert_eq!(refused["affectedForeignLocks"][0]["path"], "f.txt");
+    drop(toolset);
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    assert_eq!(
+        vb_state.get_stack(stack_a)?.head_oid(&gix_repo)?,
+        commit,
+        "the commit wasn't amended"
+    );
+
+    parameters["strictLocks"] = false.into();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.set_assignments_cache(cache.clone());
+    let amended = toolset.call_tool("amend", &parameters.to_string());
+    let locks = &amended["result"]["affectedForeignLocks"];
+    assert_eq!(locks.as_array().map(Vec::len), Some(1));
+    assert_eq!(locks[0]["path"], "f.txt");
+    assert_eq!(locks[0]["stackId"], json!(stack_b));
+    assert_eq!(locks[0]["commitId"], commit.to_string());
+    let new_commit: gix::ObjectId = amended["result"]["newCommit"]
+        .as_str()
+        .expect("the commit was amended")
+        .parse()?;
+    drop(toolset);
+
+    // The locks of the cached assignments point to the amended commit, so they don't have to be recomputed.
+    let gix_repo = ctx.gix_repo()?;
+    let outcome = cached_assignments(ctx, &gix_repo, &changes(&gix_repo)?, &cache)?;
+    assert!(outcome.recomputed_paths.is_empty());
+    let assignment = outcome
+        .assignments
+        .iter()
+        .find(|assignment| assignment.path_bytes == "f.txt")
+        .expect("f.txt is still changed");
+    assert_eq!(assignment.stack_id, Some(stack_b));
+    let lock_commits = assignment
+        .hunk_locks
+        .iter()
+        .flatten()
+        .map(|lock| lock.commit_id)
+        .collect::<Vec<_>>();
+    assert_eq!(lock_commits, [new_commit]);
+    Ok(())
+}
+
+#[test]
+fn amending_a_commit_to_emptiness_drops_it() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "branch", &["b.txt"])?;
+
+    // Amending the removal of its only file leaves the first commit without changes.
+    fs::remove_file(repo.path().join("a.txt"))?;
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let amended = toolset.call_tool(
+        "amend",
+        &json!({
+            "commitId": first.to_string(),
+            "stackId": stack_id.to_string(),
+            "messageTitle": "Change a.txt",
+            "messageBody": "",
+            "files": ["a.txt"],
+        })
+        .to_string(),
+    );
+    assert_eq!(amended["result"]["emptyCommit"], "dropped");
+    assert!(amended["result"]["newCommit"].is_null());
+    drop(toolset);
+
+    let gix_repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let head = vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?;
+    let head = gix_repo.find_commit(head)?;
+    assert!(
+        head.message_raw_sloppy()
+            .to_string()
+            .starts_with("Change b.txt"),
+        "only the commit on top is left"
+    );
+    assert_eq!(blob_at(&gix_repo, head.id, "a.txt"), None);
+    assert_eq!(
+        head.parent_ids().collect::<Vec<_>>(),
+        gix_repo
+            .find_commit(first)?
+            .parent_ids()
+            .collect::<Vec<_>>()
+    );
+    assert!(
+        but_core::diff::worktree_changes(&gix_repo)?
+            .changes
+            .is_empty()
+    );
+    Ok(())
+}
+
+#[test]
+fn rewording_a_blank_commit_keeps_it() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "create_blank_commit",
+        &json!({
+            "messageTitle": "Blank",
+            "messageBody": "",
+            "stackId": stack_id.to_string(),
+            "parentId": first.to_string(),
+        })
+        .to_string(),
+    );
+    assert!(result["error"].is_null(), "{result}");
+    let gix_repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let blank = vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?;
+    assert_ne!(blank, first, "the blank commit is the new head");
+
+    // Only the message changes, so the commit is as empty as it was before.
+    let amended = toolset.call_tool(
+        "amend",
+        &json!({
+            "commitId": blank.to_string(),
+            "stackId": stack_id.to_string(),
+            "messageTitle": "Reworded",
+            "messageBody": "",
+            "files": [],
+        })
+        .to_string(),
+    );
+    assert!(amended["error"].is_null(), "{amended}");
+    assert!(amended["result"]["emptyCommit"].is_null(), "{amended}");
+    assert!(amended["result"]["newCommit"].is_string(), "{amended}");
+    drop(toolset);
+
+    let head = vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?;
+    let head = gix_repo.find_commit(head)?;
+    assert_eq!(head.message_raw_sloppy(), "Reworded");
+    assert_eq!(head.parent_ids().collect::<Vec<_>>(), [first]);
+    Ok(())
+}
+
+#[test]
+fn assignments_are_reused_if_the_worktree_is_unchanged() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let gix_repo = ctx.gix_repo()?;
+    let changes = |gix_repo: &gix::Repository| -> anyhow::Result<_> {
+        let worktree = but_core::diff::worktree_changes(gix_repo)?;
+        Ok(unified_diff_for_changes(gix_repo, worktree.changes, 3)?.0)
+    };
+    let cache = AssignmentsCache::default();
+
+    let first = cached_assignments(ctx, &gix_repo, &changes(&gix_repo)?, &cache)?;
+    assert_eq!(first.recomputed_paths.len(), 2);
+
+    let second = cached_assignments(ctx, &gix_repo, &changes(&gix_repo)?, &cache)?;
+    assert!(
+        second.recomputed_paths.is_empty(),
+        "nothing changed, so all assignments are reused"
+    );
+    let ids = |outcome: &CachedAssignmentsOutcome| {
+        outcome.assignments.iter().map(|a| a.id).collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&first), ids(&second));
+
+    fs::write(repo.path().join("b.txt"), "b\nmore\n")?;
+    let third = cached_assignments(ctx, &gix_repo, &changes(&gix_repo)?, &cache)?;
+    assert_eq!(
+        third.recomputed_paths,
+        ["a.txt", "b.txt"],
+        "the assignments depend on each other, so all of them are recomputed"
+    );
+    assert_eq!(third.assignments.len(), 2);
+
+    fs::remove_file(repo.path().join("a.txt"))?;
+    let fourth = cached_assignments(ctx, &gix_repo, &changes(&gix_repo)?, &cache)?;
+    assert!(
+        fourth.recomputed_paths.is_empty(),
+        "a.txt isn't changed anymore, so it only loses its assignment"
+    );
+    assert_eq!(fourth.assignments.len(), 1);
+    assert_eq!(fourth.assignments[0].path_bytes, "b.txt");
+
+    let other = cached_assignments(
+        ctx,
+        &gix_repo,
+        &changes(&gix_repo)?,
+        &AssignmentsCache::default(),
+    )?;
+    assert_eq!(
+        other.recomputed_paths,
+        ["b.txt"],
+        "caches aren't shared unless they are cloned"
+    );
+    Ok(())
+}
+
+fn blob_at(repo: &gix::Repository, commit_id: gix::ObjectId, path: &str) -> Option<Vec<u8>> {
+    let tree = repo.find_commit(commit_id).ok()?.tree().ok()?;
+    let entry = tree.lookup_entry_by_path(path).ok()??;
+    Some(entry.object().ok()?.detach().data)
+}
+
+fn split_rename_parameters(
+    content_commit: Option<SplitRenameContentCommit>,
+) -> SplitRenameParameters {
+    SplitRenameParameters {
+        path: "b.txt".into(),
+        branch_name: "branch".into(),
+        message_title: "Rename a.txt to b.txt".into(),
+        message_body: String::new(),
+        content_commit,
+    }
+}
+
+#[test]
+fn split_rename_keeps_content_changes_uncommitted() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "1\n2\n3\n4\n5\n")?;
+    commit_files(ctx, "branch", &["a.txt"])?;
+    fs::remove_file(repo.path().join("a.txt"))?;
+    fs::write(repo.path().join("b.txt"), "1\n2\n3\n4\n5\n6\n")?;
+
+    let outcome = split_rename(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        split_rename_parameters(None),
+    )?;
+    assert!(outcome.content_commit_id.is_none());
+
+    let gix_repo = ctx.gix_repo()?;
+    let rename = outcome.rename_commit_id;
+    assert_eq!(blob_at(&gix_repo, rename, "a.txt"), None);
+    assert_eq!(
+        blob_at(&gix_repo, rename, "b.txt").as_deref(),
+        Some(b"1\n2\n3\n4\n5\n".as_slice())
+    );
+
+    let worktree = but_core::diff::worktree_changes(&gix_repo)?;
+    assert_eq!(worktree.changes.len(), 1, "only the content change is left");
+    assert_eq!(worktree.changes[0].path, "b.txt");
+    assert!(matches!(
+        worktree.changes[0].status,
+        but_core::TreeStatus::Modification { .. }
+    ));
+    assert_eq!(
+        fs::read(repo.path().join("b.txt"))?,
+        b"1\n2\n3\n4\n5\n6\n",
+        "nothing is lost"
+    );
+    Ok(())
+}
+
+#[test]
+fn split_rename_can_commit_content_changes() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "1\n2\n3\n4\n5\n")?;
+    commit_files(ctx, "branch", &["a.txt"])?;
+    fs::remove_file(repo.path().join("a.txt"))?;
+    fs::write(repo.path().join("b.txt"), "1\n2\n3\n4\n5\n6\n")?;
+
+    let outcome = split_rename(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        split_rename_parameters(Some(SplitRenameContentCommit {
+            branch_name: "branch".into(),
+            message_title: "Add a line to b.txt".into(),
+            message_body: String::new(),
+        })),
+    )?;
+
+    let gix_repo = ctx.gix_repo()?;
+    let rename = outcome.rename_commit_id;
+    assert_eq!(
+        blob_at(&gix_repo, rename, "b.txt").as_deref(),
+        Some(b"1\n2\n3\n4\n5\n".as_slice())
+    );
+    let content = outcome
+        .content_commit_id
+        .expect("the content changes were committed");
+    assert_eq!(
+        gix_repo
+            .find_commit(content)?
+            .parent_ids()
+            .map(|id| id.detach())
+            .collect::<Vec<_>>(),
+        [rename]
+    );
+    assert_eq!(blob_at(&gix_repo, content, "a.txt"), None);
+    assert_eq!(
+        blob_at(&gix_repo, content, "b.txt").as_deref(),
+        Some(b"1\n2\n3\n4\n5\n6\n".as_slice())
+    );
+
+    let worktree = but_core::diff::worktree_changes(&gix_repo)?;
+    assert!(worktree.changes.is_empty());
+    Ok(())
+}
+
+#[test]
+fn undone_commits_are_orphaned() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, second) = commit_files(ctx, "branch", &["b.txt"])?;
+    gitbutler_branch_actions::undo_commit(ctx, stack_id, second.to_git2())?;
+
+    let orphaned = orphaned_commits(
+        ctx,
+        GetOrphanedCommitsParameters {
+            stack_id: stack_id.to_string(),
+        },
+    )?;
+    assert_eq!(orphaned.len(), 1);
+    assert_eq!(orphaned[0].oid, second);
+    assert_eq!(orphaned[0].was_on_branch, "branch");
+    assert_eq!(orphaned[0].message_title, "Change b.txt");
+    assert!(orphaned.iter().all(|commit| commit.oid != first));
+    Ok(())
+}
+
+#[test]
+fn stacks_summary_counts_commits_without_messages() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (first_stack_id, _) = commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "first", &["b.txt"])?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    let (second_stack_id, _) = commit_files(ctx, "second", &["c.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let summary = toolset.call_tool("get_stacks_summary", "{}");
+    let stacks = summary["result"]
+        .as_array()
+        .expect("the applied stacks are listed");
+    assert_eq!(stacks.len(), 2);
+
+    let stack = |id: StackId| {
+        stacks
+            .iter()
+            .find(|stack| stack["id"] == id.to_string())
+            .expect("every applied stack is listed")
+    };
+    assert_eq!(stack(first_stack_id)["name"], "first");
+    assert_eq!(
+        stack(first_stack_id)["branches"],
+        json!([{ "name": "first", "commitCount": 2 }])
+    );
+    assert_eq!(
+        stack(second_stack_id)["branches"],
+        json!([{ "name": "second", "commitCount": 1 }])
+    );
+    assert!(
+        !summary.to_string().contains("Change"),
+        "commit messages aren't part of the summary"
+    );
+    Ok(())
+}
+
+#[test]
+fn recovered_commits_are_not_orphaned_anymore() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, second) = commit_files(ctx, "branch", &["b.txt"])?;
+    gitbutler_branch_actions::undo_commit(ctx, stack_id, second.to_git2())?;
+
+    let recovered = recover_commit(
+        ctx,
+        None,
+        None,
+        RecoverCommitParameters {
+            orphaned_oid: second.to_string(),
+            target_stack_id: stack_id.to_string(),
+            target_branch_name: "branch".into(),
+            position: RecoveryPosition::BeforeTip,
+        },
+    )?;
+    assert_eq!(recovered.branch_name, "branch");
+
+    let gix_repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let tip = vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?;
+    assert_eq!(tip.to_string(), recovered.new_oid);
+    let parents = gix_repo
+        .find_commit(tip)?
+        .parent_ids()
+        .map(|id| id.detach())
+        .collect::<Vec<_>>();
+    assert_eq!(parents, [first]);
+
+    let orphaned = orphaned_commits(
+        ctx,
+        GetOrphanedCommitsParameters {
+            stack_id: stack_id.to_string(),
+        },
+    )?;
+    assert!(orphaned.is_empty());
+    Ok(())
+}
+
+#[test]
+fn remote_only_commits_are_only_listed_on_request() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("local.txt"), "local\n")?;
+    let (_, local) = commit_files(ctx, "branch", &["local.txt"])?;
+
+    // A collaborator pushed another commit on top of the local one.
+    let gix_repo = ctx.gix_repo()?;
+    let blob = gix_repo.write_blob("remote\n")?;
+    let mut editor = gix_repo.edit_tree(gix_repo.find_commit(local)?.tree_id()?)?;
+    editor.upsert("remote.txt", gix::objs::tree::EntryKind::Blob, blob)?;
+    let tree = editor.write()?;
+    let remote = gix_repo
+        .commit("refs/remotes/origin/branch", "Add remote", tree, [local])?
+        .detach();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let commits = &status["result"]["stacks"][0]["branches"][0]["commits"];
+    assert_eq!(commits.as_array().map(Vec::len), Some(1));
+    assert!(commits[0]["remoteOnly"].is_null());
+
+    let status = toolset.call_tool(
+        "get_project_status",
+        &json!({ "includeRemoteOnly": true }).to_string(),
+    );
+    let commits = &status["result"]["stacks"][0]["branches"][0]["commits"];
+    assert_eq!(commits.as_array().map(Vec::len), Some(2));
+    assert_eq!(commits[0]["id"], remote.to_string());
+    assert_eq!(commits[0]["messageTitle"], "Add remote");
+    assert_eq!(commits[0]["remoteOnly"], true);
+    assert_eq!(commits[1]["id"], local.to_string());
+    assert!(commits[1]["remoteOnly"].is_null());
+    Ok(())
+}
+
+#[test]
+fn unpushed_work_lists_local_commits_and_divergence() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("pushed.txt"), "pushed\n")?;
+    let (stack_id, pushed) = commit_files(ctx, "branch", &["pushed.txt"])?;
+    ctx.repo()
+        .reference("refs/remotes/origin/branch", pushed.to_git2(), true, "push")?;
+    fs::write(repo.path().join("local.txt"), "local\n")?;
+    let (_, local) = commit_files(ctx, "branch", &["local.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("get_unpushed_work", "{}");
+    let branches = result["result"].as_array().expect("a list of branches");
+    assert_eq!(branches.len(), 1, "{result}");
+    assert_eq!(branches[0]["stackId"], stack_id.to_string());
+    assert_eq!(branches[0]["branchName"], "branch");
+    assert_eq!(branches[0]["unpushedCommits"][0]["id"], local.to_string());
+    assert_eq!(
+        branches[0]["unpushedCommits"].as_array().map(Vec::len),
+        Some(1)
+    );
+    assert_eq!(branches[0]["requiresForce"], false);
+
+    // A collaborator pushed another commit on top of the pushed one.
+    let gix_repo = ctx.gix_repo()?;
+    let blob = gix_repo.write_blob("remote\n")?;
+    let mut editor = gix_repo.edit_tree(gix_repo.find_commit(pushed)?.tree_id()?)?;
+    editor.upsert("remote.txt", gix::objs::tree::EntryKind::Blob, blob)?;
+    let tree = editor.write()?;
+    let remote = gix_repo
+        .commit("refs/remotes/origin/branch", "Add remote", tree, [pushed])?
+        .detach();
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "get_unpushed_work",
+        &json!({ "stackId": stack_id.to_string() }).to_string(),
+    );
+    let branch = &result["result"][0];
+    assert_eq!(
+        branch["unpushedCommits"][0]["id"],
+        local.to_string(),
+        "{result}"
+    );
+    assert_eq!(branch["remoteOnlyCommits"][0]["id"], remote.to_string());
+    assert_eq!(branch["requiresForce"], true);
+    Ok(())
+}
+
+#[test]
+fn amend_head_modifies_the_top_commit() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, head) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("a.txt"), "a\nmore\n")?;
+
+    let outcome = create_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        CommitParameters {
+            message_title: String::new(),
+            message_body: String::new(),
+            branch_name: "branch".into(),
+            branch_description: String::new(),
+            files: vec!["a.txt".into()],
+            order: None,
+            amend_head: true,
+            allow_integrated: false,
+            author_date: None,
+            force_new_branch: false,
+            stack_position: None,
+        },
+    )?;
+    let amended = outcome.new_commit.expect("the head commit was amended");
+    assert_ne!(amended, head);
+
+    let gix_repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    assert_eq!(vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?, amended);
+    let original = gix_repo.find_commit(head)?;
+    let commit = gix_repo.find_commit(amended)?;
+    assert_eq!(
+        commit.parent_ids().collect::<Vec<_>>(),
+        original.parent_ids().collect::<Vec<_>>(),
+        "no commit was added on top"
+    );
+    assert_eq!(
+        commit.message_raw_sloppy(),
+        original.message_raw_sloppy(),
+        "the message is kept"
+    );
+    assert_eq!(
+        blob_at(&gix_repo, amended, "a.txt").as_deref(),
+        Some(b"a\nmore\n".as_slice())
+    );
+    assert!(
+        but_core::diff::worktree_changes(&gix_repo)?
+            .changes
+            .is_empty()
+    );
+    Ok(())
+}
+
+#[test]
+fn force_new_branch_avoids_existing_branch_names() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, head) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({
+        "messageTitle": "Add b",
+        "messageBody": "",
+        "branchName": "branch",
+        "branchDescription": "",
+        "files": ["b.txt"],
+        "forceNewBranch": true,
+    });
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert_eq!(result["result"]["branchName"], "branch-2", "{result}");
+    drop(toolset);
+
+    let gix_repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    assert_eq!(
+        vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?,
+        head,
+        "the existing branch is left alone"
+    );
+    let new_stack = vb_state
+        .list_stacks_in_workspace()?
+        .into_iter()
+        .find(|stack| stack.heads.iter().any(|head| head.name() == "branch-2"))
+        .expect("a new branch was created");
+    assert_ne!(new_stack.id, stack_id);
+
+    // Amending and forcing a new branch contradict each other.
+    let mut parameters = parameters;
+    parameters["amendHead"] = true.into();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert!(result["error"].is_string(), "{result}");
+    Ok(())
+}
+
+#[test]
+fn pr_description_lists_the_commits_of_the_branch() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    let params = |template: Option<&str>| GetPRDescriptionParameters {
+        stack_id: stack_id.to_string(),
+        branch_name: "branch".into(),
+        template: template.map(ToOwned::to_owned),
+    };
+    let description = pr_description(ctx, params(Some("{title}: {commits}")))?;
+    assert_eq!(description.title, "Change a.txt");
+    assert_eq!(description.body, "Change a.txt: - Change a.txt");
+
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "branch", &["b.txt"])?;
+    let description = pr_description(ctx, params(None))?;
+    assert_eq!(description.title, "branch");
+    assert_eq!(
+        description.body,
+        "## branch\n\n### Changes\n\n- Change a.txt\n- Change b.txt\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn project_status_is_left_out_if_the_etag_matches() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert_eq!(status["result"]["changed"], true);
+    assert_eq!(status["result"]["fileChanges"][0]["path"], "a.txt");
+    let etag = status["result"]["etag"].clone();
+
+    let status = toolset.call_tool("get_project_status", &json!({ "etag": etag }).to_string());
+    assert_eq!(status["result"]["changed"], false);
+    assert_eq!(status["result"]["etag"], etag);
+    assert!(status["result"]["fileChanges"].is_null());
+
+    fs::write(repo.path().join("a.txt"), "a\nmore\n")?;
+    let status = toolset.call_tool("get_project_status", &json!({ "etag": etag }).to_string());
+    assert_eq!(status["result"]["changed"], true);
+    assert_ne!(status["result"]["etag"], etag);
+    assert_eq!(status["result"]["fileChanges"][0]["path"], "a.txt");
+    Ok(())
+}
+
+#[test]
+fn project_status_etag_changes_with_hunk_assignments() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["b.txt"])?;
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let hunk = &status["result"]["fileChanges"][0]["hunks"][0];
+    assert!(hunk["assignedToStack"].is_null(), "{status}");
+    let etag = status["result"]["etag"].clone();
+    drop(toolset);
+
+    // Assigning a hunk in the UI only changes the persisted assignments.
+    let mut assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    assert_eq!(assignments.len(), 1);
+    assignments[0].stack_id = Some(stack_id);
+    but_hunk_assignment::set_persisted_assignments(ctx, assignments)?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", &json!({ "etag": etag }).to_string());
+    assert_eq!(status["result"]["changed"], true, "{status}");
+    assert_ne!(status["result"]["etag"], etag);
+    let hunk = &status["result"]["fileChanges"][0]["hunks"][0];
+    assert_eq!(hunk["assignedToStack"], json!(stack_id), "{status}");
+    Ok(())
+}
+
+#[test]
+fn moving_file_changes_to_the_worktree_keeps_the_rest_of_the_commit() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (stack_id, commit_id) = commit_files(ctx, "branch", &["a.txt", "b.txt"])?;
+
+    move_file_changes(
+        ctx,
+        None,
+        None,
+        MoveFileChangesParameters {
+            source_commit_id: commit_id.to_string(),
+            source_stack_id: stack_id.to_string(),
+            destination_commit_id: WORKTREE_DESTINATION.into(),
+            destination_stack_id: String::new(),
+            files: vec!["a.txt".into()],
+            predict_only: false,
+        },
+    )?;
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let gix_repo = ctx.gix_repo()?;
+    let head = vb_state.get_stack(stack_id)?.head_oid(&gix_repo)?;
+    assert_ne!(head, commit_id, "the commit was rewritten");
+    assert_eq!(blob_at(&gix_repo, head, "a.txt"), None);
+    assert_eq!(
+        blob_at(&gix_repo, head, "b.txt").as_deref(),
+        Some(b"b\n".as_slice())
+    );
+
+    let worktree = but_core::diff::worktree_changes(&gix_repo)?;
+    assert_eq!(worktree.changes.len(), 1);
+    assert_eq!(worktree.changes[0].path, "a.txt");
+    assert!(matches!(
+        worktree.changes[0].status,
+        but_core::TreeStatus::Addition { .. }
+    ));
+    assert_eq!(
+        fs::read(repo.path().join("a.txt"))?,
+        b"a\n",
+        "nothing is lost"
+    );
+    Ok(())
+}
+
+#[test]
+fn repo_query_resolves_refs() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let gix_repo = ctx.gix_repo()?;
+    let target = gix_repo.rev_parse_single("refs/remotes/origin/master")?;
+
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::ResolveRef {
+            name: "origin/master".into(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::ResolveRef {
+            name: "refs/remotes/origin/master".into(),
+            id: Some(target.to_string()),
+        }
+    );
+
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::ResolveRef {
+            name: "does-not-exist".into(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::ResolveRef {
+            name: "does-not-exist".into(),
+            id: None,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn repo_query_reads_config_keys() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let config_path = repo.path().join(".git").join("config");
+    let mut config = fs::read_to_string(&config_path)?;
+    config.push_str("[repoquery]\n\tvalue = set\n");
+    fs::write(&config_path, config)?;
+
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::ReadConfigKey {
+            key: "repoquery.value".into(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::ReadConfigKey {
+            key: "repoquery.value".into(),
+            value: Some("set".into()),
+        }
+    );
+
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::ReadConfigKey {
+            key: "repoquery.unset".into(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::ReadConfigKey {
+            key: "repoquery.unset".into(),
+            value: None,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn repo_query_checks_if_objects_exist() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, commit_id) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::ObjectExists {
+            id: commit_id.to_string(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::ObjectExists {
+            id: commit_id.to_string(),
+            exists: true,
+            object_type: Some("commit".into()),
+        }
+    );
+
+    let missing = "1".repeat(40);
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::ObjectExists {
+            id: missing.clone(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::ObjectExists {
+            id: missing,
+            exists: false,
+            object_type: None,
+        }
+    );
+
+    assert!(
+        repo_query(
+            ctx,
+            RepoQueryParameters::ObjectExists {
+                id: "not-an-id".into(),
+            },
+        )
+        .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn repo_query_finds_the_merge_base_of_two_stacks() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, first) = commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, second) = commit_files(ctx, "second", &["b.txt"])?;
+
+    let target = ctx
+        .gix_repo()?
+        .rev_parse_single("refs/remotes/origin/master")?;
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::MergeBase {
+            first_commit_id: first.to_string(),
+            second_commit_id: second.to_string(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::MergeBase {
+            id: Some(target.to_string()),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn repo_query_parses_revisions() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, commit_id) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::RevParse {
+            spec: commit_id.to_hex_with_len(7).to_string(),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::RevParse {
+            id: commit_id.to_string(),
+            object_type: "commit".into(),
+        }
+    );
+
+    let tree_id = ctx.gix_repo()?.find_commit(commit_id)?.tree_id()?;
+    let result = repo_query(
+        ctx,
+        RepoQueryParameters::RevParse {
+            spec: format!("{commit_id}^{{tree}}"),
+        },
+    )?;
+    assert_eq!(
+        result,
+        RepoQueryResult::RevParse {
+            id: tree_id.to_string(),
+            object_type: "tree".into(),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn recent_activity_lists_a_commit_and_its_amend_in_order() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, commit_id) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("a.txt"), "amended\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({
+        "commitId": commit_id.to_string(),
+        "messageTitle": "Change a.txt",
+        "messageBody": "",
+        "stackId": stack_id.to_string(),
+        "files": ["a.txt"],
+    });
+    let result = toolset.call_tool("amend", &parameters.to_string());
+    assert!(result["error"].is_null(), "{result}");
+    drop(toolset);
+
+    let activity = recent_activity(ctx, GetRecentActivityParameters { limit: None })?;
+    let [.., commit, amend] = activity.as_slice() else {
+        panic!("expected at least two operations, got {activity:?}");
+    };
+    assert_eq!(commit.operation, "CreateCommit");
+    assert_eq!(commit.description, "Created the commit 'Change a.txt'");
+    assert_eq!(amend.operation, "AmendCommit");
+    assert_eq!(amend.description, "Amended a commit");
+    assert!(commit.created_at <= amend.created_at);
+    assert!(
+        amend
+            .affected_stacks
+            .iter()
+            .any(|stack| stack.id == stack_id),
+        "the amended stack is affected"
+    );
+
+    let activity = recent_activity(ctx, GetRecentActivityParameters { limit: Some(1) })?;
+    assert_eq!(activity.len(), 1);
+    assert_eq!(activity[0].operation, "AmendCommit");
+    Ok(())
+}
+
+fn blob_id_at(repo: &gix::Repository, commit_id: gix::ObjectId, path: &str) -> Option<String> {
+    let tree = repo.find_commit(commit_id).ok()?.tree().ok()?;
+    let entry = tree.lookup_entry_by_path(path).ok()??;
+    Some(entry.object_id().to_string())
+}
+
+#[test]
+fn commit_and_amend_results_list_the_committed_files() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["a.txt", "b.txt"])?;
+    fs::write(repo.path().join("a.txt"), "modified\n")?;
+    fs::remove_file(repo.path().join("b.txt"))?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    // The blob ids are verified by reading the commits independently of the tools.
+    let gix_repo = gix::open(repo.path())?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({
+        "messageTitle": "Change all files",
+        "messageBody": "",
+        "branchName": "branch",
+        "branchDescription": "",
+        "files": ["a.txt", "b.txt"],
+    });
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    let commit_id: gix::ObjectId = result["result"]["newCommit"]
+        .as_str()
+        .expect("a commit was created")
+        .parse()?;
+    assert_eq!(
+        result["result"]["committedFiles"],
+        json!([
+            {
+                "path": "a.txt",
+                "blobId": blob_id_at(&gix_repo, commit_id, "a.txt"),
+                "mode": "100644",
+                "action": "modified",
+            },
+            {
+                "path": "b.txt",
+                "blobId": null,
+                "mode": null,
+                "action": "deleted",
+            },
+        ])
+    );
+
+    let parameters = json!({
+        "commitId": commit_id.to_string(),
+        "messageTitle": "Change all files",
+        "messageBody": "",
+        "stackId": stack_id.to_string(),
+        "files": ["c.txt"],
+    });
+    let result = toolset.call_tool("amend", &parameters.to_string());
+    let amended_id: gix::ObjectId = result["result"]["newCommit"]
+        .as_str()
+        .expect("the commit was amended")
+        .parse()?;
+    assert_eq!(
+        result["result"]["committedFiles"],
+        json!([{
+            "path": "c.txt",
+            "blobId": blob_id_at(&gix_repo, amended_id, "c.txt"),
+            "mode": "100644",
+            "action": "added",
+        }])
+    );
+    Ok(())
+}
+
+#[test]
+fn git_attributes_are_read_from_the_worktree() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(
+        repo.path().join(".gitattributes"),
+        "*.txt text eol=lf\n*.png binary\n*.md diff=markdown merge=union\n",
+    )?;
+
+    let attributes = git_attributes(
+        ctx,
+        GetGitAttributesParameters {
+            paths: vec![
+                "a.txt".into(),
+                "image.png".into(),
+                "docs/README.md".into(),
+                "main.rs".into(),
+            ],
+        },
+    )?;
+    let paths = attributes
+        .iter()
+        .map(|path| path.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, ["a.txt", "image.png", "docs/README.md", "main.rs"]);
+
+    let txt = &attributes[0].attributes;
+    assert_eq!(txt.get("text"), Some(&AttributeValue::Set));
+    assert_eq!(txt.get("eol"), Some(&AttributeValue::Value("lf".into())));
+
+    let png = &attributes[1].attributes;
+    assert_eq!(png.get("binary"), Some(&AttributeValue::Set));
+    assert_eq!(png.get("diff"), Some(&AttributeValue::Unset));
+    assert_eq!(png.get("merge"), Some(&AttributeValue::Unset));
+    assert_eq!(png.get("text"), Some(&AttributeValue::Unset));
+
+    let md = &attributes[2].attributes;
+    assert_eq!(
+        md.get("diff"),
+        Some(&AttributeValue::Value("markdown".into()))
+    );
+    assert_eq!(
+        md.get("merge"),
+        Some(&AttributeValue::Value("union".into()))
+    );
+
+    assert!(attributes[3].attributes.is_empty());
+    Ok(())
+}
+
+#[test]
+fn branch_names_shared_by_stacks_have_to_be_qualified() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (first_stack_id, _) = commit_files(ctx, "shared", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (second_stack_id, _) = commit_files(ctx, "other", &["b.txt"])?;
+
+    // Give the head of the second stack the name of the head of the first one.
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut second_stack = vb_state.get_stack(second_stack_id)?;
+    second_stack.heads[0].name = "shared".into();
+    vb_state.set_stack(second_stack)?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let mut qualified_names = status["result"]["stacks"]
+        .as_array()
+        .expect("the stacks are listed")
+        .iter()
+        .map(|stack| {
+            let branch = &stack["branches"][0];
+            assert_eq!(branch["name"], "shared");
+            assert_eq!(branch["ambiguousName"], true);
+            branch["qualifiedName"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned()
+        })
+        .collect::<Vec<_>>();
+    qualified_names.sort();
+    let mut expected = vec![
+        qualified_branch_name(first_stack_id, "shared"),
+        qualified_branch_name(second_stack_id, "shared"),
+    ];
+    expected.sort();
+    assert_eq!(qualified_names, expected);
+
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    let err = create_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        CommitParameters {
+            message_title: "Change c.txt".into(),
+            message_body: String::new(),
+            branch_name: "shared".into(),
+            branch_description: String::new(),
+            files: vec!["c.txt".into()],
+            order: None,
+            amend_head: false,
+            allow_integrated: false,
+            author_date: None,
+            force_new_branch: false,
+            stack_position: None,
+        },
+    )
+    .expect_err("the branch name is ambiguous");
+    let value = error_to_json(&err, "create_commit");
+    assert_eq!(value["code"], "ambiguousBranchName");
+    assert_eq!(value["candidates"].as_array().map(Vec::len), Some(2));
+
+    let heads = [first_stack_id, second_stack_id].map(|id| (id, "shared".to_owned()));
+    assert_eq!(
+        resolve_branch_name(
+            heads.clone(),
+            &qualified_branch_name(second_stack_id, "shared")
+        )?,
+        Some((second_stack_id, "shared".to_owned()))
+    );
+    assert_eq!(resolve_branch_name(heads, "unknown")?, None);
+    Ok(())
+}
+
+#[test]
+fn commits_are_found_by_a_unique_part_of_their_message() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["b.txt"])?;
+
+    let commit = commit_by_message(
+        ctx,
+        GetCommitByMessageParameters {
+            message: "change A.TXT".into(),
+            stack_id: Some(stack_id.to_string()),
+        },
+    )?;
+    assert_eq!(commit.id, first);
+    assert_eq!(commit.message_title, "Change a.txt");
+
+    let err = commit_by_message(
+        ctx,
+        GetCommitByMessageParameters {
+            message: "Change".into(),
+            stack_id: None,
+        },
+    )
+    .expect_err("both commits match");
+    assert!(err.to_string().contains(&first.to_string()));
+    Ok(())
+}
+
+#[test]
+fn the_worktree_lock_status_reflects_the_exclusive_access() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let status = worktree_lock_status(ctx)?;
+    assert!(!status.is_locked);
+    assert_eq!(status.locked_by_pid, None);
+
+    let guard = ctx.project().exclusive_worktree_access();
+    let status = worktree_lock_status(ctx)?;
+    assert!(status.is_locked);
+    assert_eq!(status.locked_by_pid, Some(std::process::id()));
+    assert!(status.locked_since.is_some());
+
+    drop(guard);
+    assert!(!worktree_lock_status(ctx)?.is_locked);
+
+    // Readers make operations that change the worktree wait as well, but aren't recorded as holders.
+    let guard = ctx.project().shared_worktree_access();
+    let status = worktree_lock_status(ctx)?;
+    assert!(status.is_locked);
+    assert_eq!(status.locked_by_pid, None);
+    drop(guard);
+    assert!(!worktree_lock_status(ctx)?.is_locked);
+    Ok(())
+}
+
+#[test]
+fn reassigned_hunks_are_committed_to_the_given_stack() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let lines = (1..=20).map(|n| format!("line {n}\n")).collect::<String>();
+    fs::write(repo.path().join("lines.txt"), &lines)?;
+    let (stack_id, _) = commit_files(ctx, "first", &["lines.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "second", &["b.txt"])?;
+
+    // Change the first and the last line, which are far enough apart to end up in separate hunks.
+    let changed = lines
+        .replace("line 1\n", "line one\n")
+        .replace("line 20\n", "line twenty\n");
+    fs::write(repo.path().join("lines.txt"), &changed)?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let file_change = status["result"]["fileChanges"]
+        .as_array()
+        .and_then(|changes| changes.iter().find(|c| c["path"] == "lines.txt"))
+        .cloned()
+        .expect("lines.txt is changed");
+    assert_eq!(file_change["hunks"].as_array().map(Vec::len), Some(2));
+    let first_hunk_header = file_change["hunks"][0]["diff"]
+        .as_str()
+        .and_then(|diff| diff.lines().next())
+        .unwrap_or_default()
+        .to_owned();
+    drop(toolset);
+
+    let outcome = reassign_and_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        ReassignAndCommitParameters {
+            stack_id: stack_id.to_string(),
+            hunks: vec![HunkSelection {
+                path: "lines.txt".into(),
+                hunk_headers: vec![first_hunk_header],
+            }],
+            message_title: "Rename the first line".into(),
+            message_body: String::new(),
+        },
+    )?;
+    let commit_id = outcome.outcome.new_commit.expect("a commit was created");
+    assert_eq!(outcome.branch_name, "first");
+    assert!(outcome.warnings.is_empty());
+
+    let gix_repo = ctx.gix_repo()?;
+    let branch_head = gix_repo
+        .find_reference("refs/heads/first")?
+        .peel_to_id_in_place()?
+        .detach();
+    assert_eq!(branch_head, commit_id, "the commit is on top of the stack");
+
+    let committed = blob_at(&gix_repo, commit_id, "lines.txt").expect("lines.txt is committed");
+    let committed = String::from_utf8(committed)?;
+    assert!(committed.starts_with("line one\n"));
+    assert!(
+        committed.ends_with("line 20\n"),
+        "only the first hunk is committed"
+    );
+    Ok(())
+}
+
+fn persisted_assignment_paths(ctx: &mut CommandContext) -> anyhow::Result<Vec<String>> {
+    Ok(but_hunk_assignment::persisted_assignments(ctx)?
+        .into_iter()
+        .map(|assignment| assignment.path_bytes.to_string())
+        .collect())
+}
+
+#[test]
+fn assignments_of_committed_files_are_removed() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+
+    // Listing the changes persists their assignments.
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.call_tool("get_project_status", "{}");
+    drop(toolset);
+    let before = persisted_assignment_paths(ctx)?;
+    assert!(before.contains(&"a.txt".to_owned()));
+    assert!(before.contains(&"b.txt".to_owned()));
+
+    commit_files(ctx, "branch", &["a.txt"])?;
+
+    let after = persisted_assignment_paths(ctx)?;
+    assert!(
+        !after.contains(&"a.txt".to_owned()),
+        "the committed file has no assignments left"
+    );
+    assert!(after.contains(&"b.txt".to_owned()));
+    Ok(())
+}
+
+#[test]
+fn change_stats_are_bucketed_by_stack() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (first_stack_id, _) = commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (second_stack_id, _) = commit_files(ctx, "second", &["b.txt"])?;
+
+    // Changes to committed lines are assigned to the stack of the commit, new files aren't assigned.
+    fs::write(repo.path().join("a.txt"), "changed a\n")?;
+    fs::write(repo.path().join("b.txt"), "b1\nb2\n")?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+
+    let stats = change_stats_by_stack(ctx, &AssignmentsCache::default())?;
+    let stats_of = |stack_id| {
+        stats
+            .stacks
+            .iter()
+            .find(|stack| stack.stack_id == stack_id)
+            .map(|stack| stack.stats.clone())
+    };
+    assert_eq!(
+        stats_of(first_stack_id),
+        Some(ChangeStats {
+            files: 1,
+            insertions: 1,
+            deletions: 1,
+        })
+    );
+    assert_eq!(
+        stats_of(second_stack_id),
+        Some(ChangeStats {
+            files: 1,
+            insertions: 2,
+            deletions: 1,
+        })
+    );
+    assert_eq!(
+        stats.unassigned,
+        ChangeStats {
+            files: 1,
+            insertions: 1,
+            deletions: 0,
+        }
+    );
+    Ok(())
+}
+
+/// Add a commit with its own version of `shared.txt` on top of each of the `branches` and their stacks.
+fn add_shared_file(ctx: &CommandContext, branches: &[(StackId, &str)]) -> anyhow::Result<()> {
+    let gix_repo = ctx.gix_repo()?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    for (stack_id, branch_name) in branches {
+        let mut stack = vb_state.get_stack(*stack_id)?;
+        let tip = stack.head_oid(&gix_repo)?;
+        let blob = gix_repo.write_blob(format!("{branch_name}\n"))?;
+        let mut editor = gix_repo.edit_tree(gix_repo.find_commit(tip)?.tree_id()?)?;
+        editor.upsert("shared.txt", gix::objs::tree::EntryKind::Blob, blob)?;
+        let tree = editor.write()?;
+        let commit = gix_repo
+            .commit(
+                format!("refs/heads/{branch_name}"),
+                "Add shared.txt",
+                tree,
+                [tip],
+            )?
+            .detach();
+        stack.set_stack_head(&vb_state, &gix_repo, commit.to_git2(), None)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn stacks_changing_the_same_lines_conflict_directly() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (first_stack_id, _) = commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (second_stack_id, _) = commit_files(ctx, "second", &["b.txt"])?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    commit_files(ctx, "third", &["c.txt"])?;
+
+    // The first two stacks each add their own version of the same file.
+    add_shared_file(
+        ctx,
+        &[(first_stack_id, "first"), (second_stack_id, "second")],
+    )?;
+
+    let pairs = conflicting_stack_pairs(ctx)?;
+    assert_eq!(
+        pairs.len(),
+        1,
+        "the third stack doesn't conflict with any other"
+    );
+    let pair = &pairs[0];
+    let mut stack_ids = [pair.stack_a_id, pair.stack_b_id];
+    stack_ids.sort();
+    let mut expected = [first_stack_id, second_stack_id];
+    expected.sort();
+    assert_eq!(stack_ids, expected);
+    assert_eq!(pair.conflicting_paths, ["shared.txt"]);
+    assert_eq!(pair.severity, ConflictSeverity::DirectConflict);
+    Ok(())
+}
+
+#[test]
+fn structured_diffs_reconstruct_the_unified_diff() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("file.txt"), "one\ntwo\nthree\n")?;
+    commit_files(ctx, "branch", &["file.txt"])?;
+    fs::write(repo.path().join("file.txt"), "one\n2\nthree\nfour")?;
+
+    let gix_repo = ctx.gix_repo()?;
+    let mut diff_in = |diff_format| {
+        diff_for_paths(
+            ctx,
+            &gix_repo,
+            GetDiffForPathsParameters {
+                paths: vec!["file.txt".into()],
+                diff_format,
+                context_lines: None,
+            },
+        )
+    };
+    let unified = diff_in(DiffFormat::Unified)?.file_changes[0].hunks[0]
+        .diff
+        .clone();
+    let structured = diff_in(DiffFormat::Structured)?.file_changes[0].hunks[0]
+        .diff
+        .clone();
+
+    let HunkDiff::Structured { header, lines } = &structured else {
+        panic!("the hunk is structured: {structured:?}");
+    };
+    assert!(header.starts_with("@@ "));
+    let kinds = lines.iter().map(|line| line.kind).collect::<Vec<_>>();
+    assert!(kinds.contains(&DiffLineKind::Context));
+    assert!(kinds.contains(&DiffLineKind::Added));
+    assert!(kinds.contains(&DiffLineKind::Removed));
+    assert!(lines.iter().any(|line| line.content == "2"));
+
+    assert_eq!(structured.clone().into_format(DiffFormat::Unified), unified);
+    assert_eq!(unified.into_format(DiffFormat::Structured), structured);
+    Ok(())
+}
+
+#[test]
+fn structured_hunks_leave_out_the_diff_text() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("file.txt"), "added\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool(
+        "get_project_status",
+        &json!({ "diffFormat": "structured" }).to_string(),
+    );
+    let hunk = &status["result"]["fileChanges"][0]["hunks"][0];
+    assert!(hunk["diff"].is_null());
+    assert_eq!(
+        hunk["lines"],
+        json!([{ "kind": "added", "content": "added" }])
+    );
+
+    let status = toolset.call_tool("get_project_status", "{}");
+    let hunk = &status["result"]["fileChanges"][0]["hunks"][0];
+    assert!(hunk["lines"].is_null());
+    assert!(
+        hunk["diff"]
+            .as_str()
+            .is_some_and(|diff| diff.ends_with("+added\n"))
+    );
+    Ok(())
+}
+
+#[test]
+fn pushed_commits_are_told_apart_from_local_ones() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("pushed.txt"), "pushed\n")?;
+    let (stack_id, pushed) = commit_files(ctx, "branch", &["pushed.txt"])?;
+    // The first commit was pushed before the second one was created.
+    ctx.repo()
+        .reference("refs/remotes/origin/branch", pushed.to_git2(), true, "push")?;
+    fs::write(repo.path().join("local.txt"), "local\n")?;
+    let (_, local) = commit_files(ctx, "branch", &["local.txt"])?;
+
+    let commits = branch_upstream_commits(
+        ctx,
+        GetBranchUpstreamCommitsParameters {
+            stack_id: stack_id.to_string(),
+            branch_name: "branch".into(),
+        },
+    )?;
+    let ids = |commits: &[but_tools::workspace::SimpleCommit]| {
+        commits.iter().map(|commit| commit.id).collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&commits.pushed), [pushed]);
+    assert_eq!(ids(&commits.local_only), [local]);
+    assert!(commits.remote_only.is_empty());
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let commits = &status["result"]["stacks"][0]["branches"][0]["commits"];
+    assert_eq!(commits[0]["id"], local.to_string());
+    assert!(commits[0]["pushed"].is_null());
+    assert_eq!(commits[1]["id"], pushed.to_string());
+    assert_eq!(commits[1]["pushed"], true);
+    Ok(())
+}
+
+#[test]
+fn stacks_are_merged_after_the_stacks_they_depend_on() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (first_stack_id, _) = commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (second_stack_id, _) = commit_files(ctx, "second", &["b.txt"])?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    let (third_stack_id, _) = commit_files(ctx, "third", &["c.txt"])?;
+    add_shared_file(
+        ctx,
+        &[(first_stack_id, "first"), (second_stack_id, "second")],
+    )?;
+
+    let order = stack_merge_order(ctx, GetStackMergeOrderParameters { stack_ids: None })?;
+    assert_eq!(order.len(), 3);
+    let position_of = |stack_id| {
+        order
+            .iter()
+            .find(|suggestion| suggestion.stack_id == stack_id)
+            .map(|suggestion| suggestion.suggested_position)
+    };
+    let (earlier, later) = if position_of(first_stack_id) < position_of(second_stack_id) {
+        (first_stack_id, second_stack_id)
+    } else {
+        (second_stack_id, first_stack_id)
+    };
+    for suggestion in &order {
+        if suggestion.stack_id == later {
+            assert_eq!(suggestion.depends_on, [earlier]);
+            assert!(suggestion.reason.contains("shared.txt"));
+        } else {
+            assert!(suggestion.depends_on.is_empty(), "{suggestion:?}");
+        }
+    }
+
+    let order = stack_merge_order(
+        ctx,
+        GetStackMergeOrderParameters {
+            stack_ids: Some(vec![
+                second_stack_id.to_string(),
+                third_stack_id.to_string(),
+            ]),
+        },
+    )?;
+    assert_eq!(order.len(), 2);
+    assert!(
+        order
+            .iter()
+            .all(|suggestion| suggestion.depends_on.is_empty())
+    );
+    Ok(())
+}
+
+#[test]
+fn only_commits_after_the_known_one_are_new() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, reviewed) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, new) = commit_files(ctx, "branch", &["b.txt"])?;
+
+    let changes = changes_since(
+        ctx,
+        GetChangesSinceParameters {
+            stack_id: stack_id.to_string(),
+            branch_name: "branch".into(),
+            since_commit_id: Some(reviewed.to_string()),
+            since_snapshot_id: None,
+        },
+    )?;
+    assert!(changes.since_commit_found);
+    assert_eq!(changes.rewritten_to, None);
+    assert_eq!(
+        changes
+            .new_commits
+            .iter()
+            .map(|commit| commit.id)
+            .collect::<Vec<_>>(),
+        [new]
+    );
+    assert_eq!(changes.changed_paths, None);
+    Ok(())
+}
+
+#[test]
+fn rewritten_commits_point_to_their_successor() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, reviewed) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("a.txt"), "a\nmore\n")?;
+    let outcome = create_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        CommitParameters {
+            message_title: String::new(),
+            message_body: String::new(),
+            branch_name: "branch".into(),
+            branch_description: String::new(),
+            files: vec!["a.txt".into()],
+            order: None,
+            amend_head: true,
+            allow_integrated: false,
+            author_date: None,
+            force_new_branch: false,
+            stack_position: None,
+        },
+    )?;
+    let amended = outcome.new_commit.expect("the head commit was amended");
+
+    let changes = changes_since(
+        ctx,
+        GetChangesSinceParameters {
+            stack_id: stack_id.to_string(),
+            branch_name: "branch".into(),
+            since_commit_id: Some(reviewed.to_string()),
+            since_snapshot_id: None,
+        },
+    )?;
+    assert!(!changes.since_commit_found);
+    assert!(changes.new_commits.is_empty());
+    assert_eq!(changes.rewritten_to, Some(amended));
+    Ok(())
+}
+
+#[test]
+fn files_changed_since_a_snapshot_are_listed() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["a.txt"])?;
+    let activity = recent_activity(ctx, GetRecentActivityParameters { limit: Some(1) })?;
+    // Committing the file left its content as it was in the snapshot.
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({
+        "stackId": stack_id.to_string(),
+        "branchName": "branch",
+        "sinceSnapshotId": activity[0].snapshot_id,
+    });
+    let result = toolset.call_tool("get_changes_since", &parameters.to_string());
+    assert!(result["error"].is_null(), "{result}");
+    assert_eq!(result["result"]["changedPaths"], json!(["b.txt"]));
+    assert_eq!(
+        result["result"]["newCommits"].as_array().map(Vec::len),
+        Some(1)
+    );
+    Ok(())
+}
+
+#[test]
+fn commits_and_edits_since_a_snapshot_are_diffed() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, second) = commit_files(ctx, "branch", &["b.txt"])?;
+    // The snapshot of the second commit was taken before it was created.
+    let activity = recent_activity(ctx, GetRecentActivityParameters { limit: Some(1) })?;
+    fs::write(repo.path().join("a.txt"), "changed\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({ "snapshotId": activity[0].snapshot_id });
+    let result = toolset.call_tool("diff_against_snapshot", &parameters.to_string());
+    let diff = &result["result"];
+    assert!(result["error"].is_null(), "{result}");
+
+    let file_changes = diff["fileChanges"].as_array().expect("a list of changes");
+    assert_eq!(
+        file_changes.len(),
+        1,
+        "b.txt was in the worktree already: {diff}"
+    );
+    assert_eq!(file_changes[0]["path"], "a.txt");
+    assert_eq!(file_changes[0]["status"], "modified");
+
+    let branch_changes = diff["branchChanges"].as_array().expect("a list of changes");
+    assert_eq!(branch_changes.len(), 1, "{diff}");
+    let change = &branch_changes[0];
+    assert_eq!(change["stackId"], stack_id.to_string());
+    assert_eq!(change["branchName"], "branch");
+    assert_eq!(change["previousHead"], first.to_string());
+    assert_eq!(change["head"], second.to_string());
+    assert_eq!(change["addedCommits"][0]["id"], second.to_string());
+    assert_eq!(change["addedCommits"].as_array().map(Vec::len), Some(1));
+    assert_eq!(change["removedCommits"], json!([]));
+    Ok(())
+}
+
+#[test]
+fn renames_are_detected_above_the_similarity_threshold() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "1\n2\n3\n4\n5\n")?;
+    let (_, original) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::remove_file(repo.path().join("a.txt"))?;
+    fs::write(repo.path().join("b.txt"), "1\n2\n3\n4\n5\n6\n")?;
+    let outcome = split_rename(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        split_rename_parameters(Some(SplitRenameContentCommit {
+            branch_name: "branch".into(),
+            message_title: "Add a line to b.txt".into(),
+            message_body: String::new(),
+        })),
+    )?;
+    let content = outcome
+        .content_commit_id
+        .expect("the content changes were committed");
+
+    let gix_repo = ctx.gix_repo()?;
+    let renames = |to: gix::ObjectId, similarity_threshold| {
+        file_renames(
+            &gix_repo,
+            GetFileRenamesParameters {
+                from_commit_id: original.to_string(),
+                to_commit_id: to.to_string(),
+                similarity_threshold,
+            },
+        )
+    };
+    assert_eq!(
+        renames(outcome.rename_commit_id, None)?,
+        [RenameDetection {
+            old_path: "a.txt".into(),
+            new_path: "b.txt".into(),
+            similarity: 1.0,
+            is_copy: false,
+        }]
+    );
+
+    let [rename] = renames(content, None)?.try_into().expect("a single rename");
+    assert_eq!(
+        (rename.old_path.as_str(), rename.new_path.as_str()),
+        ("a.txt", "b.txt")
+    );
+    assert!(
+        rename.similarity > 0.5 && rename.similarity < 1.0,
+        "{rename:?}"
+    );
+    assert!(!rename.is_copy);
+
+    assert!(
+        renames(content, Some(1.0))?.is_empty(),
+        "the content of b.txt changed"
+    );
+    assert!(renames(content, Some(1.5)).is_err());
+    Ok(())
+}
+
+#[test]
+fn integrated_branches_are_not_committed_to() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, merged) = commit_files(ctx, "branch", &["a.txt"])?;
+    // The branch was merged upstream, but the stack wasn't cleaned up yet.
+    ctx.repo().reference(
+        "refs/remotes/origin/master",
+        merged.to_git2(),
+        true,
+        "merge",
+    )?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert_eq!(
+        status["result"]["stacks"][0]["branches"][0]["integrated"],
+        true
+    );
+
+    let mut parameters = json!({
+        "messageTitle": "Add b.txt",
+        "messageBody": "",
+        "branchName": "branch",
+        "branchDescription": "",
+        "files": ["b.txt"],
+    });
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert_eq!(result["code"], "branchIntegrated", "{result}");
+    assert!(result["nextBranchName"].is_null());
+
+    parameters["allowIntegrated"] = true.into();
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert!(result["error"].is_null(), "{result}");
+    assert!(result["result"]["newCommit"].is_string());
+    Ok(())
+}
+
+#[test]
+fn stacks_can_be_left_out_of_the_status() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert_eq!(status["result"]["stacks"].as_array().map(Vec::len), Some(1));
+
+    let status = toolset.call_tool(
+        "get_project_status",
+        &json!({ "includeStacks": false }).to_string(),
+    );
+    assert_eq!(status["result"]["stacks"], json!([]));
+    assert_eq!(status["result"]["fileChanges"][0]["path"], "b.txt");
+    Ok(())
+}
+
+#[test]
+fn tests_named_after_changed_files_are_suggested() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let files = [
+        "src/parser.rs",
+        "src/lexer.rs",
+        "tests/parser.rs",
+        "tests/lexer.rs",
+        "web/app.ts",
+        "web/app.test.ts",
+        "web/api.ts",
+    ];
+    for file in files {
+        let path = repo.path().join(file);
+        fs::create_dir_all(path.parent().expect("files are in directories"))?;
+        fs::write(path, format!("{file}\n"))?;
+    }
+    commit_files(ctx, "branch", &files)?;
+    fs::write(repo.path().join("src/parser.rs"), "changed\n")?;
+    fs::write(repo.path().join("web/app.ts"), "changed\n")?;
+    fs::write(repo.path().join("web/api.ts"), "changed\n")?;
+    fs::write(repo.path().join("web/api.spec.ts"), "new\n")?;
+
+    let affected = affected_tests(&ctx.gix_repo()?)?;
+    let affected_test = |path: &str, test_paths: &[&str]| AffectedTests {
+        path: path.into(),
+        test_paths: test_paths.iter().map(|p| p.to_string()).collect(),
+    };
+    assert_eq!(
+        affected,
+        [
+            affected_test("src/parser.rs", &["tests/parser.rs"]),
+            affected_test("web/api.spec.ts", &["web/api.spec.ts"]),
+            affected_test("web/api.ts", &["web/api.spec.ts"]),
+            affected_test("web/app.ts", &["web/app.test.ts"]),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn status_of_an_unchanged_project_is_identical() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let lines = (1..=20).map(|n| format!("{n}\n")).collect::<String>();
+    fs::write(repo.path().join("a.txt"), &lines)?;
+    commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "second", &["b.txt"])?;
+    fs::write(
+        repo.path().join("a.txt"),
+        lines.replace("2\n", "two\n").replace("19\n", "nineteen\n"),
+    )?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    fs::write(repo.path().join("b.txt"), "changed\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    assert!(status["error"].is_null(), "{status}");
+    assert_eq!(
+        serde_json::to_string(&toolset.call_tool("get_project_status", "{}"))?,
+        serde_json::to_string(&status)?
+    );
+
+    let file_changes = status["result"]["fileChanges"]
+        .as_array()
+        .expect("there are file changes");
+    let paths = file_changes
+        .iter()
+        .map(|change| change["path"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, ["a.txt", "b.txt", "c.txt"]);
+    let hunks = file_changes[0]["hunks"]
+        .as_array()
+        .expect("a.txt has hunks");
+    assert_eq!(hunks.len(), 2);
+    assert!(
+        hunks[0]["diff"]
+            .as_str()
+            .is_some_and(|diff| diff.contains("+two"))
+    );
+    assert!(hunks[0]["changeRef"].is_string());
+    assert_ne!(hunks[0]["changeRef"], hunks[1]["changeRef"]);
+    Ok(())
+}
+
+#[test]
+fn rebase_previews_report_conflicts_without_rewriting_commits() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, second) = commit_files(ctx, "branch", &["b.txt"])?;
+
+    // Upstream added its own version of a.txt.
+    let gix_repo = ctx.gix_repo()?;
+    let base = gix_repo
+        .find_commit(first)?
+        .parent_ids()
+        .next()
+        .expect("the first commit has a parent")
+        .detach();
+    let blob = gix_repo.write_blob("upstream\n")?;
+    let mut editor = gix_repo.edit_tree(gix_repo.find_commit(base)?.tree_id()?)?;
+    editor.upsert("a.txt", gix::objs::tree::EntryKind::Blob, blob)?;
+    let tree = editor.write()?;
+    let upstream = gix_repo
+        .commit("refs/heads/upstream", "Add a.txt upstream", tree, [base])?
+        .detach();
+
+    let preview_onto = |ctx: &mut CommandContext, onto: gix::ObjectId| {
+        branch_rebase_preview(
+            ctx,
+            GetBranchRebasePreviewParameters {
+                stack_id: stack_id.to_string(),
+                onto_oid: onto.to_string(),
+            },
+        )
+    };
+    let preview = preview_onto(ctx, base)?;
+    assert!(!preview.has_conflicts);
+    assert_eq!(
+        preview
+            .commits_to_rewrite
+            .iter()
+            .map(|commit| (commit.original_oid, commit.new_oid, commit.status))
+            .collect::<Vec<_>>(),
+        [
+            (first, None, RebaseStatus::Unchanged),
+            (second, None, RebaseStatus::Unchanged)
+        ]
+    );
+
+    let preview = preview_onto(ctx, upstream)?;
+    assert!(preview.has_conflicts);
+    assert_eq!(preview.conflict_paths, ["a.txt"]);
+    let statuses = preview
+        .commits_to_rewrite
+        .iter()
+        .map(|commit| commit.status)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        [RebaseStatus::Conflicted, RebaseStatus::Conflicted],
+        "the second commit builds on the conflicted first one"
+    );
+    let new_commit = preview.commits_to_rewrite[0]
+        .new_oid
+        .expect("the first commit is rewritten");
+    assert!(
+        ctx.gix_repo()?.find_commit(new_commit).is_err(),
+        "nothing is written to the repository"
+    );
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    assert_eq!(
+        vb_state.get_stack(stack_id)?.head_oid(&ctx.gix_repo()?)?,
+        second
+    );
+    Ok(())
+}
+
+#[test]
+fn diff_stats_count_the_changed_lines_of_each_file() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "1\n2\n3\n")?;
+    fs::write(repo.path().join("removed.txt"), "removed\n")?;
+    commit_files(ctx, "branch", &["a.txt", "removed.txt"])?;
+    fs::write(repo.path().join("a.txt"), "1\ntwo\n3\n4\n")?;
+    fs::remove_file(repo.path().join("removed.txt"))?;
+    fs::write(repo.path().join("image.bin"), b"\0\x01\x02binary")?;
+    let (_, commit_id) = commit_files(ctx, "branch", &["a.txt", "removed.txt", "image.bin"])?;
+
+    let stat = commit_diff_stat(
+        &ctx.gix_repo()?,
+        GetCommitDiffStatParameters {
+            commit_id: commit_id.to_string(),
+        },
+    )?;
+    let line = |path: &str, additions, deletions, is_binary| DiffStatLine {
+        path: path.into(),
+        additions,
+        deletions,
+        is_binary,
+    };
+    assert_eq!(
+        stat.files,
+        [
+            line("a.txt", 2, 1, false),
+            line("image.bin", 0, 0, true),
+            line("removed.txt", 0, 1, false),
+        ]
+    );
+    assert_eq!(
+        stat.summary,
+        DiffStatSummary {
+            total_files: 3,
+            total_additions: 2,
+            total_deletions: 2,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn author_dates_can_be_set_and_kept() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let mut parameters = json!({
+        "messageTitle": "Add a.txt",
+        "messageBody": "",
+        "branchName": "branch",
+        "branchDescription": "",
+        "files": ["a.txt"],
+        "authorDate": "not a date",
+    });
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert_eq!(result["code"], "invalidAuthorDate", "{result}");
+    parameters["authorDate"] = "2999-01-01 00:00:00 +0000".into();
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    assert_eq!(result["code"], "invalidAuthorDate", "{result}");
+
+    parameters["authorDate"] = "2020-02-03 04:05:06 +0100".into();
+    let result = toolset.call_tool("commit", &parameters.to_string());
+    let commit_id = result["result"]["newCommit"]
+        .as_str()
+        .expect("commit was created");
+    let commit = find_commit(ctx, commit_id)?;
+    assert_eq!(commit.author.time.seconds, 1_580_699_106);
+    assert_eq!(commit.author.time.offset, 3600);
+    assert!(
+        commit.committer.time.seconds > commit.author.time.seconds,
+        "the committer date is the current date"
+    );
+
+    let stack_id =
+        toolset.call_tool("get_project_status", "{}")["result"]["stacks"][0]["id"].clone();
+    fs::write(repo.path().join("a.txt"), "amended\n")?;
+    let mut parameters = json!({
+        "commitId": commit_id,
+        "messageTitle": "Add a.txt",
+        "messageBody": "",
+        "stackId": stack_id,
+        "files": ["a.txt"],
+    });
+    let result = toolset.call_tool("amend", &parameters.to_string());
+    let commit_id = result["result"]["newCommit"]
+        .as_str()
+        .expect("commit was amended");
+    let commit = find_commit(ctx, commit_id)?;
+    assert_eq!(
+        commit.author.time.seconds, 1_580_699_106,
+        "amending keeps the author date by default"
+    );
+
+    fs::write(repo.path().join("a.txt"), "amended again\n")?;
+    parameters["commitId"] = commit_id.into();
+    parameters["keepAuthorDate"] = false.into();
+    let result = toolset.call_tool("amend", &parameters.to_string());
+    let commit_id = result["result"]["newCommit"]
+        .as_str()
+        .expect("commit was amended");
+    let commit = find_commit(ctx, commit_id)?;
+    assert!(commit.author.time.seconds > 1_580_699_106);
+    Ok(())
+}
+
+fn find_commit(ctx: &CommandContext, id: &str) -> anyhow::Result<gix::objs::Commit> {
+    let repo = ctx.gix_repo()?;
+    let commit = repo.find_commit(gix::ObjectId::from_hex(id.as_bytes())?)?;
+    Ok(commit.decode()?.to_owned())
+}
+
+#[test]
+fn integration_previews_report_conflicting_stacks() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (conflicting_stack_id, conflicting) = commit_files(ctx, "conflicting", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (clean_stack_id, _) = commit_files(ctx, "clean", &["b.txt"])?;
+
+    let preview = preview_integration(ctx)?;
+    assert!(preview.up_to_date);
+    assert!(preview.stacks.iter().all(|stack| stack.rebases_cleanly));
+
+    // Upstream added its own version of a.txt.
+    let gix_repo = ctx.gix_repo()?;
+    let base = preview.base_id;
+    let blob = gix_repo.write_blob("upstream\n")?;
+    let mut editor = gix_repo.edit_tree(gix_repo.find_commit(base)?.tree_id()?)?;
+    editor.upsert("a.txt", gix::objs::tree::EntryKind::Blob, blob)?;
+    let tree = editor.write()?;
+    let upstream = gix_repo
+        .commit(
+            "refs/remotes/origin/master",
+            "Add a.txt upstream",
+            tree,
+            [base],
+        )?
+        .detach();
+    let tips_before = [
+        gix_repo
+            .find_reference("refs/heads/conflicting")?
+            .id()
+            .detach(),
+        gix_repo.find_reference("refs/heads/clean")?.id().detach(),
+    ];
+
+    let preview = preview_integration(ctx)?;
+    assert!(!preview.up_to_date);
+    assert_eq!(preview.new_base_id, upstream);
+    assert_eq!(preview.stacks.len(), 2);
+    let stack = |id: StackId| {
+        preview
+            .stacks
+            .iter()
+            .find(|stack| stack.stack_id == id)
+            .expect("every stack is previewed")
+    };
+
+    let clean = stack(clean_stack_id);
+    assert!(clean.rebases_cleanly);
+    assert!(clean.conflicting_commits.is_empty());
+    assert!(clean.integrated_branches.is_empty());
+
+    let conflicted = stack(conflicting_stack_id);
+    assert!(!conflicted.rebases_cleanly);
+    assert_eq!(conflicted.conflicting_commits.len(), 1);
+    assert_eq!(conflicted.conflicting_commits[0].commit_id, conflicting);
+    assert_eq!(conflicted.conflicting_commits[0].conflict_paths, ["a.txt"]);
+    assert!(conflicted.integrated_branches.is_empty());
+
+    let gix_repo = ctx.gix_repo()?;
+    let tips_after = [
+        gix_repo
+            .find_reference("refs/heads/conflicting")?
+            .id()
+            .detach(),
+        gix_repo.find_reference("refs/heads/clean")?.id().detach(),
+    ];
+    assert_eq!(tips_before, tips_after, "no branch was rebased");
+    Ok(())
+}
+
+#[test]
+fn signed_and_unsigned_commits_have_different_signature_statuses() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, unsigned) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    let gix_repo = ctx.gix_repo()?;
+    let mut commit = gix_repo.find_commit(unsigned)?.decode()?.to_owned();
+    commit.extra_headers.push((
+        "gpgsig".into(),
+        "-----BEGIN SSH SIGNATURE-----\nU1NIU0lH\n-----END SSH SIGNATURE-----\n".into(),
+    ));
+    let signed = gix_repo.write_object(&commit)?.detach();
+
+    let status = |commit_id: gix::ObjectId| {
+        commit_signature_status(
+            &gix_repo,
+            GetCommitSignatureStatusParameters {
+                commit_id: commit_id.to_string(),
+            },
+        )
+    };
+    let status_of_unsigned = status(unsigned)?;
+    assert!(!status_of_unsigned.is_signed);
+    assert_eq!(status_of_unsigned.signature_type, None);
+    assert_eq!(status_of_unsigned.verification, None);
+
+    let status_of_signed = status(signed)?;
+    assert!(status_of_signed.is_signed);
+    assert_eq!(status_of_signed.signature_type, Some(SignatureType::Ssh));
+    assert_ne!(
+        status_of_signed.verification,
+        Some(SignatureVerification::Verified),
+        "the signature was made up"
+    );
+    Ok(())
+}
+
+#[test]
+fn staged_commit_previews_only_list_the_hunks_assigned_to_the_branch() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let lines = (1..=20).map(|n| format!("line {n}\n")).collect::<String>();
+    fs::write(repo.path().join("lines.txt"), &lines)?;
+    commit_files(ctx, "first", &["lines.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "second", &["b.txt"])?;
+
+    // The changes of lines.txt depend on the first branch, while c.txt is new and unassigned.
+    let changed = lines
+        .replace("line 1\n", "line one\n")
+        .replace("line 20\n", "line twenty\n");
+    fs::write(repo.path().join("lines.txt"), &changed)?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+
+    let preview = |ctx: &mut CommandContext, branch_name: &str| {
+        staged_commit_preview(
+            ctx,
+            &AssignmentsCache::default(),
+            GetStagedCommitPreviewParameters {
+                branch_name: branch_name.into(),
+            },
+        )
+    };
+    let first = preview(ctx, "first")?;
+    assert_eq!(
+        first
+            .files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect::<Vec<_>>(),
+        ["lines.txt"]
+    );
+    assert_eq!(first.total_hunks, 2);
+    assert_eq!(first.unassigned_hunks_count, 1);
+
+    let second = preview(ctx, "second")?;
+    assert!(second.files.is_empty());
+    assert_eq!(second.total_hunks, 0);
+    assert_eq!(second.unassigned_hunks_count, 1);
+
+    assert!(preview(ctx, "missing").is_err());
+    Ok(())
+}
+
+#[test]
+fn target_branch_commits_are_listed_until_the_given_commit() -> anyhow::Result<()> {
+    let Test { ctx, .. } = &mut Test::default();
+    let gix_repo = ctx.gix_repo()?;
+    let base = gix_repo
+        .find_reference("refs/remotes/origin/master")?
+        .peel_to_id_in_place()?
+        .detach();
+    let tree = gix_repo.find_commit(base)?.tree_id()?.detach();
+    let first = gix_repo
+        .commit("refs/remotes/origin/master", "First upstream", tree, [base])?
+        .detach();
+    let second = gix_repo
+        .commit(
+            "refs/remotes/origin/master",
+            "Second upstream",
+            tree,
+            [first],
+        )?
+        .detach();
+
+    let list = |limit: Option<usize>, since: Option<gix::ObjectId>| {
+        target_branch_commits(
+            ctx,
+            GetTargetBranchCommitsParameters {
+                limit,
+                since_oid: since.map(|id| id.to_string()),
+            },
+        )
+        .map(|commits| commits.into_iter().map(|c| c.id).collect::<Vec<_>>())
+    };
+    let all = list(None, None)?;
+    assert_eq!(all[..3], [second, first, base]);
+    assert_eq!(list(Some(1), None)?, [second]);
+    assert_eq!(list(None, Some(base))?, [second, first]);
+    assert!(list(None, Some(second))?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn several_commits_are_reworded_at_once() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, second) = commit_files(ctx, "branch", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let mut parameters = json!({
+        "stackId": stack_id.to_string(),
+        "commits": [
+            { "commitId": first.to_string(), "messageTitle": "Add a", "messageBody": "The first file." },
+            { "commitId": second.to_string(), "messageTitle": "Add b", "messageBody": "" },
+        ],
+    });
+    let result = toolset.call_tool("reword_commits", &parameters.to_string());
+    let mapping = result["result"]
+        .as_array()
+        .expect("the commits were reworded");
+    assert_eq!(mapping.len(), 2);
+    assert_eq!(mapping[0]["oldId"], first.to_string());
+    assert_eq!(mapping[1]["oldId"], second.to_string());
+    drop(toolset);
+
+    let gix_repo = ctx.gix_repo()?;
+    let head = gix_repo
+        .find_reference("refs/heads/branch")?
+        .peel_to_commit()?;
+    assert_eq!(head.id.to_string(), mapping[1]["newId"]);
+    assert_eq!(head.message_raw_sloppy(), "Add b");
+    let parent = gix_repo.find_commit(head.parent_ids().next().expect("has parent"))?;
+    assert_eq!(parent.id.to_string(), mapping[0]["newId"]);
+    assert_eq!(parent.message_raw_sloppy(), "Add a\n\nThe first file.");
+
+    // Unknown commits are refused before anything is rewritten.
+    parameters["commits"][0]["commitId"] = "0000000000000000000000000000000000000000".into();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("reword_commits", &parameters.to_string());
+    assert!(result["error"].is_string(), "{result}");
+    Ok(())
+}
+
+#[test]
+fn commits_are_copied_to_the_top_of_another_branch() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (source_stack_id, source) = commit_files(ctx, "source", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (stack_id, destination_head) = commit_files(ctx, "destination", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let mut parameters = json!({
+        "sourceCommitId": source.to_string(),
+        "destinationStackId": stack_id.to_string(),
+        "destinationBranchName": "destination",
+    });
+    let result = toolset.call_tool("copy_commit_to_stack", &parameters.to_string());
+    let new_oid = result["result"]["newOid"]
+        .as_str()
+        .expect("the commit was copied")
+        .to_owned();
+    drop(toolset);
+
+    let gix_repo = ctx.gix_repo()?;
+    let head = gix_repo
+        .find_reference("refs/heads/destination")?
+        .peel_to_commit()?;
+    assert_eq!(head.id.to_string(), new_oid);
+    assert_ne!(head.id, source, "the copy is a new commit");
+    assert_eq!(
+        head.parent_ids().next().map(|id| id.detach()),
+        Some(destination_head)
+    );
+    assert_eq!(
+        head.message_raw_sloppy(),
+        gix_repo.find_commit(source)?.message_raw_sloppy()
+    );
+    assert!(head.tree()?.lookup_entry_by_path("a.txt")?.is_some());
+    assert!(head.tree()?.lookup_entry_by_path("b.txt")?.is_some());
+    assert_eq!(
+        gix_repo
+            .find_reference("refs/heads/source")?
+            .peel_to_commit()?
+            .id,
+        source,
+        "the source commit stays where it is"
+    );
+
+    // A new message is required if the message isn't copied.
+    parameters["copyMessage"] = false.into();
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("copy_commit_to_stack", &parameters.to_string());
+    assert!(result["error"].is_string(), "{result}");
+
+    // Copying a commit into its own stack is refused.
+    parameters["copyMessage"] = true.into();
+    parameters["destinationStackId"] = source_stack_id.to_string().into();
+    parameters["destinationBranchName"] = "source".into();
+    let result = toolset.call_tool("copy_commit_to_stack", &parameters.to_string());
+    assert!(result["error"].is_string(), "{result}");
+    Ok(())
+}
+
+#[test]
+fn stack_diff_summary_lists_net_changes_once() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "1\n2\n3\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("a.txt"), "1\nchanged\n3\n4\n")?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "branch", &["a.txt", "b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "get_stack_diff_summary",
+        &json!({ "stackId": stack_id.to_string() }).to_string(),
+    );
+    assert_eq!(
+        result["result"],
+        json!({
+            "files": [
+                { "path": "a.txt", "status": "added", "insertions": 4, "deletions": 0 },
+                { "path": "b.txt", "status": "added", "insertions": 1, "deletions": 0 },
+            ],
+            "totals": { "files": 2, "insertions": 5, "deletions": 0 },
+        }),
+        "a.txt was changed by both commits, but only its net change is listed"
+    );
+    Ok(())
+}
+
+#[test]
+fn repair_workspace_fixes_reported_issues_only() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, commit) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "other", &["b.txt"])?;
+    ctx.gix_repo()?.reference(
+        "refs/heads/other",
+        commit,
+        gix::refs::transaction::PreviousValue::Any,
+        "point to the tip of another stack",
+    )?;
+    let lock_file = repo.path().join(".git/index.lock");
+    fs::write(&lock_file, "")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("get_workspace_health", "{}");
+    let mut ids = result["result"]["issues"]
+        .as_array()
+        .expect("issues")
+        .iter()
+        .map(|issue| issue["id"].as_str().unwrap_or_default().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(ids.len(), 2, "{result}");
+    assert_eq!(ids[0], format!("shared_tip:{commit}"));
+    assert!(
+        ids[1].starts_with("lock_file:") && ids[1].ends_with("index.lock"),
+        "{ids:?}"
+    );
+    let lock_file_id = ids[1].clone();
+    ids.push("missing_workspace_reference".into());
+
+    let result = toolset.call_tool(
+        "repair_workspace",
+        &json!({ "issuesToFix": ids }).to_string(),
+    );
+    assert_eq!(result["result"]["fixed"], json!([lock_file_id]), "{result}");
+    assert!(!lock_file.exists());
+    let could_not_fix = result["result"]["couldNotFix"]
+        .as_array()
+        .expect("unfixed issues")
+        .iter()
+        .map(|issue| issue[0].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        could_not_fix,
+        [
+            format!("shared_tip:{commit}"),
+            "missing_workspace_reference".into()
+        ],
+        "shared tips need a decision, and issues that aren't reported are left alone"
+    );
+    Ok(())
+}
+
+#[test]
+fn repair_workspace_takes_a_snapshot_first() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join(".git/index.lock"), "")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("get_workspace_health", "{}");
+    let id = result["result"]["issues"][0]["id"].clone();
+    let result = toolset.call_tool(
+        "repair_workspace",
+        &json!({ "issuesToFix": [id] }).to_string(),
+    );
+    assert_eq!(result["result"]["fixed"], json!([id]), "{result}");
+
+    let summary = toolset.session_summary(None);
+    let titles = summary
+        .snapshots
+        .iter()
+        .map(|snapshot| snapshot.title.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["Repaired the workspace"]);
+    Ok(())
+}
+
+#[test]
+fn trailers_are_added_to_commits_and_parsed() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, commit) = commit_files(ctx, "branch", &["a.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let mut add_trailer = |commit: &str, token: &str, value: &str| {
+        let result = toolset.call_tool(
+            "add_trailer_to_commit",
+            &json!({
+                "stackId": stack_id.to_string(),
+                "commitId": commit,
+                "token": token,
+                "value": value,
+            })
+            .to_string(),
+        );
+        result["result"][0]["newId"].as_str().map(ToOwned::to_owned)
+    };
+    let signed_off = add_trailer(&commit.to_string(), "Signed-off-by", "A <a@example.com>")
+        .expect("the trailer was added");
+    let reviewed = add_trailer(&signed_off, "Reviewed-by", "B <b@example.com>")
+        .expect("the trailer was added");
+    assert_eq!(
+        add_trailer(&reviewed, "signed-off-by", "A <a@example.com>"),
+        None,
+        "trailers aren't added twice"
+    );
+    assert_eq!(add_trailer(&reviewed, "Not a token", "value"), None);
+
+    let result = toolset.call_tool(
+        "get_commit_signoff_trailers",
+        &json!({ "commitId": reviewed }).to_string(),
+    );
+    assert_eq!(
+        result["result"],
+        json!([
+            { "token": "Signed-off-by", "value": "A <a@example.com>" },
+            { "token": "Reviewed-by", "value": "B <b@example.com>" },
+        ])
+    );
+    drop(toolset);
+
+    let gix_repo = ctx.gix_repo()?;
+    assert_eq!(
+        gix_repo
+            .find_commit(reviewed.parse::<gix::ObjectId>()?)?
+            .message_raw_sloppy(),
+        "Change a.txt\n\nSigned-off-by: A <a@example.com>\nReviewed-by: B <b@example.com>\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn commit_messages_are_split_into_title_body_and_trailers() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let outcome = create_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        CommitParameters {
+            message_title: "Add a".into(),
+            message_body: "Why a is needed.\n\nMore details.\n\nSigned-off-by: A <a@example.com>"
+                .into(),
+            branch_name: "branch".into(),
+            branch_description: String::new(),
+            files: vec!["a.txt".into()],
+            order: None,
+            amend_head: false,
+            allow_integrated: false,
+            author_date: None,
+            force_new_branch: false,
+            stack_position: None,
+        },
+    )?;
+    let commit = outcome.new_commit.expect("a commit was created");
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, title_only) = commit_files(ctx, "branch", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "get_commit_message_body",
+        &json!({ "commitId": commit.to_string() }).to_string(),
+    );
+    assert_eq!(
+        result["result"],
+        json!({
+            "title": "Add a",
+            "body": "Why a is needed.\n\nMore details.",
+            "trailers": [{ "token": "Signed-off-by", "value": "A <a@example.com>" }],
+        })
+    );
+
+    let result = toolset.call_tool(
+        "get_commit_message_body",
+        &json!({ "commitId": title_only.to_string() }).to_string(),
+    );
+    assert_eq!(
+        result["result"],
+        json!({ "title": "Change b.txt", "body": "", "trailers": [] })
+    );
+    Ok(())
+}
+
+#[test]
+fn files_are_restored_from_older_commits() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "first\n")?;
+    let (_, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("a.txt"), "second\n")?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "branch", &["a.txt", "b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let mut restore = |path: &str, delete_if_missing: bool| {
+        toolset.call_tool(
+            "restore_file_from_commit",
+            &json!({
+                "commitId": first.to_string(),
+                "path": path,
+                "deleteIfMissing": delete_if_missing,
+            })
+            .to_string(),
+        )
+    };
+    let result = restore("./a.txt", false);
+    assert_eq!(result["result"]["path"], "a.txt", "{result}");
+    assert_eq!(result["result"]["status"], "modified");
+    assert_eq!(fs::read_to_string(repo.path().join("a.txt"))?, "first\n");
+
+    let result = restore("b.txt", false);
+    assert!(
+        result["error"]
+            .as_str()
+            .is_some_and(|error| error.contains("deleteIfMissing")),
+        "{result}"
+    );
+    assert!(repo.path().join("b.txt").exists());
+
+    let result = restore("b.txt", true);
+    assert_eq!(result["result"]["status"], "deleted", "{result}");
+    assert!(!repo.path().join("b.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn new_stacks_are_placed_at_the_requested_position() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (second, _) = commit_files(ctx, "second", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    fn stack_names(toolset: &mut Toolset) -> Vec<String> {
+        let summary = toolset.call_tool("get_stacks_summary", "{}");
+        summary["result"]
+            .as_array()
+            .map(|stacks| {
+                stacks
+                    .iter()
+                    .map(|stack| stack["name"].as_str().unwrap_or_default().to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    let result = toolset.call_tool(
+        "create_branch",
+        &json!({
+            "branchName": "leftmost",
+            "branchDescription": "",
+            "stackPosition": { "index": 0 },
+        })
+        .to_string(),
+    );
+    assert!(result["result"]["warnings"].is_null(), "{result}");
+    assert_eq!(stack_names(&mut toolset), ["leftmost", "first", "second"]);
+
+    let result = toolset.call_tool(
+        "create_branch",
+        &json!({
+            "branchName": "between",
+            "branchDescription": "",
+            "stackPosition": { "beforeStackId": second.to_string() },
+        })
+        .to_string(),
+    );
+    assert!(result["error"].is_null(), "{result}");
+    assert_eq!(
+        stack_names(&mut toolset),
+        ["leftmost", "first", "between", "second"]
+    );
+
+    // An unknown stack doesn't fail the commit, the stack is placed last instead.
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    let result = toolset.call_tool(
+        "commit",
+        &json!({
+            "messageTitle": "Add c.txt",
+            "messageBody": "",
+            "branchName": "unplaced",
+            "branchDescription": "",
+            "files": ["c.txt"],
+            "stackPosition": { "afterStackId": StackId::generate().to_string() },
+        })
+        .to_string(),
+    );
+    assert!(result["result"]["newCommit"].is_string(), "{result}");
+    assert!(
+        result["result"]["warnings"][0]
+            .as_str()
+            .is_some_and(|warning| warning.contains("isn't in the workspace")),
+        "{result}"
+    );
+    assert_eq!(
+        stack_names(&mut toolset),
+        ["leftmost", "first", "between", "second", "unplaced"]
+    );
+
+    // The deprecated 'order' is still accepted, but only 'stackPosition' is offered.
+    let schema = toolset.call_tool("get_tool_schema", &json!({ "name": "commit" }).to_string());
+    let properties = &schema["result"][0]["parameters"]["properties"];
+    assert!(properties["stackPosition"].is_object(), "{schema}");
+    assert!(properties["order"].is_null(), "{schema}");
+    Ok(())
+}
+
+#[test]
+fn cross_stack_diff_flags_hunks_changing_the_same_lines() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (first_stack_id, _) = commit_files(ctx, "first", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (second_stack_id, _) = commit_files(ctx, "second", &["b.txt"])?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+    commit_files(ctx, "third", &["c.txt"])?;
+    add_shared_file(
+        ctx,
+        &[(first_stack_id, "first"), (second_stack_id, "second")],
+    )?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({
+        "stackIds": [first_stack_id.to_string(), second_stack_id.to_string()],
+    });
+    let result = toolset.call_tool("get_cross_stack_diff", &parameters.to_string());
+    let paths = result["result"]
+        .as_array()
+        .map(|changes| {
+            changes
+                .iter()
+                .map(|change| change["path"].as_str().unwrap_or_default())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    assert_eq!(paths, ["a.txt", "b.txt", "shared.txt"], "{result}");
+
+    let a = &result["result"][0]["hunks"];
+    assert_eq!(a.as_array().map(Vec::len), Some(1));
+    assert!(a[0]["hasConflict"].is_null());
+
+    let shared = &result["result"][2]["hunks"];
+    assert_eq!(shared.as_array().map(Vec::len), Some(2), "{result}");
+    assert_eq!(shared[0]["hasConflict"], true);
+    assert_eq!(shared[1]["hasConflict"], true);
+    Ok(())
+}
+
+#[test]
+fn commits_with_overlapping_changes_are_related() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n")?;
+    let (stack_id, commit) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "other", &["b.txt"])?;
+    fs::write(
+        repo.path().join("a.txt"),
+        "1\n2\n3\nfour\n5\n6\n7\n8\n9\n10\n",
+    )?;
+    fs::write(repo.path().join("c.txt"), "c\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "get_related_commits",
+        &json!({ "path": "a.txt" }).to_string(),
+    );
+    let related = result["result"]
+        .as_array()
+        .expect("the related commits are listed");
+    assert_eq!(related.len(), 1, "{result}");
+    assert_eq!(related[0]["id"], commit.to_string());
+    assert_eq!(related[0]["stackId"], stack_id.to_string());
+
+    let result = toolset.call_tool(
+        "get_related_commits",
+        &json!({ "path": "c.txt" }).to_string(),
+    );
+    assert_eq!(
+        result["result"],
+        json!([]),
+        "new files don't overlap with any commit"
+    );
+
+    let result = toolset.call_tool(
+        "get_related_commits",
+        &json!({ "path": "a.txt", "hunkHeader": "@@ -20,1 +20,1 @@" }).to_string(),
+    );
+    assert!(result["error"].is_string(), "{result}");
+    Ok(())
+}
+
+#[test]
+fn workspace_health_reports_shared_tips_and_lock_files() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (_, commit) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "other", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("get_workspace_health", "{}");
+    assert_eq!(result["result"]["healthy"], true, "{result}");
+    assert_eq!(result["result"]["issues"], json!([]));
+
+    fs::write(repo.path().join(".git/index.lock"), "")?;
+    let result = toolset.call_tool("get_workspace_health", "{}");
+    assert_eq!(
+        result["result"]["healthy"], true,
+        "lock files are only warnings"
+    );
+    let issues = result["result"]["issues"].as_array().expect("issues");
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0]["severity"], "warning");
+    assert!(
+        issues[0]["description"]
+            .as_str()
+            .is_some_and(|description| description.contains("index.lock"))
+    );
+    fs::remove_file(repo.path().join(".git/index.lock"))?;
+    drop(toolset);
+
+    ctx.gix_repo()?.reference(
+        "refs/heads/other",
+        commit,
+        gix::refs::transaction::PreviousValue::Any,
+        "point to the tip of another stack",
+    )?;
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool("get_workspace_health", "{}");
+    assert_eq!(result["result"]["healthy"], false, "{result}");
+    let issues = result["result"]["issues"].as_array().expect("issues");
+    assert!(
+        issues.iter().any(|issue| issue["severity"] == "error"
+            && issue["description"]
+                .as_str()
+                .is_some_and(|description| description.contains(&commit.to_string()))),
+        "{result}"
+    );
+    Ok(())
+}
+
+/// Put `path` into a conflicted state, with conflicting index entries for `base`, `ours` and `theirs`
+/// and their conflict markers in the worktree.
+fn conflict_in_worktree(
+    ctx: &CommandContext,
+    path: &str,
+    [base, ours, theirs]: [&str; 3],
+) -> anyhow::Result<()> {
+    use gix::index::entry::{Flags, Mode, Stage};
+    let repo = ctx.gix_repo()?;
+    let mut index = repo.open_index()?;
+    index.remove_entries(|_, entry_path, _| entry_path == path);
+    for (stage, content) in [
+        (Stage::Base, base),
+        (Stage::Ours, ours),
+        (Stage::Theirs, theirs),
+    ] {
+        let blob_id = repo.write_blob(content)?.detach();
+        index.dangerously_push_entry(
+            Default::default(),
+            blob_id,
+            Flags::from_stage(stage),
+            Mode::FILE,
+            path.into(),
+        );
+    }
+    index.sort_entries();
+    index.write(Default::default())?;
+
+    let markers =
+        format!("<<<<<<< ours\n{ours}||||||| base\n{base}=======\n{theirs}>>>>>>> theirs\n");
+    fs::write(
+        repo.workdir().expect("non-bare").join(path),
+        format!("first\n{markers}middle\n{markers}last\n"),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn conflicting_hunks_of_the_worktree_are_listed_and_resolved() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    conflict_in_worktree(ctx, "conflicted.txt", ["base\n", "ours\n", "theirs\n"])?;
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+
+    let conflicts = toolset.call_tool("get_conflicting_hunks", "{}");
+    assert_eq!(
+        conflicts["result"],
+        json!([{
+            "path": "conflicted.txt",
+            "regions": [
+                { "startLine": 2, "endLine": 8, "ours": "ours\n", "base": "base\n", "theirs": "theirs\n" },
+                { "startLine": 10, "endLine": 16, "ours": "ours\n", "base": "base\n", "theirs": "theirs\n" },
+            ],
+        }])
+    );
+
+    let missing = toolset.call_tool(
+        "resolve_conflict",
+        &json!({ "path": "conflicted.txt" }).to_string(),
+    );
+    assert!(missing["error"].is_string(), "a side or content is needed");
+
+    let resolved = toolset.call_tool(
+        "resolve_conflict",
+        &json!({ "path": "conflicted.txt", "side": "theirs" }).to_string(),
+    );
+    assert_eq!(resolved["result"], json!([]), "no conflicts remain");
+    assert_eq!(
+        fs::read_to_string(repo.path().join("conflicted.txt"))?,
+        "first\ntheirs\nmiddle\ntheirs\nlast\n"
+    );
+
+    let again = toolset.call_tool(
+        "resolve_conflict",
+        &json!({ "path": "conflicted.txt", "side": "ours" }).to_string(),
+    );
+    assert!(
+        again["error"]
+            .as_str()
+            .unwrap()
+            .contains("isn't conflicted"),
+        "resolved files can't be resolved again"
+    );
+    Ok(())
+}
+
+#[test]
+fn stale_assignments_are_pruned() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("x.txt"), "x\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["x.txt"])?;
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+
+    // Seed the assignments of both files, with the one of a.txt assigned to the stack.
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.call_tool("get_project_status", "{}");
+    drop(toolset);
+    let mut assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    for assignment in &mut assignments {
+        if assignment.path_bytes == "a.txt" {
+            assignment.stack_id = Some(stack_id);
+        }
+    }
+    but_hunk_assignment::set_persisted_assignments(ctx, assignments)?;
+
+    // a.txt is reverted, and b.txt grows, which shifts its hunk without losing the assignment.
+    fs::remove_file(repo.path().join("a.txt"))?;
+    fs::write(repo.path().join("b.txt"), "b\nmore b\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let pruned = toolset.call_tool("prune_stale_assignments", "{}");
+    let pruned = pruned["result"].as_array().expect("a list of stacks");
+    assert_eq!(pruned.len(), 1, "only the assignment of a.txt is stale");
+    assert_eq!(pruned[0]["stackId"], json!(stack_id));
+    assert_eq!(pruned[0]["removed"][0]["path"], "a.txt");
+    assert!(pruned[0]["removed"][0]["hunkHeader"].is_object());
+    let pruned_again = toolset.call_tool("prune_stale_assignments", "{}");
+    assert_eq!(
+        pruned_again["result"],
+        json!([]),
+        "nothing is stale anymore"
+    );
+    drop(toolset);
+    assert_eq!(persisted_assignment_paths(ctx)?, ["b.txt"]);
+
+    // With auto-pruning, listing the changes removes stale assignments as well.
+    let mut assignments = but_hunk_assignment::persisted_assignments(ctx)?;
+    let mut stale = assignments[0].clone();
+    stale.path_bytes = "gone.txt".into();
+    stale.path = "gone.txt".into();
+    assignments.push(stale);
+    but_hunk_assignment::set_persisted_assignments(ctx, assignments)?;
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.set_auto_prune(true);
+    toolset.call_tool(
+        "get_diff_for_paths",
+        &json!({ "paths": ["b.txt"] }).to_string(),
+    );
+    drop(toolset);
+    assert_eq!(
+        persisted_assignment_paths(ctx)?,
+        ["b.txt", "gone.txt"],
+        "diffs of single paths don't touch the assignments"
+    );
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    toolset.set_auto_prune(true);
+    toolset.call_tool("get_project_status", "{}");
+    drop(toolset);
+    assert_eq!(persisted_assignment_paths(ctx)?, ["b.txt"]);
+    Ok(())
+}
+
+#[test]
+fn branch_commits_are_listed_from_the_head_with_positions_and_parents() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let mut commits = vec![];
+    for file in ["first.txt", "second.txt", "third.txt"] {
+        fs::write(repo.path().join(file), format!("{file}\n"))?;
+        let (stack_id, commit) = commit_files(ctx, "branch", &[file])?;
+        commits.push((stack_id, commit.to_string()));
+    }
+    let stack_id = commits[0].0;
+    let [first, second, third] = [&commits[0].1, &commits[1].1, &commits[2].1];
+
+    let assert_topology = |status: &serde_json::Value| {
+        let branch_commits = &status["result"]["stacks"][0]["branches"][0]["commits"];
+        let listed = branch_commits
+            .as_array()
+            .expect("the branch has commits")
+            .iter()
+            .map(|commit| {
+                (
+                    commit["id"].as_str().unwrap_or_default().to_owned(),
+                    commit["position"].clone(),
+                    commit["parentIds"][0]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listed[..2],
+            [
+                (third.clone(), json!(0), second.clone()),
+                (second.clone(), json!(1), first.clone()),
+            ],
+            "{status}"
+        );
+        assert_eq!(listed[2].0, *first);
+        assert_eq!(listed[2].1, json!(2));
+    };
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    assert_topology(&toolset.call_tool("get_project_status", "{}"));
+
+    // Parents that don't match the expected position are rejected with a hint.
+    let blank_commit = |parent: &str, position: u32| {
+        json!({
+            "messageTitle": "Blank",
+            "messageBody": "",
+            "stackId": stack_id.to_string(),
+            "parentId": parent,
+            "position": position,
+        })
+        .to_string()
+    };
+    let result = toolset.call_tool("create_blank_commit", &blank_commit(third, 2));
+    let error = result["error"]
+        .as_str()
+        .expect("the position doesn't match");
+    assert!(error.contains("is the head of branch 'branch'"), "{error}");
+    assert!(error.contains("did you mean position 0?"), "{error}");
+    assert!(error.contains(&format!("use {first} as parent")), "{error}");
+    let result = toolset.call_tool("create_blank_commit", &blank_commit(first, 2));
+    assert!(result["error"].is_null(), "{result}");
+    drop(toolset);
+
+    // The positions are the same if the stacks are listed with the new workspace implementation.
+    let mut settings = AppSettings::default();
+    settings.feature_flags.ws3 = true;
+    let mut ws3_ctx = CommandContext::open(ctx.project(), settings)?;
+    let mut toolset = workspace_toolset(&mut ws3_ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let listed = &status["result"]["stacks"][0]["branches"][0]["commits"];
+    let positions = (0..4)
+        .map(|i| listed[i]["position"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        positions,
+        [json!(0), json!(1), json!(2), json!(3)],
+        "{status}"
+    );
+    assert_eq!(
+        listed[3]["id"],
+        json!(first),
+        "the oldest commit wasn't rewritten"
+    );
+    assert_eq!(
+        listed[2]["parentIds"],
+        json!([first]),
+        "the blank commit is on top of it"
+    );
+    Ok(())
+}
+
+#[test]
+fn linked_issues_are_extracted_from_the_commits_of_a_branch() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let outcome = create_commit(
+        ctx,
+        None,
+        None,
+        &MessageSanitizer::default(),
+        CommitParameters {
+            message_title: "Add a for PROJ-7".into(),
+            message_body: "Fixes #12".into(),
+            branch_name: "branch".into(),
+            branch_description: String::new(),
+            files: vec!["a.txt".into()],
+            order: None,
+            amend_head: false,
+            allow_integrated: false,
+            author_date: None,
+            force_new_branch: false,
+            stack_position: None,
+        },
+    )?;
+    let commit = outcome.new_commit.expect("a commit was created");
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (stack_id, _) = commit_files(ctx, "branch", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let params = json!({ "stackId": stack_id.to_string(), "branchName": "branch" }).to_string();
+    let result = toolset.call_tool("get_branch_linked_issues", &params);
+    assert_eq!(
+        result["result"],
+        json!([
+            { "refId": "PROJ-7", "commitId": commit.to_string() },
+            { "refId": "#12", "commitId": commit.to_string() },
+        ])
+    );
+
+    fs::create_dir_all(repo.path().join(".gitbutler"))?;
+    fs::write(
+        repo.path().join(".gitbutler/issue-patterns.toml"),
+        "[[patterns]]\nregex = '(?P<ref>#\\d+)'\nurl = \"https://github.com/org/repo/issues/{number}\"\n",
+    )?;
+    let result = toolset.call_tool("get_branch_linked_issues", &params);
+    assert_eq!(
+        result["result"],
+        json!([{
+            "refId": "#12",
+            "url": "https://github.com/org/repo/issues/12",
+            "commitId": commit.to_string(),
+        }]),
+        "the configured patterns replace the default ones"
+    );
+    Ok(())
+}
+
+#[test]
+fn token_estimate_grows_with_the_worktree() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let small = toolset.call_tool("get_workspace_token_estimate", "{}")["result"].clone();
+    assert_eq!(small["changedFiles"], 1);
+    assert_eq!(small["charsPerToken"], 4.0);
+
+    fs::write(repo.path().join("b.txt"), "b\n".repeat(1000))?;
+    let large = toolset.call_tool("get_workspace_token_estimate", "{}")["result"].clone();
+    assert_eq!(large["changedFiles"], 2);
+    assert!(large["diffBytes"].as_u64() > small["diffBytes"].as_u64());
+    assert!(large["totalTokens"].as_u64().unwrap() >= small["totalTokens"].as_u64().unwrap() + 500);
+
+    let finer = toolset.call_tool(
+        "get_workspace_token_estimate",
+        &json!({ "charsPerToken": 2.0 }).to_string(),
+    )["result"]
+        .clone();
+    assert!(
+        finer["diffTokens"].as_u64() > large["diffTokens"].as_u64(),
+        "fewer characters per token make for more tokens"
+    );
+    Ok(())
+}
+
+#[test]
+fn file_moves_are_followed_back_through_the_stack() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let content = (1..=20)
+        .map(|line| format!("line {line}\n"))
+        .collect::<String>();
+    fs::write(repo.path().join("a.txt"), &content)?;
+    commit_files(ctx, "branch", &["a.txt"])?;
+    fs::rename(repo.path().join("a.txt"), repo.path().join("b.txt"))?;
+    let (_, first_move) = commit_files(ctx, "branch", &["a.txt", "b.txt"])?;
+    fs::write(repo.path().join("other.txt"), "other\n")?;
+    commit_files(ctx, "branch", &["other.txt"])?;
+    fs::remove_file(repo.path().join("b.txt"))?;
+    fs::write(repo.path().join("c.txt"), format!("{content}line 21\n"))?;
+    let (stack_id, second_move) = commit_files(ctx, "branch", &["b.txt", "c.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "get_file_move_history",
+        &json!({ "currentPath": "c.txt", "stackId": stack_id.to_string() }).to_string(),
+    );
+    let events = result["result"].as_array().expect("a list of moves");
+    assert_eq!(events.len(), 2, "{result}");
+    assert_eq!(
+        events[0],
+        json!({
+            "fromPath": "a.txt",
+            "toPath": "b.txt",
+            "commitId": first_move.to_string(),
+            "similarity": 1.0,
+        })
+    );
+    assert_eq!(events[1]["fromPath"], "b.txt");
+    assert_eq!(events[1]["toPath"], "c.txt");
+    assert_eq!(events[1]["commitId"], second_move.to_string());
+    let similarity = events[1]["similarity"].as_f64().unwrap_or_default();
+    assert!(similarity > 0.5 && similarity < 1.0, "{similarity}");
+
+    let result = toolset.call_tool(
+        "get_file_move_history",
+        &json!({ "currentPath": "other.txt", "stackId": stack_id.to_string() }).to_string(),
+    );
+    assert_eq!(result["result"], json!([]));
+    Ok(())
+}
+
+#[test]
+fn stack_commit_map_keeps_positions_across_amends() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    let (_, second) = commit_files(ctx, "branch", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let parameters = json!({ "stackId": stack_id.to_string() }).to_string();
+    let before = toolset.call_tool("get_stack_commit_map", &parameters);
+    assert_eq!(
+        before["result"],
+        json!({ "branch/0": second.to_string(), "branch/1": first.to_string() })
+    );
+
+    fs::write(repo.path().join("a.txt"), "a\nmore\n")?;
+    let amended = toolset.call_tool(
+        "amend",
+        &json!({
+            "commitId": first.to_string(),
+            "stackId": stack_id.to_string(),
+            "messageTitle": "Change a.txt",
+            "messageBody": "",
+            "files": ["a.txt"],
+        })
+        .to_string(),
+    );
+    let after = toolset.call_tool("get_stack_commit_map", &parameters);
+    assert_eq!(after["result"]["branch/1"], amended["result"]["newCommit"]);
+    assert_ne!(after["result"]["branch/0"], before["result"]["branch/0"]);
+    assert_eq!(
+        after["result"].as_object().map(|map| map.len()),
+        Some(2),
+        "the amended commit keeps its position"
+    );
+    Ok(())
+}
+
+#[test]
+fn ahead_behind_of_tips_with_merge_commits_matches_walking_each_tip() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let mut tips = vec![];
+    for (branch, commits) in [("a", 2), ("b", 1), ("c", 3)] {
+        let mut tip = None;
+        for n in 0..commits {
+            let file = format!("{branch}{n}.txt");
+            fs::write(repo.path().join(&file), "content\n")?;
+            tip = Some(commit_files(ctx, branch, &[file.as_str()])?.1);
+        }
+        tips.extend(tip);
+    }
+
+    // Merge `b` into `a`, and continue on top of the merge, and merge `a` into `c`.
+    let git = ctx.gix_repo()?;
+    let tree = |id: gix::ObjectId| -> anyhow::Result<gix::ObjectId> {
+        Ok(git.find_commit(id)?.tree_id()?.detach())
+    };
+    let merged = git
+        .commit(
+            "refs/heads/merged",
+            "Merge b",
+            tree(tips[0])?,
+            [tips[0], tips[1]],
+        )?
+        .detach();
+    let after_merge = git
+        .commit(
+            "refs/heads/merged",
+            "After the merge",
+            tree(merged)?,
+            [merged],
+        )?
+        .detach();
+    let merged_into_c = git
+        .commit(
+            "refs/heads/merged-c",
+            "Merge a",
+            tree(tips[2])?,
+            [tips[2], tips[0]],
+        )?
+        .detach();
+
+    let count = |tip: gix::ObjectId, hidden: gix::ObjectId| -> anyhow::Result<usize> {
+        Ok(tip
+            .attach(&git)
+            .ancestors()
+            .with_hidden(Some(hidden))
+            .all()?
+            .count())
+    };
+    let merge_tips = [after_merge, merged_into_c, tips[1]];
+    let base = git.merge_base(tips[0], tips[1])?.detach();
+    for target in [base, tips[0], tips[1], merged, merged_into_c] {
+        let expected = merge_tips
+            .iter()
+            .map(|tip| {
+                Ok(AheadBehind {
+                    ahead: count(*tip, target)?,
+                    behind: count(target, *tip)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(
+            ahead_behind_per_tip(&git, target, &merge_tips)?,
+            expected,
+            "{target}"
+        );
+    }
+
+    // A tip that doesn't share any history with the target can't be compared.
+    let unrelated = git
+        .commit(
+            "refs/heads/unrelated",
+            "Unrelated",
+            tree(tips[0])?,
+            None::<gix::ObjectId>,
+        )?
+        .detach();
+    assert!(ahead_behind_per_tip(&git, base, &[tips[0], unrelated]).is_err());
+    Ok(())
+}
+
+#[test]
+fn ahead_behind_of_all_stacks_matches_walking_each_stack() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    let mut tips = vec![];
+    for (branch, commits) in [("a", 2), ("b", 1), ("c", 3)] {
+        let mut tip = None;
+        for n in 0..commits {
+            let file = format!("{branch}{n}.txt");
+            fs::write(repo.path().join(&file), "content\n")?;
+            tip = Some(commit_files(ctx, branch, &[file.as_str()])?.1);
+        }
+        tips.extend(tip);
+    }
+
+    let git = ctx.gix_repo()?;
+    let base = git.merge_base(tips[0], tips[1])?.detach();
+    let count = |tip: gix::ObjectId, hidden: gix::ObjectId| -> anyhow::Result<usize> {
+        Ok(tip
+            .attach(&git)
+            .ancestors()
+            .with_hidden(Some(hidden))
+            .all()?
+            .count())
+    };
+    for target in [base, tips[0], tips[2]] {
+        let expected = tips
+            .iter()
+            .map(|tip| {
+                Ok(AheadBehind {
+                    ahead: count(*tip, target)?,
+                    behind: count(target, *tip)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(
+            ahead_behind_per_tip(&git, target, &tips)?,
+            expected,
+            "{target}"
+        );
+    }
+    let aheads = ahead_behind_per_tip(&git, base, &tips)?
+        .into_iter()
+        .map(|ahead_behind| ahead_behind.ahead)
+        .collect::<Vec<_>>();
+    assert_eq!(aheads, [2, 1, 3]);
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let status = toolset.call_tool("get_project_status", "{}");
+    let mut aheads = status["result"]["stacks"]
+        .as_array()
+        .expect("a list of stacks")
+        .iter()
+        .map(|stack| {
+            assert_eq!(stack["targetAheadBehind"]["behind"], 0);
+            stack["targetAheadBehind"]["ahead"].as_u64()
+        })
+        .collect::<Vec<_>>();
+    aheads.sort();
+    assert_eq!(aheads, [Some(1), Some(2), Some(3)]);
+    Ok(())
+}
+
+#[test]
+fn moving_a_commit_to_a_new_branch_splits_the_branch() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("b.txt"), "b\n")?;
+    commit_files(ctx, "branch", &["b.txt"])?;
+
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "move_commit_to_new_branch",
+        &json!({
+            "stackId": stack_id.to_string(),
+            "sourceBranchName": "branch",
+            "commitId": first.to_string(),
+            "newBranchName": "split",
+            "newBranchDescription": "The a file",
+        })
+        .to_string(),
+    );
+    assert!(result["error"].is_null(), "{result}");
+    let titles = |branch: &serde_json::Value| {
+        branch["commits"]
+            .as_array()
+            .map(|commits| {
+                commits
+                    .iter()
+                    .map(|commit| {
+                        commit["messageTitle"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_owned()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+    assert_eq!(titles(&result["result"]["sourceBranch"]), ["Change b.txt"]);
+    assert_eq!(titles(&result["result"]["newBranch"]), ["Change a.txt"]);
+    assert_eq!(result["result"]["newBranch"]["name"], "split");
+    assert_eq!(result["result"]["newBranch"]["description"], "The a file");
+    Ok(())
+}
+
+#[test]
+fn failing_to_move_a_commit_to_a_new_branch_leaves_no_stack_behind() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &mut Test::default();
+    fs::write(repo.path().join("a.txt"), "a\n")?;
+    let (stack_id, first) = commit_files(ctx, "branch", &["a.txt"])?;
+    fs::write(repo.path().join("a.txt"), "a\nmore\n")?;
+    commit_files(ctx, "branch", &["a.txt"])?;
+
+    // The second commit depends on the first one, so the first one can't be moved.
+    let mut toolset = workspace_toolset(ctx, None, "message".into())?;
+    let result = toolset.call_tool(
+        "move_commit_to_new_branch",
+        &json!({
+            "stackId": stack_id.to_string(),
+            "sourceBranchName": "branch",
+            "commitId": first.to_string(),
+            "newBranchName": "split",
+        })
+        .to_string(),
+    );
+    assert!(
+        result["error"]
+            .as_str()
+            .is_some_and(|error| error.contains("dependent changes")),
+        "{result}"
+    );
+    drop(toolset);
+
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stacks = vb_state.list_all_stacks()?;
+    assert_eq!(stacks.len(), 1, "only the source stack is left");
+    assert_eq!(stacks[0].id, stack_id);
+    assert!(
+        stacks[0]
+            .branches()
+            .iter()
+            .all(|branch| branch.name() != "split"),
+        "the new branch was removed with its stack"
+    );
+    Ok(())
+}
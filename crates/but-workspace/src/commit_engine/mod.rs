@@ -53,6 +53,8 @@ pub enum Destination {
         commit_id: gix::ObjectId,
         /// If `Some()`, set the commit message as well.
         new_message: Option<String>,
+        /// If `Some()`, set the time of the author signature as well, keeping the author itself.
+        author_date: Option<gix::date::Time>,
     },
 }
 
@@ -249,6 +251,7 @@ pub fn create_commit(
             Destination::AmendCommit {
                 commit_id,
                 new_message,
+                author_date,
             } => {
                 let mut commit = commit_id
                     .attach(repo)
@@ -260,6 +263,9 @@ pub fn create_commit(
                 if let Some(message) = new_message {
                     commit.message = message.into();
                 }
+                if let Some(time) = author_date {
+                    commit.author.time = time;
+                }
                 Some(but_rebase::commit::create(
                     repo,
                     commit,
@@ -679,6 +685,10 @@ fn create_possibly_signed_commit(
 }
 
 /// Less pure but a simpler version of [`create_commit_and_update_refs_with_project`]
+///
+/// If `author_date` is set, it's used as time of the author signature instead of the current time.
+/// The committer time is not affected.
+#[allow(clippy::too_many_arguments)]
 pub fn create_commit_simple(
     ctx: &CommandContext,
     stack_id: StackId,
@@ -686,9 +696,16 @@ pub fn create_commit_simple(
     worktree_changes: Vec<DiffSpec>,
     message: String,
     stack_branch_name: String,
+    author_date: Option<gix::date::Time>,
     perm: &mut WorktreeWritePermission,
 ) -> anyhow::Result<CreateCommitOutcome> {
-    let repo = but_core::open_repo_for_merging(ctx.project().worktree_path())?;
+    let mut repo = but_core::open_repo_for_merging(ctx.project().worktree_path())?;
+    if let Some(time) = author_date {
+        repo.config_snapshot_mut().set_raw_value(
+            &gix::config::tree::gitoxide::Commit::AUTHOR_DATE,
+            time.format(gix::date::time::format::ISO8601).as_str(),
+        )?;
+    }
     // If parent_id was not set but a stack branch name was provided, pick the current head of that branch as parent.
     let parent_commit_id: Option<gix::ObjectId> = match parent_id {
         Some(id) => Some(id),
@@ -23,6 +23,7 @@ fn all_changes_and_renames_to_topmost_commit_no_parent() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: head_commit.into(),
             new_message: Some("init: amended".into()),
+            author_date: None,
         },
     )?;
     insta::assert_debug_snapshot!(&outcome, @r"
@@ -69,6 +70,7 @@ fn all_aspects_of_amended_commit_are_copied() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: repo.rev_parse_single("merge")?.detach(),
             new_message: None,
+            author_date: None,
         },
     )?;
     let tree = visualize_tree(&repo, &outcome)?;
@@ -102,6 +104,7 @@ fn new_file_and_deletion_onto_merge_commit() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: repo.rev_parse_single("merge")?.detach(),
             new_message: None,
+            author_date: None,
         },
     )?;
     let tree = visualize_tree(&repo, &outcome)?;
@@ -124,6 +127,7 @@ fn make_a_file_empty() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: repo.rev_parse_single("merge")?.detach(),
             new_message: None,
+            author_date: None,
         },
     )?;
     let tree = visualize_tree(&repo, &outcome)?;
@@ -148,6 +152,7 @@ fn new_file_and_deletion_onto_merge_commit_with_hunks() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: repo.rev_parse_single("merge")?.detach(),
             new_message: None,
+            author_date: None,
         },
         None,
         vec![
@@ -199,6 +204,7 @@ fn signatures_are_redone() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: head_id,
             new_message: None,
+            author_date: None,
         },
     )?;
 
@@ -228,6 +234,7 @@ fn signatures_are_redone() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: head_id,
             new_message: None,
+            author_date: None,
         },
     )?;
     let new_commit = commit_from_outcome(&repo, &outcome)?;
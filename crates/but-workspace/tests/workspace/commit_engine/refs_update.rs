@@ -128,6 +128,7 @@ fn new_commits_to_tip_from_unborn_head() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: new_commit,
             new_message: None,
+            author_date: None,
         },
         None,
         to_change_specs_whole_file(but_core::diff::worktree_changes(&repo)?),
@@ -692,6 +693,7 @@ fn insert_commit_into_single_stack_with_signatures() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: repo.rev_parse_single("@~1")?.detach(),
             new_message: None,
+            author_date: None,
         },
         None,
         to_change_specs_all_hunks(&repo, but_core::diff::worktree_changes(&repo)?)?,
@@ -1040,6 +1042,7 @@ fn workspace_commit_with_merge_conflict() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: branch_b,
             new_message: None,
+            author_date: None,
         },
     ] {
         let out = but_workspace::commit_engine::create_commit_and_update_refs(
@@ -1568,6 +1571,7 @@ fn amend_on_top_of_branch_in_workspace() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: branch_a,
             new_message: None,
+            author_date: None,
         },
         None,
         to_change_specs_all_hunks(&repo, but_core::diff::worktree_changes(&repo)?)?,
@@ -1640,6 +1644,7 @@ fn amend_edit_message_only() -> anyhow::Result<()> {
         Destination::AmendCommit {
             commit_id: branch_a,
             new_message: Some("add 10 to the beginning (amended)".into()),
+            author_date: None,
         },
         None,
         to_change_specs_all_hunks(&repo, but_core::diff::worktree_changes(&repo)?)?,
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     path::PathBuf,
     str::FromStr,
     sync::{Arc, Mutex},
@@ -183,9 +184,17 @@ pub struct GitButlerUpdateBranchesRequest {
 #[tool(tool_box)]
 impl ServerHandler for Mcp {
     fn get_info(&self) -> ServerInfo {
+        let mut capabilities = ServerCapabilities::builder().enable_tools().build();
+        // Clients detect the features of the tools the actions are carried out with by these.
+        capabilities.experimental = Some(BTreeMap::from([(
+            "gitbutler".into(),
+            but_tools::workspace::workspace_capabilities()
+                .into_iter()
+                .collect(),
+        )]));
         ServerInfo {
             instructions: Some("GitButler MCP server".into()),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities,
             server_info: Implementation {
                 name: "GitButler MCP Server".into(),
                 version: "1.0.0".into(),
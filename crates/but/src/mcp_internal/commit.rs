@@ -108,6 +108,7 @@ pub fn amend(
     let destination = but_workspace::commit_engine::Destination::AmendCommit {
         commit_id,
         new_message: Some(commit_message),
+        author_date: None,
     };
 
     let mut guard = project.exclusive_worktree_access();
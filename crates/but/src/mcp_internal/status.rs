@@ -14,6 +14,9 @@ pub struct RichHunk {
     pub assigned_to_stack: Option<but_workspace::StackId>,
     /// The locks this hunk has, if any.
     pub dependency_locks: Vec<but_hunk_dependency::ui::HunkLock>,
+    /// How certain the assignment of this hunk is, from 0.0 if it's unassigned to 1.0 if it's locked to exactly one commit.
+    /// This is `None` if the hunk has no assignment.
+    pub assignment_confidence: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -83,20 +86,21 @@ fn get_file_changes(
                             .find(|a| {
                                 a.path_bytes == change.path && a.hunk_header == Some(hunk.into())
                             })
-                            .map(|a| (a.stack_id, a.hunk_locks.clone()));
+                            .map(|a| (a.stack_id, a.hunk_locks.clone(), a.confidence));
 
-                        let (assigned_to_stack, dependency_locks) =
-                            if let Some((stack_id, locks)) = assignment {
+                        let (assigned_to_stack, dependency_locks, assignment_confidence) =
+                            if let Some((stack_id, locks, confidence)) = assignment {
                                 let locks = locks.unwrap_or_default();
-                                (stack_id, locks)
+                                (stack_id, locks, Some(confidence))
                             } else {
-                                (None, vec![])
+                                (None, vec![], None)
                             };
 
                         RichHunk {
                             diff,
                             assigned_to_stack,
                             dependency_locks,
+                            assignment_confidence,
                         }
                     })
                     .collect::<Vec<_>>();
@@ -88,6 +88,7 @@ fn amend_diff_specs(
         commit_engine::Destination::AmendCommit {
             commit_id: oid,
             new_message: None,
+            author_date: None,
         },
         None,
         but_workspace::flatten_diff_specs(diff_specs),
@@ -297,6 +297,7 @@ fn amend_with_commit_engine(
         commit_engine::Destination::AmendCommit {
             commit_id: commit_oid.to_gix(),
             new_message: None,
+            author_date: None,
         },
         None,
         worktree_changes,
@@ -49,6 +49,7 @@ impl BranchManager<'_> {
                     assigned_diffspec,
                     "WIP Assignments".to_string(),
                     head.to_owned(),
+                    None,
                     perm,
                 )?;
             }
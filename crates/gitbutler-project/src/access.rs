@@ -1,6 +1,7 @@
 use anyhow::{bail, Context};
 use parking_lot::{ArcRwLockReadGuard, ArcRwLockWriteGuard, RawRwLock};
 use std::path::PathBuf;
+use std::time::SystemTime;
 use std::{collections::BTreeMap, sync::Arc};
 
 use crate::{Project, ProjectId};
@@ -39,12 +40,41 @@ impl Project {
     /// GitButler are able to read or write the same repository.
     pub fn exclusive_worktree_access(&self) -> WriteWorkspaceGuard {
         let mut map = WORKTREE_LOCKS.lock();
+        let inner = map.entry(self.id).or_default().write_arc();
+        WORKTREE_LOCK_HOLDERS.lock().insert(
+            self.id,
+            WorktreeLockHolder {
+                pid: std::process::id(),
+                since: SystemTime::now(),
+            },
+        );
         WriteWorkspaceGuard {
-            inner: map.entry(self.id).or_default().write_arc().into(),
+            inner: inner.into(),
             perm: WorktreeWritePermission(()),
+            project_id: self.id,
         }
     }
 
+    /// Return who holds the [exclusive worktree access](Self::exclusive_worktree_access()) right now,
+    /// or `None` if nobody does.
+    ///
+    /// Note that, just like the access itself, this only knows about holders *within this process*.
+    pub fn worktree_lock_holder(&self) -> Option<WorktreeLockHolder> {
+        WORKTREE_LOCK_HOLDERS.lock().get(&self.id).copied()
+    }
+
+    /// Return `true` if anyone holds the [exclusive](Self::exclusive_worktree_access()) or
+    /// [shared](Self::shared_worktree_access()) worktree access right now, so the exclusive access would have to wait.
+    ///
+    /// This tries to obtain the exclusive access without waiting, and releases it right away if that worked.
+    /// There is no file-based lock for the worktree, so just like the access itself, this only sees holders
+    /// *within this process*. Other processes are only kept out by [`Self::try_exclusive_access()`], which is held
+    /// for as long as a project is open, not just for the duration of an operation.
+    pub fn is_worktree_access_held(&self) -> bool {
+        let lock = WORKTREE_LOCKS.lock().get(&self.id).cloned();
+        lock.is_some_and(|lock| lock.try_write().is_none())
+    }
+
     /// Return a guard for shared (read) worktree access, and block while waiting for writers to disappear.
     /// There can be multiple readers, but only a single writer. Waiting writers will be handled with priority,
     /// thus block readers to prevent writer starvation.
@@ -57,10 +87,21 @@ impl Project {
 pub struct WriteWorkspaceGuard {
     inner: Option<parking_lot::ArcRwLockWriteGuard<RawRwLock, ()>>,
     perm: WorktreeWritePermission,
+    project_id: ProjectId,
+}
+
+/// Information about the holder of the exclusive worktree access of a project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorktreeLockHolder {
+    /// The id of the process holding the access.
+    pub pid: u32,
+    /// The time at which the access was obtained.
+    pub since: SystemTime,
 }
 
 impl Drop for WriteWorkspaceGuard {
     fn drop(&mut self) {
+        WORKTREE_LOCK_HOLDERS.lock().remove(&self.project_id);
         let lock = self
             .inner
             .take()
@@ -124,6 +165,10 @@ impl WorktreeWritePermission {
 static WORKTREE_LOCKS: parking_lot::Mutex<BTreeMap<ProjectId, Arc<parking_lot::RwLock<()>>>> =
     parking_lot::Mutex::new(BTreeMap::new());
 
+/// The holders of the exclusive worktree access, kept separately so they can be queried without waiting for `WORKTREE_LOCKS`.
+static WORKTREE_LOCK_HOLDERS: parking_lot::Mutex<BTreeMap<ProjectId, WorktreeLockHolder>> =
+    parking_lot::Mutex::new(BTreeMap::new());
+
 /// A file-based lock that can indicate exclusive access.
 ///
 /// As opposed to its actual implementation, it will ignore failures due to lack of filesystem support.
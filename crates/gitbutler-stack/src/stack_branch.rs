@@ -127,6 +127,17 @@ impl StackBranch {
         matches!(self.head, CommitOrChangeId::ChangeId(_))
     }
 
+    /// The commit this branch pointed to when it was persisted, without consulting its git reference.
+    ///
+    /// This is useful for states read from oplog snapshots, and is `None` for legacy change id heads.
+    pub fn persisted_head_oid(&self) -> Option<gix::ObjectId> {
+        match &self.head {
+            CommitOrChangeId::CommitId(id) => gix::ObjectId::from_str(id).ok(),
+            #[allow(deprecated)]
+            CommitOrChangeId::ChangeId(_) => None,
+        }
+    }
+
     pub fn migrate_change_id(
         &mut self,
         repo: &git2::Repository,
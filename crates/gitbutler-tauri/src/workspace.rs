@@ -128,6 +128,7 @@ pub fn create_commit_from_worktree_changes(
         worktree_changes,
         message.clone(),
         stack_branch_name,
+        None,
         guard.write_permission(),
     );
 
@@ -171,6 +172,7 @@ pub fn amend_commit_from_worktree_changes(
             commit_id: commit_id.into(),
             // TODO: Expose this in the UI for 'edit message' functionality.
             new_message: None,
+            author_date: None,
         },
         None,
         worktree_changes,